@@ -0,0 +1,29 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The base32 decoders (Crockford base32 for `Tsid`, base32hex for `Xid`) either reject the
+//! input or accept something that displays and parses back to the same ID.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use snowflake::{Tsid, Xid};
+
+fuzz_target!(|data: &[u8]| {
+    let s = match std::str::from_utf8(data) {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+    if let Ok(id) = s.parse::<Tsid>() {
+        assert_eq!(id.to_string().parse(), Ok(id), "input: {:?}", s);
+        // Tsids are case insensitive.
+        assert_eq!(s.to_ascii_lowercase().parse(), Ok(id), "input: {:?}", s);
+    }
+    if let Ok(id) = s.parse::<Xid>() {
+        assert_eq!(id.to_string(), s);
+    }
+});
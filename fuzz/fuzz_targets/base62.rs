@@ -0,0 +1,32 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Short codes (base62 with the default alphabet) decode only from the exact code that encodes
+//! the value.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use snowflake::short_code::ShortCodec;
+use snowflake::{ProcessUniqueId, SmallUniqueId};
+
+fuzz_target!(|data: &[u8]| {
+    let s = match std::str::from_utf8(data) {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+    let codec = ShortCodec::new(ShortCodec::DEFAULT_ALPHABET, "fuzz").unwrap();
+    if let Ok(value) = codec.decode(s) {
+        assert_eq!(codec.encode(value), s);
+    }
+    if let Ok(id) = ProcessUniqueId::from_short_code(s, &codec) {
+        assert_eq!(id.to_short_code(&codec), s);
+    }
+    if let Ok(id) = SmallUniqueId::from_short_code(s, &codec) {
+        assert_eq!(id.to_short_code(&codec), s);
+    }
+});
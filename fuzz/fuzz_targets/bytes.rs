@@ -0,0 +1,35 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The byte decoders either reject the input or accept something that encodes back to the same
+//! bytes.
+#![no_main]
+
+use std::convert::TryInto;
+
+use libfuzzer_sys::fuzz_target;
+use snowflake::codec::{read_ids, write_ids};
+use snowflake::{CompositeId, ProcessUniqueId};
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(bytes) = data.get(..16) {
+        let bytes: [u8; 16] = bytes.try_into().unwrap();
+        if let Ok(id) = ProcessUniqueId::from_bytes(bytes) {
+            assert_eq!(id.to_bytes(), bytes);
+        }
+        if let Ok(id) = CompositeId::<u8>::from_bytes(bytes) {
+            assert_eq!(id.to_bytes(), bytes);
+        }
+    }
+
+    if let Ok(ids) = read_ids(&mut &data[..]) {
+        let mut encoded = Vec::new();
+        write_ids(&mut encoded, &ids).unwrap();
+        assert!(data.starts_with(&encoded));
+    }
+});
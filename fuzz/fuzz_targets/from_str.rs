@@ -0,0 +1,54 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Every `FromStr` implementation either rejects the input or accepts something that displays
+//! and parses back to the same ID.
+#![no_main]
+
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+
+use libfuzzer_sys::fuzz_target;
+use snowflake::{
+    CompositeId, FlakeId, HostUniqueId, IdPath, ObjectId, ProcessUniqueId, PushId,
+    SmallUniqueId, SnowflakeId, SonyflakeId,
+};
+
+fn round_trip<T>(s: &str)
+where
+    T: FromStr + Display + PartialEq + Debug,
+{
+    if let Ok(id) = s.parse::<T>() {
+        assert_eq!(id.to_string().parse::<T>().ok(), Some(id), "input: {:?}", s);
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let s = match std::str::from_utf8(data) {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+    round_trip::<ProcessUniqueId>(s);
+    round_trip::<SnowflakeId>(s);
+    round_trip::<SonyflakeId>(s);
+    round_trip::<FlakeId>(s);
+    round_trip::<ObjectId>(s);
+    round_trip::<PushId>(s);
+    round_trip::<SmallUniqueId>(s);
+    round_trip::<HostUniqueId>(s);
+    round_trip::<CompositeId<u16>>(s);
+    round_trip::<IdPath>(s);
+
+    if let Ok(id) = ProcessUniqueId::parse_lenient(s) {
+        assert_eq!(ProcessUniqueId::parse_lenient(&id.to_string()), Ok(id));
+    }
+    // Only the canonical encoding is accepted, so it encodes back to the input.
+    if let Ok(id) = ProcessUniqueId::from_base64_url(s) {
+        assert_eq!(id.to_base64_url(), s);
+    }
+});
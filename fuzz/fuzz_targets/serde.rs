@@ -0,0 +1,44 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Deserializing IDs from JSON either fails or produces a valid ID that serializes back to the
+//! same ID.
+#![no_main]
+
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+
+use libfuzzer_sys::fuzz_target;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use snowflake::{
+    FlakeId, ObjectId, ProcessUniqueId, SmallUniqueId, SnowflakeId, SonyflakeId, Tsid, Xid,
+};
+
+fn round_trip<T>(data: &[u8])
+where
+    T: DeserializeOwned + Serialize + FromStr + Display + PartialEq + Debug + Copy,
+{
+    if let Ok(id) = serde_json::from_slice::<T>(data) {
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(serde_json::from_str::<T>(&json).ok(), Some(id));
+        // Deserialization mustn't let through IDs that parsing rejects.
+        assert_eq!(id.to_string().parse::<T>().ok(), Some(id));
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    round_trip::<ProcessUniqueId>(data);
+    round_trip::<SnowflakeId>(data);
+    round_trip::<SonyflakeId>(data);
+    round_trip::<Tsid>(data);
+    round_trip::<Xid>(data);
+    round_trip::<ObjectId>(data);
+    round_trip::<FlakeId>(data);
+    round_trip::<SmallUniqueId>(data);
+});
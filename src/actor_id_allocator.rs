@@ -0,0 +1,138 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt;
+
+use crate::process_unique_id::{reserve_local, ProcessUniqueId};
+
+/// How many IDs an allocator reserves at a time unless configured otherwise.
+const DEFAULT_RANGE_SIZE: u64 = 1024;
+
+/// A handle for allocating process unique IDs from a range reserved up front, meant to be
+/// stored in an actor (or mailbox, connection, ...) that creates an ID per message.
+///
+/// The allocator reserves a range of IDs when it's created and hands them out from plain fields,
+/// so allocating an ID never touches thread local or shared state until the range runs out.
+/// Cloning an allocator gives the clone a range of its own:
+///
+/// ```
+/// use snowflake::ActorIdAllocator;
+///
+/// struct Actor {
+///     ids: ActorIdAllocator,
+/// }
+///
+/// let mut a = Actor { ids: ActorIdAllocator::new() };
+/// let mut b = Actor { ids: a.ids.clone() };
+/// let (x, y) = (a.ids.next_id(), b.ids.next_id());
+/// assert_ne!(x, y);
+/// ```
+///
+/// Like IDs from an [`IdStream`](crate::IdStream), the IDs are unique with respect to all other
+/// process unique IDs but aren't ordered with respect to IDs created by other means. IDs left in
+/// a range when the allocator is dropped are never used.
+pub struct ActorIdAllocator {
+    next: ProcessUniqueId,
+    remaining: u64,
+    range_size: u64,
+}
+
+impl ActorIdAllocator {
+    /// Creates an allocator reserving 1024 IDs at a time.
+    #[inline]
+    pub fn new() -> Self {
+        ActorIdAllocator::with_range_size(DEFAULT_RANGE_SIZE)
+    }
+
+    /// Creates an allocator reserving `size` IDs at a time.
+    ///
+    /// Larger ranges go back to the thread local generator less often but leave more IDs unused
+    /// when an allocator is dropped; there are plenty of IDs to go around, so err on the large
+    /// side for busy actors.
+    ///
+    /// **panics** if `size` is zero.
+    pub fn with_range_size(size: u64) -> Self {
+        assert!(size > 0, "the range size must not be zero");
+        let (next, remaining) = reserve_local(size);
+        ActorIdAllocator {
+            next,
+            remaining,
+            range_size: size,
+        }
+    }
+
+    /// The number of IDs reserved at a time.
+    #[inline]
+    pub fn range_size(&self) -> u64 {
+        self.range_size
+    }
+
+    /// Allocates an ID.
+    ///
+    /// **panics** under the same conditions as [`ProcessUniqueId::new`].
+    #[inline]
+    pub fn next_id(&mut self) -> ProcessUniqueId {
+        if self.remaining == 0 {
+            self.refill();
+        }
+        let id = self.next;
+        self.remaining -= 1;
+        self.next.offset = self.next.offset.wrapping_add(1);
+        id
+    }
+
+    #[cold]
+    fn refill(&mut self) {
+        let (next, remaining) = reserve_local(self.range_size);
+        self.next = next;
+        self.remaining = remaining;
+    }
+}
+
+/// Reserves a new range for the clone.
+impl Clone for ActorIdAllocator {
+    #[inline]
+    fn clone(&self) -> Self {
+        ActorIdAllocator::with_range_size(self.range_size)
+    }
+}
+
+impl Default for ActorIdAllocator {
+    #[inline]
+    fn default() -> Self {
+        ActorIdAllocator::new()
+    }
+}
+
+impl fmt::Debug for ActorIdAllocator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ActorIdAllocator")
+            .field("range_size", &self.range_size)
+            .field("remaining", &self.remaining)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ActorIdAllocator;
+    use crate::ProcessUniqueId;
+
+    #[test]
+    fn test_allocator_unique() {
+        let mut a = ActorIdAllocator::with_range_size(3);
+        let mut b = a.clone();
+        assert_eq!(b.range_size(), 3);
+        let mut ids: Vec<_> = (0..10).map(|_| a.next_id()).collect();
+        ids.extend((0..10).map(|_| b.next_id()));
+        ids.extend((0..10).map(|_| ProcessUniqueId::new()));
+        let len = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), len);
+    }
+}
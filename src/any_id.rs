@@ -0,0 +1,226 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::fmt;
+use core::str::FromStr;
+
+use crate::display::pad_id;
+use crate::{
+    FlakeId, ObjectId, ParseIdError, ProcessUniqueId, PushId, SmallUniqueId, SnowflakeId,
+    SonyflakeId, Tsid, Xid,
+};
+
+/// An ID of any of the kinds this crate generates.
+///
+/// Displayed the same way as the ID it holds. The alternate form (`{:#}`) tags it with its
+/// [`kind`](AnyId::kind), as in `sonyflake:123`, so that it parses back as the same kind.
+///
+/// Parsing accepts the tagged form, or any ID's own form and works out which kind it is. Some
+/// strings are valid IDs of more than one kind; they're taken to be the first of these that fits:
+/// process unique ID, small unique ID, Snowflake ID (so decimal numbers are never Sonyflake IDs),
+/// TSID, ObjectId, flake ID, Xid, and push ID.
+///
+/// ```
+/// use snowflake::{AnyId, SnowflakeId, SonyflakeId};
+///
+/// let id: AnyId = "1541815603606036480".parse().unwrap();
+/// assert_eq!(id.kind(), "snowflake");
+/// let sonyflake: AnyId = "sonyflake:1541815603606036480".parse().unwrap();
+/// assert_eq!(sonyflake.kind(), "sonyflake");
+/// assert_eq!(format!("{:#}", sonyflake), "sonyflake:1541815603606036480");
+/// assert_eq!(sonyflake.to_string(), "1541815603606036480");
+/// ```
+///
+/// With `serde_support`, serializes as a string in the tagged form, and deserializes from either
+/// form.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum AnyId {
+    /// A [`ProcessUniqueId`].
+    ProcessUniqueId(ProcessUniqueId),
+    /// A [`SmallUniqueId`].
+    SmallUniqueId(SmallUniqueId),
+    /// A [`SnowflakeId`].
+    SnowflakeId(SnowflakeId),
+    /// A [`SonyflakeId`].
+    SonyflakeId(SonyflakeId),
+    /// A [`Tsid`].
+    Tsid(Tsid),
+    /// An [`ObjectId`].
+    ObjectId(ObjectId),
+    /// An [`Xid`].
+    Xid(Xid),
+    /// A [`FlakeId`].
+    FlakeId(FlakeId),
+    /// A [`PushId`].
+    PushId(PushId),
+}
+
+impl AnyId {
+    /// A short name for the kind of ID held, e.g. `"puid"` or `"snowflake"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AnyId::ProcessUniqueId(_) => "puid",
+            AnyId::SmallUniqueId(_) => "spuid",
+            AnyId::SnowflakeId(_) => "snowflake",
+            AnyId::SonyflakeId(_) => "sonyflake",
+            AnyId::Tsid(_) => "tsid",
+            AnyId::ObjectId(_) => "objectid",
+            AnyId::Xid(_) => "xid",
+            AnyId::FlakeId(_) => "flake",
+            AnyId::PushId(_) => "pushid",
+        }
+    }
+}
+
+impl fmt::Display for AnyId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            return pad_id(
+                f,
+                format_args!("{}:", self.kind()),
+                format_args!("{}", Untagged(self)),
+            );
+        }
+        Untagged(self).fmt(f)
+    }
+}
+
+// Displays an ID without the alternate flag.
+struct Untagged<'a>(&'a AnyId);
+
+impl fmt::Display for Untagged<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            AnyId::ProcessUniqueId(id) => id.fmt(f),
+            AnyId::SmallUniqueId(id) => id.fmt(f),
+            AnyId::SnowflakeId(id) => id.fmt(f),
+            AnyId::SonyflakeId(id) => id.fmt(f),
+            AnyId::Tsid(id) => id.fmt(f),
+            AnyId::ObjectId(id) => id.fmt(f),
+            AnyId::Xid(id) => id.fmt(f),
+            AnyId::FlakeId(id) => id.fmt(f),
+            AnyId::PushId(id) => id.fmt(f),
+        }
+    }
+}
+
+/// Parses the tagged form or any ID's own form; see [`AnyId`].
+impl FromStr for AnyId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        fn parse<T: FromStr + Into<AnyId>>(s: &str) -> Option<AnyId> {
+            s.parse::<T>().ok().map(Into::into)
+        }
+
+        let id = match s.split_once(':') {
+            Some((kind, s)) => match kind {
+                "puid" => parse::<ProcessUniqueId>(s),
+                "spuid" => parse::<SmallUniqueId>(s),
+                "snowflake" => parse::<SnowflakeId>(s),
+                "sonyflake" => parse::<SonyflakeId>(s),
+                "tsid" => parse::<Tsid>(s),
+                "objectid" => parse::<ObjectId>(s),
+                "xid" => parse::<Xid>(s),
+                "flake" => parse::<FlakeId>(s),
+                "pushid" => parse::<PushId>(s),
+                _ => None,
+            },
+            None => parse::<ProcessUniqueId>(s)
+                .or_else(|| parse::<SmallUniqueId>(s))
+                .or_else(|| parse::<SnowflakeId>(s))
+                .or_else(|| parse::<Tsid>(s))
+                .or_else(|| parse::<ObjectId>(s))
+                .or_else(|| parse::<FlakeId>(s))
+                .or_else(|| parse::<Xid>(s))
+                .or_else(|| parse::<PushId>(s)),
+        };
+        id.ok_or_else(|| ParseIdError::at(s, 0, "a known kind of ID"))
+    }
+}
+
+#[cfg(feature = "serde_support")]
+impl serde::Serialize for AnyId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{:#}", self))
+    }
+}
+
+#[cfg(feature = "serde_support")]
+impl<'de> serde::Deserialize<'de> for AnyId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        crate::serde::as_string::deserialize(deserializer)
+    }
+}
+
+macro_rules! from_id {
+    ($($ty:ident),*) => {
+        $(
+            impl From<$ty> for AnyId {
+                #[inline]
+                fn from(id: $ty) -> Self {
+                    AnyId::$ty(id)
+                }
+            }
+        )*
+    };
+}
+
+from_id!(
+    ProcessUniqueId,
+    SmallUniqueId,
+    SnowflakeId,
+    SonyflakeId,
+    Tsid,
+    ObjectId,
+    Xid,
+    FlakeId,
+    PushId
+);
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::AnyId;
+    use crate::{FlakeId, ObjectId, ProcessUniqueId, SmallUniqueId, Tsid, Xid};
+
+    #[test]
+    fn test_parse_any_id() {
+        let ids: [AnyId; 6] = [
+            ProcessUniqueId::new().into(),
+            SmallUniqueId::new().into(),
+            Tsid::from(0x0123_4567_89ab_cdef_u64).into(),
+            ObjectId::from_bytes([7; 12]).into(),
+            FlakeId::from(u128::MAX).into(),
+            Xid::from_bytes([7; 12]).into(),
+        ];
+        for id in &ids {
+            assert_eq!(id.to_string().parse::<AnyId>(), Ok(*id));
+            assert_eq!(format!("{:#}", id).parse::<AnyId>(), Ok(*id));
+        }
+        let push: AnyId = "-NxVvDkdAY9R2Wg1dK8f".parse().unwrap();
+        assert_eq!(push.kind(), "pushid");
+        let one: AnyId = "1".parse().unwrap();
+        assert_eq!(format!("{:>#14}", one), "   snowflake:1");
+        assert!("unknown:1".parse::<AnyId>().is_err());
+        assert!("snowflake:-1".parse::<AnyId>().is_err());
+        assert!("".parse::<AnyId>().is_err());
+    }
+
+    #[cfg(feature = "serde_support")]
+    #[test]
+    fn test_any_id_serde() {
+        use core::convert::TryFrom;
+
+        let id = AnyId::from(crate::SonyflakeId::try_from(42u64).unwrap());
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, r#""sonyflake:42""#);
+        assert_eq!(serde_json::from_str::<AnyId>(&json).unwrap(), id);
+        let bare: AnyId = serde_json::from_str(r#""42""#).unwrap();
+        assert_eq!(bare.kind(), "snowflake");
+    }
+}
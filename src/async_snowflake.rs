@@ -0,0 +1,91 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::time::Duration;
+
+use crate::snowflake_id::{SequenceExhausted, SnowflakeGenerator, SnowflakeId};
+
+/// A [`SnowflakeGenerator`] for async code.
+///
+/// When the current millisecond's sequence is exhausted, [`next`](AsyncSnowflakeGenerator::next)
+/// sleeps on the async runtime's timer instead of spinning on the executor thread. Uses tokio if
+/// the `tokio` feature is enabled and async-std otherwise.
+#[derive(Debug)]
+pub struct AsyncSnowflakeGenerator {
+    inner: SnowflakeGenerator,
+}
+
+impl AsyncSnowflakeGenerator {
+    /// Wraps a snowflake generator.
+    pub fn new(inner: SnowflakeGenerator) -> Self {
+        AsyncSnowflakeGenerator { inner }
+    }
+
+    /// Generates a new ID, yielding to the runtime until the next millisecond if the current one's
+    /// sequence is exhausted.
+    pub async fn next(&self) -> SnowflakeId {
+        loop {
+            match self.inner.try_next() {
+                Ok(id) => return id,
+                Err(SequenceExhausted) => sleep(Duration::from_millis(1)).await,
+            }
+        }
+    }
+
+    /// Generates a new ID without waiting. See [`SnowflakeGenerator::try_next`].
+    #[inline]
+    pub fn try_next(&self) -> Result<SnowflakeId, SequenceExhausted> {
+        self.inner.try_next()
+    }
+
+    /// The wrapped generator.
+    #[inline]
+    pub fn get_ref(&self) -> &SnowflakeGenerator {
+        &self.inner
+    }
+
+    /// Unwraps the generator.
+    #[inline]
+    pub fn into_inner(self) -> SnowflakeGenerator {
+        self.inner
+    }
+}
+
+impl From<SnowflakeGenerator> for AsyncSnowflakeGenerator {
+    fn from(inner: SnowflakeGenerator) -> Self {
+        AsyncSnowflakeGenerator::new(inner)
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+#[cfg(not(feature = "tokio"))]
+pub(crate) async fn sleep(duration: Duration) {
+    async_std::task::sleep(duration).await
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod test {
+    use super::AsyncSnowflakeGenerator;
+    use crate::SnowflakeGenerator;
+
+    #[tokio::test]
+    async fn test_next_past_exhaustion() {
+        let gen = AsyncSnowflakeGenerator::new(SnowflakeGenerator::new(9));
+        let mut last = gen.next().await;
+        // More than one millisecond's worth of sequence numbers.
+        for _ in 0..10_000 {
+            let id = gen.next().await;
+            assert!(id > last);
+            last = id;
+        }
+        assert_eq!(gen.get_ref().node_id(), 9);
+    }
+}
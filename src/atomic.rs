@@ -0,0 +1,37 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Atomics used by the generators. With the `portable-atomic` feature, and on targets without
+// native 64-bit atomics, these come from the `portable-atomic` crate, which emulates them.
+#![allow(unused_imports)]
+
+#[cfg(not(any(feature = "portable-atomic", not(target_has_atomic = "64"))))]
+pub(crate) use core::sync::atomic::{
+    fence, AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering,
+};
+#[cfg(any(feature = "portable-atomic", not(target_has_atomic = "64")))]
+pub(crate) use portable_atomic::{fence, AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+/// Aligns (and so pads) a value to its own cache line, so it doesn't share one with neighboring
+/// statics. Spatial prefetchers on x86_64 and aarch64 pull in cache lines in pairs, hence 128
+/// bytes there.
+#[cfg_attr(any(target_arch = "x86_64", target_arch = "aarch64"), repr(align(128)))]
+#[cfg_attr(
+    not(any(target_arch = "x86_64", target_arch = "aarch64")),
+    repr(align(64))
+)]
+pub(crate) struct CachePadded<T>(pub(crate) T);
+
+impl<T> core::ops::Deref for CachePadded<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A record of recent process unique ID allocations.
+//!
+//! Requires the `audit` feature. Every time a thread (or an [`IdHandle`](crate::IdHandle))
+//! claims a block of IDs, the block, the time, and the claiming thread are appended to a
+//! fixed-size ring buffer. Since a thread hands out its block's IDs in order, the buffer tells
+//! which thread created a recent ID, and roughly when:
+//!
+//! ```
+//! use snowflake::{audit, ProcessUniqueId};
+//!
+//! let id = ProcessUniqueId::new();
+//! let allocation = audit::find(id).unwrap();
+//! assert_eq!(allocation.thread_id(), std::thread::current().id());
+//!
+//! for allocation in audit::dump() {
+//!     eprintln!("{}", allocation);
+//! }
+//! ```
+//!
+//! Recording only happens when a block is claimed, so creating IDs stays as fast as without the
+//! feature.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+use std::time::SystemTime;
+
+use crate::ProcessUniqueId;
+
+/// The number of allocations kept unless configured otherwise.
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+struct Log {
+    capacity: usize,
+    entries: VecDeque<Allocation>,
+}
+
+static LOG: Mutex<Log> = Mutex::new(Log {
+    capacity: DEFAULT_CAPACITY,
+    entries: VecDeque::new(),
+});
+
+fn log() -> std::sync::MutexGuard<'static, Log> {
+    LOG.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A block of IDs claimed by a thread.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Allocation {
+    first: ProcessUniqueId,
+    last: u64,
+    time: SystemTime,
+    thread_id: ThreadId,
+    thread_name: Option<String>,
+}
+
+impl Allocation {
+    /// The first ID in the block.
+    #[inline]
+    pub fn first(&self) -> ProcessUniqueId {
+        self.first
+    }
+
+    /// The last ID in the block.
+    #[inline]
+    pub fn last(&self) -> ProcessUniqueId {
+        ProcessUniqueId {
+            prefix: self.first.prefix,
+            offset: self.last,
+        }
+    }
+
+    /// Returns true if `id` is in the block.
+    #[inline]
+    pub fn contains(&self, id: ProcessUniqueId) -> bool {
+        id.prefix == self.first.prefix && (self.first.offset..=self.last).contains(&id.offset)
+    }
+
+    /// When the block was claimed.
+    #[inline]
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// The ID of the thread that claimed the block.
+    #[inline]
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread_id
+    }
+
+    /// The name of the thread that claimed the block, if it had one.
+    #[inline]
+    pub fn thread_name(&self) -> Option<&str> {
+        self.thread_name.as_deref()
+    }
+}
+
+/// Displays the allocation as one line: the IDs, the thread, and the time.
+impl fmt::Display for Allocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}..={} by {:?}",
+            self.first,
+            self.last(),
+            self.thread_id
+        )?;
+        if let Some(name) = &self.thread_name {
+            write!(f, " ({})", name)?;
+        }
+        write!(f, " at {:?}", self.time)
+    }
+}
+
+/// Records a block claimed by the current thread.
+pub(crate) fn record(first: ProcessUniqueId, last: u64) {
+    let thread = thread::current();
+    let allocation = Allocation {
+        first,
+        last,
+        time: SystemTime::now(),
+        thread_id: thread.id(),
+        thread_name: thread.name().map(String::from),
+    };
+    let mut log = log();
+    if log.capacity == 0 {
+        return;
+    }
+    if log.entries.len() == log.capacity {
+        log.entries.pop_front();
+    }
+    log.entries.push_back(allocation);
+}
+
+/// Sets the number of allocations kept, dropping the oldest ones if there are more than that.
+/// Zero turns recording off.
+pub fn set_capacity(capacity: usize) {
+    let mut log = log();
+    log.capacity = capacity;
+    let excess = log.entries.len().saturating_sub(capacity);
+    log.entries.drain(..excess);
+}
+
+/// Returns the recorded allocations, oldest first.
+pub fn dump() -> Vec<Allocation> {
+    log().entries.iter().cloned().collect()
+}
+
+/// Returns the most recent recorded allocation containing `id`, if it's still in the buffer.
+pub fn find(id: ProcessUniqueId) -> Option<Allocation> {
+    log().entries.iter().rev().find(|a| a.contains(id)).cloned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::find;
+    use crate::{IdHandle, IdRange, ProcessUniqueId};
+    use std::thread;
+
+    #[test]
+    fn test_audit_find() {
+        let (id, thread_id) = thread::Builder::new()
+            .name("audited".into())
+            .spawn(|| (ProcessUniqueId::new(), thread::current().id()))
+            .unwrap()
+            .join()
+            .unwrap();
+        let allocation = find(id).unwrap();
+        assert_eq!(allocation.thread_id(), thread_id);
+        assert_eq!(allocation.thread_name(), Some("audited"));
+        assert_eq!(allocation.first(), id);
+        assert!(allocation.to_string().contains("(audited)"));
+
+        let handle = IdHandle::new();
+        let id = handle.next();
+        assert!(find(id).unwrap().contains(handle.next()));
+
+        // More than a thread's block holds, so it's claimed from the shared prefix.
+        let range = IdRange::reserve(1 << 40);
+        let allocation = find(range.get(0).unwrap()).unwrap();
+        assert!(allocation.contains(range.get((1 << 40) - 1).unwrap()));
+    }
+}
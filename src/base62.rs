@@ -0,0 +1,188 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::convert::TryFrom;
+
+use crate::process_unique_id::ParseIdError;
+use crate::{FlakeId, ProcessUniqueId, SmallUniqueId, SnowflakeId, SonyflakeId, Tsid};
+
+// In ASCII order, so that codes of the same length sort the same way as the values.
+const ALPHABET: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Writes `value` in base 62 with no leading zeros: at most 11 characters for a `u64` and 22 for
+/// a `u128`.
+fn encode(mut value: u128) -> String {
+    let mut code = Vec::with_capacity(22);
+    loop {
+        code.push(ALPHABET[(value % 62) as usize]);
+        value /= 62;
+        if value == 0 {
+            break;
+        }
+    }
+    code.reverse();
+    // The alphabet is ASCII.
+    String::from_utf8(code).unwrap()
+}
+
+/// Reads a value written by `encode`, rejecting leading zeros so that every value has one code.
+fn decode(code: &str) -> Result<u128, ParseIdError> {
+    fn digit(c: u8) -> Option<u8> {
+        match c {
+            b'0'..=b'9' => Some(c - b'0'),
+            b'A'..=b'Z' => Some(c - b'A' + 10),
+            b'a'..=b'z' => Some(c - b'a' + 36),
+            _ => None,
+        }
+    }
+
+    if code.is_empty() {
+        return Err(ParseIdError::at(code, 0, "a letter or digit"));
+    }
+    if code.len() > 1 && code.starts_with('0') {
+        return Err(ParseIdError::at(code, 0, "a code without leading zeros"));
+    }
+    code.bytes().enumerate().try_fold(0u128, |value, (i, c)| {
+        let digit = digit(c).ok_or_else(|| ParseIdError::at(code, i, "a letter or digit"))?;
+        value
+            .checked_mul(62)
+            .and_then(|v| v.checked_add(u128::from(digit)))
+            .ok_or_else(|| ParseIdError::at(code, 0, "a value that fits in 128 bits"))
+    })
+}
+
+fn decode_u64(code: &str) -> Result<u64, ParseIdError> {
+    u64::try_from(decode(code)?)
+        .map_err(|_| ParseIdError::at(code, 0, "a value that fits in 64 bits"))
+}
+
+impl ProcessUniqueId {
+    /// Encodes this ID (its [`to_u128`](ProcessUniqueId::to_u128) form) in base 62, the shortest
+    /// alphanumeric form, for QR codes and SMS links.
+    ///
+    /// ```
+    /// use snowflake::ProcessUniqueId;
+    ///
+    /// let id = ProcessUniqueId::new();
+    /// let code = id.to_base62();
+    /// assert!(code.len() <= 22 && code.bytes().all(|c| c.is_ascii_alphanumeric()));
+    /// assert_eq!(ProcessUniqueId::from_base62(&code), Ok(id));
+    /// ```
+    ///
+    /// Codes don't have a fixed length, so they only sort the same way as the IDs when they're
+    /// the same length.
+    #[inline]
+    pub fn to_base62(self) -> String {
+        encode(self.to_u128())
+    }
+
+    /// Decodes the form produced by [`to_base62`](ProcessUniqueId::to_base62). As with
+    /// `from_u128`, only IDs created by the current process are unique in it.
+    pub fn from_base62(code: &str) -> Result<Self, ParseIdError> {
+        ProcessUniqueId::from_u128(decode(code)?)
+            .ok_or_else(|| ParseIdError::at(code, 0, "a prefix that fits in a usize"))
+    }
+}
+
+impl SmallUniqueId {
+    /// Encodes this ID in base 62, at most 11 characters. See
+    /// [`ProcessUniqueId::to_base62`].
+    #[inline]
+    pub fn to_base62(self) -> String {
+        encode(u64::from(self).into())
+    }
+
+    /// Decodes the form produced by [`to_base62`](SmallUniqueId::to_base62).
+    pub fn from_base62(code: &str) -> Result<Self, ParseIdError> {
+        decode_u64(code).map(SmallUniqueId::from)
+    }
+}
+
+impl SnowflakeId {
+    /// Encodes this ID in base 62, at most 11 characters. See
+    /// [`ProcessUniqueId::to_base62`].
+    #[inline]
+    pub fn to_base62(self) -> String {
+        encode(u64::from(self).into())
+    }
+
+    /// Decodes the form produced by [`to_base62`](SnowflakeId::to_base62).
+    pub fn from_base62(code: &str) -> Result<Self, ParseIdError> {
+        SnowflakeId::try_from(decode_u64(code)?)
+            .map_err(|_| ParseIdError::at(code, 0, "a value below 2^63"))
+    }
+}
+
+impl SonyflakeId {
+    /// Encodes this ID in base 62, at most 11 characters. See
+    /// [`ProcessUniqueId::to_base62`].
+    #[inline]
+    pub fn to_base62(self) -> String {
+        encode(u64::from(self).into())
+    }
+
+    /// Decodes the form produced by [`to_base62`](SonyflakeId::to_base62).
+    pub fn from_base62(code: &str) -> Result<Self, ParseIdError> {
+        SonyflakeId::try_from(decode_u64(code)?)
+            .map_err(|_| ParseIdError::at(code, 0, "a value below 2^63"))
+    }
+}
+
+impl Tsid {
+    /// Encodes this ID in base 62, at most 11 characters. See
+    /// [`ProcessUniqueId::to_base62`].
+    #[inline]
+    pub fn to_base62(self) -> String {
+        encode(u64::from(self).into())
+    }
+
+    /// Decodes the form produced by [`to_base62`](Tsid::to_base62).
+    pub fn from_base62(code: &str) -> Result<Self, ParseIdError> {
+        decode_u64(code).map(Tsid::from)
+    }
+}
+
+impl FlakeId {
+    /// Encodes this ID in base 62, at most 22 characters. See
+    /// [`ProcessUniqueId::to_base62`].
+    #[inline]
+    pub fn to_base62(self) -> String {
+        encode(u128::from(self))
+    }
+
+    /// Decodes the form produced by [`to_base62`](FlakeId::to_base62).
+    pub fn from_base62(code: &str) -> Result<Self, ParseIdError> {
+        decode(code).map(FlakeId::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, encode};
+    use crate::{SnowflakeId, Tsid};
+
+    #[test]
+    fn test_base62() {
+        for &value in &[0, 61, 62, u128::from(u64::MAX), u128::MAX] {
+            assert_eq!(decode(&encode(value)), Ok(value));
+        }
+        assert_eq!(encode(61), "z");
+        assert_eq!(encode(62), "10");
+        assert_eq!(encode(u128::from(u64::MAX)).len(), 11);
+        assert_eq!(encode(u128::MAX).len(), 22);
+        assert!(decode("").is_err());
+        assert!(decode("01").is_err());
+        assert!(decode("a-b").is_err());
+        // One past u128::MAX.
+        assert!(decode("7n42DGM5Tflk9n8mt7Fhc8").is_err());
+
+        assert!(Tsid::from_base62(&encode(1 << 64)).is_err());
+        assert!(SnowflakeId::from_base62(&encode(1 << 63)).is_err());
+        let id = Tsid::from(u64::MAX);
+        assert_eq!(Tsid::from_base62(&id.to_base62()), Ok(id));
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Bevy integration.
+//!
+//! Requires the `bevy` feature. With it, every ID type is a [`Component`] and implements
+//! [`Reflect`] as an opaque value (so reflection can't be used to edit the parts of an ID).
+//! [`SnowflakePlugin`] registers the ID types with the app's type registry; generic types like
+//! [`ScopedUniqueId`](crate::ScopedUniqueId) have to be registered for each namespace used.
+//!
+//! For giving entities a stable handle, use the [`UniqueId`] component.
+use bevy_app::{App, Plugin};
+use bevy_ecs::component::Component;
+use bevy_ecs::reflect::ReflectComponent;
+use bevy_reflect::std_traits::ReflectDefault;
+use bevy_reflect::Reflect;
+
+use crate::{
+    FlakeId, ObjectId, ProcessUniqueId, PushId, SmallUniqueId, SnowflakeId, SonyflakeId, Tsid, Xid,
+};
+
+/// A component holding a process unique ID, assigned when the component is created.
+///
+/// Make it a required component and every entity gets its own ID without any setup:
+///
+/// ```
+/// use bevy_ecs::prelude::*;
+/// use snowflake::bevy::UniqueId;
+///
+/// #[derive(Component)]
+/// #[require(UniqueId)]
+/// struct Enemy;
+///
+/// let mut world = World::new();
+/// let a = world.spawn(Enemy).id();
+/// let b = world.spawn(Enemy).id();
+/// assert_ne!(world.get::<UniqueId>(a), world.get::<UniqueId>(b));
+/// ```
+///
+/// The component is immutable, and isn't copied when an entity is cloned: the clone gets a new
+/// ID when it needs one. Deserializing a scene through reflection also assigns new IDs, so
+/// don't use it for handles that need to survive a save and load.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Component, Reflect)]
+#[component(immutable, clone_behavior = Ignore)]
+#[reflect(Component, Default, Debug, PartialEq, Hash)]
+pub struct UniqueId(ProcessUniqueId);
+
+impl UniqueId {
+    /// Creates a component holding a new ID.
+    #[inline]
+    pub fn new() -> Self {
+        UniqueId(ProcessUniqueId::new())
+    }
+
+    /// The ID.
+    #[inline]
+    pub fn id(&self) -> ProcessUniqueId {
+        self.0
+    }
+}
+
+impl Default for UniqueId {
+    #[inline]
+    fn default() -> Self {
+        UniqueId::new()
+    }
+}
+
+/// Registers this crate's ID types, and [`UniqueId`], with the app's type registry.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct SnowflakePlugin;
+
+impl Plugin for SnowflakePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<UniqueId>()
+            .register_type::<ProcessUniqueId>()
+            .register_type::<SmallUniqueId>()
+            .register_type::<SnowflakeId>()
+            .register_type::<SonyflakeId>()
+            .register_type::<Tsid>()
+            .register_type::<ObjectId>()
+            .register_type::<Xid>()
+            .register_type::<FlakeId>()
+            .register_type::<PushId>();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SnowflakePlugin, UniqueId};
+    use crate::{ProcessUniqueId, Tsid};
+    use bevy_app::App;
+    use bevy_ecs::reflect::AppTypeRegistry;
+    use core::any::TypeId;
+
+    #[test]
+    fn test_plugin() {
+        let mut app = App::new();
+        app.add_plugins(SnowflakePlugin);
+        let registry = app.world().resource::<AppTypeRegistry>().read();
+        for id in [
+            TypeId::of::<UniqueId>(),
+            TypeId::of::<ProcessUniqueId>(),
+            TypeId::of::<Tsid>(),
+        ] {
+            assert!(registry.contains(id));
+        }
+        drop(registry);
+
+        let entity = app.world_mut().spawn(Tsid::from(7u64)).id();
+        assert_eq!(app.world().get::<Tsid>(entity), Some(&Tsid::from(7u64)));
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Mint, inspect, and convert IDs from the command line.
+
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
+use std::process;
+use std::time::UNIX_EPOCH;
+
+use snowflake::node::StaticNodeId;
+use snowflake::{ProcessUniqueId, SmallUniqueId, SnowflakeGenerator, SnowflakeId};
+
+const USAGE: &str = "\
+usage:
+    snowflake-cli mint <puid|spuid|snowflake> [count] [--node <id>]
+    snowflake-cli inspect <id> [--epoch-ms <ms>]
+    snowflake-cli convert <id> <string|int|hex>
+
+IDs may be given in their display form (puid-1-2, spuid-1-2, or a decimal snowflake ID) or as
+<kind>:<integer>, where the integer is decimal or 0x-prefixed hex (e.g., puid:0x10000000000000002).
+
+Process unique IDs are only unique within the process that minted them, so minting them here is
+mostly useful for testing.";
+
+/// A parsed ID of any kind.
+enum Id {
+    Process(ProcessUniqueId),
+    Small(SmallUniqueId),
+    Snowflake(SnowflakeId),
+}
+
+impl Id {
+    fn parse(s: &str) -> Result<Id, String> {
+        let invalid = || format!("invalid ID: {}", s);
+        if let Some((kind, value)) = s.split_once(':') {
+            let value = parse_int(value).ok_or_else(invalid)?;
+            return match kind {
+                "puid" => ProcessUniqueId::from_u128(value)
+                    .map(Id::Process)
+                    .ok_or_else(invalid),
+                "spuid" => u64::try_from(value)
+                    .map(|v| Id::Small(v.into()))
+                    .map_err(|_| invalid()),
+                "snowflake" => u64::try_from(value)
+                    .ok()
+                    .and_then(|v| SnowflakeId::try_from(v).ok())
+                    .map(Id::Snowflake)
+                    .ok_or_else(invalid),
+                _ => Err(format!("unknown ID kind: {}", kind)),
+            };
+        }
+        if s.starts_with("puid-") {
+            s.parse().map(Id::Process).map_err(|_| invalid())
+        } else if s.starts_with("spuid-") {
+            s.parse().map(Id::Small).map_err(|_| invalid())
+        } else {
+            s.parse().map(Id::Snowflake).map_err(|_| invalid())
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Id::Process(_) => "puid",
+            Id::Small(_) => "spuid",
+            Id::Snowflake(_) => "snowflake",
+        }
+    }
+
+    /// The ID as a single integer: `to_u128` for process unique IDs, the raw 64 bits otherwise.
+    fn to_int(&self) -> u128 {
+        match self {
+            Id::Process(id) => id.to_u128(),
+            Id::Small(id) => u64::from(*id).into(),
+            Id::Snowflake(id) => u64::from(*id).into(),
+        }
+    }
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Id::Process(id) => id.fmt(f),
+            Id::Small(id) => id.fmt(f),
+            Id::Snowflake(id) => id.fmt(f),
+        }
+    }
+}
+
+fn parse_int(s: &str) -> Option<u128> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u128::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Formats milliseconds since the UNIX epoch as an RFC 3339 UTC timestamp.
+fn format_unix_ms(ms: u64) -> String {
+    let days = (ms / 86_400_000) as i64;
+    let ms_of_day = ms % 86_400_000;
+    // Howard Hinnant's days-to-civil algorithm.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        ms_of_day / 3_600_000,
+        ms_of_day / 60_000 % 60,
+        ms_of_day / 1000 % 60,
+        ms_of_day % 1000
+    )
+}
+
+/// Removes `--<name> <value>` from `args`, returning the value.
+fn take_flag(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    match args.iter().position(|a| a == name) {
+        Some(i) if i + 1 < args.len() => {
+            let value = args.remove(i + 1);
+            args.remove(i);
+            Ok(Some(value))
+        }
+        Some(_) => Err(format!("{} requires a value", name)),
+        None => Ok(None),
+    }
+}
+
+fn mint(mut args: Vec<String>) -> Result<(), String> {
+    let node = take_flag(&mut args, "--node")?
+        .map(|n| {
+            n.parse::<u64>()
+                .map_err(|_| format!("invalid node ID: {}", n))
+        })
+        .transpose()?;
+    let (kind, count) = match args.as_slice() {
+        [kind] => (kind, 1),
+        [kind, count] => (
+            kind,
+            count
+                .parse::<u64>()
+                .map_err(|_| format!("invalid count: {}", count))?,
+        ),
+        _ => return Err(USAGE.into()),
+    };
+    match kind.as_str() {
+        "puid" => (0..count).for_each(|_| println!("{}", ProcessUniqueId::new())),
+        "spuid" => (0..count).for_each(|_| println!("{}", SmallUniqueId::new())),
+        "snowflake" => {
+            let gen = SnowflakeGenerator::from_provider(&StaticNodeId(node.unwrap_or(0)))
+                .map_err(|e| e.to_string())?;
+            (0..count).for_each(|_| println!("{}", gen.next()));
+        }
+        _ => return Err(format!("unknown ID kind: {}", kind)),
+    }
+    Ok(())
+}
+
+fn inspect(mut args: Vec<String>) -> Result<(), String> {
+    let epoch_ms = match take_flag(&mut args, "--epoch-ms")? {
+        Some(ms) => ms
+            .parse::<u64>()
+            .map_err(|_| format!("invalid epoch: {}", ms))?,
+        // The epoch generators use unless configured otherwise.
+        None => SnowflakeGenerator::new(0)
+            .epoch()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64,
+    };
+    let id = match args.as_slice() {
+        [id] => Id::parse(id)?,
+        _ => return Err(USAGE.into()),
+    };
+    println!("kind:      {}", id.kind());
+    match &id {
+        Id::Process(id) => {
+            let (prefix, offset) = (id.to_u128() >> 64, id.to_u128() as u64);
+            println!("prefix:    {:#x}", prefix);
+            println!("offset:    {:#x}", offset);
+        }
+        Id::Small(id) => {
+            println!("prefix:    {:#x}", id.prefix());
+            println!("offset:    {:#x}", id.offset());
+        }
+        Id::Snowflake(id) => {
+            let unix_ms = epoch_ms.saturating_add(id.timestamp());
+            println!(
+                "timestamp: {} ({})",
+                id.timestamp(),
+                format_unix_ms(unix_ms)
+            );
+            println!("node:      {}", id.node_id());
+            println!("sequence:  {}", id.sequence());
+        }
+    }
+    println!("string:    {}", id);
+    println!("int:       {}", id.to_int());
+    println!("hex:       {:#x}", id.to_int());
+    Ok(())
+}
+
+fn convert(args: Vec<String>) -> Result<(), String> {
+    let (id, encoding) = match args.as_slice() {
+        [id, encoding] => (Id::parse(id)?, encoding),
+        _ => return Err(USAGE.into()),
+    };
+    match encoding.as_str() {
+        "string" => println!("{}", id),
+        "int" => println!("{}", id.to_int()),
+        "hex" => println!("{:#x}", id.to_int()),
+        _ => return Err(format!("unknown encoding: {}", encoding)),
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("mint") => mint(args.split_off(1)),
+        Some("inspect") => inspect(args.split_off(1)),
+        Some("convert") => convert(args.split_off(1)),
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(USAGE.into()),
+    };
+    if let Err(e) = result {
+        eprintln!("snowflake-cli: {}", e);
+        process::exit(2);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_unix_ms, Id};
+
+    #[test]
+    fn test_format_unix_ms() {
+        assert_eq!(format_unix_ms(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_unix_ms(1_288_834_974_657),
+            "2010-11-04T01:42:54.657Z"
+        );
+    }
+
+    #[test]
+    fn test_parse_forms() {
+        for (input, expected) in &[
+            ("puid-1-2", "puid-1-2"),
+            ("puid:0x10000000000000002", "puid-1-2"),
+            ("spuid:0x10000000002", "spuid-1-2"),
+            ("snowflake:0x1000", "4096"),
+            ("4096", "4096"),
+        ] {
+            assert_eq!(Id::parse(input).unwrap().to_string(), *expected);
+        }
+        assert!(Id::parse("nope:1").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! clap integration.
+//!
+//! Requires the `clap` feature. The ID types implement clap's `ValueParserFactory`, so they can
+//! be used as argument types directly, with `clap::value_parser!` or with clap's derive:
+//!
+//! ```
+//! use clap::{value_parser, Arg, Command};
+//! use snowflake::{AnyId, ProcessUniqueId};
+//!
+//! let cmd = Command::new("jobs")
+//!     .arg(Arg::new("id").long("id").value_parser(value_parser!(ProcessUniqueId)))
+//!     // Takes any kind of ID the crate knows, e.g. `--parent 0DS8RXW6W0DYY`.
+//!     .arg(Arg::new("parent").long("parent").value_parser(value_parser!(AnyId)));
+//!
+//! let matches = cmd.clone().get_matches_from(["jobs", "--id", "puid-1-2a"]);
+//! assert_eq!(matches.get_one::<ProcessUniqueId>("id").unwrap().to_string(), "puid-1-2a");
+//!
+//! let err = cmd.try_get_matches_from(["jobs", "--id", "puid-1-2x"]).unwrap_err();
+//! assert!(err.to_string().contains(
+//!     "invalid value 'puid-1-2x' for '--id <id>': \
+//!      invalid ID at byte 8: expected a hex digit, found 'x'"
+//! ));
+//! ```
+//!
+//! IDs can't be listed ahead of time, so there are no possible values for shell completions to
+//! offer; completion scripts leave them to be typed (or pasted) in.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::str::FromStr;
+use std::ffi::OsStr;
+
+use clap::builder::{StringValueParser, TypedValueParser, ValueParserFactory};
+use clap::{Arg, Command};
+
+use crate::{
+    AnyId, FlakeId, LamportId, ObjectId, ParseIdError, ProcessUniqueId, PushId, SmallUniqueId,
+    SnowflakeId, SonyflakeId, Tsid, Xid,
+};
+
+/// Parses command line arguments into IDs of type `T` with their `FromStr` implementations,
+/// reporting the [`ParseIdError`] when they don't parse.
+pub struct IdValueParser<T> {
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> IdValueParser<T> {
+    /// Creates a parser for `T`.
+    #[inline]
+    pub fn new() -> Self {
+        IdValueParser {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for IdValueParser<T> {
+    #[inline]
+    fn default() -> Self {
+        IdValueParser::new()
+    }
+}
+
+impl<T> Clone for IdValueParser<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        IdValueParser::new()
+    }
+}
+
+impl<T> fmt::Debug for IdValueParser<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("IdValueParser")
+    }
+}
+
+impl<T> TypedValueParser for IdValueParser<T>
+where
+    T: FromStr<Err = ParseIdError> + Clone + Send + Sync + 'static,
+{
+    type Value = T;
+
+    fn parse_ref(&self, cmd: &Command, arg: Option<&Arg>, value: &OsStr) -> Result<T, clap::Error> {
+        // `try_map` puts the error after clap's "invalid value '...' for '...'".
+        StringValueParser::new()
+            .try_map(|s| s.parse::<T>())
+            .parse_ref(cmd, arg, value)
+    }
+}
+
+macro_rules! impl_value_parser_factory {
+    ($($ty:ident),*) => {$(
+        impl ValueParserFactory for $ty {
+            type Parser = IdValueParser<$ty>;
+
+            #[inline]
+            fn value_parser() -> IdValueParser<$ty> {
+                IdValueParser::new()
+            }
+        }
+    )*};
+}
+
+impl_value_parser_factory!(
+    ProcessUniqueId,
+    SnowflakeId,
+    FlakeId,
+    SonyflakeId,
+    Tsid,
+    SmallUniqueId,
+    ObjectId,
+    Xid,
+    PushId,
+    LamportId,
+    AnyId
+);
+
+#[cfg(test)]
+mod test {
+    use crate::{AnyId, SnowflakeGenerator, SnowflakeId};
+    use clap::error::ErrorKind;
+    use clap::{value_parser, Arg, Command};
+
+    #[test]
+    fn test_clap() {
+        let cmd = Command::new("test")
+            .arg(
+                Arg::new("id")
+                    .long("id")
+                    .value_parser(value_parser!(SnowflakeId)),
+            )
+            .arg(
+                Arg::new("any")
+                    .long("any")
+                    .value_parser(value_parser!(AnyId)),
+            );
+
+        let id = SnowflakeGenerator::new(1).next();
+        let matches = cmd
+            .clone()
+            .try_get_matches_from(["test", "--id", &id.to_string(), "--any", "puid-1-2"])
+            .unwrap();
+        assert_eq!(matches.get_one::<SnowflakeId>("id"), Some(&id));
+        assert!(matches!(
+            matches.get_one::<AnyId>("any"),
+            Some(AnyId::ProcessUniqueId(_))
+        ));
+
+        let err = cmd
+            .try_get_matches_from(["test", "--id", "12a"])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+        assert!(err.to_string().contains("invalid ID at byte 2"));
+    }
+}
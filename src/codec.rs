@@ -0,0 +1,129 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A binary format for checkpointing sequences of [`ProcessUniqueId`]s.
+//!
+//! # Format
+//!
+//! | bytes  | contents                                               |
+//! |--------|--------------------------------------------------------|
+//! | 4      | magic: `PUID`                                          |
+//! | 1      | format version: `1`                                    |
+//! | 8      | number of IDs, little-endian `u64`                     |
+//! | 16 × n | the IDs, each in its `to_bytes` form                   |
+//!
+//! Readers reject unknown versions rather than guessing, so the format can evolve.
+//!
+//! Note: as with deserialization, an ID read back in a *different* process is not unique in that
+//! process. Only reload checkpoints written by the current process (e.g., for a crash-consistent
+//! handoff between its components), or treat the IDs as opaque data.
+
+use std::io::{self, Read, Write};
+
+use crate::ProcessUniqueId;
+
+const MAGIC: &[u8; 4] = b"PUID";
+
+/// The format version written by [`write_ids`].
+pub const VERSION: u8 = 1;
+
+// Don't trust the length prefix with more than this much memory up front.
+const MAX_PREALLOCATE: usize = 4096;
+
+/// Writes `ids` to `writer`.
+pub fn write_ids<W: Write + ?Sized>(writer: &mut W, ids: &[ProcessUniqueId]) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])?;
+    writer.write_all(&(ids.len() as u64).to_le_bytes())?;
+    for id in ids {
+        writer.write_all(&id.to_bytes())?;
+    }
+    Ok(())
+}
+
+/// Reads IDs written by [`write_ids`] from `reader`.
+///
+/// Fails with [`io::ErrorKind::InvalidData`] if the input isn't an ID sequence, was written by an
+/// unsupported version, or contains an ID that can't exist on this platform; and with
+/// [`io::ErrorKind::UnexpectedEof`] if it's truncated.
+pub fn read_ids<R: Read + ?Sized>(reader: &mut R) -> io::Result<Vec<ProcessUniqueId>> {
+    let mut header = [0u8; 13];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(invalid_data("not a process unique ID sequence"));
+    }
+    if header[4] != VERSION {
+        return Err(invalid_data(format!(
+            "unsupported ID sequence version {}",
+            header[4]
+        )));
+    }
+    let mut len = [0u8; 8];
+    len.copy_from_slice(&header[5..]);
+    let len = u64::from_le_bytes(len);
+
+    let mut ids = Vec::with_capacity(len.min(MAX_PREALLOCATE as u64) as usize);
+    for _ in 0..len {
+        let mut bytes = [0u8; 16];
+        reader.read_exact(&mut bytes)?;
+        let id = ProcessUniqueId::from_bytes(bytes)
+            .map_err(|_| invalid_data("process unique ID prefix out of range"))?;
+        ids.push(id);
+    }
+    Ok(ids)
+}
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_ids, write_ids};
+    use crate::ProcessUniqueId;
+    use std::io;
+
+    #[test]
+    fn test_round_trip() {
+        let ids: Vec<_> = (0..100).map(|_| ProcessUniqueId::new()).collect();
+        let mut buf = Vec::new();
+        write_ids(&mut buf, &ids).unwrap();
+        assert_eq!(buf.len(), 13 + 16 * ids.len());
+        assert_eq!(read_ids(&mut &buf[..]).unwrap(), ids);
+
+        let mut empty = Vec::new();
+        write_ids(&mut empty, &[]).unwrap();
+        assert!(read_ids(&mut &empty[..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut buf = Vec::new();
+        write_ids(&mut buf, &[ProcessUniqueId::new()]).unwrap();
+
+        let err = read_ids(&mut &buf[..buf.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut bad_version = buf.clone();
+        bad_version[4] = 2;
+        let err = read_ids(&mut &bad_version[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let err = read_ids(&mut &b"JSON{}{}{}{}{}{}"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // A huge length prefix must not be trusted with an allocation.
+        let mut huge = buf[..5].to_vec();
+        huge.extend_from_slice(&u64::MAX.to_le_bytes());
+        let err = read_ids(&mut &huge[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
+
+use crate::display::pad_id;
+use crate::process_unique_id::{expect_tag, hex_field, hex_field_to_dash};
+use crate::{IdOutOfRange, ParseIdError, ProcessUniqueId};
+
+/// A small value (a shard, tenant, type tag, ...) stored in the high bits of a [`CompositeId`].
+///
+/// Implemented for `u8`, `u16`, and `u32`; implement it for your own types (e.g. an enum of
+/// entity kinds) to store them directly:
+///
+/// ```
+/// use snowflake::Discriminant;
+///
+/// #[derive(Copy, Clone, PartialEq, Debug)]
+/// enum Kind {
+///     User,
+///     Order,
+/// }
+///
+/// impl Discriminant for Kind {
+///     const BITS: u32 = 1;
+///
+///     fn to_bits(self) -> u32 {
+///         self as u32
+///     }
+///
+///     fn from_bits(bits: u32) -> Option<Self> {
+///         match bits {
+///             0 => Some(Kind::User),
+///             1 => Some(Kind::Order),
+///             _ => None,
+///         }
+///     }
+/// }
+/// ```
+///
+/// The bits are part of the encoded ID, so changing how a type maps to them changes the meaning
+/// of IDs that have already been stored.
+pub trait Discriminant: Copy {
+    /// The number of bits the discriminant takes, at most 32. Every bit taken is a bit less for
+    /// the prefix.
+    const BITS: u32;
+
+    /// The discriminant's bits, which must be less than `2^BITS`.
+    fn to_bits(self) -> u32;
+
+    /// Converts bits returned by `to_bits` back, returning `None` if they don't stand for a
+    /// discriminant.
+    fn from_bits(bits: u32) -> Option<Self>;
+}
+
+macro_rules! impl_discriminant {
+    ($($ty:ty),*) => {$(
+        impl Discriminant for $ty {
+            const BITS: u32 = <$ty>::BITS;
+
+            #[inline]
+            fn to_bits(self) -> u32 {
+                self.into()
+            }
+
+            #[inline]
+            fn from_bits(bits: u32) -> Option<Self> {
+                <$ty>::try_from(bits).ok()
+            }
+        }
+    )*};
+}
+
+impl_discriminant!(u8, u16, u32);
+
+/// A process unique ID tagged with a [`Discriminant`].
+///
+/// The discriminant is packed into the high bits of the ID's `u128` form, in place of the high
+/// bits of the prefix (which only get used after billions of threads):
+///
+/// ```
+/// use snowflake::{CompositeId, ProcessUniqueId};
+///
+/// let tenant = 7u16;
+/// let id = CompositeId::new(tenant);
+/// assert_eq!(id.discriminant(), 7);
+///
+/// let packed = id.to_u128();
+/// assert_eq!(packed >> 112, 7);
+/// assert_eq!(CompositeId::<u16>::from_u128(packed), Some(id));
+/// assert_eq!(id.to_string().parse(), Ok(id));
+/// ```
+///
+/// The encoding (`to_u128`, `to_bytes`, and the `cid-<discriminant>-<prefix>-<offset>` display
+/// form, all hex) is stable, and sorts by discriminant first and then by ID, as do the IDs
+/// themselves.
+#[derive(Copy, Clone)]
+pub struct CompositeId<D> {
+    discriminant: D,
+    id: ProcessUniqueId,
+}
+
+impl<D: Discriminant> CompositeId<D> {
+    /// Creates a new unique ID tagged with `discriminant`.
+    ///
+    /// **panics** if the discriminant's bits don't fit in `D::BITS`, or under the same
+    /// conditions as [`ProcessUniqueId::new`]. Running out of prefixes happens sooner the more
+    /// bits the discriminant takes: after 2^32 threads with a 32 bit discriminant.
+    #[cfg(feature = "std")]
+    pub fn new(discriminant: D) -> Self {
+        CompositeId::from_parts(discriminant, ProcessUniqueId::new())
+            .expect("the discriminant or the ID's prefix is out of range")
+    }
+
+    /// Tags an existing ID with `discriminant`.
+    ///
+    /// Fails if the discriminant's bits don't fit in `D::BITS`, or if the ID's prefix doesn't fit
+    /// in the `64 - D::BITS` bits left for it.
+    pub fn from_parts(discriminant: D, id: ProcessUniqueId) -> Result<Self, IdOutOfRange> {
+        let bits = u128::from(discriminant.to_bits());
+        if D::BITS > 32 || bits >> D::BITS != 0 || (id.prefix as u128) >> (64 - D::BITS) != 0 {
+            return Err(IdOutOfRange::new());
+        }
+        Ok(CompositeId { discriminant, id })
+    }
+
+    /// The discriminant.
+    #[inline]
+    pub fn discriminant(&self) -> D {
+        self.discriminant
+    }
+
+    /// The ID without its discriminant.
+    #[inline]
+    pub fn id(&self) -> ProcessUniqueId {
+        self.id
+    }
+
+    /// Packs the ID into a `u128`: the discriminant in the top `D::BITS` bits, then the prefix,
+    /// then the offset in the low 64 bits.
+    #[inline]
+    pub fn to_u128(&self) -> u128 {
+        // Shifted in two steps so a zero bit discriminant doesn't shift by 128.
+        ((u128::from(self.discriminant.to_bits()) << 96) << (32 - D::BITS)) | self.id.to_u128()
+    }
+
+    /// Unpacks an ID packed by [`to_u128`](CompositeId::to_u128).
+    ///
+    /// Returns `None` if the discriminant bits don't stand for a discriminant or the prefix
+    /// doesn't fit in a `usize`.
+    pub fn from_u128(value: u128) -> Option<Self> {
+        let bits = (value >> 96) >> (32 - D::BITS);
+        let discriminant = D::from_bits(bits as u32)?;
+        let id = ProcessUniqueId::from_u128(value & (u128::MAX >> D::BITS))?;
+        Some(CompositeId { discriminant, id })
+    }
+
+    /// Writes the ID as 16 bytes: [`to_u128`](CompositeId::to_u128) in big-endian.
+    #[inline]
+    pub fn to_bytes(&self) -> [u8; 16] {
+        self.to_u128().to_be_bytes()
+    }
+
+    /// Reads an ID written by [`to_bytes`](CompositeId::to_bytes).
+    #[inline]
+    pub fn from_bytes(bytes: [u8; 16]) -> Result<Self, IdOutOfRange> {
+        CompositeId::from_u128(u128::from_be_bytes(bytes)).ok_or_else(IdOutOfRange::new)
+    }
+}
+
+impl<D: Discriminant> PartialEq for CompositeId<D> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.to_u128() == other.to_u128()
+    }
+}
+
+impl<D: Discriminant> Eq for CompositeId<D> {}
+
+impl<D: Discriminant> PartialOrd for CompositeId<D> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<D: Discriminant> Ord for CompositeId<D> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_u128().cmp(&other.to_u128())
+    }
+}
+
+impl<D: Discriminant> Hash for CompositeId<D> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.to_u128().hash(state);
+    }
+}
+
+impl<D: Discriminant> fmt::Display for CompositeId<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(
+            f,
+            format_args!(
+                "cid-{:x}-{:x}-",
+                self.discriminant.to_bits(),
+                self.id.prefix
+            ),
+            format_args!("{:x}", self.id.offset),
+        )
+    }
+}
+
+impl<D: Discriminant> fmt::Debug for CompositeId<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CompositeId({})", self)
+    }
+}
+
+/// Parses the `cid-<discriminant>-<prefix>-<offset>` form produced by `Display`.
+impl<D: Discriminant> FromStr for CompositeId<D> {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        let start = expect_tag(s, "cid-", "`cid-`")?;
+        let (bits, prefix_start) = hex_field_to_dash(s, start)?;
+        let (prefix, offset_start) = hex_field_to_dash(s, prefix_start)?;
+        let offset = hex_field(s, offset_start, s.len())?;
+        let discriminant = u32::try_from(bits)
+            .ok()
+            .and_then(D::from_bits)
+            .ok_or_else(|| ParseIdError::at(s, start, "a valid discriminant"))?;
+        let prefix = usize::try_from(prefix)
+            .map_err(|_| ParseIdError::at(s, prefix_start, "a prefix that fits in a usize"))?;
+        CompositeId::from_parts(discriminant, ProcessUniqueId { prefix, offset }).map_err(|_| {
+            ParseIdError::at(
+                s,
+                prefix_start,
+                "a prefix that fits next to the discriminant",
+            )
+        })
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::CompositeId;
+    use crate::ProcessUniqueId;
+
+    #[test]
+    fn test_composite_id_encoding() {
+        let id: ProcessUniqueId = "puid-3-2a".parse().unwrap();
+        let tagged = CompositeId::from_parts(0xabu8, id).unwrap();
+        assert_eq!(tagged.to_u128(), 0xab00_0000_0000_0003_0000_0000_0000_002a);
+        assert_eq!(tagged.to_string(), "cid-ab-3-2a");
+        assert_eq!(CompositeId::from_bytes(tagged.to_bytes()), Ok(tagged));
+        assert_eq!("cid-ab-3-2a".parse(), Ok(tagged));
+        assert!("cid-100-3-2a".parse::<CompositeId<u8>>().is_err());
+        assert!("cid-ab-3".parse::<CompositeId<u8>>().is_err());
+
+        let unit = CompositeId::from_parts(0u32, id).unwrap();
+        assert_eq!(CompositeId::from_u128(unit.to_u128()), Some(unit));
+        let wide: ProcessUniqueId = "puid-100000000-0".parse().unwrap();
+        assert!(CompositeId::from_parts(1u32, wide).is_err());
+        assert!(CompositeId::from_parts(1u16, wide).is_ok());
+
+        assert!(CompositeId::new(1u8) < CompositeId::new(2u8));
+    }
+}
@@ -0,0 +1,399 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Generators built from configuration files.
+//!
+//! Requires the `config` feature. A [`GeneratorConfig`] deserializes from any serde format, so
+//! the kind of ID a service issues (and how) can change per environment without a rebuild. In
+//! TOML:
+//!
+//! ```toml
+//! kind = "snowflake"
+//! epoch_ms = 1288834974657
+//! drift = "wait"
+//!
+//! [node_id]
+//! provider = "kubernetes"
+//! offset = 100
+//! ```
+//!
+//! ```
+//! use snowflake::config::GeneratorConfig;
+//!
+//! let config: GeneratorConfig = serde_json::from_str(
+//!     r#"{"kind": "tsid", "node_bits": 12, "node_id": {"provider": "static", "id": 7}}"#,
+//! )
+//! .unwrap();
+//! let gen = config.build().unwrap();
+//! assert_eq!(gen.next_any().kind(), "tsid");
+//! ```
+//!
+//! Node IDs come from a [`NodeIdProvider`](crate::node::NodeIdProvider): `static` (with an
+//! `id`), `kubernetes` (with optional `index_var`, `pod_name_var` and `offset`, see
+//! [`KubernetesNodeId`]) or `private-ip` (with an optional `addr`, see [`PrivateIpNodeId`]).
+
+use std::error::Error;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::atomic::{AtomicU64, Ordering};
+use crate::node::{KubernetesNodeId, NodeIdError, NodeIdProvider, PrivateIpNodeId, StaticNodeId};
+use crate::{
+    AnyId, DynIdGen, FlakeGenerator, ObjectId, ProcessUniqueId, PushIdGenerator, SmallUniqueId,
+    SnowflakeGenerator, SonyflakeGenerator, TsidGenerator, XidGenerator,
+};
+
+/// The kind of ID to generate, named as in [`AnyId::kind`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeneratorKind {
+    /// [`ProcessUniqueId`]s.
+    Puid,
+    /// [`SmallUniqueId`]s.
+    Spuid,
+    /// [`SnowflakeId`](crate::SnowflakeId)s. Takes a node ID and an epoch.
+    Snowflake,
+    /// [`SonyflakeId`](crate::SonyflakeId)s. Takes a node ID and an epoch (the start time).
+    Sonyflake,
+    /// [`Tsid`](crate::Tsid)s. Takes a node ID and a number of node bits.
+    Tsid,
+    /// [`Xid`](crate::Xid)s. Takes a node ID (the machine ID).
+    Xid,
+    /// [`ObjectId`]s.
+    ObjectId,
+    /// [`FlakeId`](crate::FlakeId)s. Takes a node ID (the worker ID).
+    Flake,
+    /// [`PushId`](crate::PushId)s.
+    PushId,
+}
+
+impl GeneratorKind {
+    /// The kind's name, as in the configuration.
+    pub fn name(self) -> &'static str {
+        match self {
+            GeneratorKind::Puid => "puid",
+            GeneratorKind::Spuid => "spuid",
+            GeneratorKind::Snowflake => "snowflake",
+            GeneratorKind::Sonyflake => "sonyflake",
+            GeneratorKind::Tsid => "tsid",
+            GeneratorKind::Xid => "xid",
+            GeneratorKind::ObjectId => "objectid",
+            GeneratorKind::Flake => "flake",
+            GeneratorKind::PushId => "pushid",
+        }
+    }
+}
+
+/// Where a generator's node ID comes from.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize)]
+#[serde(tag = "provider", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum NodeIdConfig {
+    /// A fixed node ID.
+    Static {
+        /// The node ID.
+        id: u64,
+    },
+    /// The pod's identity; see [`KubernetesNodeId`].
+    Kubernetes {
+        /// Overrides the pod index environment variable.
+        #[serde(default)]
+        index_var: Option<String>,
+        /// Overrides the pod name environment variable.
+        #[serde(default)]
+        pod_name_var: Option<String>,
+        /// Added to the pod ordinal.
+        #[serde(default)]
+        offset: u64,
+    },
+    /// The host's private IPv4 address; see [`PrivateIpNodeId`].
+    PrivateIp {
+        /// The address to use instead of discovering it.
+        #[serde(default)]
+        addr: Option<Ipv4Addr>,
+    },
+}
+
+impl NodeIdConfig {
+    fn provider(&self) -> Box<dyn NodeIdProvider> {
+        match self {
+            NodeIdConfig::Static { id } => Box::new(StaticNodeId(*id)),
+            NodeIdConfig::Kubernetes {
+                index_var,
+                pod_name_var,
+                offset,
+            } => {
+                let mut provider = KubernetesNodeId::new().offset(*offset);
+                if let Some(name) = index_var {
+                    provider = provider.index_var(name.as_str());
+                }
+                if let Some(name) = pod_name_var {
+                    provider = provider.pod_name_var(name.as_str());
+                }
+                Box::new(provider)
+            }
+            NodeIdConfig::PrivateIp { addr: Some(addr) } => {
+                Box::new(PrivateIpNodeId::with_addr(*addr))
+            }
+            NodeIdConfig::PrivateIp { addr: None } => Box::new(PrivateIpNodeId::new()),
+        }
+    }
+}
+
+/// What to do when the system clock goes backwards.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DriftPolicy {
+    /// Keep issuing IDs, as the generators do on their own: time-based IDs carry on from the
+    /// latest time seen, so they're timestamped ahead of the clock until it catches up.
+    #[default]
+    Tolerate,
+    /// Block until the clock catches up with the latest time seen, so no ID is ever timestamped
+    /// ahead of the clock.
+    Wait,
+}
+
+/// The configuration of an ID generator; see the [module documentation](self).
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeneratorConfig {
+    /// The kind of ID to generate.
+    pub kind: GeneratorKind,
+    /// The epoch, in milliseconds since the UNIX epoch, for the kinds that take one. Defaults
+    /// to the kind's usual epoch.
+    #[serde(default)]
+    pub epoch_ms: Option<u64>,
+    /// The number of bits reserved for the node ID, for the kinds that take one. Defaults to
+    /// the kind's usual layout.
+    #[serde(default)]
+    pub node_bits: Option<u32>,
+    /// Where the node ID comes from, for the kinds that take one.
+    #[serde(default)]
+    pub node_id: Option<NodeIdConfig>,
+    /// What to do when the system clock goes backwards.
+    #[serde(default)]
+    pub drift: DriftPolicy,
+}
+
+impl GeneratorConfig {
+    /// Creates a configuration for `kind` with every other setting left at its default.
+    pub fn new(kind: GeneratorKind) -> Self {
+        GeneratorConfig {
+            kind,
+            epoch_ms: None,
+            node_bits: None,
+            node_id: None,
+            drift: DriftPolicy::Tolerate,
+        }
+    }
+
+    /// Builds the generator, looking up its node ID.
+    ///
+    /// Fails if a setting isn't supported by the kind of ID, or the kind needs a node ID and
+    /// none is configured or the provider can't supply one.
+    pub fn build(&self) -> Result<Box<dyn DynIdGen + Send + Sync>, ConfigError> {
+        let kind = self.kind;
+        let takes_epoch = matches!(kind, GeneratorKind::Snowflake | GeneratorKind::Sonyflake);
+        let takes_node_bits = kind == GeneratorKind::Tsid;
+        let takes_node_id = takes_epoch
+            || matches!(
+                kind,
+                GeneratorKind::Tsid | GeneratorKind::Xid | GeneratorKind::Flake
+            );
+        if self.epoch_ms.is_some() && !takes_epoch {
+            return Err(ConfigError::Unsupported {
+                kind,
+                setting: "epoch_ms",
+            });
+        }
+        if self.node_bits.is_some() && !takes_node_bits {
+            return Err(ConfigError::Unsupported {
+                kind,
+                setting: "node_bits",
+            });
+        }
+        let provider = match (&self.node_id, takes_node_id) {
+            (Some(node_id), true) => Some(node_id.provider()),
+            (None, true) => return Err(ConfigError::MissingNodeId(kind)),
+            (Some(_), false) => {
+                return Err(ConfigError::Unsupported {
+                    kind,
+                    setting: "node_id",
+                })
+            }
+            (None, false) => None,
+        };
+        let epoch = self
+            .epoch_ms
+            .map(|ms| UNIX_EPOCH + Duration::from_millis(ms));
+
+        let gen: Box<dyn DynIdGen + Send + Sync> = match (kind, provider) {
+            (GeneratorKind::Snowflake, Some(provider)) => {
+                let gen = SnowflakeGenerator::from_provider(&*provider)?;
+                Box::new(match epoch {
+                    Some(epoch) => gen.with_epoch(epoch),
+                    None => gen,
+                })
+            }
+            (GeneratorKind::Sonyflake, Some(provider)) => {
+                let gen = SonyflakeGenerator::from_provider(&*provider)?;
+                Box::new(match epoch {
+                    Some(epoch) => gen.with_start_time(epoch),
+                    None => gen,
+                })
+            }
+            (GeneratorKind::Tsid, Some(provider)) => {
+                let node_bits = self.node_bits.unwrap_or(10);
+                if node_bits > 20 {
+                    return Err(ConfigError::NodeBits(node_bits));
+                }
+                let node_id = provider.node_id(node_bits)?;
+                Box::new(TsidGenerator::with_node_bits(node_id, node_bits))
+            }
+            (GeneratorKind::Xid, Some(provider)) => {
+                Box::new(XidGenerator::from_provider(&*provider)?)
+            }
+            (GeneratorKind::Flake, Some(provider)) => {
+                Box::new(FlakeGenerator::from_provider(&*provider)?)
+            }
+            (GeneratorKind::Puid, _) => Box::new(ProcessUniqueId::new),
+            (GeneratorKind::Spuid, _) => Box::new(SmallUniqueId::new),
+            (GeneratorKind::ObjectId, _) => Box::new(ObjectId::new),
+            (GeneratorKind::PushId, _) => Box::new(PushIdGenerator::new()),
+            (_, None) => unreachable!("the node ID was checked above"),
+        };
+        Ok(match self.drift {
+            DriftPolicy::Tolerate => gen,
+            DriftPolicy::Wait => Box::new(WaitForClock {
+                inner: gen,
+                latest_ms: AtomicU64::new(0),
+            }),
+        })
+    }
+}
+
+/// A generator that holds back IDs while the clock is behind the latest reading it has seen.
+struct WaitForClock {
+    inner: Box<dyn DynIdGen + Send + Sync>,
+    latest_ms: AtomicU64,
+}
+
+impl DynIdGen for WaitForClock {
+    fn next_any(&self) -> AnyId {
+        loop {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_millis() as u64);
+            let latest = self.latest_ms.fetch_max(now, Ordering::Relaxed);
+            if now >= latest {
+                return self.inner.next_any();
+            }
+            thread::sleep(Duration::from_millis(latest - now));
+        }
+    }
+}
+
+/// An error returned by [`GeneratorConfig::build`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ConfigError {
+    /// The kind of ID doesn't take the setting.
+    Unsupported {
+        /// The kind of ID.
+        kind: GeneratorKind,
+        /// The setting's name.
+        setting: &'static str,
+    },
+    /// The kind of ID needs a node ID, but none is configured.
+    MissingNodeId(GeneratorKind),
+    /// More node bits than the kind of ID has room for.
+    NodeBits(u32),
+    /// The node ID provider couldn't supply a node ID.
+    NodeId(NodeIdError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Unsupported { kind, setting } => write!(
+                f,
+                "{} generators don't take a `{}` setting",
+                kind.name(),
+                setting
+            ),
+            ConfigError::MissingNodeId(kind) => {
+                write!(f, "{} generators need a `node_id` setting", kind.name())
+            }
+            ConfigError::NodeBits(bits) => write!(f, "{} node bits is too many", bits),
+            ConfigError::NodeId(e) => write!(f, "failed to get a node ID: {}", e),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::NodeId(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<NodeIdError> for ConfigError {
+    fn from(e: NodeIdError) -> Self {
+        ConfigError::NodeId(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ConfigError, DriftPolicy, GeneratorConfig, GeneratorKind, NodeIdConfig};
+    use crate::AnyId;
+
+    #[test]
+    fn test_generator_config() {
+        let config: GeneratorConfig = serde_json::from_str(
+            r#"{
+                "kind": "snowflake",
+                "epoch_ms": 1600000000000,
+                "drift": "wait",
+                "node_id": {"provider": "static", "id": 5}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.node_id, Some(NodeIdConfig::Static { id: 5 }));
+        assert_eq!(config.drift, DriftPolicy::Wait);
+        match config.build().unwrap().next_any() {
+            AnyId::SnowflakeId(id) => assert_eq!(id.node_id(), 5),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let gen = GeneratorConfig::new(GeneratorKind::ObjectId)
+            .build()
+            .unwrap();
+        assert_eq!(gen.next_any().kind(), "objectid");
+
+        let mut config = GeneratorConfig::new(GeneratorKind::Xid);
+        assert_eq!(
+            config.build().err(),
+            Some(ConfigError::MissingNodeId(GeneratorKind::Xid))
+        );
+        config.epoch_ms = Some(0);
+        assert_eq!(
+            config.build().err().unwrap().to_string(),
+            "xid generators don't take a `epoch_ms` setting"
+        );
+
+        let config = GeneratorConfig {
+            node_id: Some(NodeIdConfig::Static { id: 1 << 10 }),
+            ..GeneratorConfig::new(GeneratorKind::Snowflake)
+        };
+        assert!(matches!(config.build(), Err(ConfigError::NodeId(_))));
+        assert!(serde_json::from_str::<GeneratorConfig>(r#"{"kind": "ulid"}"#).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The correlation ID of the code running right now, for tagging log records.
+//!
+//! [`with_correlation_id`] sets the ID for the duration of a closure, and anything called from it
+//! (a logging layer, say) can look it up with [`current_correlation_id`] instead of having it
+//! passed down:
+//!
+//! ```
+//! use snowflake::context::{current_correlation_id, with_correlation_id};
+//! use snowflake::ProcessUniqueId;
+//!
+//! fn log(message: &str) -> String {
+//!     match current_correlation_id() {
+//!         Some(id) => format!("[{}] {}", id, message),
+//!         None => message.to_owned(),
+//!     }
+//! }
+//!
+//! let id = ProcessUniqueId::new();
+//! let line = with_correlation_id(id, || log("handling request"));
+//! assert_eq!(line, format!("[{}] handling request", id));
+//! assert_eq!(log("idle"), "idle");
+//! ```
+//!
+//! The closure's ID is kept in a thread local, so it doesn't follow async code across `.await`s.
+//! With the `tokio` feature, `scope_correlation_id` sets the ID for a future instead, in a task
+//! local. A closure's ID takes precedence over its task's.
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Arc;
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+#[cfg(feature = "tokio")]
+tokio::task_local! {
+    static TASK_CURRENT: Arc<str>;
+}
+
+/// Calls `f` with `id` as the current correlation ID, restoring the previous one (if any)
+/// afterwards.
+pub fn with_correlation_id<R>(id: impl fmt::Display, f: impl FnOnce() -> R) -> R {
+    // Restores the previous ID, even if `f` panics.
+    struct Restore(Option<Arc<str>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            let _ = CURRENT.try_with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let id = Arc::from(id.to_string());
+    let _restore = Restore(CURRENT.with(|current| current.replace(Some(id))));
+    f()
+}
+
+/// Polls `future` with `id` as the current correlation ID, on whatever thread it runs.
+///
+/// Requires the `tokio` feature, and must be polled from within a Tokio runtime.
+///
+/// ```
+/// use snowflake::context::{current_correlation_id, scope_correlation_id};
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// scope_correlation_id("job-7", async {
+///     tokio::task::yield_now().await;
+///     assert_eq!(current_correlation_id().as_deref(), Some("job-7"));
+/// })
+/// .await;
+/// # });
+/// ```
+#[cfg(feature = "tokio")]
+pub fn scope_correlation_id<F: std::future::Future>(
+    id: impl fmt::Display,
+    future: F,
+) -> impl std::future::Future<Output = F::Output> {
+    TASK_CURRENT.scope(Arc::from(id.to_string()), future)
+}
+
+/// The current correlation ID: that of the innermost [`with_correlation_id`] call on this thread,
+/// or else (with the `tokio` feature) of the `scope_correlation_id` future being polled.
+pub fn current_correlation_id() -> Option<Arc<str>> {
+    if let Some(id) = CURRENT
+        .try_with(|current| current.borrow().clone())
+        .ok()
+        .flatten()
+    {
+        return Some(id);
+    }
+    #[cfg(feature = "tokio")]
+    if let Ok(id) = TASK_CURRENT.try_with(Arc::clone) {
+        return Some(id);
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::{current_correlation_id, with_correlation_id};
+    use std::panic;
+
+    #[test]
+    fn test_nested_correlation_ids() {
+        assert_eq!(current_correlation_id(), None);
+        with_correlation_id("outer", || {
+            with_correlation_id(7, || {
+                assert_eq!(current_correlation_id().as_deref(), Some("7"));
+            });
+            let inner = panic::catch_unwind(|| with_correlation_id("inner", || panic!()));
+            assert!(inner.is_err());
+            assert_eq!(current_correlation_id().as_deref(), Some("outer"));
+            // Other threads don't see it.
+            let other = std::thread::spawn(current_correlation_id).join().unwrap();
+            assert_eq!(other, None);
+        });
+        assert_eq!(current_correlation_id(), None);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_task_correlation_id() {
+        use super::scope_correlation_id;
+
+        scope_correlation_id("task", async {
+            tokio::task::yield_now().await;
+            assert_eq!(current_correlation_id().as_deref(), Some("task"));
+            with_correlation_id("closure", || {
+                assert_eq!(current_correlation_id().as_deref(), Some("closure"));
+            });
+            assert_eq!(current_correlation_id().as_deref(), Some("task"));
+        })
+        .await;
+        assert_eq!(current_correlation_id(), None);
+    }
+}
@@ -0,0 +1,315 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Host unique IDs shared by cooperating processes.
+//!
+//! A [`CrossProcessGenerator`] hands out [`HostUniqueId`]s using prefixes claimed from a
+//! host-wide [`PrefixCounter`]. Two counters are provided:
+//!
+//! - [`LockedFileCounter`] (the default) stores the counter in a file guarded by an advisory lock.
+//!   It works everywhere and survives restarts, but every claim is a few syscalls.
+//! - `SharedMemoryCounter` (`shm` feature) keeps the counter in a shared memory mapping and
+//!   claims prefixes with an atomic compare-and-swap, without any syscalls.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use crate::display::pad_id;
+use crate::process_unique_id::{parse_tagged, ParseIdError};
+
+/// An ID that is unique among all processes sharing a prefix counter on the same host.
+///
+/// Like [`ProcessUniqueId`](crate::ProcessUniqueId), a host unique ID is a prefix followed by an
+/// offset, but the prefixes are allocated host-wide by a [`CrossProcessGenerator`].
+///
+/// # Memory layout
+///
+/// `#[repr(C)]`: the prefix then the offset, both `u64`s in native byte order, for 16 bytes with no
+/// padding on every target.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[repr(C)]
+pub struct HostUniqueId {
+    prefix: u64,
+    offset: u64,
+}
+
+const _: () = {
+    assert!(mem::offset_of!(HostUniqueId, prefix) == 0);
+    assert!(mem::offset_of!(HostUniqueId, offset) == 8);
+    assert!(mem::size_of::<HostUniqueId>() == 16);
+    assert!(mem::align_of::<HostUniqueId>() == mem::align_of::<u64>());
+};
+
+impl HostUniqueId {
+    /// The host-wide prefix.
+    #[inline]
+    pub fn prefix(self) -> u64 {
+        self.prefix
+    }
+
+    /// The offset within the prefix.
+    #[inline]
+    pub fn offset(self) -> u64 {
+        self.offset
+    }
+}
+
+impl fmt::Display for HostUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(
+            f,
+            format_args!("huid-{:x}-", self.prefix),
+            format_args!("{:x}", self.offset),
+        )
+    }
+}
+
+/// Parses the `huid-<prefix>-<offset>` form produced by `Display`.
+impl FromStr for HostUniqueId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        let (prefix, offset, _) = parse_tagged(s, "huid-", "`huid-`")?;
+        Ok(HostUniqueId { prefix, offset })
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    // The process that claimed `prefix`. A forked child must not keep using its parent's prefix.
+    pid: u32,
+    prefix: u64,
+    next_offset: u64,
+}
+
+/// A host-wide counter from which [`CrossProcessGenerator`]s claim prefixes.
+pub trait PrefixCounter {
+    /// Claims a prefix that no other caller (in any process sharing the counter) has claimed.
+    fn claim(&self) -> io::Result<u64>;
+}
+
+/// A [`PrefixCounter`] stored as decimal text in a file, guarded by an exclusive advisory lock.
+///
+/// Because the counter file outlives the processes using it, prefixes also stay unique across
+/// restarts (as long as the file isn't deleted).
+#[derive(Debug)]
+pub struct LockedFileCounter {
+    path: PathBuf,
+}
+
+impl LockedFileCounter {
+    /// Uses the counter file at `path`, creating it if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        Ok(LockedFileCounter { path })
+    }
+}
+
+impl PrefixCounter for LockedFileCounter {
+    fn claim(&self) -> io::Result<u64> {
+        let mut file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        file.lock()?;
+        let result = claim_locked(&mut file);
+        file.unlock()?;
+        result
+    }
+}
+
+/// Generates [`HostUniqueId`]s, coordinating with other processes through a [`PrefixCounter`].
+///
+/// Every generator claims a prefix from the counter and then hands out IDs from that prefix
+/// without touching the counter again. A process that forks (e.g., a pre-fork web server)
+/// notices that its PID changed and claims a fresh prefix in the child.
+#[derive(Debug)]
+pub struct CrossProcessGenerator<C = LockedFileCounter> {
+    counter: C,
+    state: Mutex<Option<State>>,
+}
+
+impl CrossProcessGenerator {
+    /// Creates a generator coordinating through the [`LockedFileCounter`] at `path`, creating it
+    /// if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        LockedFileCounter::open(path).map(CrossProcessGenerator::with_counter)
+    }
+}
+
+impl<C: PrefixCounter> CrossProcessGenerator<C> {
+    /// Creates a generator claiming prefixes from `counter`.
+    pub fn with_counter(counter: C) -> Self {
+        CrossProcessGenerator {
+            counter,
+            state: Mutex::new(None),
+        }
+    }
+
+    /// The counter prefixes are claimed from.
+    #[inline]
+    pub fn counter(&self) -> &C {
+        &self.counter
+    }
+
+    /// Generates a new ID, claiming a prefix from the counter first if necessary.
+    pub fn next(&self) -> io::Result<HostUniqueId> {
+        let pid = process::id();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let state = match &mut *state {
+            Some(state) if state.pid == pid => state,
+            state => state.insert(State {
+                pid,
+                prefix: self.counter.claim()?,
+                next_offset: 0,
+            }),
+        };
+        let id = HostUniqueId {
+            prefix: state.prefix,
+            offset: state.next_offset,
+        };
+        if state.next_offset == u64::MAX {
+            state.prefix = self.counter.claim()?;
+            state.next_offset = 0;
+        } else {
+            state.next_offset += 1;
+        }
+        Ok(id)
+    }
+}
+
+/// Increments the counter in the locked `file`, returning its previous value.
+fn claim_locked(file: &mut File) -> io::Result<u64> {
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let prefix: u64 = match contents.trim() {
+        "" => 0,
+        value => value.parse().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "corrupt prefix counter file")
+        })?,
+    };
+    let next = prefix
+        .checked_add(1)
+        .ok_or_else(|| io::Error::other("host prefix counter exhausted"))?;
+    file.seek(SeekFrom::Start(0))?;
+    file.set_len(0)?;
+    writeln!(file, "{}", next)?;
+    // Reaches the disk before the lock is released (and the prefix used), so a crash can't roll
+    // the counter back.
+    file.sync_data()?;
+    Ok(prefix)
+}
+
+/// A [`PrefixCounter`] kept in a shared memory mapping and claimed with an atomic
+/// compare-and-swap.
+///
+/// Every process that maps the same file shares the counter. Put the file on a memory-backed
+/// filesystem (e.g., `/dev/shm/<name>` on Linux, which is where `shm_open` segments live) to keep
+/// claims off the disk entirely; prefixes are then unique until the host reboots.
+///
+/// Requires the `shm` feature, and isn't available with the `safe` feature: mapping the file takes
+/// `unsafe` code.
+#[cfg(all(feature = "shm", not(feature = "safe")))]
+#[derive(Debug)]
+pub struct SharedMemoryCounter {
+    map: memmap2::MmapMut,
+}
+
+#[cfg(all(feature = "shm", not(feature = "safe")))]
+impl SharedMemoryCounter {
+    /// Maps the counter stored in the first 8 bytes of the file at `path`, creating the file
+    /// (with the counter at zero) if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(path)?;
+        // Growing the file zero-fills it, so racing openers agree on the initial value.
+        if file.metadata()?.len() < 8 {
+            file.set_len(8)?;
+        }
+        // SAFETY: the counter is only ever accessed atomically (see `counter`), so concurrent
+        // modification by other processes is fine.
+        let map = unsafe { memmap2::MmapOptions::new().len(8).map_mut(&file)? };
+        Ok(SharedMemoryCounter { map })
+    }
+
+    // This must be a native atomic: a lock-based fallback (e.g. `portable-atomic` on some
+    // targets) only excludes threads within the same process.
+    fn counter(&self) -> &core::sync::atomic::AtomicU64 {
+        // SAFETY: the mapping is page aligned, at least 8 bytes long, lives as long as `self`,
+        // and is only accessed through this atomic.
+        unsafe { &*(self.map.as_ptr() as *const core::sync::atomic::AtomicU64) }
+    }
+}
+
+#[cfg(all(feature = "shm", not(feature = "safe")))]
+impl PrefixCounter for SharedMemoryCounter {
+    fn claim(&self) -> io::Result<u64> {
+        use core::sync::atomic::Ordering;
+
+        self.counter()
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |prev| {
+                prev.checked_add(1)
+            })
+            .map_err(|_| io::Error::other("host prefix counter exhausted"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CrossProcessGenerator;
+    use std::fs;
+
+    #[test]
+    fn test_generators_share_counter() {
+        let path = std::env::temp_dir().join(format!("snowflake-xproc-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let a = CrossProcessGenerator::open(&path).unwrap();
+        let b = CrossProcessGenerator::open(&path).unwrap();
+        let a1 = a.next().unwrap();
+        let b1 = b.next().unwrap();
+        let a2 = a.next().unwrap();
+        assert_eq!((a1.prefix(), a1.offset()), (0, 0));
+        assert_eq!((b1.prefix(), b1.offset()), (1, 0));
+        assert_eq!((a2.prefix(), a2.offset()), (0, 1));
+        assert_eq!(fs::read_to_string(&path).unwrap().trim(), "2");
+        assert_eq!(b1.to_string(), "huid-1-0");
+        assert_eq!("huid-1-0".parse(), Ok(b1));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(all(feature = "shm", not(feature = "safe")))]
+    #[test]
+    fn test_shared_memory_counter() {
+        use super::{PrefixCounter, SharedMemoryCounter};
+
+        let path = std::env::temp_dir().join(format!("snowflake-shm-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let a = SharedMemoryCounter::open(&path).unwrap();
+        let b = SharedMemoryCounter::open(&path).unwrap();
+        assert_eq!(a.claim().unwrap(), 0);
+        assert_eq!(b.claim().unwrap(), 1);
+        assert_eq!(a.claim().unwrap(), 2);
+
+        let gen = CrossProcessGenerator::with_counter(b);
+        assert_eq!(gen.next().unwrap().prefix(), 3);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,249 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::Index;
+
+use crate::ProcessUniqueId;
+
+/// The number of low offset bits that index into a segment's table. Threads claim offsets in
+/// blocks of 2^32 by default, so each thread's IDs normally land in a segment of their own.
+const SEGMENT_BITS: u32 = 32;
+
+/// The IDs sharing a prefix and the high bits of their offset, indexed by the low bits.
+#[derive(Clone)]
+struct Segment<V> {
+    key: (usize, u64),
+    slots: Vec<Option<V>>,
+}
+
+#[inline]
+fn split(id: ProcessUniqueId) -> ((usize, u64), usize) {
+    let index = id.offset & ((1 << SEGMENT_BITS) - 1);
+    ((id.prefix, id.offset >> SEGMENT_BITS), index as usize)
+}
+
+/// A map keyed by process unique IDs, laid out to take advantage of how they're created.
+///
+/// Each thread hands out IDs with the same prefix and consecutive offsets, so instead of hashing
+/// the map keeps a short sorted list of "segments" (one per prefix and block of offsets, so
+/// roughly one per thread that created IDs) and stores the values of each in a table indexed by
+/// offset. A lookup is a binary search over a handful of segments and an index into a `Vec`.
+///
+/// ```
+/// use snowflake::{DensePrefixMap, ProcessUniqueId};
+///
+/// let mut entities = DensePrefixMap::new();
+/// let ids: Vec<_> = (0..100).map(|_| ProcessUniqueId::new()).collect();
+/// for (i, &id) in ids.iter().enumerate() {
+///     entities.insert(id, i);
+/// }
+/// assert_eq!(entities[&ids[42]], 42);
+/// assert_eq!(entities.remove(&ids[42]), Some(42));
+/// assert_eq!(entities.len(), 99);
+/// ```
+///
+/// **Memory:** a segment's table is as long as the largest offset (within the segment) inserted
+/// so far, whether or not the IDs before it are in the map, and tables don't shrink when values
+/// are removed. That's a good fit for IDs created by this process and kept around for a while,
+/// like entities, and a bad fit for a few IDs out of a long running process (or IDs parsed from
+/// elsewhere), which are better kept in an [`IdMap`](crate::IdMap).
+#[derive(Clone)]
+pub struct DensePrefixMap<V> {
+    segments: Vec<Segment<V>>,
+    len: usize,
+}
+
+impl<V> DensePrefixMap<V> {
+    /// Creates an empty map.
+    #[inline]
+    pub fn new() -> Self {
+        DensePrefixMap {
+            segments: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// The number of values in the map.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the map has no values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    fn segment(&self, key: (usize, u64)) -> Result<usize, usize> {
+        self.segments.binary_search_by_key(&key, |s| s.key)
+    }
+
+    /// Returns the value for `id`, if any.
+    #[inline]
+    pub fn get(&self, id: &ProcessUniqueId) -> Option<&V> {
+        let (key, index) = split(*id);
+        let segment = &self.segments[self.segment(key).ok()?];
+        segment.slots.get(index)?.as_ref()
+    }
+
+    /// Returns a mutable reference to the value for `id`, if any.
+    #[inline]
+    pub fn get_mut(&mut self, id: &ProcessUniqueId) -> Option<&mut V> {
+        let (key, index) = split(*id);
+        let segment = self.segment(key).ok()?;
+        self.segments[segment].slots.get_mut(index)?.as_mut()
+    }
+
+    /// Returns true if the map has a value for `id`.
+    #[inline]
+    pub fn contains_key(&self, id: &ProcessUniqueId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Inserts a value for `id`, returning the value it replaces, if any.
+    pub fn insert(&mut self, id: ProcessUniqueId, value: V) -> Option<V> {
+        let (key, index) = split(id);
+        let segment = match self.segment(key) {
+            Ok(segment) => segment,
+            Err(at) => {
+                self.segments.insert(
+                    at,
+                    Segment {
+                        key,
+                        slots: Vec::new(),
+                    },
+                );
+                at
+            }
+        };
+        let slots = &mut self.segments[segment].slots;
+        if slots.len() <= index {
+            slots.resize_with(index + 1, || None);
+        }
+        let old = slots[index].replace(value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Removes the value for `id`, returning it if there was one.
+    pub fn remove(&mut self, id: &ProcessUniqueId) -> Option<V> {
+        let (key, index) = split(*id);
+        let segment = self.segment(key).ok()?;
+        let old = self.segments[segment].slots.get_mut(index)?.take();
+        if old.is_some() {
+            self.len -= 1;
+        }
+        old
+    }
+
+    /// Removes all values, freeing the tables.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.segments.clear();
+        self.len = 0;
+    }
+
+    /// Iterates over the IDs and values in the map, in ID order.
+    pub fn iter(&self) -> impl Iterator<Item = (ProcessUniqueId, &V)> + '_ {
+        self.segments.iter().flat_map(|segment| {
+            let (prefix, high) = segment.key;
+            segment
+                .slots
+                .iter()
+                .enumerate()
+                .filter_map(move |(index, slot)| {
+                    let id = ProcessUniqueId {
+                        prefix,
+                        offset: high << SEGMENT_BITS | index as u64,
+                    };
+                    Some((id, slot.as_ref()?))
+                })
+        })
+    }
+}
+
+impl<V> Default for DensePrefixMap<V> {
+    #[inline]
+    fn default() -> Self {
+        DensePrefixMap::new()
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for DensePrefixMap<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// **panics** if there's no value for the ID.
+impl<V> Index<&ProcessUniqueId> for DensePrefixMap<V> {
+    type Output = V;
+
+    #[inline]
+    fn index(&self, id: &ProcessUniqueId) -> &V {
+        self.get(id).expect("no value for the ID")
+    }
+}
+
+impl<V> Extend<(ProcessUniqueId, V)> for DensePrefixMap<V> {
+    fn extend<I: IntoIterator<Item = (ProcessUniqueId, V)>>(&mut self, iter: I) {
+        for (id, value) in iter {
+            self.insert(id, value);
+        }
+    }
+}
+
+impl<V> FromIterator<(ProcessUniqueId, V)> for DensePrefixMap<V> {
+    fn from_iter<I: IntoIterator<Item = (ProcessUniqueId, V)>>(iter: I) -> Self {
+        let mut map = DensePrefixMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DensePrefixMap;
+    use crate::ProcessUniqueId;
+    use std::thread;
+
+    #[test]
+    fn test_dense_prefix_map() {
+        let mut ids: Vec<_> = (0..100).map(|_| ProcessUniqueId::new()).collect();
+        ids.extend(
+            thread::spawn(|| (0..100).map(|_| ProcessUniqueId::new()).collect::<Vec<_>>())
+                .join()
+                .unwrap(),
+        );
+        let mut map: DensePrefixMap<usize> =
+            ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+        assert_eq!(map.len(), 200);
+        assert!(ids.iter().enumerate().all(|(i, id)| map[id] == i));
+        assert!(!map.contains_key(&ProcessUniqueId::new()));
+
+        assert_eq!(map.insert(ids[7], 1000), Some(7));
+        *map.get_mut(&ids[8]).unwrap() += 1000;
+        assert_eq!(map.remove(&ids[9]), Some(9));
+        assert_eq!(map.remove(&ids[9]), None);
+        assert_eq!(map.len(), 199);
+
+        let mut sorted = ids.clone();
+        sorted.remove(9);
+        sorted.sort();
+        assert!(map.iter().map(|(id, _)| id).eq(sorted));
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&ids[0]), None);
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::fmt;
+
+/// Displays an ID with a custom prefix instead of the default one.
+///
+/// Returned by [`ProcessUniqueId::display_as`](crate::ProcessUniqueId::display_as) and
+/// [`SmallUniqueId::display_as`](crate::SmallUniqueId::display_as).
+#[derive(Copy, Clone, Debug)]
+pub struct DisplayAs<'a> {
+    label: &'a str,
+    prefix: u64,
+    offset: u64,
+}
+
+impl<'a> DisplayAs<'a> {
+    #[inline]
+    pub(crate) fn new(label: &'a str, prefix: u64, offset: u64) -> Self {
+        DisplayAs {
+            label,
+            prefix,
+            offset,
+        }
+    }
+}
+
+impl fmt::Display for DisplayAs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(
+            f,
+            format_args!("{}-{:x}-", self.label, self.prefix),
+            format_args!("{:x}", self.offset),
+        )
+    }
+}
+
+/// Counts the characters written to it.
+struct CharCount(usize);
+
+impl fmt::Write for CharCount {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.chars().count();
+        Ok(())
+    }
+}
+
+/// Passes the first `remaining` characters written to it on to a formatter.
+struct Truncate<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+    remaining: usize,
+}
+
+impl fmt::Write for Truncate<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = s
+            .char_indices()
+            .nth(self.remaining)
+            .map_or(s.len(), |(i, _)| i);
+        self.remaining -= s[..end].chars().count();
+        self.f.write_str(&s[..end])
+    }
+}
+
+/// Writes an ID made up of `head` (its tag and any fields before the last one) followed by
+/// `digits` (its last field), honoring the formatter's flags the way `str` does: precision
+/// truncates, and width pads with the fill character according to the alignment (left by
+/// default).
+///
+/// The `0` flag pads with zeros between `head` and `digits` instead, like it pads numbers after
+/// their sign, so `format!("{:012}", id)` gives e.g. `puid-0-00002a`.
+pub(crate) fn pad_id(
+    f: &mut fmt::Formatter,
+    head: fmt::Arguments,
+    digits: fmt::Arguments,
+) -> fmt::Result {
+    use core::fmt::Write;
+
+    if f.width().is_none() && f.precision().is_none() {
+        f.write_fmt(head)?;
+        return f.write_fmt(digits);
+    }
+
+    let mut count = CharCount(0);
+    count.write_fmt(head)?;
+    count.write_fmt(digits)?;
+    let len = f
+        .precision()
+        .map_or(count.0, |precision| precision.min(count.0));
+    let padding = f.width().unwrap_or(0).saturating_sub(len);
+    let zeros = f.sign_aware_zero_pad();
+    let (before, after) = match f.align() {
+        _ if zeros => (0, 0),
+        None | Some(fmt::Alignment::Left) => (0, padding),
+        Some(fmt::Alignment::Right) => (padding, 0),
+        Some(fmt::Alignment::Center) => (padding / 2, padding - padding / 2),
+    };
+    let fill = f.fill();
+
+    for _ in 0..before {
+        f.write_char(fill)?;
+    }
+    let mut out = Truncate { f, remaining: len };
+    out.write_fmt(head)?;
+    if zeros {
+        for _ in 0..padding {
+            out.f.write_char('0')?;
+        }
+    }
+    out.write_fmt(digits)?;
+    for _ in 0..after {
+        f.write_char(fill)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use crate::{ProcessUniqueId, SnowflakeId};
+    use core::convert::TryFrom;
+
+    #[test]
+    fn test_pad_id() {
+        let id: ProcessUniqueId = "puid-0-2a".parse().unwrap();
+        assert_eq!(format!("{}", id), "puid-0-2a");
+        assert_eq!(format!("[{:12}]", id), "[puid-0-2a   ]");
+        assert_eq!(format!("[{:>12}]", id), "[   puid-0-2a]");
+        assert_eq!(format!("[{:*^13}]", id), "[**puid-0-2a**]");
+        assert_eq!(format!("{:012}", id), "puid-0-0002a");
+        assert_eq!(format!("{:.6}", id), "puid-0");
+        assert_eq!(format!("[{:>8.6}]", id), "[  puid-0]");
+        assert_eq!(format!("{:4}", id), "puid-0-2a");
+        assert_eq!(
+            format!("{:08}", SnowflakeId::try_from(42u64).unwrap()),
+            "00000042"
+        );
+        assert_eq!(format!("{:>6}", id.display_as("é")), "é-0-2a");
+        assert_eq!(format!("{:>7}", id.display_as("é")), " é-0-2a");
+    }
+}
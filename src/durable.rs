@@ -0,0 +1,436 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! IDs that stay unique across restarts.
+//!
+//! # Durable process unique IDs
+//!
+//! [`init`] points the process-wide prefix counter at a data directory. The counter starts from
+//! the high-water mark recorded there by previous runs, and every time a prefix at or beyond the
+//! recorded mark is handed out, a new mark is durably written (to a temporary file which is
+//! synced and atomically renamed into place) *before* any ID using that prefix is returned. IDs
+//! created after `init` are therefore unique across all processes that ever used the directory,
+//! as long as only one process uses it at a time.
+//!
+//! Marks are reserved [`RESERVE`] prefixes at a time, so the directory is written at most once
+//! every `RESERVE` prefix allocations (i.e., new threads) and a restart skips at most that many
+//! prefixes.
+//!
+//! ```no_run
+//! snowflake::durable::init("/var/lib/my-service").expect("failed to load ID high-water mark");
+//! let id = snowflake::ProcessUniqueId::new();
+//! ```
+//!
+//! Durable IDs and a prefix counter shared between copies of the crate (see
+//! [`ProcessUniqueId::use_prefix_counter`](crate::ProcessUniqueId::use_prefix_counter)) don't
+//! mix: the high-water mark only covers the prefixes claimed through the copy that called
+//! `init`, not those other copies claim from its counter. Whichever of `init` and
+//! `use_prefix_counter` is called second fails, and a copy that called `init` shouldn't hand its
+//! [`prefix_counter`](crate::ProcessUniqueId::prefix_counter) to others:
+//!
+//! ```
+//! use snowflake::{durable, AtomicPrefixCounter, ProcessUniqueId};
+//!
+//! let dir = std::env::temp_dir().join(format!("snowflake-durable-{}", std::process::id()));
+//! std::fs::create_dir_all(&dir).unwrap();
+//! durable::init(&dir).unwrap();
+//!
+//! static HOST: AtomicPrefixCounter = AtomicPrefixCounter::new(0);
+//! assert!(ProcessUniqueId::use_prefix_counter(&HOST).is_err());
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+//!
+//! # Durable sequences
+//!
+//! A [`DurableSequence`] hands out consecutive `u64`s the way a database sequence does: it
+//! durably records the end of a block of IDs, then issues the IDs in that block from memory.
+//!
+//! # Durable snowflake generators
+//!
+//! [`SnowflakeGenerator::with_persistence`](crate::SnowflakeGenerator::with_persistence) records
+//! a high-water mark for the generator's timestamps in a file, [`TIMESTAMP_RESERVE_MS`] ahead of
+//! the latest timestamp issued. After a restart the generator won't issue IDs below the mark,
+//! even if the clock went backwards while the process was down; it waits for the clock to catch
+//! up instead.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::atomic::{AtomicU64, Ordering};
+use crate::process_unique_id::{global_counter, uses_shared_counter};
+
+/// The name of the high-water mark file within the data directory.
+pub const FILE_NAME: &str = "snowflake-prefix.hwm";
+
+/// How many prefixes are reserved each time the high-water mark is advanced.
+pub const RESERVE: usize = 64;
+
+/// How far ahead of the latest issued timestamp a persisted snowflake generator records its
+/// high-water mark, in milliseconds.
+pub const TIMESTAMP_RESERVE_MS: u64 = 1000;
+
+static DURABLE: OnceLock<HighWaterMark> = OnceLock::new();
+
+/// An error returned by [`init`].
+#[derive(Debug)]
+pub enum InitError {
+    /// Process unique IDs were created before `init` was called, or `init` was called twice.
+    AlreadyInUse,
+    /// The high-water mark file exists but doesn't contain a valid mark.
+    Corrupt(PathBuf),
+    /// Reading or writing the high-water mark failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InitError::AlreadyInUse => {
+                f.write_str("process unique IDs were already in use before durable::init")
+            }
+            InitError::Corrupt(path) => {
+                write!(f, "corrupt high-water mark file: {}", path.display())
+            }
+            InitError::Io(e) => write!(f, "failed to persist high-water mark: {}", e),
+        }
+    }
+}
+
+impl Error for InitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InitError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for InitError {
+    fn from(e: io::Error) -> Self {
+        InitError::Io(e)
+    }
+}
+
+/// Makes process unique IDs durable using the high-water mark stored in `dir`.
+///
+/// Must be called before the first process unique ID is created (including IDs created
+/// indirectly, e.g., by an [`IdStream`](crate::IdStream)), before any other threads that might
+/// create IDs are started. Fails with [`InitError::AlreadyInUse`] if this copy of the crate claims
+/// prefixes from another copy's counter (see the [module documentation](self)):
+///
+/// ```
+/// use snowflake::{durable, AtomicPrefixCounter, ProcessUniqueId};
+///
+/// static HOST: AtomicPrefixCounter = AtomicPrefixCounter::new(0);
+/// ProcessUniqueId::use_prefix_counter(&HOST).unwrap();
+/// let dir = std::env::temp_dir().join(format!("snowflake-shared-{}", std::process::id()));
+/// assert!(matches!(durable::init(&dir), Err(durable::InitError::AlreadyInUse)));
+/// ```
+pub fn init<P: AsRef<Path>>(dir: P) -> Result<(), InitError> {
+    if uses_shared_counter() {
+        return Err(InitError::AlreadyInUse);
+    }
+    let mark = HighWaterMark::open(dir.as_ref())?;
+    let start = *mark.persisted.lock().unwrap();
+    global_counter()
+        .compare_exchange(0, start, Ordering::Relaxed, Ordering::Relaxed)
+        .map_err(|_| InitError::AlreadyInUse)?;
+    DURABLE.set(mark).map_err(|_| InitError::AlreadyInUse)
+}
+
+/// Whether [`init`] has been called.
+pub(crate) fn is_initialized() -> bool {
+    DURABLE.get().is_some()
+}
+
+/// Called by the prefix counter for every prefix it hands out.
+///
+/// **panics** if the new high-water mark can't be persisted: handing out the prefix anyways could
+/// lead to duplicate IDs after a restart.
+#[inline]
+pub(crate) fn on_prefix_allocated(prefix: usize) {
+    if let Some(mark) = DURABLE.get() {
+        mark.ensure_covers(prefix)
+            .unwrap_or_else(|e| panic!("failed to persist ID high-water mark: {}", e));
+    }
+}
+
+struct HighWaterMark {
+    dir: PathBuf,
+    // Every prefix below this has been durably recorded as (possibly) used.
+    persisted: Mutex<usize>,
+}
+
+impl HighWaterMark {
+    fn open(dir: &Path) -> Result<Self, InitError> {
+        let path = dir.join(FILE_NAME);
+        let persisted = match read_counter(&path) {
+            Ok(mark) => mark.unwrap_or(0) as usize,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                return Err(InitError::Corrupt(path))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(HighWaterMark {
+            dir: dir.into(),
+            persisted: Mutex::new(persisted),
+        })
+    }
+
+    fn ensure_covers(&self, prefix: usize) -> io::Result<()> {
+        let mut persisted = self.persisted.lock().unwrap_or_else(|e| e.into_inner());
+        if prefix < *persisted {
+            return Ok(());
+        }
+        let mark = prefix.saturating_add(RESERVE);
+        self.write(mark)?;
+        *persisted = mark;
+        Ok(())
+    }
+
+    fn write(&self, mark: usize) -> io::Result<()> {
+        write_atomically(&self.dir.join(FILE_NAME), mark as u64)
+    }
+}
+
+/// The high-water mark of a persisted snowflake generator's timestamps.
+#[derive(Debug)]
+pub(crate) struct TimestampMark {
+    path: PathBuf,
+    // Milliseconds since the UNIX epoch. Every timestamp below this has been durably recorded as
+    // (possibly) used.
+    persisted: AtomicU64,
+    write: Mutex<()>,
+}
+
+impl TimestampMark {
+    pub(crate) fn open(path: &Path) -> io::Result<Self> {
+        let persisted = read_counter(path)?.unwrap_or(0);
+        Ok(TimestampMark {
+            path: path.into(),
+            persisted: AtomicU64::new(persisted),
+            write: Mutex::new(()),
+        })
+    }
+
+    /// The first timestamp (in milliseconds since the UNIX epoch) that may be issued.
+    pub(crate) fn start(&self) -> u64 {
+        self.persisted.load(Ordering::Acquire)
+    }
+
+    /// Makes sure `unix_ms` is below the persisted mark, advancing the mark if it isn't.
+    ///
+    /// **panics** if the new mark can't be persisted: issuing the ID anyways could lead to
+    /// duplicate IDs after a restart.
+    #[inline]
+    pub(crate) fn ensure_covers(&self, unix_ms: u64) {
+        if unix_ms >= self.persisted.load(Ordering::Acquire) {
+            self.advance(unix_ms);
+        }
+    }
+
+    #[cold]
+    fn advance(&self, unix_ms: u64) {
+        let _guard = self.write.lock().unwrap_or_else(|e| e.into_inner());
+        if unix_ms < self.persisted.load(Ordering::Acquire) {
+            return;
+        }
+        let mark = unix_ms.saturating_add(TIMESTAMP_RESERVE_MS);
+        write_atomically(&self.path, mark)
+            .unwrap_or_else(|e| panic!("failed to persist snowflake timestamp mark: {}", e));
+        self.persisted.store(mark, Ordering::Release);
+    }
+}
+
+/// Reads a counter written by `write_atomically`, returning `None` if the file doesn't exist.
+fn read_counter(path: &Path) -> io::Result<Option<u64>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => contents.trim().parse().map(Some).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("corrupt counter file: {}", path.display()),
+            )
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Durably replaces the contents of `path` with `value`: the value is written to a temporary
+/// file, synced, and renamed into place.
+fn write_atomically(path: &Path, value: u64) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = File::create(&tmp)?;
+    writeln!(file, "{}", value)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp, path)?;
+    // Make the rename itself durable. Directories can't be opened on all platforms; there the
+    // rename is as durable as the OS makes it.
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = File::open(dir) {
+        dir.sync_all()?;
+    }
+    Ok(())
+}
+
+/// A durable, block-reserving `u64` sequence.
+///
+/// The sequence file records the first value *not* yet reserved. Whenever the current block runs
+/// out, the end of the next block is written (and synced) before any value from it is handed out,
+/// so a crash loses at most the unused remainder of one block and values are never reissued.
+///
+/// Only one `DurableSequence` (in one process) may use a given file at a time.
+#[derive(Debug)]
+pub struct DurableSequence {
+    path: PathBuf,
+    block_size: u64,
+    // (next value, end of the reserved block)
+    state: Mutex<(u64, u64)>,
+}
+
+impl DurableSequence {
+    /// Opens (or creates) the sequence stored at `path`, reserving `block_size` values at a time.
+    ///
+    /// **panics** if `block_size` is zero.
+    pub fn open<P: AsRef<Path>>(path: P, block_size: u64) -> io::Result<Self> {
+        assert!(block_size > 0, "block size must be non-zero");
+        let path = path.as_ref().to_path_buf();
+        let start = read_counter(&path)?.unwrap_or(0);
+        Ok(DurableSequence {
+            path,
+            block_size,
+            state: Mutex::new((start, start)),
+        })
+    }
+
+    /// Returns the next value in the sequence, reserving a new block first if necessary.
+    pub fn next(&self) -> io::Result<u64> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (next, end) = *state;
+        if next == end {
+            let new_end = end
+                .checked_add(self.block_size)
+                .ok_or_else(|| io::Error::other("durable sequence exhausted"))?;
+            write_atomically(&self.path, new_end)?;
+            state.1 = new_end;
+        }
+        state.0 = next + 1;
+        Ok(next)
+    }
+
+    /// The number of values reserved per block.
+    #[inline]
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        DurableSequence, HighWaterMark, InitError, FILE_NAME, RESERVE, TIMESTAMP_RESERVE_MS,
+    };
+    use crate::{SequenceExhausted, SnowflakeGenerator};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("snowflake-durable-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_high_water_mark() {
+        let dir = temp_dir("hwm");
+        let mark = HighWaterMark::open(&dir).unwrap();
+        assert_eq!(*mark.persisted.lock().unwrap(), 0);
+        mark.ensure_covers(0).unwrap();
+        assert_eq!(*mark.persisted.lock().unwrap(), RESERVE);
+        mark.ensure_covers(RESERVE - 1).unwrap();
+        mark.ensure_covers(RESERVE).unwrap();
+        drop(mark);
+
+        // A restart resumes above everything that might have been handed out.
+        let mark = HighWaterMark::open(&dir).unwrap();
+        assert_eq!(*mark.persisted.lock().unwrap(), 2 * RESERVE);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt() {
+        let dir = temp_dir("corrupt");
+        fs::write(dir.join(FILE_NAME), "garbage").unwrap();
+        assert!(matches!(
+            HighWaterMark::open(&dir),
+            Err(InitError::Corrupt(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_durable_sequence() {
+        let dir = temp_dir("sequence");
+        let path = dir.join("ids.seq");
+        let seq = DurableSequence::open(&path, 10).unwrap();
+        let first: Vec<_> = (0..15).map(|_| seq.next().unwrap()).collect();
+        assert_eq!(first, (0..15).collect::<Vec<_>>());
+        assert_eq!(fs::read_to_string(&path).unwrap().trim(), "20");
+        drop(seq);
+
+        // "Crash" and recover: the rest of the second block is skipped.
+        let seq = DurableSequence::open(&path, 10).unwrap();
+        assert_eq!(seq.next().unwrap(), 20);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_persisted_snowflake_generator() {
+        let dir = temp_dir("snowflake");
+        let path = dir.join("gen.hwm");
+        let gen = SnowflakeGenerator::new(1).with_persistence(&path).unwrap();
+        let epoch_ms = gen.epoch().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let id = gen.next();
+        let mark: u64 = fs::read_to_string(&path).unwrap().trim().parse().unwrap();
+        assert_eq!(mark, epoch_ms + id.timestamp() + TIMESTAMP_RESERVE_MS);
+        drop(gen);
+
+        // The clock "went backwards" while we were down: refuse to issue IDs below the mark.
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let mark = now_ms + 60_000;
+        fs::write(&path, format!("{}\n", mark)).unwrap();
+        let gen = SnowflakeGenerator::new(1).with_persistence(&path).unwrap();
+        assert_eq!(gen.try_next(), Err(SequenceExhausted));
+        let batch = gen.next_batch(1).next().unwrap();
+        assert_eq!(batch.timestamp(), mark - epoch_ms);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_init_after_use() {
+        crate::ProcessUniqueId::new();
+        let dir = temp_dir("init");
+        assert!(matches!(super::init(&dir), Err(InitError::AlreadyInUse)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! DynamoDB partition and sort keys for time-ordered IDs.
+//!
+//! Keying a table on the IDs themselves spreads writes well but makes "everything from the last
+//! hour" a full scan; keying it on time makes that a query but sends every write to one
+//! partition. [`KeyScheme`] does both: the partition key is the start of the ID's time bucket
+//! plus a write shard (a stable hash of the ID), and the sort key is the ID itself, encoded so
+//! that DynamoDB's byte order matches the IDs' order. Reading a bucket back means querying each
+//! of its [`partition_keys`](KeyScheme::partition_keys) and merging by sort key.
+//!
+//! ```
+//! use snowflake::dynamodb::{KeyScheme, SortKey};
+//! use snowflake::SnowflakeId;
+//! use std::time::Duration;
+//!
+//! let scheme = KeyScheme::new(Duration::from_secs(3600)).with_write_shards(4);
+//! let id: SnowflakeId = "1541815603606036480".parse().unwrap();
+//! let partition = scheme.partition_key(&id);
+//! assert_eq!(partition.split('#').next(), Some("1656432000000"));
+//! assert!(scheme.partition_keys(&id).contains(&partition));
+//! assert_eq!(id.sort_key(), "01541815603606036480");
+//! assert_eq!(SnowflakeId::from_sort_key(&id.sort_key()), Ok(id));
+//! ```
+//!
+//! With the `dynamodb` feature, [`SnowflakeId`]s and [`FlakeId`]s also convert to and from
+//! `aws_sdk_dynamodb` `AttributeValue`s.
+//!
+//! There's no ULID type in this crate; [`FlakeId`] is the 128-bit, millisecond-ordered ID to use
+//! in its place.
+use std::convert::TryFrom;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::process_unique_id::expect_len;
+use crate::{FlakeId, ParseIdError, SnowflakeId, Timestamped};
+
+/// An ID with a sort key encoding.
+pub trait SortKey: Timestamped + Sized {
+    /// The ID as a fixed-width string whose byte order matches the IDs' order.
+    fn sort_key(&self) -> String;
+
+    /// Parses a string produced by [`sort_key`](SortKey::sort_key).
+    fn from_sort_key(key: &str) -> Result<Self, ParseIdError>;
+}
+
+/// Twenty zero-padded decimal digits, enough for any `u64`.
+impl SortKey for SnowflakeId {
+    fn sort_key(&self) -> String {
+        format!("{:020}", u64::from(*self))
+    }
+
+    fn from_sort_key(key: &str) -> Result<Self, ParseIdError> {
+        if let Some(i) = key.bytes().take(20).position(|b| !b.is_ascii_digit()) {
+            return Err(ParseIdError::at(key, i, "a decimal digit"));
+        }
+        expect_len(key, 20, "a decimal digit")?;
+        key.parse()
+    }
+}
+
+/// Thirty-two lowercase hex digits, the same as `Display`.
+impl SortKey for FlakeId {
+    fn sort_key(&self) -> String {
+        format!("{:032x}", u128::from(*self))
+    }
+
+    fn from_sort_key(key: &str) -> Result<Self, ParseIdError> {
+        key.parse()
+    }
+}
+
+/// How IDs map to DynamoDB partition and sort keys; see the [module documentation](self).
+///
+/// Partition keys have the form `<bucket>#<shard>`, where `<bucket>` is the start of the ID's
+/// time bucket in milliseconds since the UNIX epoch and `<shard>` is in `0..write_shards`. With a
+/// single write shard (the default), they're just `<bucket>`. An ID's shard depends only on its
+/// sort key and the number of shards, so it never changes as long as the scheme doesn't.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct KeyScheme {
+    bucket_ms: u64,
+    write_shards: u32,
+}
+
+impl KeyScheme {
+    /// A scheme with time buckets `bucket` long and a single write shard.
+    ///
+    /// **panics** if `bucket` is shorter than a millisecond.
+    pub fn new(bucket: Duration) -> Self {
+        let bucket_ms = bucket.as_millis();
+        assert!(
+            bucket_ms > 0,
+            "partition buckets must be at least a millisecond long"
+        );
+        KeyScheme {
+            bucket_ms: u64::try_from(bucket_ms).unwrap_or(u64::MAX),
+            write_shards: 1,
+        }
+    }
+
+    /// Spreads each bucket's writes over `shards` partition keys instead of one.
+    ///
+    /// **panics** if `shards` is 0.
+    pub fn with_write_shards(mut self, shards: u32) -> Self {
+        assert!(shards > 0, "there must be at least one write shard");
+        self.write_shards = shards;
+        self
+    }
+
+    /// The length of a time bucket.
+    #[inline]
+    pub fn bucket(&self) -> Duration {
+        Duration::from_millis(self.bucket_ms)
+    }
+
+    /// The number of partition keys per bucket.
+    #[inline]
+    pub fn write_shards(&self) -> u32 {
+        self.write_shards
+    }
+
+    /// The partition key to store `id` under.
+    pub fn partition_key<K: SortKey>(&self, id: &K) -> String {
+        self.format_key(self.bucket_start(id), self.shard(id))
+    }
+
+    /// The write shard `id` falls in.
+    pub fn shard<K: SortKey>(&self, id: &K) -> u32 {
+        (mix(id.sort_key().as_bytes()) % u64::from(self.write_shards)) as u32
+    }
+
+    /// Every partition key of the bucket `id` falls in, to query when reading the bucket back.
+    pub fn partition_keys<K: SortKey>(&self, id: &K) -> Vec<String> {
+        let start = self.bucket_start(id);
+        (0..self.write_shards)
+            .map(|shard| self.format_key(start, shard))
+            .collect()
+    }
+
+    /// The start of the bucket `id` falls in, in milliseconds since the UNIX epoch.
+    pub fn bucket_start<K: SortKey>(&self, id: &K) -> u64 {
+        let ms = id
+            .created_at()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| {
+                u64::try_from(since.as_millis()).unwrap_or(u64::MAX)
+            });
+        ms - ms % self.bucket_ms
+    }
+
+    fn format_key(&self, start: u64, shard: u32) -> String {
+        if self.write_shards == 1 {
+            start.to_string()
+        } else {
+            format!("{}#{}", start, shard)
+        }
+    }
+}
+
+/// A hash of `bytes` that won't change between releases or processes, unlike `std`'s.
+fn mix(bytes: &[u8]) -> u64 {
+    // FNV-1a, then a SplitMix64 finalizer so that the low bits depend on every byte.
+    let mut hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash: u64, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    });
+    crate::obfuscate::splitmix64(&mut hash)
+}
+
+#[cfg(feature = "dynamodb")]
+mod attribute_value {
+    use core::convert::TryFrom;
+
+    use aws_sdk_dynamodb::types::AttributeValue;
+
+    use super::SortKey;
+    use crate::{FlakeId, ParseIdError, SnowflakeId};
+
+    /// A number attribute, which DynamoDB sorts numerically.
+    impl From<SnowflakeId> for AttributeValue {
+        #[inline]
+        fn from(id: SnowflakeId) -> Self {
+            AttributeValue::N(id.to_string())
+        }
+    }
+
+    /// Accepts number attributes and [sort keys](SortKey::sort_key).
+    impl TryFrom<&AttributeValue> for SnowflakeId {
+        type Error = ParseIdError;
+
+        fn try_from(value: &AttributeValue) -> Result<Self, ParseIdError> {
+            match value {
+                AttributeValue::N(n) => n.parse(),
+                AttributeValue::S(s) => SnowflakeId::from_sort_key(s),
+                _ => Err(ParseIdError::invalid("a number or string attribute")),
+            }
+        }
+    }
+
+    /// A string attribute holding the [sort key](SortKey::sort_key). Flake IDs have more digits
+    /// than a DynamoDB number can hold.
+    impl From<FlakeId> for AttributeValue {
+        #[inline]
+        fn from(id: FlakeId) -> Self {
+            AttributeValue::S(id.sort_key())
+        }
+    }
+
+    /// Accepts string attributes holding the hex form.
+    impl TryFrom<&AttributeValue> for FlakeId {
+        type Error = ParseIdError;
+
+        fn try_from(value: &AttributeValue) -> Result<Self, ParseIdError> {
+            match value {
+                AttributeValue::S(s) => FlakeId::from_sort_key(s),
+                _ => Err(ParseIdError::invalid("a string attribute")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{KeyScheme, SortKey};
+    use crate::{FlakeId, SnowflakeId};
+    use std::convert::TryFrom;
+    use std::time::Duration;
+
+    fn snowflake(timestamp: u64, sequence: u64) -> SnowflakeId {
+        SnowflakeId::try_from(timestamp << 22 | 1 << 12 | sequence).unwrap()
+    }
+
+    #[test]
+    fn test_sort_keys_ordered() {
+        let keys: Vec<_> = [0, 9, 10, 1 << 40, i64::MAX as u64]
+            .iter()
+            .map(|&n| SnowflakeId::try_from(n).unwrap().sort_key())
+            .collect();
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        let flakes: Vec<_> = [0, 15, 16, u128::MAX]
+            .iter()
+            .map(|&n| FlakeId::from(n).sort_key())
+            .collect();
+        assert!(flakes.windows(2).all(|w| w[0] < w[1] && w[0].len() == 32));
+        assert_eq!(
+            FlakeId::from_sort_key(&flakes[3]),
+            Ok(FlakeId::from(u128::MAX))
+        );
+        assert!(SnowflakeId::from_sort_key("42").is_err());
+    }
+
+    #[test]
+    fn test_write_shards() {
+        let scheme = KeyScheme::new(Duration::from_secs(60)).with_write_shards(8);
+        let mut seen = [0; 8];
+        for sequence in 0..1024 {
+            let id = snowflake(1_000_000, sequence);
+            let key = scheme.partition_key(&id);
+            assert_eq!(key, format!("1288835940000#{}", scheme.shard(&id)));
+            seen[scheme.shard(&id) as usize] += 1;
+        }
+        assert!(seen.iter().all(|&n| n > 64), "{:?}", seen);
+
+        let single = KeyScheme::new(Duration::from_secs(60));
+        let id = snowflake(1_000_000, 0);
+        assert_eq!(single.partition_keys(&id), ["1288835940000"]);
+    }
+
+    #[cfg(feature = "dynamodb")]
+    #[test]
+    fn test_attribute_values() {
+        use aws_sdk_dynamodb::types::AttributeValue;
+
+        let id = snowflake(1_000_000, 2);
+        let value = AttributeValue::from(id);
+        assert_eq!(value, AttributeValue::N(id.to_string()));
+        assert_eq!(SnowflakeId::try_from(&value), Ok(id));
+        let flake = FlakeId::from(42);
+        assert_eq!(FlakeId::try_from(&AttributeValue::from(flake)), Ok(flake));
+        assert!(FlakeId::try_from(&AttributeValue::Bool(true)).is_err());
+    }
+}
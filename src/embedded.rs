@@ -0,0 +1,101 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Process unique IDs without threads or thread local storage.
+//!
+//! Requires the `embedded` feature. On embedded targets (embassy executors, RTOS tasks, bare
+//! metal interrupt handlers) there is no `std::thread` to hang a block of IDs off of. Instead,
+//! give each core (or each executor) its own [`EmbeddedIdGenerator`], typically in a `static`:
+//!
+//! ```
+//! use snowflake::embedded::EmbeddedIdGenerator;
+//!
+//! static IDS: EmbeddedIdGenerator = EmbeddedIdGenerator::new();
+//!
+//! let a = IDS.next();
+//! let b = IDS.next();
+//! assert!(a < b);
+//! ```
+//!
+//! The generator's state is guarded by a [`critical_section`], so it can be shared with interrupt
+//! handlers. Keeping one generator per core keeps the critical sections short and uncontended.
+//! Without the `std` feature, the platform has to provide a `critical-section` implementation
+//! (most HALs do, e.g. with a `critical-section-single-core` feature); with it, the crate enables
+//! `critical-section`'s `std` implementation.
+
+use core::cell::Cell;
+
+use critical_section::Mutex;
+
+use crate::process_unique_id::next_global;
+use crate::ProcessUniqueId;
+
+/// A process unique ID generator for one core or executor, safe to use from interrupt handlers.
+///
+/// Each generator claims a whole prefix the first time it's used and hands out its offsets in
+/// order, moving on to a new prefix when they run out. IDs are unique with respect to all other
+/// process unique IDs, and ordered with respect to other IDs from the same generator.
+#[derive(Debug)]
+pub struct EmbeddedIdGenerator {
+    // The next ID to hand out, `None` until a prefix has been claimed or after it's used up.
+    next: Mutex<Cell<Option<ProcessUniqueId>>>,
+}
+
+impl EmbeddedIdGenerator {
+    /// Creates a generator. Creating a generator is free; its prefix is claimed when the first ID
+    /// is requested.
+    #[inline]
+    pub const fn new() -> Self {
+        EmbeddedIdGenerator {
+            next: Mutex::new(Cell::new(None)),
+        }
+    }
+
+    /// Creates a new unique ID.
+    ///
+    /// **panics** if there are no more prefixes available.
+    pub fn next(&self) -> ProcessUniqueId {
+        critical_section::with(|cs| {
+            let next = self.next.borrow(cs);
+            let id = next.get().unwrap_or_else(|| ProcessUniqueId {
+                // The same counter as `ProcessUniqueId::new()`'s, so the two can be mixed.
+                prefix: next_global(),
+                offset: 0,
+            });
+            next.set(id.offset.checked_add(1).map(|offset| ProcessUniqueId {
+                prefix: id.prefix,
+                offset,
+            }));
+            id
+        })
+    }
+}
+
+impl Default for EmbeddedIdGenerator {
+    #[inline]
+    fn default() -> Self {
+        EmbeddedIdGenerator::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EmbeddedIdGenerator;
+    use crate::ProcessUniqueId;
+
+    #[test]
+    fn test_embedded_generator() {
+        let a = EmbeddedIdGenerator::new();
+        let b = EmbeddedIdGenerator::new();
+        let first = a.next();
+        assert_eq!(first.offset, 0);
+        assert_eq!(a.next().offset, 1);
+        assert_ne!(b.next().prefix, first.prefix);
+        assert_ne!(ProcessUniqueId::new().prefix, first.prefix);
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::error;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(feature = "config")]
+use crate::config::ConfigError;
+#[cfg(feature = "durable")]
+use crate::durable::InitError;
+#[cfg(feature = "std")]
+use crate::hlc::ClockDrift;
+#[cfg(feature = "std")]
+use crate::lease::LeaseError;
+#[cfg(feature = "std")]
+use crate::node::NodeIdError;
+#[cfg(feature = "opaque")]
+use crate::opaque::OpaqueError;
+#[cfg(feature = "std")]
+use crate::short_code::InvalidAlphabet;
+#[cfg(feature = "std")]
+use crate::PrefixCounterInUse;
+#[cfg(feature = "std")]
+use crate::RateLimited;
+use crate::{
+    IdOutOfRange, IdUnavailable, LenientParseError, ParseIdError, SequenceExhausted, VarintError,
+};
+
+/// Any error returned by this crate.
+///
+/// Each fallible API returns its own error type, which says exactly what can go wrong there.
+/// They all convert into `Error`, so code calling several of them can use `?` with a single
+/// error type:
+///
+/// ```
+/// use snowflake::{Error, ProcessUniqueId, SnowflakeId};
+///
+/// fn parse_both(a: &str, b: &str) -> Result<(ProcessUniqueId, SnowflakeId), Error> {
+///     Ok((a.parse()?, b.parse()?))
+/// }
+///
+/// match parse_both("puid-1-2", "x") {
+///     Err(Error::Parse(err)) => assert_eq!(err.offset(), 0),
+///     _ => unreachable!(),
+/// }
+/// ```
+///
+/// An `Error` displays as the error it wraps, and its [`source`](error::Error::source) is that
+/// error's source (e.g. the `io::Error` behind a failed lease backend), so error reporters don't
+/// print the same message twice.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// No more process unique IDs are available.
+    Exhausted(IdUnavailable),
+    /// Another prefix counter couldn't be shared.
+    #[cfg(feature = "std")]
+    PrefixCounter(PrefixCounterInUse),
+    /// A snowflake generator ran out of sequence numbers for the current millisecond.
+    SequenceExhausted(SequenceExhausted),
+    /// A rate limited generator refused to issue an ID.
+    #[cfg(feature = "std")]
+    RateLimited(RateLimited),
+    /// A remote timestamp was too far ahead of the local clock.
+    #[cfg(feature = "std")]
+    ClockDrift(ClockDrift),
+    /// An ID couldn't be parsed.
+    Parse(ParseIdError),
+    /// An ID couldn't be parsed leniently.
+    LenientParse(LenientParseError),
+    /// An integer or bytes don't make a valid ID.
+    OutOfRange(IdOutOfRange),
+    /// A varint-encoded ID couldn't be decoded.
+    Varint(VarintError),
+    /// A node ID couldn't be determined.
+    #[cfg(feature = "std")]
+    NodeId(NodeIdError),
+    /// A node ID lease couldn't be claimed or kept.
+    #[cfg(feature = "std")]
+    Lease(LeaseError),
+    /// A short code alphabet can't be used.
+    #[cfg(feature = "std")]
+    InvalidAlphabet(InvalidAlphabet),
+    /// An opaque ID token couldn't be opened.
+    #[cfg(feature = "opaque")]
+    Opaque(OpaqueError),
+    /// The durable prefix high-water mark couldn't be set up.
+    #[cfg(feature = "durable")]
+    Durable(InitError),
+    /// A generator couldn't be built from its configuration.
+    #[cfg(feature = "config")]
+    Config(ConfigError),
+    /// Reading or writing a file or socket failed.
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl Error {
+    fn inner(&self) -> &(dyn error::Error + 'static) {
+        match self {
+            Error::Exhausted(e) => e,
+            #[cfg(feature = "std")]
+            Error::PrefixCounter(e) => e,
+            Error::SequenceExhausted(e) => e,
+            #[cfg(feature = "std")]
+            Error::RateLimited(e) => e,
+            #[cfg(feature = "std")]
+            Error::ClockDrift(e) => e,
+            Error::Parse(e) => e,
+            Error::LenientParse(e) => e,
+            Error::OutOfRange(e) => e,
+            Error::Varint(e) => e,
+            #[cfg(feature = "std")]
+            Error::NodeId(e) => e,
+            #[cfg(feature = "std")]
+            Error::Lease(e) => e,
+            #[cfg(feature = "std")]
+            Error::InvalidAlphabet(e) => e,
+            #[cfg(feature = "opaque")]
+            Error::Opaque(e) => e,
+            #[cfg(feature = "durable")]
+            Error::Durable(e) => e,
+            #[cfg(feature = "config")]
+            Error::Config(e) => e,
+            #[cfg(feature = "std")]
+            Error::Io(e) => e,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.inner(), f)
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.inner().source()
+    }
+}
+
+macro_rules! impl_from {
+    ($($(#[$cfg:meta])* $variant:ident($ty:ty),)*) => {$(
+        $(#[$cfg])*
+        impl From<$ty> for Error {
+            #[inline]
+            fn from(e: $ty) -> Self {
+                Error::$variant(e)
+            }
+        }
+    )*};
+}
+
+impl_from! {
+    Exhausted(IdUnavailable),
+    #[cfg(feature = "std")]
+    PrefixCounter(PrefixCounterInUse),
+    SequenceExhausted(SequenceExhausted),
+    #[cfg(feature = "std")]
+    RateLimited(RateLimited),
+    #[cfg(feature = "std")]
+    ClockDrift(ClockDrift),
+    Parse(ParseIdError),
+    LenientParse(LenientParseError),
+    OutOfRange(IdOutOfRange),
+    Varint(VarintError),
+    #[cfg(feature = "std")]
+    NodeId(NodeIdError),
+    #[cfg(feature = "std")]
+    Lease(LeaseError),
+    #[cfg(feature = "std")]
+    InvalidAlphabet(InvalidAlphabet),
+    #[cfg(feature = "opaque")]
+    Opaque(OpaqueError),
+    #[cfg(feature = "durable")]
+    Durable(InitError),
+    #[cfg(feature = "config")]
+    Config(ConfigError),
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::Error;
+    use crate::lease::LeaseError;
+    use crate::ProcessUniqueId;
+    use std::error::Error as _;
+    use std::io;
+
+    #[test]
+    fn test_error() {
+        let down = io::Error::other("down");
+        let err = Error::from(LeaseError::Backend(Box::new(down)));
+        assert_eq!(err.to_string(), "lease backend error: down");
+        assert_eq!(err.source().unwrap().to_string(), "down");
+
+        let err = Error::from("puid-x".parse::<ProcessUniqueId>().unwrap_err());
+        assert!(matches!(err, Error::Parse(_)));
+        assert!(err.source().is_none());
+    }
+}
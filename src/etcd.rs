@@ -0,0 +1,366 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Node IDs leased from etcd.
+//!
+//! Requires the `etcd` feature. [`EtcdNodeId`] is a [`NodeIdProvider`] that claims a node ID by
+//! creating the key `<prefix><node ID>` in etcd, attached to an etcd lease, and keeps the lease
+//! alive in the background. Other processes using the same prefix skip taken IDs, and the key
+//! disappears with the lease if the process dies, freeing the ID.
+//!
+//! If the lease can't be renewed before it expires (etcd is unreachable, or it revoked the lease),
+//! the node ID is surrendered: [`is_valid`](EtcdNodeId::is_valid) turns false and the
+//! [`on_lost`](EtcdNodeId::on_lost) callbacks run, and generators using the node ID must stop.
+//! The lease deadline is tracked locally, from the moment a grant or renewal was *sent*, so the
+//! node ID is surrendered no later than etcd forgets it.
+//!
+//! ```no_run
+//! use snowflake::etcd::EtcdNodeId;
+//! use snowflake::SnowflakeGenerator;
+//! use std::process;
+//!
+//! let node = EtcdNodeId::connect(&["http://etcd:2379"], "/snowflake/nodes/").unwrap();
+//! node.on_lost(|node_id| {
+//!     eprintln!("lost node ID {}", node_id);
+//!     process::exit(1);
+//! });
+//! let ids = SnowflakeGenerator::from_provider(&node).unwrap();
+//! ```
+use std::collections::BTreeSet;
+use std::fmt;
+use std::process;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use etcd_client::{
+    Client, Compare, CompareOp, GetOptions, LeaseKeepAliveStream, LeaseKeeper, PutOptions, Txn,
+    TxnOp,
+};
+use tokio::runtime::Runtime;
+
+use crate::node::{background_runtime, block_on, check_width, NodeIdError, NodeIdProvider};
+
+const DEFAULT_TTL: Duration = Duration::from_secs(10);
+
+/// How many times to retry claiming a node ID that another process took first.
+const CLAIM_ATTEMPTS: usize = 8;
+
+type LostCallback = Arc<dyn Fn(u64) + Send + Sync>;
+
+/// A node ID leased from etcd; see the [module documentation](self).
+///
+/// The node ID is claimed by the first call to [`node_id`](NodeIdProvider::node_id), and later
+/// calls return the same one. Once it's lost, the next call claims a new one. Dropping the
+/// provider revokes the lease, freeing the node ID right away.
+pub struct EtcdNodeId {
+    // Only `None` while being dropped.
+    runtime: Option<Runtime>,
+    client: Client,
+    prefix: String,
+    ttl: Duration,
+    // Held while claiming, so that concurrent callers share one node ID.
+    claiming: Mutex<()>,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    held: Mutex<Option<Held>>,
+    callbacks: Mutex<Vec<LostCallback>>,
+}
+
+struct Held {
+    node_id: u64,
+    lease: i64,
+}
+
+impl EtcdNodeId {
+    /// Connects to the etcd cluster at `endpoints`, claiming node IDs under the key prefix
+    /// `prefix`.
+    ///
+    /// Doesn't claim a node ID until the first call to [`node_id`](NodeIdProvider::node_id).
+    pub fn connect<E: AsRef<str>>(endpoints: &[E], prefix: &str) -> Result<Self, NodeIdError> {
+        let runtime = background_runtime("snowflake-etcd")?;
+        let endpoints: Vec<String> = endpoints.iter().map(|e| e.as_ref().to_owned()).collect();
+        let client = block_on(&runtime, Client::connect(endpoints, None)).map_err(unavailable)?;
+        Ok(EtcdNodeId {
+            runtime: Some(runtime),
+            client,
+            prefix: prefix.to_owned(),
+            ttl: DEFAULT_TTL,
+            claiming: Mutex::new(()),
+            shared: Arc::new(Shared {
+                held: Mutex::new(None),
+                callbacks: Mutex::new(Vec::new()),
+            }),
+        })
+    }
+
+    /// Leases node IDs for `ttl` (rounded up to whole seconds) instead of 10 seconds.
+    ///
+    /// The lease is renewed every third of the TTL. A shorter TTL frees the node IDs of crashed
+    /// processes sooner, but surrenders node IDs during shorter etcd outages.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Duration::from_secs(ttl_secs(ttl) as u64);
+        self
+    }
+
+    /// The key prefix node IDs are claimed under.
+    #[inline]
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Returns true if a node ID is claimed and hasn't been lost.
+    pub fn is_valid(&self) -> bool {
+        self.shared.held().is_some()
+    }
+
+    /// Registers a callback to be invoked (with the node ID) whenever a claimed node ID is lost.
+    ///
+    /// Callbacks run on a background thread.
+    pub fn on_lost<F>(&self, callback: F)
+    where
+        F: Fn(u64) + Send + Sync + 'static,
+    {
+        self.shared.callbacks().push(Arc::new(callback));
+    }
+
+    fn runtime(&self) -> &Runtime {
+        self.runtime
+            .as_ref()
+            .expect("etcd runtime already shut down")
+    }
+}
+
+impl NodeIdProvider for EtcdNodeId {
+    fn node_id(&self, bits: u32) -> Result<u64, NodeIdError> {
+        let _claiming = self.claiming.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(held) = &*self.shared.held() {
+            return check_width(held.node_id, bits);
+        }
+        let sent = Instant::now();
+        let mut client = self.client.clone();
+        let (prefix, ttl) = (self.prefix.as_str(), self.ttl);
+        let (node_id, lease) = block_on(self.runtime(), async move {
+            let lease = client
+                .lease_grant(ttl_secs(ttl), None)
+                .await
+                .map_err(unavailable)?
+                .id();
+            let claimed = claim(&mut client, prefix, bits, lease).await;
+            if claimed.is_err() {
+                let _ = client.lease_revoke(lease).await;
+            }
+            claimed.map(|node_id| (node_id, lease))
+        })?;
+        *self.shared.held() = Some(Held { node_id, lease });
+        self.runtime().spawn(keep_alive(
+            self.client.clone(),
+            self.shared.clone(),
+            lease,
+            ttl,
+            sent + ttl,
+        ));
+        Ok(node_id)
+    }
+}
+
+impl Drop for EtcdNodeId {
+    fn drop(&mut self) {
+        let runtime = match self.runtime.take() {
+            Some(runtime) => runtime,
+            None => return,
+        };
+        if let Some(held) = self.shared.held().take() {
+            let mut client = self.client.clone();
+            let _ = block_on(
+                &runtime,
+                async move { client.lease_revoke(held.lease).await },
+            );
+        }
+        // Dropping the runtime would block, which isn't allowed in async code.
+        runtime.shutdown_background();
+    }
+}
+
+impl fmt::Debug for EtcdNodeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EtcdNodeId")
+            .field("prefix", &self.prefix)
+            .field("ttl", &self.ttl)
+            .field("node_id", &self.shared.held().as_ref().map(|h| h.node_id))
+            .finish()
+    }
+}
+
+impl Shared {
+    fn held(&self) -> MutexGuard<'_, Option<Held>> {
+        self.held.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn callbacks(&self) -> MutexGuard<'_, Vec<LostCallback>> {
+        self.callbacks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Gives up the node ID held under `lease`, if it's still held, returning it.
+    fn surrender(&self, lease: i64) -> Option<u64> {
+        let mut held = self.held();
+        match &*held {
+            Some(current) if current.lease == lease => held.take().map(|held| held.node_id),
+            _ => None,
+        }
+    }
+}
+
+/// Claims the lowest free node ID that fits in `bits` bits, attaching its key to `lease`.
+async fn claim(
+    client: &mut Client,
+    prefix: &str,
+    bits: u32,
+    lease: i64,
+) -> Result<u64, NodeIdError> {
+    let limit = if bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    };
+    for _ in 0..CLAIM_ATTEMPTS {
+        let taken: BTreeSet<u64> = client
+            .get(
+                prefix,
+                Some(GetOptions::new().with_prefix().with_keys_only()),
+            )
+            .await
+            .map_err(unavailable)?
+            .kvs()
+            .iter()
+            .filter_map(|kv| parse_key(prefix, kv.key()))
+            .collect();
+        let node_id = match (0..=limit).find(|id| !taken.contains(id)) {
+            Some(node_id) => node_id,
+            None => {
+                return Err(NodeIdError::Unavailable(format!(
+                    "every node ID under {:?} is taken",
+                    prefix
+                )))
+            }
+        };
+        // Only create the key if nobody else has since.
+        let key = format!("{}{}", prefix, node_id);
+        let txn = Txn::new()
+            .when([Compare::create_revision(key.as_str(), CompareOp::Equal, 0)])
+            .and_then([TxnOp::put(
+                key.as_str(),
+                format!("pid {}", process::id()),
+                Some(PutOptions::new().with_lease(lease)),
+            )]);
+        if client.txn(txn).await.map_err(unavailable)?.succeeded() {
+            return Ok(node_id);
+        }
+    }
+    Err(NodeIdError::Unavailable(format!(
+        "lost {} races for a node ID under {:?}",
+        CLAIM_ATTEMPTS, prefix
+    )))
+}
+
+/// Renews `lease` every third of `ttl` until it can't be renewed before `deadline`, then
+/// surrenders the node ID.
+async fn keep_alive(
+    mut client: Client,
+    shared: Arc<Shared>,
+    lease: i64,
+    ttl: Duration,
+    mut deadline: Instant,
+) {
+    let mut stream = None;
+    loop {
+        tokio::time::sleep(ttl / 3).await;
+        let sent = Instant::now();
+        let renewal =
+            tokio::time::timeout_at(deadline.into(), renew(&mut client, &mut stream, lease));
+        match renewal.await {
+            Ok(Ok(true)) => deadline = sent + ttl,
+            // etcd no longer knows the lease.
+            Ok(Ok(false)) => break,
+            // Reconnect and try again next time.
+            Ok(Err(_)) => stream = None,
+            Err(_) => break,
+        }
+        if Instant::now() >= deadline {
+            break;
+        }
+    }
+    let node_id = match shared.surrender(lease) {
+        Some(node_id) => node_id,
+        None => return,
+    };
+    // Free the node ID right away if etcd is still listening.
+    let _ = client.lease_revoke(lease).await;
+    let callbacks = shared.callbacks().clone();
+    // Keep callbacks that block (or claim a new node ID) off the runtime's only worker.
+    tokio::task::spawn_blocking(move || {
+        for callback in callbacks {
+            callback(node_id);
+        }
+    });
+}
+
+async fn renew(
+    client: &mut Client,
+    stream: &mut Option<(LeaseKeeper, LeaseKeepAliveStream)>,
+    lease: i64,
+) -> Result<bool, etcd_client::Error> {
+    if stream.is_none() {
+        *stream = Some(client.lease_keep_alive(lease).await?);
+    }
+    let (keeper, responses) = stream.as_mut().expect("keep-alive stream just opened");
+    keeper.keep_alive().await?;
+    match responses.message().await? {
+        Some(response) => Ok(response.ttl() > 0),
+        None => Err(etcd_client::Error::LeaseKeepAliveError(
+            "keep-alive stream closed".into(),
+        )),
+    }
+}
+
+fn unavailable(e: etcd_client::Error) -> NodeIdError {
+    NodeIdError::Unavailable(format!("etcd: {}", e))
+}
+
+/// The TTL in whole seconds (at least one), rounded up.
+fn ttl_secs(ttl: Duration) -> i64 {
+    let secs = ttl.as_secs() + u64::from(ttl.subsec_nanos() > 0);
+    secs.clamp(1, i64::MAX as u64) as i64
+}
+
+/// The node ID in a `<prefix><node ID>` key.
+fn parse_key(prefix: &str, key: &[u8]) -> Option<u64> {
+    let id = key.strip_prefix(prefix.as_bytes())?;
+    if id.is_empty() || !id.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    std::str::from_utf8(id).ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_key, ttl_secs};
+    use std::time::Duration;
+
+    #[test]
+    fn test_keys_and_ttls() {
+        assert_eq!(parse_key("/ids/", b"/ids/42"), Some(42));
+        assert_eq!(parse_key("/ids/", b"/ids/+42"), None);
+        assert_eq!(parse_key("/ids/", b"/ids/"), None);
+        assert_eq!(parse_key("/ids/", b"/other/42"), None);
+        assert_eq!(ttl_secs(Duration::from_millis(1500)), 2);
+        assert_eq!(ttl_secs(Duration::from_secs(10)), 10);
+        assert_eq!(ttl_secs(Duration::ZERO), 1);
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::sync::OnceLock;
+
+use fake::{Dummy, Faker, RngExt};
+
+use crate::atomic::{AtomicU64, Ordering};
+use crate::{
+    FlakeGenerator, FlakeId, LamportId, ObjectId, ProcessUniqueId, PushId, PushIdGenerator,
+    SmallUniqueId, SnowflakeGenerator, SnowflakeId, SonyflakeGenerator, SonyflakeId, Tsid,
+    TsidGenerator, Xid, XidGenerator,
+};
+
+// Fixtures need IDs that are valid (a snowflake ID's timestamp is a real time) and that don't
+// collide, so fakes come from this crate's generators rather than from `rng`. Generators that
+// need a node ID share one per type, with node ID zero.
+macro_rules! impl_dummy {
+    ($($ty:ident => $next:expr;)*) => {$(
+        impl Dummy<Faker> for $ty {
+            #[inline]
+            fn dummy_with_rng<R: RngExt + ?Sized>(_: &Faker, _: &mut R) -> Self {
+                $next
+            }
+        }
+    )*};
+}
+
+static LAMPORT: AtomicU64 = AtomicU64::new(0);
+
+impl_dummy! {
+    ProcessUniqueId => ProcessUniqueId::new();
+    SmallUniqueId => SmallUniqueId::new();
+    SnowflakeId => {
+        static GEN: OnceLock<SnowflakeGenerator> = OnceLock::new();
+        GEN.get_or_init(|| SnowflakeGenerator::new(0)).next()
+    };
+    SonyflakeId => {
+        static GEN: OnceLock<SonyflakeGenerator> = OnceLock::new();
+        GEN.get_or_init(|| SonyflakeGenerator::new(0)).next()
+    };
+    FlakeId => {
+        static GEN: OnceLock<FlakeGenerator> = OnceLock::new();
+        GEN.get_or_init(|| FlakeGenerator::new(0)).next()
+    };
+    Tsid => {
+        static GEN: OnceLock<TsidGenerator> = OnceLock::new();
+        GEN.get_or_init(|| TsidGenerator::new(0)).next()
+    };
+    Xid => {
+        static GEN: OnceLock<XidGenerator> = OnceLock::new();
+        GEN.get_or_init(|| XidGenerator::new(0)).next()
+    };
+    PushId => {
+        static GEN: OnceLock<PushIdGenerator> = OnceLock::new();
+        GEN.get_or_init(PushIdGenerator::new).next()
+    };
+    ObjectId => ObjectId::new();
+    LamportId => LamportId::from_parts(LAMPORT.fetch_add(1, Ordering::Relaxed) + 1, 0);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        FlakeId, LamportId, ObjectId, ProcessUniqueId, PushId, SmallUniqueId, SnowflakeId,
+        SonyflakeId, Tsid, Xid,
+    };
+    use fake::{Dummy, Fake, Faker};
+    use std::collections::HashSet;
+    use std::fmt::Debug;
+    use std::hash::Hash;
+
+    fn check<T: Dummy<Faker> + Eq + Hash + Debug>() {
+        let ids: HashSet<T> = (0..1000).map(|_| Faker.fake()).collect();
+        assert_eq!(ids.len(), 1000);
+    }
+
+    #[derive(Dummy)]
+    struct Order {
+        id: SnowflakeId,
+        customer: ProcessUniqueId,
+        items: Vec<Tsid>,
+    }
+
+    #[test]
+    fn test_fake() {
+        check::<ProcessUniqueId>();
+        check::<SmallUniqueId>();
+        check::<SnowflakeId>();
+        check::<SonyflakeId>();
+        check::<FlakeId>();
+        check::<Tsid>();
+        check::<Xid>();
+        check::<PushId>();
+        check::<ObjectId>();
+        check::<LamportId>();
+
+        let a: Order = Faker.fake();
+        let b: Order = Faker.fake();
+        assert_ne!(a.id, b.id);
+        assert_ne!(a.customer, b.customer);
+        assert_eq!(a.id.to_string().parse(), Ok(a.id));
+        let items: HashSet<Tsid> = a.items.iter().chain(&b.items).copied().collect();
+        assert_eq!(items.len(), a.items.len() + b.items.len());
+    }
+}
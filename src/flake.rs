@@ -0,0 +1,308 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::fmt;
+use core::mem;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "bevy")]
+use bevy_ecs::reflect::ReflectComponent;
+
+#[cfg(feature = "std")]
+use crate::atomic::{AtomicU64, Ordering};
+use crate::display::pad_id;
+#[cfg(feature = "std")]
+use crate::node::{NodeIdError, NodeIdProvider};
+use crate::process_unique_id::{expect_len, ParseIdError};
+#[cfg(feature = "std")]
+use crate::stats;
+
+const WORKER_BITS: u32 = 48;
+const SEQUENCE_BITS: u32 = 16;
+
+const WORKER_MASK: u128 = (1 << WORKER_BITS) - 1;
+#[cfg(feature = "std")]
+const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
+
+/// The generator packs the timestamp into the top 48 bits of its state, which lasts until the
+/// year 10889.
+#[cfg(feature = "std")]
+const MAX_STATE_TIMESTAMP: u64 = (1 << (64 - SEQUENCE_BITS)) - 1;
+
+/// A 128-bit, time-ordered ID in the layout of Boundary's flake.
+///
+/// A 64-bit timestamp in milliseconds since the UNIX epoch, a 48-bit worker ID (traditionally a
+/// MAC address), and a 16-bit sequence number. The worker ID space is large enough to assign
+/// without coordination, and each worker can create 65536 IDs per millisecond.
+///
+/// Displayed as 32 lowercase hex digits, which sort the same way as the number.
+///
+/// # Memory layout
+///
+/// `#[repr(transparent)]` over a `u128`, in native byte order. It's aligned like a `u128`: 16 bytes
+/// on x86_64 and aarch64, as in C, but the alignment of 128-bit integers varies between targets
+/// (and Rust versions before 1.77 used 8 on x86_64). Across FFI, prefer passing the two halves of
+/// `u128::from(id)` or its big-endian bytes.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(
+        zerocopy::FromBytes,
+        zerocopy::IntoBytes,
+        zerocopy::Immutable,
+        zerocopy::KnownLayout
+    )
+)]
+#[cfg_attr(feature = "bitcode", derive(bitcode::Encode, bitcode::Decode))]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_ecs::component::Component, bevy_reflect::Reflect),
+    reflect(opaque, Component, Debug, PartialEq, Hash, Clone)
+)]
+#[repr(transparent)]
+pub struct FlakeId(u128);
+
+const _: () = assert!(
+    mem::size_of::<FlakeId>() == 16 && mem::align_of::<FlakeId>() == mem::align_of::<u128>()
+);
+
+impl FlakeId {
+    /// Milliseconds since the UNIX epoch.
+    #[inline]
+    pub fn timestamp(self) -> u64 {
+        (self.0 >> (WORKER_BITS + SEQUENCE_BITS)) as u64
+    }
+
+    /// The ID of the worker that generated this ID.
+    #[inline]
+    pub fn worker_id(self) -> u64 {
+        ((self.0 >> SEQUENCE_BITS) & WORKER_MASK) as u64
+    }
+
+    /// The sequence number within the millisecond.
+    #[inline]
+    pub fn sequence(self) -> u16 {
+        self.0 as u16
+    }
+}
+
+/// Every `u128` is a valid flake ID.
+impl From<u128> for FlakeId {
+    #[inline]
+    fn from(value: u128) -> Self {
+        FlakeId(value)
+    }
+}
+
+impl From<FlakeId> for u128 {
+    #[inline]
+    fn from(id: FlakeId) -> u128 {
+        id.0
+    }
+}
+
+/// Reinterprets the ID's bits as a UUID. The result isn't a valid RFC 4122 UUID (it has no
+/// version or variant), but it round-trips and sorts the same way as the ID.
+#[cfg(feature = "uuid")]
+impl From<FlakeId> for uuid::Uuid {
+    #[inline]
+    fn from(id: FlakeId) -> uuid::Uuid {
+        uuid::Uuid::from_u128(id.0)
+    }
+}
+
+/// Reinterprets a UUID's bits as a flake ID.
+#[cfg(feature = "uuid")]
+impl From<uuid::Uuid> for FlakeId {
+    #[inline]
+    fn from(uuid: uuid::Uuid) -> Self {
+        FlakeId(uuid.as_u128())
+    }
+}
+
+/// Reinterprets the ID's bits as a GUID, as for UUIDs.
+#[cfg(feature = "windows")]
+impl From<FlakeId> for windows_core::GUID {
+    #[inline]
+    fn from(id: FlakeId) -> windows_core::GUID {
+        windows_core::GUID::from_u128(id.0)
+    }
+}
+
+/// Reinterprets a GUID's bits as a flake ID.
+#[cfg(feature = "windows")]
+impl From<windows_core::GUID> for FlakeId {
+    #[inline]
+    fn from(guid: windows_core::GUID) -> Self {
+        FlakeId(guid.to_u128())
+    }
+}
+
+impl fmt::Display for FlakeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(f, format_args!(""), format_args!("{:032x}", self.0))
+    }
+}
+
+/// Parses the 32-digit hex form produced by `Display`, in either case.
+impl FromStr for FlakeId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        if let Some(i) = s.bytes().take(32).position(|b| !b.is_ascii_hexdigit()) {
+            return Err(ParseIdError::at(s, i, "a hex digit"));
+        }
+        expect_len(s, 32, "a hex digit")?;
+        u128::from_str_radix(s, 16)
+            .map(FlakeId)
+            .map_err(|_| ParseIdError::at(s, 0, "a hex digit"))
+    }
+}
+
+/// Generates [`FlakeId`]s for a single worker.
+///
+/// If a millisecond's 65536 sequence numbers run out, the generator moves on to the next
+/// millisecond early instead of waiting. If the clock goes backwards, it keeps counting from the
+/// last time it saw.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct FlakeGenerator {
+    worker_id: u64,
+    // The last ID's `timestamp << SEQUENCE_BITS | sequence`.
+    state: AtomicU64,
+    clock: stats::ClockWatch,
+}
+
+#[cfg(feature = "std")]
+impl FlakeGenerator {
+    /// Creates a generator for `worker_id`.
+    ///
+    /// **panics** if `worker_id` doesn't fit in 48 bits.
+    pub fn new(worker_id: u64) -> Self {
+        assert!(
+            worker_id >> WORKER_BITS == 0,
+            "worker ID {} doesn't fit in {} bits",
+            worker_id,
+            WORKER_BITS
+        );
+        FlakeGenerator {
+            worker_id,
+            state: AtomicU64::new(0),
+            clock: stats::ClockWatch::new(),
+        }
+    }
+
+    /// Creates a generator using the worker ID supplied by `provider`.
+    pub fn from_provider<P: NodeIdProvider + ?Sized>(provider: &P) -> Result<Self, NodeIdError> {
+        Ok(FlakeGenerator::new(provider.node_id(WORKER_BITS)?))
+    }
+
+    /// This generator's worker ID.
+    #[inline]
+    pub fn worker_id(&self) -> u64 {
+        self.worker_id
+    }
+
+    /// Generates a new ID.
+    pub fn next(&self) -> FlakeId {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        self.next_at(now)
+    }
+
+    fn next_at(&self, now: u64) -> FlakeId {
+        self.clock.observe(now, &stats::FLAKE);
+        let mut current = self.state.load(Ordering::Relaxed);
+        loop {
+            let (timestamp, sequence) = (current >> SEQUENCE_BITS, current & MAX_SEQUENCE);
+            let (next, exhausted) = if timestamp < now {
+                (now << SEQUENCE_BITS, false)
+            } else if sequence == MAX_SEQUENCE {
+                ((timestamp + 1) << SEQUENCE_BITS, true)
+            } else {
+                (current + 1, false)
+            };
+            assert!(
+                next >> SEQUENCE_BITS <= MAX_STATE_TIMESTAMP,
+                "flake timestamp overflow"
+            );
+            match self.state.compare_exchange_weak(
+                current,
+                next,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    stats::FLAKE.issued(1);
+                    if exhausted {
+                        stats::FLAKE.exhausted();
+                    }
+                    let timestamp = u128::from(next >> SEQUENCE_BITS);
+                    let sequence = u128::from(next & MAX_SEQUENCE);
+                    return FlakeId(
+                        (timestamp << (WORKER_BITS + SEQUENCE_BITS))
+                            | (u128::from(self.worker_id) << SEQUENCE_BITS)
+                            | sequence,
+                    );
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::{FlakeGenerator, FlakeId, MAX_SEQUENCE};
+
+    #[test]
+    fn test_layout() {
+        let gen = FlakeGenerator::new(0xa1b2_c3d4_e5f6);
+        let id = gen.next_at(1_500_000_000_000);
+        assert_eq!(id.timestamp(), 1_500_000_000_000);
+        assert_eq!(id.worker_id(), 0xa1b2_c3d4_e5f6);
+        assert_eq!(id.sequence(), 0);
+        assert_eq!(
+            id.to_string(),
+            format!("{:016x}a1b2c3d4e5f60000", 1_500_000_000_000u64)
+        );
+        assert_eq!(id.to_string().parse(), Ok(id));
+        assert_eq!(id.to_string().to_uppercase().parse(), Ok(id));
+        assert_eq!(FlakeId::from(u128::from(id)), id);
+        #[cfg(feature = "uuid")]
+        assert_eq!(FlakeId::from(uuid::Uuid::from(id)), id);
+        #[cfg(feature = "windows")]
+        assert_eq!(FlakeId::from(windows_core::GUID::from(id)), id);
+        assert!("+0000000000000000000000000000000"
+            .parse::<FlakeId>()
+            .is_err());
+        assert!("0".parse::<FlakeId>().is_err());
+    }
+
+    #[test]
+    fn test_sequence_overflow() {
+        let gen = FlakeGenerator::new(1);
+        for i in 0..=MAX_SEQUENCE {
+            assert_eq!(gen.next_at(10).sequence(), i as u16);
+        }
+        let exhausted = crate::stats().flake().exhausted();
+        let id = gen.next_at(10);
+        assert_eq!((id.timestamp(), id.sequence()), (11, 0));
+        assert!(crate::stats().flake().exhausted() > exhausted);
+        // The clock going backwards doesn't reorder IDs.
+        let rollbacks = crate::stats().flake().clock_rollbacks();
+        let next = gen.next_at(5);
+        assert!(next > id);
+        assert!(gen.next() > next);
+        assert!(crate::stats().flake().clock_rollbacks() > rollbacks);
+    }
+}
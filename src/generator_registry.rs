@@ -0,0 +1,230 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
+
+use crate::atomic::{AtomicU64, Ordering};
+use crate::IdGen;
+
+type Factory<K, G> = dyn Fn(&K) -> G + Send + Sync;
+
+struct Entry<G> {
+    gen: Arc<G>,
+    // Nanoseconds between the registry's creation and the last time the entry was used.
+    last_used: AtomicU64,
+}
+
+/// Independent generators keyed by tenant (or stream) name, created on first use.
+///
+/// The factory decides how each tenant's generator is set up, so tenants can use different node
+/// IDs, epochs, or even kinds of generator (with `G = Box<dyn DynIdGen + Send + Sync>`):
+///
+/// ```
+/// use snowflake::{GeneratorRegistry, SnowflakeGenerator};
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let registry = GeneratorRegistry::new(|tenant: &String| {
+///     let gen = SnowflakeGenerator::new(1);
+///     if tenant == "legacy" {
+///         gen.with_epoch(UNIX_EPOCH + Duration::from_secs(1_420_070_400))
+///     } else {
+///         gen
+///     }
+/// })
+/// .with_max_tenants(10_000);
+///
+/// let a = registry.next("acme");
+/// let b = registry.next("acme");
+/// assert!(a < b);
+/// assert_eq!(registry.len(), 1);
+/// ```
+///
+/// Looking up an existing tenant only takes a read lock.
+///
+/// # Eviction
+///
+/// Evicted tenants ([`remove`](GeneratorRegistry::remove),
+/// [`evict_idle`](GeneratorRegistry::evict_idle), or going over the
+/// [`max_tenants`](GeneratorRegistry::with_max_tenants) limit) get a new generator from the
+/// factory the next time they're used, which doesn't know what the old one issued. Clock based
+/// generators start over from the current time, so this is only safe once the old generator is
+/// no longer in use and the clock has moved past every ID it issued (including batches it
+/// borrowed from the future). Generators handed out by [`get`](GeneratorRegistry::get) stay
+/// usable after eviction; they're just no longer in the registry.
+pub struct GeneratorRegistry<K, G> {
+    factory: Box<Factory<K, G>>,
+    max_tenants: Option<usize>,
+    created: Instant,
+    entries: RwLock<HashMap<K, Entry<G>>>,
+}
+
+impl<K: Hash + Eq, G> GeneratorRegistry<K, G> {
+    /// Creates an empty registry that builds each tenant's generator with `factory`.
+    pub fn new<F>(factory: F) -> Self
+    where
+        F: Fn(&K) -> G + Send + Sync + 'static,
+    {
+        GeneratorRegistry {
+            factory: Box::new(factory),
+            max_tenants: None,
+            created: Instant::now(),
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Keeps at most `max` tenants, evicting the least recently used one to make room for a new
+    /// one. See [Eviction](GeneratorRegistry#eviction).
+    ///
+    /// **panics** if `max` is zero.
+    pub fn with_max_tenants(mut self, max: usize) -> Self {
+        assert!(max > 0, "a registry must have room for at least one tenant");
+        self.max_tenants = Some(max);
+        self
+    }
+
+    /// Returns the tenant's generator, creating it if needed.
+    pub fn get<Q>(&self, key: &Q) -> Arc<G>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        let now = self.elapsed();
+        if let Some(entry) = self.read().get(key) {
+            entry.last_used.store(now, Ordering::Relaxed);
+            return entry.gen.clone();
+        }
+
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = entries.get(key) {
+            entry.last_used.store(now, Ordering::Relaxed);
+            return entry.gen.clone();
+        }
+        if let Some(max) = self.max_tenants {
+            while entries.len() >= max {
+                let lru = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used.load(Ordering::Relaxed))
+                    .map(|(_, entry)| Arc::as_ptr(&entry.gen));
+                entries.retain(|_, entry| Some(Arc::as_ptr(&entry.gen)) != lru);
+            }
+        }
+        let key = key.to_owned();
+        let gen = Arc::new((self.factory)(&key));
+        entries.insert(
+            key,
+            Entry {
+                gen: gen.clone(),
+                last_used: AtomicU64::new(now),
+            },
+        );
+        gen
+    }
+
+    /// Generates an ID with the tenant's generator, creating it if needed.
+    #[inline]
+    pub fn next<Q>(&self, key: &Q) -> G::Id
+    where
+        G: IdGen,
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        self.get(key).next()
+    }
+
+    /// Returns true if the tenant currently has a generator.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.read().contains_key(key)
+    }
+
+    /// Evicts the tenant, returning its generator if it had one.
+    pub fn remove<Q>(&self, key: &Q) -> Option<Arc<G>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries.remove(key).map(|entry| entry.gen)
+    }
+
+    /// Evicts every tenant that hasn't been used for at least `idle`, returning how many were
+    /// evicted.
+    pub fn evict_idle(&self, idle: Duration) -> usize {
+        let cutoff = self.elapsed().saturating_sub(idle.as_nanos() as u64);
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        let before = entries.len();
+        entries.retain(|_, entry| entry.last_used.load(Ordering::Relaxed) > cutoff);
+        before - entries.len()
+    }
+
+    /// The number of tenants with a generator.
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Returns true if no tenant has a generator.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<K, Entry<G>>> {
+        self.entries.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn elapsed(&self) -> u64 {
+        self.created.elapsed().as_nanos() as u64
+    }
+}
+
+impl<K, G> fmt::Debug for GeneratorRegistry<K, G> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tenants = self.entries.read().map(|e| e.len()).unwrap_or(0);
+        f.debug_struct("GeneratorRegistry")
+            .field("tenants", &tenants)
+            .field("max_tenants", &self.max_tenants)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GeneratorRegistry;
+    use crate::SnowflakeGenerator;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_registry() {
+        let registry = GeneratorRegistry::new(|tenant: &String| {
+            SnowflakeGenerator::new(if tenant == "a" { 1 } else { 2 })
+        })
+        .with_max_tenants(2);
+        assert_eq!(registry.next("a").node_id(), 1);
+        assert_eq!(registry.next("b").node_id(), 2);
+        let a = registry.get("a");
+        assert!(Arc::ptr_eq(&a, &registry.get("a")));
+
+        // "b" is the least recently used tenant.
+        registry.next("c");
+        assert!(registry.contains_key("a"));
+        assert!(!registry.contains_key("b"));
+        assert_eq!(registry.len(), 2);
+
+        assert!(registry.remove("c").is_some());
+        assert_eq!(registry.evict_idle(Duration::ZERO), 1);
+        assert!(registry.is_empty());
+        assert!(!Arc::ptr_eq(&a, &registry.get("a")));
+    }
+}
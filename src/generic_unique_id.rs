@@ -0,0 +1,217 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::Hash;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::cell::Cell;
+
+#[cfg(feature = "std")]
+use crate::atomic::{AtomicU64, Ordering};
+use crate::display::pad_id;
+use crate::process_unique_id::{expect_tag, ParseIdError};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// An unsigned integer type that can be used as the prefix or offset of a [`GenericUniqueId`].
+///
+/// Implemented for `u8`, `u16`, `u32`, `u64`, `u128`, and `usize`.
+pub trait Counter: Copy + Ord + Hash + fmt::Debug + fmt::LowerHex + private::Sealed {
+    #[doc(hidden)]
+    const SLOT: usize;
+
+    #[doc(hidden)]
+    const MAX: u128;
+
+    #[doc(hidden)]
+    fn from_u128(value: u128) -> Option<Self>;
+}
+
+macro_rules! impl_counter {
+    ($($ty:ty => $slot:expr),*) => {$(
+        impl private::Sealed for $ty {}
+
+        impl Counter for $ty {
+            const SLOT: usize = $slot;
+            const MAX: u128 = <$ty>::MAX as u128;
+
+            #[inline]
+            fn from_u128(value: u128) -> Option<Self> {
+                <$ty>::try_from(value).ok()
+            }
+        }
+    )*};
+}
+
+impl_counter!(u8 => 0, u16 => 1, u32 => 2, u64 => 3, u128 => 4, usize => 5);
+
+/// A process unique ID with a prefix of type `P` and an offset of type `O`.
+///
+/// [`ProcessUniqueId`](crate::ProcessUniqueId) is fixed at a `usize` prefix and a `u64` offset.
+/// `GenericUniqueId` lets small targets shrink IDs (a `GenericUniqueId<u16, u16>` takes four
+/// bytes) and large deployments grow them:
+///
+/// ```
+/// use snowflake::GenericUniqueId;
+///
+/// let a = GenericUniqueId::<u16, u16>::new();
+/// let b = GenericUniqueId::<u16, u16>::new();
+/// assert_ne!(a, b);
+/// assert_eq!(core::mem::size_of_val(&a), 4);
+/// assert_eq!(a.to_string().parse(), Ok(a));
+/// ```
+///
+/// Each thread takes a whole prefix and hands out its offsets in order, moving on to a new prefix
+/// when the offsets run out, so small offsets mean prefixes are used up quickly. All
+/// `GenericUniqueId` types draw prefixes from one process-wide counter.
+///
+/// Displayed as `gpuid-<prefix>-<offset>` (hex).
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct GenericUniqueId<P, O> {
+    prefix: P,
+    offset: O,
+}
+
+// The next prefix to hand out to a thread.
+#[cfg(feature = "std")]
+static NEXT_PREFIX: AtomicU64 = AtomicU64::new(0);
+
+// The current prefix and next offset of each offset type (indexed by `Counter::SLOT`), `None`
+// when the thread needs a new prefix.
+#[cfg(feature = "std")]
+thread_local! {
+    static LOCAL: [Cell<Option<(u64, u128)>>; 6] = const {
+        [const { Cell::new(None) }; 6]
+    };
+}
+
+#[cfg(feature = "std")]
+fn next_prefix() -> u64 {
+    NEXT_PREFIX
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |prefix| {
+            prefix.checked_add(1)
+        })
+        .expect("Snow Crash: Go home and reevaluate your threading model!")
+}
+
+impl<P: Counter, O: Counter> GenericUniqueId<P, O> {
+    /// Creates a new unique ID.
+    ///
+    /// **panics** if the prefixes that fit in a `P` have all been handed out.
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        let (prefix, offset) = LOCAL.with(|slots| {
+            let slot = &slots[O::SLOT];
+            let (prefix, offset) = slot.get().unwrap_or_else(|| (next_prefix(), 0));
+            slot.set(if offset == O::MAX {
+                None
+            } else {
+                Some((prefix, offset + 1))
+            });
+            (prefix, offset)
+        });
+        GenericUniqueId {
+            prefix: P::from_u128(prefix.into())
+                .expect("Snow Crash: Go home and reevaluate your threading model!"),
+            // Offsets in the slot never exceed `O::MAX`.
+            offset: O::from_u128(offset).unwrap(),
+        }
+    }
+
+    /// The ID's prefix.
+    #[inline]
+    pub fn prefix(&self) -> P {
+        self.prefix
+    }
+
+    /// The ID's offset within its prefix.
+    #[inline]
+    pub fn offset(&self) -> O {
+        self.offset
+    }
+}
+
+#[cfg(feature = "std")]
+impl<P: Counter, O: Counter> Default for GenericUniqueId<P, O> {
+    #[inline]
+    fn default() -> Self {
+        GenericUniqueId::new()
+    }
+}
+
+impl<P: Counter, O: Counter> fmt::Display for GenericUniqueId<P, O> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(
+            f,
+            format_args!("gpuid-{:x}-", self.prefix),
+            format_args!("{:x}", self.offset),
+        )
+    }
+}
+
+/// Parses the `gpuid-<prefix>-<offset>` form produced by `Display`.
+impl<P: Counter, O: Counter> FromStr for GenericUniqueId<P, O> {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        // Parses the field starting at `start`, returning it and where it ends.
+        fn field<T: Counter>(s: &str, start: usize) -> Result<(T, usize), ParseIdError> {
+            let digits = s[start..].bytes().take_while(u8::is_ascii_hexdigit).count();
+            let end = start + digits;
+            if digits == 0 {
+                return Err(ParseIdError::at(s, end, "a hex digit"));
+            }
+            u128::from_str_radix(&s[start..end], 16)
+                .ok()
+                .and_then(T::from_u128)
+                .map(|value| (value, end))
+                .ok_or_else(|| ParseIdError::at(s, start, "a value that fits in the field's type"))
+        }
+
+        let start = expect_tag(s, "gpuid-", "`gpuid-`")?;
+        let (prefix, end) = field(s, start)?;
+        if s.as_bytes().get(end) != Some(&b'-') {
+            return Err(ParseIdError::at(s, end, "a hex digit or `-`"));
+        }
+        let (offset, end) = field(s, end + 1)?;
+        if end != s.len() {
+            return Err(ParseIdError::at(s, end, "a hex digit"));
+        }
+        Ok(GenericUniqueId { prefix, offset })
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::GenericUniqueId;
+
+    #[test]
+    fn test_generic_unique_id() {
+        // A thread moves on to a new prefix after 256 IDs with a `u8` offset.
+        let ids: Vec<_> = (0..600)
+            .map(|_| GenericUniqueId::<u32, u8>::new())
+            .collect();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), ids.len());
+        assert_eq!(ids[255].offset(), 255);
+        assert_eq!(ids[256].offset(), 0);
+        assert_ne!(ids[256].prefix(), ids[255].prefix());
+
+        let wide = GenericUniqueId::<u128, u128>::new();
+        assert_eq!(wide.to_string().parse(), Ok(wide));
+        assert!("gpuid-100-0".parse::<GenericUniqueId<u8, u8>>().is_err());
+        assert!("gpuid-+1-0".parse::<GenericUniqueId<u8, u8>>().is_err());
+        assert!("puid-1-0".parse::<GenericUniqueId<u8, u8>>().is_err());
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use async_graphql::{InputValueError, InputValueResult, Scalar, ScalarType, Value};
+
+use crate::{
+    FlakeId, LamportId, ObjectId, ProcessUniqueId, PushId, SmallUniqueId, SnowflakeId, SonyflakeId,
+    Tsid, Xid,
+};
+
+// Each ID type is a scalar of the same name, in its display form. Snowflake IDs are strings
+// too: GraphQL's `Int` is only 32 bits.
+macro_rules! impl_scalar {
+    ($($ty:ident),*) => {$(
+        #[Scalar]
+        impl ScalarType for $ty {
+            fn parse(value: Value) -> InputValueResult<Self> {
+                match &value {
+                    Value::String(s) => s.parse().map_err(InputValueError::custom),
+                    _ => Err(InputValueError::expected_type(value)),
+                }
+            }
+
+            fn is_valid(value: &Value) -> bool {
+                matches!(value, Value::String(s) if s.parse::<$ty>().is_ok())
+            }
+
+            fn to_value(&self) -> Value {
+                Value::String(self.to_string())
+            }
+        }
+    )*};
+}
+
+impl_scalar!(
+    ProcessUniqueId,
+    SnowflakeId,
+    FlakeId,
+    SonyflakeId,
+    Tsid,
+    SmallUniqueId,
+    ObjectId,
+    Xid,
+    PushId,
+    LamportId
+);
+
+#[cfg(test)]
+mod test {
+    use crate::{ProcessUniqueId, SnowflakeGenerator, SnowflakeId};
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema};
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn echo(&self, id: ProcessUniqueId) -> ProcessUniqueId {
+            id
+        }
+
+        async fn snowflake(&self, id: SnowflakeId) -> SnowflakeId {
+            id
+        }
+    }
+
+    #[tokio::test]
+    async fn test_graphql_scalars() {
+        let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+        assert!(schema.sdl().contains("scalar ProcessUniqueId"));
+
+        let id = ProcessUniqueId::new();
+        let response = schema
+            .execute(format!("{{ echo(id: \"{}\") }}", id))
+            .await
+            .into_result()
+            .unwrap();
+        assert_eq!(response.data.to_string(), format!("{{echo: \"{}\"}}", id));
+
+        let id = SnowflakeGenerator::new(1).next();
+        let response = schema
+            .execute(format!("{{ snowflake(id: \"{}\") }}", id))
+            .await;
+        assert!(response.errors.is_empty());
+
+        let response = schema.execute("{ echo(id: \"puid-1-x\") }").await;
+        assert!(!response.errors.is_empty());
+        let response = schema.execute("{ echo(id: 12) }").await;
+        assert!(!response.errors.is_empty());
+    }
+}
@@ -0,0 +1,304 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Hybrid logical clocks.
+//!
+//! A hybrid logical clock (HLC) issues timestamps that stay close to wall clock time but, unlike
+//! it, respect causality: a timestamp issued after receiving a message is always greater than the
+//! message's timestamp, however far apart the two nodes' clocks are. Each [`HlcTimestamp`] is a
+//! physical time in milliseconds, a logical counter that orders events within a millisecond, and
+//! the ID of the node that issued it, which breaks the remaining ties so timestamps are totally
+//! ordered and unique across nodes.
+//!
+//! ```
+//! use snowflake::hlc::Hlc;
+//!
+//! let a = Hlc::new(1);
+//! let b = Hlc::new(2);
+//!
+//! let sent = a.now();
+//! let received = b.update_with_received(sent).unwrap();
+//! assert!(received > sent);
+//! assert!(b.now() > received);
+//! ```
+//!
+//! Timestamps pack the physical time and counter into 64 bits (48 and 16 bits); if the counter
+//! overflows within a millisecond, the clock moves on to the next millisecond early.
+
+use core::convert::TryFrom;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::error::Error;
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "std")]
+use crate::atomic::{AtomicU64, Ordering};
+use crate::display::pad_id;
+use crate::process_unique_id::{expect_tag, hex_field, hex_field_to_dash, ParseIdError};
+
+const LOGICAL_BITS: u32 = 16;
+const MAX_PHYSICAL: u64 = (1 << 48) - 1;
+
+/// A hybrid logical clock timestamp.
+///
+/// Timestamps order by physical time, then logical counter, then node ID. Displayed as
+/// `hlc-<physical>-<logical>-<node>` (hex).
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct HlcTimestamp {
+    // `physical << LOGICAL_BITS | logical`.
+    time: u64,
+    node: u16,
+}
+
+impl HlcTimestamp {
+    /// Creates a timestamp from its parts.
+    ///
+    /// Returns `None` if `physical` doesn't fit in 48 bits.
+    pub fn from_parts(physical: u64, logical: u16, node: u16) -> Option<Self> {
+        if physical > MAX_PHYSICAL {
+            return None;
+        }
+        Some(HlcTimestamp {
+            time: physical << LOGICAL_BITS | u64::from(logical),
+            node,
+        })
+    }
+
+    /// The physical component, in milliseconds since the UNIX epoch.
+    #[inline]
+    pub fn physical(self) -> u64 {
+        self.time >> LOGICAL_BITS
+    }
+
+    /// The logical counter, which orders timestamps with the same physical time.
+    #[inline]
+    pub fn logical(self) -> u16 {
+        self.time as u16
+    }
+
+    /// The ID of the node that issued the timestamp.
+    #[inline]
+    pub fn node(self) -> u16 {
+        self.node
+    }
+
+    /// The physical time and logical counter packed into 64 bits, without the node ID.
+    ///
+    /// Packed values order the same way as the timestamps, except that timestamps from different
+    /// nodes can share a packed value.
+    #[inline]
+    pub fn to_u64(self) -> u64 {
+        self.time
+    }
+}
+
+impl fmt::Display for HlcTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(
+            f,
+            format_args!("hlc-{:x}-{:x}-", self.physical(), self.logical()),
+            format_args!("{:x}", self.node),
+        )
+    }
+}
+
+/// Parses the `hlc-<physical>-<logical>-<node>` form produced by `Display`.
+impl FromStr for HlcTimestamp {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        let start = expect_tag(s, "hlc-", "`hlc-`")?;
+        let (physical, logical_start) = hex_field_to_dash(s, start)?;
+        let (logical, node_start) = hex_field_to_dash(s, logical_start)?;
+        let node = hex_field(s, node_start, s.len())?;
+        let logical = u16::try_from(logical).map_err(|_| {
+            ParseIdError::at(s, logical_start, "a logical counter of at most 16 bits")
+        })?;
+        let node = u16::try_from(node)
+            .map_err(|_| ParseIdError::at(s, node_start, "a node ID of at most 16 bits"))?;
+        HlcTimestamp::from_parts(physical, logical, node)
+            .ok_or_else(|| ParseIdError::at(s, start, "a physical time of at most 48 bits"))
+    }
+}
+
+/// An error returned by [`Hlc::update_with_received`] when a remote timestamp is further ahead
+/// of the local clock than the configured maximum drift.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ClockDrift {
+    ahead: Duration,
+}
+
+#[cfg(feature = "std")]
+impl ClockDrift {
+    /// How far ahead of the local clock the remote timestamp was.
+    #[inline]
+    pub fn ahead(&self) -> Duration {
+        self.ahead
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for ClockDrift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "remote timestamp is {:?} ahead of the local clock",
+            self.ahead
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for ClockDrift {}
+
+/// A hybrid logical clock for one node.
+///
+/// The clock is lock-free: its whole state is the last issued timestamp, packed into an
+/// `AtomicU64` updated by CAS.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct Hlc {
+    node: u16,
+    max_drift: Option<u64>,
+    // The last issued `HlcTimestamp::time`.
+    last: AtomicU64,
+}
+
+#[cfg(feature = "std")]
+impl Hlc {
+    /// Creates a clock for `node`.
+    pub fn new(node: u16) -> Self {
+        Hlc {
+            node,
+            max_drift: None,
+            last: AtomicU64::new(0),
+        }
+    }
+
+    /// Rejects remote timestamps more than `max_drift` ahead of the local clock, instead of
+    /// dragging the local clock forward to match them.
+    pub fn with_max_drift(mut self, max_drift: Duration) -> Self {
+        self.max_drift = Some(max_drift.as_millis() as u64);
+        self
+    }
+
+    /// This clock's node ID.
+    #[inline]
+    pub fn node(&self) -> u16 {
+        self.node
+    }
+
+    /// Issues a timestamp for a local event (or a message about to be sent).
+    ///
+    /// **panics** if the clock is past the end of the 48-bit millisecond range (in the year
+    /// 10889).
+    pub fn now(&self) -> HlcTimestamp {
+        self.now_at(physical_now())
+    }
+
+    /// Merges a timestamp received from another node, returning a timestamp for the receive
+    /// event that is greater than both `remote` and every timestamp this clock issued before.
+    ///
+    /// **panics** under the same conditions as [`now`](Hlc::now).
+    pub fn update_with_received(&self, remote: HlcTimestamp) -> Result<HlcTimestamp, ClockDrift> {
+        self.update_at(remote, physical_now())
+    }
+
+    fn now_at(&self, physical: u64) -> HlcTimestamp {
+        self.advance(physical, 0)
+    }
+
+    fn update_at(&self, remote: HlcTimestamp, physical: u64) -> Result<HlcTimestamp, ClockDrift> {
+        if let Some(max_drift) = self.max_drift {
+            let ahead = remote.physical().saturating_sub(physical);
+            if ahead > max_drift {
+                return Err(ClockDrift {
+                    ahead: Duration::from_millis(ahead),
+                });
+            }
+        }
+        Ok(self.advance(physical, remote.time + 1))
+    }
+
+    /// Issues the smallest timestamp after the last one that is at least `floor` and the physical
+    /// time.
+    fn advance(&self, physical: u64, floor: u64) -> HlcTimestamp {
+        assert!(physical <= MAX_PHYSICAL, "HLC physical time overflow");
+        let mut last = self.last.load(Ordering::Relaxed);
+        loop {
+            let next = (last + 1).max(floor).max(physical << LOGICAL_BITS);
+            assert!(
+                next >> LOGICAL_BITS <= MAX_PHYSICAL,
+                "HLC physical time overflow"
+            );
+            match self
+                .last
+                .compare_exchange_weak(last, next, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => {
+                    return HlcTimestamp {
+                        time: next,
+                        node: self.node,
+                    }
+                }
+                Err(actual) => last = actual,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn physical_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::{Hlc, HlcTimestamp};
+    use std::time::Duration;
+
+    #[test]
+    fn test_hlc_merge() {
+        let a = Hlc::new(1);
+        let t1 = a.now_at(100);
+        assert_eq!((t1.physical(), t1.logical(), t1.node()), (100, 0, 1));
+        // The clock went backwards: keep counting from the last timestamp.
+        let t2 = a.now_at(90);
+        assert_eq!((t2.physical(), t2.logical()), (100, 1));
+
+        // A remote timestamp ahead of us drags the clock forward.
+        let remote = HlcTimestamp::from_parts(200, 5, 2).unwrap();
+        let t3 = a.update_at(remote, 110).unwrap();
+        assert_eq!((t3.physical(), t3.logical()), (200, 6));
+        assert!(t3 > remote);
+        // Once physical time passes it, the counter resets.
+        let t4 = a.now_at(300);
+        assert_eq!((t4.physical(), t4.logical()), (300, 0));
+
+        let strict = Hlc::new(3).with_max_drift(Duration::from_millis(50));
+        let err = strict.update_at(remote, 100).unwrap_err();
+        assert_eq!(err.ahead(), Duration::from_millis(100));
+        assert!(strict.update_at(remote, 160).is_ok());
+    }
+
+    #[test]
+    fn test_hlc_string() {
+        let ts = HlcTimestamp::from_parts(0x18b, 2, 7).unwrap();
+        assert_eq!(ts.to_string(), "hlc-18b-2-7");
+        assert_eq!("hlc-18b-2-7".parse(), Ok(ts));
+        assert!("hlc-18b-10000-7".parse::<HlcTimestamp>().is_err());
+        assert!("hlc-1000000000000-0-0".parse::<HlcTimestamp>().is_err());
+        assert!(HlcTimestamp::from_parts(1 << 48, 0, 0).is_none());
+    }
+}
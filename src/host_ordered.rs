@@ -0,0 +1,274 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt;
+use std::mem;
+use std::process;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::atomic::{AtomicU64, Ordering};
+use crate::display::pad_id;
+use crate::obfuscate::splitmix64;
+use crate::process_unique_id::{expect_len, expect_tag, ParseIdError};
+
+/// An ID that sorts in creation order among every process on the same host.
+///
+/// A host-ordered ID is 128 bits: the time it was created, in nanoseconds since the UNIX epoch,
+/// then a hash of the host's boot ID and a hash of the creating process's PID and start time.
+/// Every process on a host reads the same system clock, so IDs from different processes (e.g.,
+/// the daemons whose logs are being merged) sort by when they were created. The boot and process
+/// hashes only break ties between processes creating IDs in the same nanosecond, and tell which
+/// process (and which boot) created an ID. (Leading with the process start time instead would
+/// sort IDs by when their processes started, not by when the IDs were created.)
+///
+/// Displayed as `hoid-` followed by 32 hex digits, so the display forms sort the same way.
+///
+/// ```
+/// use snowflake::HostOrderedId;
+///
+/// let first = HostOrderedId::new();
+/// let second = HostOrderedId::new();
+/// assert!(first < second);
+/// assert!(first.to_string() < second.to_string());
+/// assert_eq!(first.process(), second.process());
+/// ```
+///
+/// # Memory layout
+///
+/// `#[repr(C)]`: the timestamp (a `u64`), then the boot hash and the process hash (`u32`s), in
+/// native byte order. That's 16 bytes with no padding, aligned like a `u64`. (In native byte
+/// order, the bytes don't sort the way the IDs do; `u128::from(id).to_be_bytes()` does.)
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[repr(C)]
+pub struct HostOrderedId {
+    timestamp: u64,
+    boot: u32,
+    process: u32,
+}
+
+const _: () = {
+    assert!(mem::offset_of!(HostOrderedId, timestamp) == 0);
+    assert!(mem::offset_of!(HostOrderedId, boot) == 8);
+    assert!(mem::offset_of!(HostOrderedId, process) == 12);
+    assert!(mem::size_of::<HostOrderedId>() == 16);
+    assert!(mem::align_of::<HostOrderedId>() == mem::align_of::<u64>());
+};
+
+// The last timestamp used by this process.
+static LAST: AtomicU64 = AtomicU64::new(0);
+// The PID in the upper 32 bits and the process hash in the lower 32, once hashed.
+static PROCESS: AtomicU64 = AtomicU64::new(0);
+static BOOT: OnceLock<u32> = OnceLock::new();
+
+impl HostOrderedId {
+    /// Creates a new host-ordered ID.
+    ///
+    /// IDs from one process always increase: if the clock goes backwards, or several IDs are
+    /// created in the same nanosecond, this carries on from the last timestamp used plus one
+    /// nanosecond. A process that forks notices that its PID changed and hashes the child's
+    /// identity afresh.
+    pub fn new() -> Self {
+        let process = process_hash();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+        // Never fails: the closure always returns `Some`.
+        let last = LAST
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+                Some(now.max(last + 1))
+            })
+            .unwrap();
+        HostOrderedId {
+            timestamp: now.max(last + 1),
+            boot: *BOOT.get_or_init(boot_hash),
+            process,
+        }
+    }
+
+    /// Nanoseconds since the UNIX epoch at which this ID was created.
+    #[inline]
+    pub fn timestamp_nanos(self) -> u64 {
+        self.timestamp
+    }
+
+    /// A hash of the boot ID of the host that created this ID, or zero on platforms without boot
+    /// IDs (anything but Linux).
+    #[inline]
+    pub fn boot(self) -> u32 {
+        self.boot
+    }
+
+    /// A hash of the PID and start time of the process that created this ID.
+    #[inline]
+    pub fn process(self) -> u32 {
+        self.process
+    }
+}
+
+impl Default for HostOrderedId {
+    #[inline]
+    fn default() -> Self {
+        HostOrderedId::new()
+    }
+}
+
+impl From<u128> for HostOrderedId {
+    #[inline]
+    fn from(value: u128) -> Self {
+        HostOrderedId {
+            timestamp: (value >> 64) as u64,
+            boot: (value >> 32) as u32,
+            process: value as u32,
+        }
+    }
+}
+
+impl From<HostOrderedId> for u128 {
+    #[inline]
+    fn from(id: HostOrderedId) -> u128 {
+        u128::from(id.timestamp) << 64 | u128::from(id.boot) << 32 | u128::from(id.process)
+    }
+}
+
+/// Reinterprets the ID's bits as a GUID, which sorts the same way (as a `u128`).
+#[cfg(feature = "windows")]
+impl From<HostOrderedId> for windows_core::GUID {
+    #[inline]
+    fn from(id: HostOrderedId) -> windows_core::GUID {
+        windows_core::GUID::from_u128(id.into())
+    }
+}
+
+/// Reinterprets a GUID's bits as a host-ordered ID.
+#[cfg(feature = "windows")]
+impl From<windows_core::GUID> for HostOrderedId {
+    #[inline]
+    fn from(guid: windows_core::GUID) -> Self {
+        guid.to_u128().into()
+    }
+}
+
+impl fmt::Display for HostOrderedId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(
+            f,
+            format_args!("hoid-"),
+            format_args!("{:032x}", u128::from(*self)),
+        )
+    }
+}
+
+/// Parses the `hoid-<32 hex digits>` form produced by `Display`, in either case.
+impl FromStr for HostOrderedId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        let start = expect_tag(s, "hoid-", "`hoid-`")?;
+        let digits = &s[start..];
+        if let Some(i) = digits.bytes().take(32).position(|b| !b.is_ascii_hexdigit()) {
+            return Err(ParseIdError::at(s, start + i, "a hex digit"));
+        }
+        expect_len(s, start + 32, "a hex digit")?;
+        u128::from_str_radix(digits, 16)
+            .map(HostOrderedId::from)
+            .map_err(|_| ParseIdError::at(s, start, "a hex digit"))
+    }
+}
+
+fn hash(bytes: &[u8]) -> u32 {
+    let mut state = 0;
+    for chunk in bytes.chunks(8) {
+        let mut word = [0u8; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        let mut input = state ^ u64::from_le_bytes(word);
+        state = splitmix64(&mut input);
+    }
+    (state >> 32) as u32
+}
+
+#[cfg(target_os = "linux")]
+fn boot_hash() -> u32 {
+    std::fs::read_to_string("/proc/sys/kernel/random/boot_id")
+        .map_or(0, |boot_id| hash(boot_id.trim().as_bytes()))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn boot_hash() -> u32 {
+    0
+}
+
+/// The process's start time, in clock ticks since boot.
+#[cfg(target_os = "linux")]
+fn start_time() -> Option<u64> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // The command name (field 2) is in parentheses and may contain spaces; the start time is
+    // field 22.
+    let rest = &stat[stat.rfind(')')? + 1..];
+    rest.split_whitespace().nth(19)?.parse().ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn start_time() -> Option<u64> {
+    None
+}
+
+/// A hash of the PID and start time, hashed again after a fork. PIDs are reused, but not by
+/// processes that started at the same time.
+fn process_hash() -> u32 {
+    let pid = process::id();
+    let process = PROCESS.load(Ordering::Relaxed);
+    if process != 0 && (process >> 32) as u32 == pid {
+        return process as u32;
+    }
+    // Without `/proc`, the first time this process created an ID stands in for its start time.
+    let start = start_time().unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64)
+    });
+    let mut bytes = [0u8; 12];
+    bytes[..4].copy_from_slice(&pid.to_le_bytes());
+    bytes[4..].copy_from_slice(&start.to_le_bytes());
+    let hash = hash(&bytes);
+    // Racing threads store the same hash, unless they read different clocks without `/proc`, in
+    // which case their IDs still differ in their timestamps.
+    PROCESS.store(u64::from(pid) << 32 | u64::from(hash), Ordering::Relaxed);
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use super::HostOrderedId;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn test_host_ordered_id() {
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+        let ids: Vec<HostOrderedId> = (0..1000).map(|_| HostOrderedId::new()).collect();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(ids[0].timestamp_nanos() >= before);
+        assert!(ids.iter().all(|id| id.process() == ids[0].process()));
+        #[cfg(target_os = "linux")]
+        assert_ne!(ids[0].boot(), 0);
+
+        let id = ids[0];
+        assert_eq!(HostOrderedId::from(u128::from(id)), id);
+        assert_eq!(id.to_string().len(), 37);
+        assert_eq!(id.to_string().parse(), Ok(id));
+        assert_eq!(format!("hoid-{:032X}", u128::from(id)).parse(), Ok(id));
+        let err = "hoid-12x".parse::<HostOrderedId>().unwrap_err();
+        assert_eq!(err.offset(), 7);
+        assert!("huid-0".parse::<HostOrderedId>().is_err());
+        assert!(format!("hoid-{:033x}", 1).parse::<HostOrderedId>().is_err());
+    }
+}
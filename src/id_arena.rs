@@ -0,0 +1,318 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+use crate::ProcessUniqueId;
+
+/// A reference to a value in an [`IdArena`].
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct ArenaId {
+    arena: ProcessUniqueId,
+    index: usize,
+}
+
+impl ArenaId {
+    /// The value's position in the arena's storage.
+    #[inline]
+    pub fn index(self) -> usize {
+        self.index
+    }
+}
+
+/// Values stored side by side, addressed by [`ArenaId`]s.
+///
+/// Removing a value leaves a hole rather than moving the values after it, and holes are never
+/// filled again, so an ID keeps referring to its value for as long as the value is there and to
+/// nothing afterwards. Each arena is tagged with a process unique ID, so IDs from another arena
+/// don't find anything either.
+///
+/// In a long running program the holes add up. [`compact`](IdArena::compact) moves the values
+/// back together and returns an [`IdRemap`] from their old IDs to their new ones, for rewriting
+/// the references kept elsewhere:
+///
+/// ```
+/// use snowflake::IdArena;
+///
+/// let mut nodes = IdArena::new();
+/// let a = nodes.insert("a");
+/// let b = nodes.insert("b");
+/// let mut c = nodes.insert("c");
+/// nodes.remove(b);
+/// assert_eq!(nodes.holes(), 1);
+///
+/// let remap = nodes.compact();
+/// assert_eq!(nodes.holes(), 0);
+/// assert_eq!(nodes.get(a), None);
+/// assert!(remap.rewrite(&mut c));
+/// assert_eq!(nodes[c], "c");
+/// assert_eq!(remap.get(b), None);
+/// ```
+pub struct IdArena<T> {
+    // Replaced on compaction, so IDs from before it stop resolving instead of finding the wrong
+    // value.
+    id: ProcessUniqueId,
+    slots: Vec<Option<T>>,
+    len: usize,
+}
+
+impl<T> IdArena<T> {
+    /// Creates an empty arena.
+    #[inline]
+    pub fn new() -> Self {
+        IdArena {
+            id: ProcessUniqueId::new(),
+            slots: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// The number of values in the arena.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the arena has no values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of holes left by removed values since the last compaction.
+    #[inline]
+    pub fn holes(&self) -> usize {
+        self.slots.len() - self.len
+    }
+
+    #[inline]
+    fn slot(&self, id: ArenaId) -> Option<&Option<T>> {
+        if id.arena != self.id {
+            return None;
+        }
+        self.slots.get(id.index)
+    }
+
+    #[inline]
+    fn slot_mut(&mut self, id: ArenaId) -> Option<&mut Option<T>> {
+        if id.arena != self.id {
+            return None;
+        }
+        self.slots.get_mut(id.index)
+    }
+
+    /// Adds a value to the end of the arena, returning its ID.
+    pub fn insert(&mut self, value: T) -> ArenaId {
+        let id = ArenaId {
+            arena: self.id,
+            index: self.slots.len(),
+        };
+        self.slots.push(Some(value));
+        self.len += 1;
+        id
+    }
+
+    /// Returns the value for `id`, if any.
+    #[inline]
+    pub fn get(&self, id: ArenaId) -> Option<&T> {
+        self.slot(id)?.as_ref()
+    }
+
+    /// Returns a mutable reference to the value for `id`, if any.
+    #[inline]
+    pub fn get_mut(&mut self, id: ArenaId) -> Option<&mut T> {
+        self.slot_mut(id)?.as_mut()
+    }
+
+    /// Returns true if the arena has a value for `id`.
+    #[inline]
+    pub fn contains(&self, id: ArenaId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Removes the value for `id`, returning it if there was one.
+    pub fn remove(&mut self, id: ArenaId) -> Option<T> {
+        let old = self.slot_mut(id)?.take();
+        if old.is_some() {
+            self.len -= 1;
+        }
+        old
+    }
+
+    /// Iterates over the IDs and values in the arena, in the order they were inserted.
+    pub fn iter(&self) -> impl Iterator<Item = (ArenaId, &T)> + '_ {
+        let arena = self.id;
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, slot)| Some((ArenaId { arena, index }, slot.as_ref()?)))
+    }
+
+    /// Moves the values together, in the order they were inserted, and frees the space the holes
+    /// took up.
+    ///
+    /// Every ID handed out so far stops resolving, including those of values that didn't move:
+    /// the returned [`IdRemap`] maps them to their new IDs.
+    pub fn compact(&mut self) -> IdRemap {
+        let mut indices = Vec::with_capacity(self.slots.len());
+        let mut slots = Vec::with_capacity(self.len);
+        for slot in self.slots.drain(..) {
+            indices.push(slot.is_some().then_some(slots.len()));
+            if slot.is_some() {
+                slots.push(slot);
+            }
+        }
+        self.slots = slots;
+        let remap = IdRemap {
+            from: self.id,
+            to: ProcessUniqueId::new(),
+            indices,
+        };
+        self.id = remap.to;
+        remap
+    }
+}
+
+impl<T> Default for IdArena<T> {
+    #[inline]
+    fn default() -> Self {
+        IdArena::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for IdArena<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// **panics** if there's no value for the ID.
+impl<T> Index<ArenaId> for IdArena<T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, id: ArenaId) -> &T {
+        self.get(id).expect("no value for the ID")
+    }
+}
+
+/// **panics** if there's no value for the ID.
+impl<T> IndexMut<ArenaId> for IdArena<T> {
+    #[inline]
+    fn index_mut(&mut self, id: ArenaId) -> &mut T {
+        self.get_mut(id).expect("no value for the ID")
+    }
+}
+
+/// The old and new IDs of the values moved by [`IdArena::compact`].
+#[derive(Clone, Debug)]
+pub struct IdRemap {
+    from: ProcessUniqueId,
+    to: ProcessUniqueId,
+    indices: Vec<Option<usize>>,
+}
+
+impl IdRemap {
+    /// The new ID of the value `old` referred to before the compaction, or `None` if it had been
+    /// removed (or `old` is from another arena or compaction).
+    #[inline]
+    pub fn get(&self, old: ArenaId) -> Option<ArenaId> {
+        if old.arena != self.from {
+            return None;
+        }
+        Some(ArenaId {
+            arena: self.to,
+            index: (*self.indices.get(old.index)?)?,
+        })
+    }
+
+    /// Rewrites a reference kept from before the compaction, returning whether it refers to a
+    /// value in the compacted arena. References that already do are left alone, so rewriting twice
+    /// is harmless; references to removed values are left as they are, and keep resolving to
+    /// nothing.
+    #[inline]
+    pub fn rewrite(&self, id: &mut ArenaId) -> bool {
+        if id.arena == self.to {
+            return true;
+        }
+        match self.get(*id) {
+            Some(new) => {
+                *id = new;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Rewrites every reference in `ids` (see [`rewrite`](IdRemap::rewrite)), returning how many
+    /// referred to removed values.
+    pub fn rewrite_all<'a, I: IntoIterator<Item = &'a mut ArenaId>>(&self, ids: I) -> usize {
+        ids.into_iter()
+            .map(|id| self.rewrite(id))
+            .filter(|&ok| !ok)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IdArena;
+
+    #[test]
+    fn test_id_arena() {
+        let mut arena = IdArena::new();
+        let ids: Vec<_> = (0..100).map(|i| arena.insert(i)).collect();
+        assert_eq!(arena.len(), 100);
+        assert!(ids.iter().enumerate().all(|(i, &id)| arena[id] == i));
+        assert_eq!(IdArena::<usize>::new().get(ids[0]), None);
+
+        arena[ids[7]] += 1000;
+        assert_eq!(arena.remove(ids[9]), Some(9));
+        assert_eq!(arena.remove(ids[9]), None);
+        assert!(!arena.contains(ids[9]));
+        assert_eq!(arena.len(), 99);
+        assert_eq!(arena.holes(), 1);
+        let extra = arena.insert(100);
+        assert_eq!(extra.index(), 100);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut arena = IdArena::new();
+        let mut ids: Vec<_> = (0..100).map(|i| arena.insert(i)).collect();
+        for &id in ids.iter().step_by(3) {
+            arena.remove(id);
+        }
+        let before: Vec<_> = arena.iter().map(|(_, &v)| v).collect();
+
+        let remap = arena.compact();
+        assert_eq!(arena.holes(), 0);
+        assert_eq!(arena.len(), 66);
+        assert!(arena.iter().map(|(_, &v)| v).eq(before));
+        assert!(arena.iter().enumerate().all(|(i, (id, _))| id.index() == i));
+        assert_eq!(arena.get(ids[1]), None);
+
+        let stale = ids.clone();
+        assert_eq!(remap.rewrite_all(&mut ids), 34);
+        assert!(ids
+            .iter()
+            .enumerate()
+            .all(|(i, &id)| arena.get(id) == (i % 3 != 0).then_some(&i)));
+        // Rewriting again changes nothing.
+        let rewritten = ids.clone();
+        assert_eq!(remap.rewrite_all(&mut ids), 34);
+        assert_eq!(ids, rewritten);
+
+        // A second compaction only maps the IDs from the first.
+        arena.remove(ids[1]);
+        let second = arena.compact();
+        assert_eq!(second.get(stale[2]), None);
+        assert_eq!(arena[second.get(ids[2]).unwrap()], 2);
+        assert_eq!(second.get(ids[1]), None);
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use crate::{
+    AnyId, FlakeGenerator, IdHandle, PushIdGenerator, SnowflakeGenerator, SonyflakeGenerator,
+    TsidGenerator, XidGenerator,
+};
+
+/// A source of IDs.
+///
+/// Implemented by the crate's generators and by closures (or functions) returning IDs, so the
+/// process-wide constructors can be used directly:
+///
+/// ```
+/// use snowflake::{IdGen, ProcessUniqueId, SnowflakeGenerator};
+///
+/// fn two<G: IdGen>(gen: &G) -> (G::Id, G::Id) {
+///     (gen.next(), gen.next())
+/// }
+///
+/// let (a, b) = two(&SnowflakeGenerator::new(1));
+/// assert!(a < b);
+/// let (a, b) = two(&ProcessUniqueId::new);
+/// assert_ne!(a, b);
+/// ```
+pub trait IdGen {
+    /// The type of ID generated.
+    type Id;
+
+    /// Generates a new ID.
+    fn next(&self) -> Self::Id;
+}
+
+impl<F, I> IdGen for F
+where
+    F: Fn() -> I,
+{
+    type Id = I;
+
+    #[inline]
+    fn next(&self) -> I {
+        self()
+    }
+}
+
+macro_rules! id_gen {
+    ($($gen:ident => $id:ident),*) => {
+        $(
+            impl IdGen for $gen {
+                type Id = crate::$id;
+
+                #[inline]
+                fn next(&self) -> crate::$id {
+                    $gen::next(self)
+                }
+            }
+        )*
+    };
+}
+
+id_gen!(
+    SnowflakeGenerator => SnowflakeId,
+    SonyflakeGenerator => SonyflakeId,
+    TsidGenerator => Tsid,
+    XidGenerator => Xid,
+    FlakeGenerator => FlakeId,
+    PushIdGenerator => PushId,
+    IdHandle => ProcessUniqueId
+);
+
+/// An object-safe [`IdGen`] yielding [`AnyId`]s, for choosing the kind of ID at runtime.
+///
+/// Implemented for every `IdGen` whose IDs convert into `AnyId`.
+///
+/// ```
+/// use snowflake::{DynIdGen, ProcessUniqueId, SnowflakeGenerator};
+///
+/// fn from_config(kind: &str) -> Box<dyn DynIdGen + Send + Sync> {
+///     match kind {
+///         "snowflake" => Box::new(SnowflakeGenerator::new(1)),
+///         _ => Box::new(ProcessUniqueId::new),
+///     }
+/// }
+///
+/// assert_eq!(from_config("snowflake").next_any().kind(), "snowflake");
+/// assert_eq!(from_config("puid").next_any().kind(), "puid");
+/// ```
+pub trait DynIdGen {
+    /// Generates a new ID.
+    fn next_any(&self) -> AnyId;
+}
+
+impl<G> DynIdGen for G
+where
+    G: IdGen + ?Sized,
+    G::Id: Into<AnyId>,
+{
+    #[inline]
+    fn next_any(&self) -> AnyId {
+        self.next().into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DynIdGen, IdGen};
+    use crate::{AnyId, ObjectId, SmallUniqueId, TsidGenerator, XidGenerator};
+
+    #[test]
+    fn test_dyn() {
+        let gens: Vec<Box<dyn DynIdGen>> = vec![
+            Box::new(TsidGenerator::new(1)),
+            Box::new(XidGenerator::new(1)),
+            Box::new(ObjectId::new),
+            Box::new(SmallUniqueId::new),
+        ];
+        let kinds: Vec<_> = gens.iter().map(|g| g.next_any().kind()).collect();
+        assert_eq!(kinds, ["tsid", "xid", "objectid", "spuid"]);
+
+        let gen = TsidGenerator::new(1);
+        let id = IdGen::next(&gen);
+        match gen.next_any() {
+            AnyId::Tsid(next) => assert!(next > id),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(AnyId::from(id).to_string(), id.to_string());
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt;
+
+use crate::process_unique_id::{
+    next_in, try_next_block_lock_free, try_next_in, IdUnavailable, LocalBlock, ProcessUniqueId,
+    EMPTY_BLOCK,
+};
+
+/// A handle that creates process unique IDs from its own block, without going through thread
+/// local storage.
+///
+/// A handle works like the thread local state behind [`ProcessUniqueId::new`]: it claims a block
+/// of offsets from the shared prefix when it needs one and hands them out in order. Create one
+/// per thread or task and keep it around for hot loops:
+///
+/// ```
+/// use snowflake::IdHandle;
+///
+/// let handle = IdHandle::new();
+/// let a = handle.next();
+/// let b = handle.next();
+/// assert!(a < b);
+/// ```
+///
+/// Handles can be sent to other threads but not shared between them. IDs from a handle are
+/// unique with respect to all other process unique IDs but aren't ordered with respect to IDs
+/// created by other means. The rest of a handle's block is lost when it's dropped, so create
+/// handles for long-lived loops, not per ID.
+pub struct IdHandle {
+    block: LocalBlock,
+}
+
+impl IdHandle {
+    /// Creates a new handle.
+    ///
+    /// Creating a handle is free; its first block is claimed when the first ID is requested.
+    #[inline]
+    pub fn new() -> Self {
+        IdHandle {
+            block: LocalBlock::new(EMPTY_BLOCK),
+        }
+    }
+
+    /// Creates a new unique ID.
+    ///
+    /// **panics** under the same conditions as [`ProcessUniqueId::new`].
+    #[inline]
+    pub fn next(&self) -> ProcessUniqueId {
+        next_in(&self.block)
+    }
+
+    /// Like [`next`](IdHandle::next), but returns an error instead of panicking when there are no
+    /// more unique IDs available.
+    ///
+    /// Like [`ProcessUniqueId::try_new`], it claims whole prefixes without locking, and with the
+    /// `no-panic` feature optimized builds check at link time that it never panics.
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+    #[inline]
+    pub fn try_next(&self) -> Result<ProcessUniqueId, IdUnavailable> {
+        try_next_in(&self.block, try_next_block_lock_free).ok_or_else(IdUnavailable::new)
+    }
+}
+
+impl Default for IdHandle {
+    #[inline]
+    fn default() -> Self {
+        IdHandle::new()
+    }
+}
+
+impl fmt::Debug for IdHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IdHandle").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IdHandle;
+    use crate::ProcessUniqueId;
+    use std::thread;
+
+    #[test]
+    fn test_handle_unique() {
+        let handle = IdHandle::new();
+        let mut ids: Vec<_> = (0..1000).map(|_| handle.next()).collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        ids.extend((0..10).map(|_| ProcessUniqueId::new()));
+        let moved = thread::spawn(move || handle.next()).join().unwrap();
+        ids.push(moved);
+        ids.push(IdHandle::new().next());
+        let len = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), len);
+    }
+}
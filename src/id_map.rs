@@ -0,0 +1,130 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasherDefault, Hasher};
+
+use crate::ProcessUniqueId;
+
+/// An odd constant with well spread bits (from FxHash), for multiplicative hashing.
+const MULTIPLIER: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+/// A fast, non-cryptographic hasher for IDs.
+///
+/// IDs are already unique, so there's nothing to gain from SipHash (the standard library's
+/// default). Each word written is folded into the state with a rotate, xor, and multiply by an
+/// odd constant: sequential offsets, which only differ in their low bits, still end up in
+/// different buckets, and the multiply carries the differences up into the high bits hash tables
+/// use to tag entries.
+///
+/// **Not HashDoS resistant.** Only use it for keys you generate, not ones parsed from untrusted
+/// input: an attacker who can choose keys can make them all collide.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct IdHasher {
+    hash: u64,
+}
+
+impl IdHasher {
+    #[inline]
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(MULTIPLIER);
+    }
+}
+
+impl Hasher for IdHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.add(u64::from_le_bytes(word));
+        }
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.add(i.into());
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.add(i.into());
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.add(i.into());
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.add(i);
+    }
+
+    #[inline]
+    fn write_u128(&mut self, i: u128) {
+        self.add(i as u64);
+        self.add((i >> 64) as u64);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.add(i as u64);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// Builds [`IdHasher`]s, for use as a `HashMap` or `HashSet`'s hasher.
+pub type BuildIdHasher = BuildHasherDefault<IdHasher>;
+
+/// A `HashMap` keyed by IDs (process unique IDs unless specified), using [`IdHasher`].
+///
+/// ```
+/// use snowflake::{IdMap, ProcessUniqueId, SnowflakeId};
+///
+/// let mut names = IdMap::default();
+/// let id = ProcessUniqueId::new();
+/// names.insert(id, "first");
+/// assert_eq!(names[&id], "first");
+///
+/// let by_snowflake: IdMap<&str, SnowflakeId> = IdMap::default();
+/// # drop(by_snowflake);
+/// ```
+///
+/// See `IdHasher` for when *not* to use it.
+pub type IdMap<V, K = ProcessUniqueId> = HashMap<K, V, BuildIdHasher>;
+
+/// A `HashSet` of IDs (process unique IDs unless specified), using [`IdHasher`].
+pub type IdSet<K = ProcessUniqueId> = HashSet<K, BuildIdHasher>;
+
+#[cfg(test)]
+mod test {
+    use super::{IdMap, IdSet};
+    use crate::{ObjectId, ProcessUniqueId};
+    use std::hash::BuildHasher;
+
+    #[test]
+    fn test_id_map() {
+        let ids: Vec<_> = (0..1000).map(|_| ProcessUniqueId::new()).collect();
+        let map: IdMap<usize> = ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+        assert!(ids.iter().enumerate().all(|(i, id)| map[id] == i));
+
+        // Sequential IDs spread over both the low and high bits.
+        let hashes: Vec<u64> = ids.iter().map(|id| map.hasher().hash_one(id)).collect();
+        let low: IdSet<u64> = hashes.iter().map(|h| h & 0x3ff).collect();
+        let high: IdSet<u64> = hashes.iter().map(|h| h >> 57).collect();
+        assert!(low.len() > 500);
+        assert_eq!(high.len(), 128);
+
+        let set: IdSet<ObjectId> = (0..10).map(|_| ObjectId::new()).collect();
+        assert_eq!(set.len(), 10);
+    }
+}
@@ -0,0 +1,271 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::process_unique_id::{hex_field, usize_hex_field};
+use crate::{ParseIdError, ProcessUniqueId};
+
+struct Node {
+    id: ProcessUniqueId,
+    parent: Option<IdPath>,
+    depth: usize,
+}
+
+/// A process unique ID along with the chain of IDs of its parents, like a span in a trace or a
+/// node in a scene graph.
+///
+/// Paths share their ancestors, so creating a child and cloning a path are cheap (an allocation
+/// and a reference count increment respectively) however deep the path is.
+///
+/// ```
+/// use snowflake::IdPath;
+///
+/// let request = IdPath::new();
+/// let query = IdPath::child_of(&request);
+/// let retry = IdPath::child_of(&query);
+///
+/// assert_eq!(retry.parent(), Some(&query));
+/// assert_eq!(retry.depth(), 2);
+/// assert!(retry.is_descendant_of(&request));
+/// assert_eq!(retry.ancestors().last().unwrap().id(), request.id());
+/// ```
+///
+/// Paths display (and parse) as a list of IDs, root first, separated by slashes. Only the root
+/// is written out in full: a child created by the same thread as its parent (so sharing its
+/// prefix) is written as just its offset, e.g. `puid-0-1/2/3-0`.
+#[derive(Clone)]
+pub struct IdPath(Arc<Node>);
+
+impl IdPath {
+    /// Creates a root path (one without a parent) with a new ID.
+    #[inline]
+    pub fn new() -> Self {
+        IdPath::root(ProcessUniqueId::new())
+    }
+
+    /// Creates a root path (one without a parent) for `id`.
+    pub fn root(id: ProcessUniqueId) -> Self {
+        IdPath(Arc::new(Node {
+            id,
+            parent: None,
+            depth: 0,
+        }))
+    }
+
+    /// Creates a child of `parent` with a new ID.
+    #[inline]
+    pub fn child_of(parent: &IdPath) -> Self {
+        parent.child(ProcessUniqueId::new())
+    }
+
+    /// Creates a child of this path for `id`.
+    pub fn child(&self, id: ProcessUniqueId) -> Self {
+        IdPath(Arc::new(Node {
+            id,
+            parent: Some(self.clone()),
+            depth: self.depth() + 1,
+        }))
+    }
+
+    /// The last ID in the path.
+    #[inline]
+    pub fn id(&self) -> ProcessUniqueId {
+        self.0.id
+    }
+
+    /// The path without its last ID, or `None` for a root path.
+    #[inline]
+    pub fn parent(&self) -> Option<&IdPath> {
+        self.0.parent.as_ref()
+    }
+
+    /// The number of ancestors: zero for a root path.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.0.depth
+    }
+
+    /// The first ID in the path.
+    pub fn root_id(&self) -> ProcessUniqueId {
+        self.ancestors().last().unwrap_or(self).id()
+    }
+
+    /// Iterates over this path and its ancestors, from this path up to the root.
+    #[inline]
+    pub fn ancestors(&self) -> Ancestors<'_> {
+        Ancestors { next: Some(self) }
+    }
+
+    /// Returns true if `ancestor` is a proper ancestor of this path (not the path itself).
+    pub fn is_descendant_of(&self, ancestor: &IdPath) -> bool {
+        self.depth() > ancestor.depth()
+            && self.ancestors().nth(self.depth() - ancestor.depth()) == Some(ancestor)
+    }
+
+    /// The IDs in the path, root first.
+    pub fn to_vec(&self) -> Vec<ProcessUniqueId> {
+        let mut ids: Vec<_> = self.ancestors().map(IdPath::id).collect();
+        ids.reverse();
+        ids
+    }
+}
+
+impl Default for IdPath {
+    #[inline]
+    fn default() -> Self {
+        IdPath::new()
+    }
+}
+
+impl Drop for IdPath {
+    fn drop(&mut self) {
+        // Unlink ancestors nothing else refers to one at a time, so dropping a deep path doesn't
+        // recurse once per level.
+        let mut parent = Arc::get_mut(&mut self.0).and_then(|node| node.parent.take());
+        while let Some(mut path) = parent {
+            parent = Arc::get_mut(&mut path.0).and_then(|node| node.parent.take());
+        }
+    }
+}
+
+impl PartialEq for IdPath {
+    fn eq(&self, other: &IdPath) -> bool {
+        self.depth() == other.depth()
+            && self
+                .ancestors()
+                .zip(other.ancestors())
+                .take_while(|(a, b)| !Arc::ptr_eq(&a.0, &b.0))
+                .all(|(a, b)| a.id() == b.id())
+    }
+}
+
+impl Eq for IdPath {}
+
+impl Hash for IdPath {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.depth());
+        for path in self.ancestors() {
+            path.id().hash(state);
+        }
+    }
+}
+
+impl fmt::Display for IdPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ids = self.to_vec();
+        write!(f, "{}", ids[0])?;
+        for pair in ids.windows(2) {
+            let (parent, id) = (pair[0], pair[1]);
+            if id.prefix == parent.prefix {
+                write!(f, "/{:x}", id.offset)?;
+            } else {
+                write!(f, "/{:x}-{:x}", id.prefix, id.offset)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for IdPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "IdPath({})", self)
+    }
+}
+
+/// Parses the form produced by `Display`.
+impl FromStr for IdPath {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        let mut parts = s.split('/');
+        let root = parts.next().unwrap_or_default();
+        let mut path = IdPath::root(root.parse()?);
+        let mut start = root.len() + 1;
+        for part in parts {
+            let end = start + part.len();
+            let (prefix, offset_start) = match part.find('-') {
+                Some(dash) => (usize_hex_field(s, start, start + dash)?, start + dash + 1),
+                None => (path.id().prefix, start),
+            };
+            let offset = hex_field(s, offset_start, end)?;
+            path = path.child(ProcessUniqueId { prefix, offset });
+            start = end + 1;
+        }
+        Ok(path)
+    }
+}
+
+/// An iterator over a path and its ancestors, returned by [`IdPath::ancestors`].
+#[derive(Clone, Debug)]
+pub struct Ancestors<'a> {
+    next: Option<&'a IdPath>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = &'a IdPath;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a IdPath> {
+        let path = self.next?;
+        self.next = path.parent();
+        Some(path)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.next.map_or(0, |path| path.depth() + 1);
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Ancestors<'_> {}
+
+#[cfg(test)]
+mod test {
+    use super::IdPath;
+    use crate::ProcessUniqueId;
+    use std::thread;
+
+    #[test]
+    fn test_id_path_display() {
+        let root = IdPath::root("puid-0-1".parse().unwrap());
+        let child = root.child("puid-0-2".parse().unwrap());
+        let other = child.child("puid-3-0".parse().unwrap());
+        assert_eq!(other.to_string(), "puid-0-1/2/3-0");
+        assert_eq!(other.to_string().parse::<IdPath>(), Ok(other.clone()));
+        assert_eq!(other.root_id(), root.id());
+        assert!("puid-0-1/".parse::<IdPath>().is_err());
+        assert!("puid-0-1/x".parse::<IdPath>().is_err());
+        assert!("0-1/2".parse::<IdPath>().is_err());
+
+        let remote = thread::spawn(move || IdPath::child_of(&child))
+            .join()
+            .unwrap();
+        assert_eq!(remote.to_string().parse::<IdPath>(), Ok(remote));
+    }
+
+    #[test]
+    fn test_id_path_ancestry() {
+        let root = IdPath::new();
+        let mut path = root.clone();
+        for _ in 0..100_000 {
+            path = IdPath::child_of(&path);
+        }
+        assert_eq!(path.depth(), 100_000);
+        assert_eq!(path.ancestors().len(), 100_001);
+        assert!(path.is_descendant_of(&root));
+        assert!(!root.is_descendant_of(&path));
+        assert!(!path.is_descendant_of(&path));
+        assert!(!path.is_descendant_of(&IdPath::root(ProcessUniqueId::new())));
+        // Dropping a deep path doesn't overflow the stack.
+        drop(path);
+    }
+}
@@ -0,0 +1,310 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt;
+
+use crate::atomic::{AtomicU32, AtomicU64, Ordering};
+use crate::display::pad_id;
+
+// Marks the end of the freelist.
+const NIL: u32 = u32::MAX;
+
+/// An ID handed out by an [`IdPool`].
+///
+/// The index is a dense slot number below the pool's capacity, suitable for indexing into a
+/// `Vec`; the epoch tells apart successive holders of the same slot. Displayed as
+/// `pool-{index}-{epoch}`.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct PooledId {
+    index: u32,
+    epoch: u32,
+}
+
+impl PooledId {
+    /// The slot this ID occupies. Always less than the pool's capacity.
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// How many times the slot had been acquired or released before this ID was issued.
+    #[inline]
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+
+    /// Packs the ID into a `u64`, epoch in the high half.
+    #[inline]
+    pub fn to_u64(&self) -> u64 {
+        u64::from(self.epoch) << 32 | u64::from(self.index)
+    }
+
+    /// Unpacks an ID packed with [`to_u64`](PooledId::to_u64).
+    #[inline]
+    pub fn from_u64(value: u64) -> Self {
+        PooledId {
+            index: value as u32,
+            epoch: (value >> 32) as u32,
+        }
+    }
+}
+
+impl fmt::Display for PooledId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(
+            f,
+            format_args!("pool-{}-", self.index),
+            format_args!("{}", self.epoch),
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Slot {
+    // Odd while the slot is held, even while it's free.
+    epoch: AtomicU32,
+    // Next free slot, or `NIL`. Only meaningful while the slot is on the freelist.
+    next: AtomicU32,
+}
+
+/// A fixed-capacity pool of IDs that can be released and re-issued.
+///
+/// Long running programs that allocate and free handles at a high rate can use a pool to keep
+/// IDs dense: at most `capacity` IDs are live at once and their indices never exceed it, so
+/// downstream tables stay small. Each slot carries an epoch that is bumped on every acquire and
+/// release, so a re-issued ID never equals one handed out earlier and stale IDs can be detected
+/// with [`is_live`](IdPool::is_live).
+///
+/// ```
+/// use snowflake::IdPool;
+///
+/// let pool = IdPool::new(2);
+/// let a = pool.acquire().unwrap();
+/// let b = pool.acquire().unwrap();
+/// assert!(pool.acquire().is_none());
+///
+/// assert!(pool.release(a));
+/// assert!(!pool.release(a));
+/// let c = pool.acquire().unwrap();
+/// assert_eq!(c.index(), a.index());
+/// assert_ne!(c, a);
+/// assert!(!pool.is_live(a));
+/// assert!(pool.is_live(b) && pool.is_live(c));
+/// ```
+///
+/// The pool is lock-free: released slots go on a Treiber stack whose head is tagged with a
+/// counter to rule out ABA, and slots that have never been used are handed out by bumping an
+/// atomic counter.
+///
+/// # Limits
+///
+/// Epochs are 32 bits wide, so after 2^31 acquire/release cycles of a single slot its IDs start
+/// repeating.
+#[derive(Debug)]
+pub struct IdPool {
+    slots: Box<[Slot]>,
+    // Freelist head: a tag in the high half (bumped on every update) and a slot index (or `NIL`)
+    // in the low half.
+    head: AtomicU64,
+    // Number of slots that have ever been handed out.
+    fresh: AtomicU32,
+}
+
+impl IdPool {
+    /// Creates a pool handing out at most `capacity` live IDs.
+    ///
+    /// **panics** if `capacity` is `u32::MAX`.
+    pub fn new(capacity: u32) -> Self {
+        assert!(capacity < NIL, "IdPool capacity must be below u32::MAX");
+        IdPool {
+            slots: (0..capacity)
+                .map(|_| Slot {
+                    epoch: AtomicU32::new(0),
+                    next: AtomicU32::new(NIL),
+                })
+                .collect(),
+            head: AtomicU64::new(u64::from(NIL)),
+            fresh: AtomicU32::new(0),
+        }
+    }
+
+    /// The maximum number of live IDs.
+    #[inline]
+    pub fn capacity(&self) -> u32 {
+        self.slots.len() as u32
+    }
+
+    /// Acquires an ID, reusing a released slot if there is one.
+    ///
+    /// Returns `None` if all `capacity` IDs are live.
+    pub fn acquire(&self) -> Option<PooledId> {
+        let index = match self.pop() {
+            Some(index) => index,
+            None => self.bump()?,
+        };
+        // We own the slot now, so nobody else touches its epoch until we hand the ID out.
+        let epoch = self.slots[index as usize]
+            .epoch
+            .fetch_add(1, Ordering::AcqRel)
+            .wrapping_add(1);
+        Some(PooledId { index, epoch })
+    }
+
+    /// Releases `id` so its slot can be re-issued.
+    ///
+    /// Returns `false`, and does nothing, if `id` isn't live: it was already released, or it
+    /// didn't come from this pool.
+    pub fn release(&self, id: PooledId) -> bool {
+        let slot = match self.slots.get(id.index as usize) {
+            Some(slot) => slot,
+            None => return false,
+        };
+        if id.epoch & 1 == 0
+            || slot
+                .epoch
+                .compare_exchange(
+                    id.epoch,
+                    id.epoch.wrapping_add(1),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_err()
+        {
+            return false;
+        }
+        self.push(id.index);
+        true
+    }
+
+    /// Returns `true` if `id` was acquired from this pool and hasn't been released since.
+    pub fn is_live(&self, id: PooledId) -> bool {
+        id.epoch & 1 == 1
+            && self
+                .slots
+                .get(id.index as usize)
+                .is_some_and(|slot| slot.epoch.load(Ordering::Acquire) == id.epoch)
+    }
+
+    fn bump(&self) -> Option<u32> {
+        let capacity = self.capacity();
+        self.fresh
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                if n < capacity {
+                    Some(n + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()
+    }
+
+    fn pop(&self) -> Option<u32> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let index = head as u32;
+            if index == NIL {
+                return None;
+            }
+            // Slots are never deallocated, so reading a stale `next` is harmless: the tag makes
+            // the CAS fail if the slot was popped (and maybe pushed back) in the meantime.
+            let next = self.slots[index as usize].next.load(Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                tagged(head, next),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Some(index),
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    fn push(&self, index: u32) {
+        let slot = &self.slots[index as usize];
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            slot.next.store(head as u32, Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                tagged(head, index),
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+}
+
+// A new freelist head pointing at `index`, with the tag of `head` bumped.
+#[inline]
+fn tagged(head: u64, index: u32) -> u64 {
+    ((head >> 32).wrapping_add(1) << 32) | u64::from(index)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IdPool, PooledId};
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_reuse() {
+        let pool = IdPool::new(3);
+        let ids: Vec<_> = (0..3).map(|_| pool.acquire().unwrap()).collect();
+        assert_eq!(
+            ids.iter().map(PooledId::index).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert!(pool.acquire().is_none());
+
+        assert!(pool.release(ids[1]));
+        assert!(pool.release(ids[2]));
+        assert!(!pool.release(ids[2]));
+        assert!(!pool.release(PooledId::from_u64(7)));
+        // Last released, first re-issued.
+        let again = pool.acquire().unwrap();
+        assert_eq!((again.index(), again.epoch()), (2, 3));
+        assert_eq!(PooledId::from_u64(again.to_u64()), again);
+        assert_eq!(again.to_string(), "pool-2-3");
+        assert_eq!(pool.acquire().unwrap().index(), 1);
+        assert!(pool.acquire().is_none());
+    }
+
+    #[test]
+    fn test_concurrent() {
+        let pool = Arc::new(IdPool::new(64));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                thread::spawn(move || {
+                    let mut seen = Vec::new();
+                    for _ in 0..10_000 {
+                        let a = pool.acquire().unwrap();
+                        let b = pool.acquire().unwrap();
+                        assert_ne!(a.index(), b.index());
+                        assert!(pool.release(a));
+                        seen.push(b);
+                        assert!(pool.release(b));
+                    }
+                    seen
+                })
+            })
+            .collect();
+        let mut all = HashSet::new();
+        for t in threads {
+            for id in t.join().unwrap() {
+                assert!(id.index() < 64);
+                assert!(all.insert(id));
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::convert::TryFrom;
+use std::iter::FusedIterator;
+
+use crate::process_unique_id::{reserve_exact, ProcessUniqueId};
+
+/// A contiguous range of process unique IDs sharing a prefix, reserved up front.
+///
+/// Reserving a range is a single operation however many IDs it holds, checking whether an ID
+/// belongs to it is a comparison, and it can be split into sub-ranges to hand out to workers:
+///
+/// ```
+/// use snowflake::IdRange;
+/// use std::thread;
+///
+/// let range = IdRange::reserve(1_000_000);
+/// let workers: Vec<_> = range
+///     .split_into(4)
+///     .into_iter()
+///     .map(|part| thread::spawn(move || part.collect::<Vec<_>>()))
+///     .collect();
+/// for worker in workers {
+///     for id in worker.join().unwrap() {
+///         assert!(range.contains(&id));
+///     }
+/// }
+/// ```
+///
+/// A range is an iterator over its IDs, in order. Like IDs from an [`IdStream`](crate::IdStream),
+/// they're unique with respect to all other process unique IDs but aren't ordered with respect to
+/// IDs created by other means.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct IdRange {
+    prefix: usize,
+    start: u64,
+    len: u64,
+}
+
+impl IdRange {
+    /// Reserves `n` consecutive IDs.
+    ///
+    /// Takes the IDs from the current thread's block when it has enough left, and otherwise
+    /// claims them from the shared counter. Reserving zero IDs returns an empty range.
+    ///
+    /// **panics** under the same conditions as [`ProcessUniqueId::new`].
+    pub fn reserve(n: u64) -> Self {
+        if n == 0 {
+            return IdRange {
+                prefix: 0,
+                start: 0,
+                len: 0,
+            };
+        }
+        let first = reserve_exact(n);
+        IdRange {
+            prefix: first.prefix,
+            start: first.offset,
+            len: n,
+        }
+    }
+
+    /// The number of IDs in the range.
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if the range has no IDs.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the `index`th ID in the range, if the range is that long.
+    #[inline]
+    pub fn get(&self, index: u64) -> Option<ProcessUniqueId> {
+        if index >= self.len {
+            return None;
+        }
+        Some(ProcessUniqueId {
+            prefix: self.prefix,
+            offset: self.start + index,
+        })
+    }
+
+    /// Returns true if `id` is in the range.
+    #[inline]
+    pub fn contains(&self, id: &ProcessUniqueId) -> bool {
+        id.prefix == self.prefix && id.offset.wrapping_sub(self.start) < self.len
+    }
+
+    /// Splits the range into the first `mid` IDs and the rest.
+    ///
+    /// **panics** if `mid` is greater than the range's length.
+    pub fn split_at(&self, mid: u64) -> (IdRange, IdRange) {
+        assert!(mid <= self.len, "split point out of range");
+        let head = IdRange {
+            prefix: self.prefix,
+            start: self.start,
+            len: mid,
+        };
+        let tail = IdRange {
+            prefix: self.prefix,
+            start: self.start + mid,
+            len: self.len - mid,
+        };
+        (head, tail)
+    }
+
+    /// Splits the range into `parts` consecutive sub-ranges whose lengths differ by at most one.
+    ///
+    /// **panics** if `parts` is zero.
+    pub fn split_into(&self, parts: u64) -> Vec<IdRange> {
+        assert!(parts > 0, "can't split a range into zero parts");
+        let (size, extra) = (self.len / parts, self.len % parts);
+        let mut rest = self.clone();
+        (0..parts)
+            .map(|i| {
+                let (part, tail) = rest.split_at(size + u64::from(i < extra));
+                rest = tail;
+                part
+            })
+            .collect()
+    }
+}
+
+impl Iterator for IdRange {
+    type Item = ProcessUniqueId;
+
+    #[inline]
+    fn next(&mut self) -> Option<ProcessUniqueId> {
+        let id = self.get(0)?;
+        self.len -= 1;
+        // Doesn't overflow unless the range is now empty.
+        self.start = self.start.wrapping_add(1);
+        Some(id)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.len) {
+            Ok(len) => (len, Some(len)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+}
+
+impl DoubleEndedIterator for IdRange {
+    #[inline]
+    fn next_back(&mut self) -> Option<ProcessUniqueId> {
+        let id = self.get(self.len.checked_sub(1)?)?;
+        self.len -= 1;
+        Some(id)
+    }
+}
+
+impl FusedIterator for IdRange {}
+
+#[cfg(test)]
+mod test {
+    use super::IdRange;
+    use crate::ProcessUniqueId;
+
+    #[test]
+    fn test_id_range() {
+        let range = IdRange::reserve(10);
+        assert_eq!(range.len(), 10);
+        let ids: Vec<_> = range.clone().collect();
+        assert!(ids.windows(2).all(|w| w[1].offset == w[0].offset + 1));
+        assert!(ids.iter().all(|id| range.contains(id)));
+        assert!(!range.contains(&ProcessUniqueId::new()));
+        assert_eq!(range.clone().next_back(), ids.last().copied());
+
+        let parts = range.split_into(3);
+        assert_eq!(
+            parts.iter().map(IdRange::len).collect::<Vec<_>>(),
+            [4, 3, 3]
+        );
+        assert!(parts.into_iter().flatten().eq(ids));
+
+        let (head, tail) = range.split_at(10);
+        assert_eq!(head.len(), 10);
+        assert!(tail.is_empty());
+        assert!(IdRange::reserve(0).is_empty());
+    }
+
+    #[test]
+    fn test_id_range_larger_than_block() {
+        let big = IdRange::reserve(u64::MAX);
+        let next = IdRange::reserve(u64::MAX);
+        assert_ne!(big.get(0).unwrap().prefix, next.get(0).unwrap().prefix);
+        assert!(!big.contains(&next.get(0).unwrap()));
+        assert!(!big.contains(&ProcessUniqueId::new()));
+    }
+}
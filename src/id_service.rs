@@ -0,0 +1,183 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A host-local ID service over a Unix domain socket.
+//!
+//! An [`IdServer`] hands out prefixes from a [`PrefixCounter`] to any process that connects to
+//! its socket, and a [`ServiceCounter`] is the client side: plug it into a
+//! [`CrossProcessGenerator`] (or use [`ServiceGenerator::connect`]) to generate
+//! [`HostUniqueId`](crate::HostUniqueId)s that are unique among every client of the server.
+//!
+//! # Protocol
+//!
+//! The protocol is line based so clients are easy to write in any language. A client sends
+//! `PREFIX\n` and the server replies with either the claimed prefix in decimal (`42\n`) or an
+//! error (`ERR <message>\n`). A connection may claim any number of prefixes; once it has claimed
+//! one, it may hand out IDs `(prefix, 0)` through `(prefix, 2^64 - 1)` without asking again.
+//!
+//! ```no_run
+//! use snowflake::cross_process::LockedFileCounter;
+//! use snowflake::id_service::{IdServer, ServiceGenerator};
+//!
+//! // In the daemon:
+//! let counter = LockedFileCounter::open("/var/lib/ids/prefix").unwrap();
+//! let server = IdServer::bind("/run/ids.sock", counter).unwrap();
+//! std::thread::spawn(move || server.serve());
+//!
+//! // In each client:
+//! let ids = ServiceGenerator::connect("/run/ids.sock");
+//! let id = ids.next().unwrap();
+//! ```
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crate::cross_process::{CrossProcessGenerator, PrefixCounter};
+
+const CLAIM: &str = "PREFIX";
+
+/// Serves prefixes from a [`PrefixCounter`] over a Unix domain socket.
+///
+/// The server only remembers what its counter remembers: back it with a
+/// [`LockedFileCounter`](crate::cross_process::LockedFileCounter) so that restarting the daemon
+/// doesn't hand out prefixes that running clients are still using.
+#[derive(Debug)]
+pub struct IdServer<C> {
+    listener: UnixListener,
+    counter: C,
+}
+
+impl<C: PrefixCounter + Sync> IdServer<C> {
+    /// Listens on the socket at `path`, claiming prefixes from `counter`.
+    pub fn bind<P: AsRef<Path>>(path: P, counter: C) -> io::Result<Self> {
+        Ok(IdServer {
+            listener: UnixListener::bind(path)?,
+            counter,
+        })
+    }
+
+    /// The counter prefixes are claimed from.
+    #[inline]
+    pub fn counter(&self) -> &C {
+        &self.counter
+    }
+
+    /// Accepts and serves connections (each on its own thread) until accepting fails.
+    pub fn serve(&self) -> io::Result<()> {
+        thread::scope(|scope| loop {
+            let (stream, _) = self.listener.accept()?;
+            scope.spawn(move || {
+                // A misbehaving client only affects its own connection.
+                let _ = self.serve_connection(stream);
+            });
+        })
+    }
+
+    fn serve_connection(&self, stream: UnixStream) -> io::Result<()> {
+        let mut writer = &stream;
+        for line in BufReader::new(&stream).lines() {
+            let line = line?;
+            if line.trim() != CLAIM {
+                writeln!(writer, "ERR unknown request")?;
+                continue;
+            }
+            match self.counter.claim() {
+                Ok(prefix) => writeln!(writer, "{}", prefix)?,
+                Err(e) => writeln!(writer, "ERR {}", e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A [`PrefixCounter`] that claims prefixes from an [`IdServer`].
+///
+/// Each claim opens a new connection, so the client recovers from server restarts on its own.
+/// Claims are rare (one per generator, per fork), so the extra connection setup doesn't matter.
+#[derive(Debug, Clone)]
+pub struct ServiceCounter {
+    path: PathBuf,
+}
+
+impl ServiceCounter {
+    /// Claims prefixes from the server listening at `path`.
+    ///
+    /// Doesn't connect until the first claim.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        ServiceCounter {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl PrefixCounter for ServiceCounter {
+    fn claim(&self) -> io::Result<u64> {
+        let mut stream = UnixStream::connect(&self.path)?;
+        writeln!(stream, "{}", CLAIM)?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        let line = line.trim();
+        if let Some(message) = line.strip_prefix("ERR ") {
+            return Err(io::Error::other(format!("ID service: {}", message)));
+        }
+        line.parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("malformed ID service response: {:?}", line),
+            )
+        })
+    }
+}
+
+/// A [`CrossProcessGenerator`] that claims its prefixes from an [`IdServer`].
+pub type ServiceGenerator = CrossProcessGenerator<ServiceCounter>;
+
+impl ServiceGenerator {
+    /// Creates a generator claiming prefixes from the server listening at `path`.
+    pub fn connect<P: AsRef<Path>>(path: P) -> Self {
+        CrossProcessGenerator::with_counter(ServiceCounter::new(path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IdServer, ServiceCounter, ServiceGenerator};
+    use crate::cross_process::{LockedFileCounter, PrefixCounter};
+    use std::fs;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+
+    #[test]
+    fn test_service() {
+        let dir = std::env::temp_dir().join(format!("snowflake-service-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("ids.sock");
+        let counter = LockedFileCounter::open(dir.join("prefix")).unwrap();
+        let server = IdServer::bind(&socket, counter).unwrap();
+        thread::spawn(move || server.serve());
+
+        let a = ServiceGenerator::connect(&socket);
+        let b = ServiceGenerator::connect(&socket);
+        assert_eq!(a.next().unwrap().prefix(), 0);
+        assert_eq!(b.next().unwrap().prefix(), 1);
+        assert_eq!(a.next().unwrap().prefix(), 0);
+        assert_eq!(ServiceCounter::new(&socket).claim().unwrap(), 2);
+
+        // Speak the protocol directly, as a non-Rust client would.
+        let mut stream = UnixStream::connect(&socket).unwrap();
+        stream.write_all(b"PREFIX\nHELLO\n").unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "3");
+        assert!(lines.next().unwrap().unwrap().starts_with("ERR "));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
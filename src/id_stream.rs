@@ -0,0 +1,124 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::iter::FusedIterator;
+
+use crate::process_unique_id::{reserve_local, ProcessUniqueId};
+
+/// How many IDs a stream reserves from the thread local generator at a time.
+const CHUNK_SIZE: u64 = 1024;
+
+/// A never-ending iterator of process unique IDs.
+///
+/// The stream reserves IDs from the current thread's generator in chunks and hands them out
+/// without touching thread local storage in between, so it's cheaper than calling
+/// [`ProcessUniqueId::new`] in a loop:
+///
+/// ```
+/// use snowflake::{IdStream, ProcessUniqueId};
+///
+/// let names = vec!["a", "b", "c"];
+/// let named: Vec<(ProcessUniqueId, &str)> = IdStream::new().zip(names).collect();
+/// assert_eq!(named.len(), 3);
+/// ```
+///
+/// IDs from a stream are unique with respect to all other process unique IDs but, because they're
+/// reserved in chunks, aren't ordered with respect to IDs created by other means.
+///
+/// A clone of a stream starts empty and reserves a chunk of its own, so the two never hand out
+/// the same ID.
+#[derive(Debug, Default)]
+pub struct IdStream {
+    next: Option<ProcessUniqueId>,
+    remaining: u64,
+}
+
+impl IdStream {
+    /// Creates a new ID stream.
+    ///
+    /// Creating a stream is free; IDs are reserved when first requested.
+    #[inline]
+    pub fn new() -> Self {
+        IdStream {
+            next: None,
+            remaining: 0,
+        }
+    }
+}
+
+/// Starts the clone without a chunk, so it doesn't share the original's.
+impl Clone for IdStream {
+    #[inline]
+    fn clone(&self) -> Self {
+        IdStream::new()
+    }
+}
+
+impl Iterator for IdStream {
+    type Item = ProcessUniqueId;
+
+    #[inline]
+    fn next(&mut self) -> Option<ProcessUniqueId> {
+        let id = match self.next {
+            Some(id) if self.remaining > 0 => id,
+            _ => {
+                let (first, count) = reserve_local(CHUNK_SIZE);
+                self.remaining = count;
+                first
+            }
+        };
+        self.remaining -= 1;
+        self.next = if self.remaining > 0 {
+            Some(ProcessUniqueId {
+                prefix: id.prefix,
+                offset: id.offset + 1,
+            })
+        } else {
+            None
+        };
+        Some(id)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+impl FusedIterator for IdStream {}
+
+#[cfg(test)]
+mod test {
+    use super::{IdStream, CHUNK_SIZE};
+    use crate::ProcessUniqueId;
+
+    #[test]
+    fn test_stream_unique() {
+        let mut stream = IdStream::new();
+        let mut ids: Vec<_> = stream.by_ref().take(3 * CHUNK_SIZE as usize).collect();
+        ids.extend((0..10).map(|_| ProcessUniqueId::new()));
+        ids.extend(stream.take(10));
+        let len = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), len);
+    }
+
+    #[test]
+    fn test_clone_unique() {
+        let mut stream = IdStream::new();
+        stream.next();
+        let mut clone = stream.clone();
+        let mut ids: Vec<_> = stream.take(2 * CHUNK_SIZE as usize).collect();
+        ids.extend(clone.by_ref().take(2 * CHUNK_SIZE as usize));
+        ids.extend(clone.clone().take(10));
+        let len = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), len);
+    }
+}
@@ -0,0 +1,196 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+use futures_core::stream::{FusedStream, Stream};
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use crate::async_snowflake::sleep;
+use crate::IdGen;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use crate::{AsyncSnowflakeGenerator, RateLimitedGen, SnowflakeId};
+
+type IdFuture<Id> = Pin<Box<dyn Future<Output = Id> + Send>>;
+
+/// A never-ending `Stream` of IDs, for generators that may have to wait before issuing one.
+///
+/// Whatever the source, async pipelines take IDs the same way:
+///
+/// - [`from_gen`](IdStreamAsync::from_gen): any [`IdGen`], which never waits.
+/// - `From<RateLimitedGen<G>>` (`tokio` or `async-std` feature): waits on the runtime's timer
+///   while the rate limit is reached, instead of blocking the executor thread.
+/// - `From<AsyncSnowflakeGenerator>` (likewise): waits for the next millisecond when the current
+///   one's sequence is exhausted.
+/// - [`from_fn`](IdStreamAsync::from_fn): any async function, e.g. one fetching blocks of IDs
+///   from a remote service.
+///
+/// ```
+/// use futures_util::StreamExt;
+/// use snowflake::{IdStreamAsync, SnowflakeGenerator};
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let mut stream = IdStreamAsync::from_gen(SnowflakeGenerator::new(1));
+/// let first = stream.next().await.unwrap();
+/// let second = stream.next().await.unwrap();
+/// assert!(first < second);
+/// # });
+/// ```
+pub struct IdStreamAsync<Id> {
+    next: Box<dyn FnMut() -> IdFuture<Id> + Send>,
+    pending: Option<IdFuture<Id>>,
+}
+
+impl<Id> IdStreamAsync<Id> {
+    /// Streams the IDs returned by the futures `f` creates, one future per ID.
+    ///
+    /// ```
+    /// use futures_util::StreamExt;
+    /// use snowflake::IdStreamAsync;
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// // Stands in for a client fetching IDs from a central service.
+    /// let remote = Arc::new(AtomicU64::new(100));
+    /// let mut stream = IdStreamAsync::from_fn(move || {
+    ///     let remote = remote.clone();
+    ///     async move { remote.fetch_add(1, Ordering::Relaxed) }
+    /// });
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// assert_eq!(stream.next().await, Some(100));
+    /// assert_eq!(stream.next().await, Some(101));
+    /// # });
+    /// ```
+    pub fn from_fn<F, Fut>(mut f: F) -> Self
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Id> + Send + 'static,
+    {
+        IdStreamAsync {
+            next: Box::new(move || Box::pin(f())),
+            pending: None,
+        }
+    }
+}
+
+impl<Id: Send + 'static> IdStreamAsync<Id> {
+    /// Streams the IDs `gen` generates. Each ID is ready as soon as it's polled for.
+    pub fn from_gen<G>(gen: G) -> Self
+    where
+        G: IdGen<Id = Id> + Send + 'static,
+    {
+        IdStreamAsync::from_fn(move || {
+            let id = gen.next();
+            async move { id }
+        })
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl<G> From<RateLimitedGen<G>> for IdStreamAsync<G::Id>
+where
+    G: IdGen + Send + Sync + 'static,
+    G::Id: Send + 'static,
+{
+    fn from(gen: RateLimitedGen<G>) -> Self {
+        let gen = Arc::new(gen);
+        IdStreamAsync::from_fn(move || {
+            let gen = gen.clone();
+            async move {
+                loop {
+                    match gen.try_next() {
+                        Ok(id) => return id,
+                        Err(limited) => sleep(limited.retry_after()).await,
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl From<AsyncSnowflakeGenerator> for IdStreamAsync<SnowflakeId> {
+    fn from(gen: AsyncSnowflakeGenerator) -> Self {
+        let gen = Arc::new(gen);
+        IdStreamAsync::from_fn(move || {
+            let gen = gen.clone();
+            async move { gen.next().await }
+        })
+    }
+}
+
+impl<Id> Stream for IdStreamAsync<Id> {
+    type Item = Id;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Id>> {
+        let this = self.get_mut();
+        let next = &mut this.next;
+        let pending = this.pending.get_or_insert_with(next);
+        let id = ready!(pending.as_mut().poll(cx));
+        this.pending = None;
+        Poll::Ready(Some(id))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+impl<Id> FusedStream for IdStreamAsync<Id> {
+    #[inline]
+    fn is_terminated(&self) -> bool {
+        false
+    }
+}
+
+impl<Id> fmt::Debug for IdStreamAsync<Id> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IdStreamAsync")
+            .field("pending", &self.pending.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IdStreamAsync;
+    use crate::ProcessUniqueId;
+    use futures_util::StreamExt;
+
+    #[tokio::test]
+    async fn test_id_stream_async() {
+        let stream = IdStreamAsync::from_gen(ProcessUniqueId::new);
+        let mut ids: Vec<_> = stream.take(100).collect().await;
+        let len = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), len);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_rate_limited_stream() {
+        use crate::{AsyncSnowflakeGenerator, RateLimitedGen, SnowflakeGenerator};
+        use std::time::{Duration, Instant};
+
+        let start = Instant::now();
+        let gen = RateLimitedGen::new(ProcessUniqueId::new, 1000, 1);
+        let ids: Vec<_> = IdStreamAsync::from(gen).take(20).collect().await;
+        assert_eq!(ids.len(), 20);
+        assert!(start.elapsed() >= Duration::from_millis(19));
+
+        let gen = AsyncSnowflakeGenerator::new(SnowflakeGenerator::new(1));
+        let ids: Vec<_> = IdStreamAsync::from(gen).take(5000).collect().await;
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! String interning.
+//!
+//! An [`Interner`] maps strings to [`Symbol`]s, small `Copy` IDs that compare and hash much
+//! faster than the strings they stand for, and back. Symbols are process unique IDs, so symbols
+//! from different interners never collide: looking a symbol up in the wrong interner finds
+//! nothing rather than some other string.
+//!
+//! ```
+//! use snowflake::intern::Interner;
+//!
+//! let names = Interner::new();
+//! let a = names.intern("main");
+//! let b = names.intern("main");
+//! assert_eq!(a, b);
+//! assert_eq!(&*names.resolve(a).unwrap(), "main");
+//!
+//! assert_eq!(Interner::new().resolve(a), None);
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::{IdMap, ProcessUniqueId};
+
+/// An interned string, see [`Interner`].
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Symbol(ProcessUniqueId);
+
+impl Symbol {
+    /// The process unique ID behind the symbol.
+    #[inline]
+    pub fn id(self) -> ProcessUniqueId {
+        self.0
+    }
+}
+
+#[derive(Default)]
+struct Tables {
+    symbols: HashMap<Arc<str>, Symbol>,
+    names: IdMap<Arc<str>, Symbol>,
+}
+
+/// A concurrent string interner.
+///
+/// Lookups of strings that are already interned only take a read lock, so they can run in
+/// parallel; interning a new string takes a write lock. Interned strings are kept until the
+/// interner is dropped.
+#[derive(Default)]
+pub struct Interner {
+    tables: RwLock<Tables>,
+}
+
+impl Interner {
+    /// Creates an empty interner.
+    #[inline]
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    /// Returns the symbol for `name`, interning it if it isn't already.
+    pub fn intern(&self, name: &str) -> Symbol {
+        if let Some(symbol) = self.get(name) {
+            return symbol;
+        }
+        let mut tables = self.tables.write().unwrap_or_else(|e| e.into_inner());
+        // Another thread may have interned it since we looked.
+        if let Some(&symbol) = tables.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(ProcessUniqueId::new());
+        let name: Arc<str> = name.into();
+        tables.symbols.insert(name.clone(), symbol);
+        tables.names.insert(symbol, name);
+        symbol
+    }
+
+    /// Returns the symbol for `name` if it has been interned.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        let tables = self.tables.read().unwrap_or_else(|e| e.into_inner());
+        tables.symbols.get(name).copied()
+    }
+
+    /// Returns the string `symbol` stands for, or `None` if it came from another interner.
+    pub fn resolve(&self, symbol: Symbol) -> Option<Arc<str>> {
+        let tables = self.tables.read().unwrap_or_else(|e| e.into_inner());
+        tables.names.get(&symbol).cloned()
+    }
+
+    /// The number of interned strings.
+    pub fn len(&self) -> usize {
+        let tables = self.tables.read().unwrap_or_else(|e| e.into_inner());
+        tables.symbols.len()
+    }
+
+    /// Returns true if no strings have been interned.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for Interner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Interner")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// A process-wide interner, for symbols that are shared across a whole program.
+pub fn global() -> &'static Interner {
+    static GLOBAL: OnceLock<Interner> = OnceLock::new();
+    GLOBAL.get_or_init(Interner::new)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{global, Interner};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_intern_threaded() {
+        let names = Arc::new(Interner::new());
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let names = names.clone();
+                thread::spawn(move || {
+                    (0..100)
+                        .map(|i| names.intern(&i.to_string()))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let symbols: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        assert!(symbols.windows(2).all(|w| w[0] == w[1]));
+        assert_eq!(names.len(), 100);
+        assert_eq!(&*names.resolve(symbols[0][42]).unwrap(), "42");
+        assert_eq!(names.get("42"), Some(symbols[0][42]));
+        assert_eq!(names.get("nope"), None);
+
+        assert_eq!(global().resolve(symbols[0][42]), None);
+        assert_eq!(global().intern("42"), global().intern("42"));
+    }
+}
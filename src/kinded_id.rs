@@ -0,0 +1,338 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
+
+use crate::display::pad_id;
+use crate::process_unique_id::{hex_field, usize_hex_field};
+use crate::{CompositeId, Discriminant, IdOutOfRange, ParseIdError, ProcessUniqueId};
+
+/// The kinds of thing a [`KindedId`] can identify (entities, jobs, sessions, ...), usually an
+/// enum.
+///
+/// ```
+/// use snowflake::IdKind;
+///
+/// #[derive(Copy, Clone, PartialEq, Debug)]
+/// enum Kind {
+///     Entity = 1,
+///     Job = 2,
+///     Session = 3,
+/// }
+///
+/// impl IdKind for Kind {
+///     fn to_byte(self) -> u8 {
+///         self as u8
+///     }
+///
+///     fn from_byte(byte: u8) -> Option<Self> {
+///         match byte {
+///             1 => Some(Kind::Entity),
+///             2 => Some(Kind::Job),
+///             3 => Some(Kind::Session),
+///             _ => None,
+///         }
+///     }
+///
+///     fn name(self) -> &'static str {
+///         match self {
+///             Kind::Entity => "entity",
+///             Kind::Job => "job",
+///             Kind::Session => "session",
+///         }
+///     }
+/// }
+/// ```
+///
+/// The byte is part of the encoded ID and the name part of its display form, so changing either
+/// changes the meaning of IDs that have already been stored.
+pub trait IdKind: Copy + PartialEq {
+    /// The kind's byte.
+    fn to_byte(self) -> u8;
+
+    /// Converts a byte returned by `to_byte` back, returning `None` if it doesn't stand for a
+    /// kind.
+    fn from_byte(byte: u8) -> Option<Self>;
+
+    /// The kind's name in the display form, e.g. `job`. Must not be empty, and must be different
+    /// for every kind.
+    fn name(self) -> &'static str;
+
+    /// Looks up a kind by its [`name`](IdKind::name).
+    ///
+    /// The default implementation tries every byte; override it if that's too slow.
+    fn from_name(name: &str) -> Option<Self> {
+        (0..=u8::MAX)
+            .filter_map(Self::from_byte)
+            .find(|kind| kind.name() == name)
+    }
+}
+
+// Stores an `IdKind` as an 8 bit `CompositeId` discriminant.
+#[derive(Copy, Clone)]
+struct Tag<K>(K);
+
+impl<K: IdKind> Discriminant for Tag<K> {
+    const BITS: u32 = 8;
+
+    #[inline]
+    fn to_bits(self) -> u32 {
+        self.0.to_byte().into()
+    }
+
+    #[inline]
+    fn from_bits(bits: u32) -> Option<Self> {
+        u8::try_from(bits).ok().and_then(K::from_byte).map(Tag)
+    }
+}
+
+/// A process unique ID that knows what kind of thing it identifies.
+///
+/// The kind's byte takes the top 8 bits of the ID's `u128` form (see [`CompositeId`]), and its
+/// name starts the display form, `<kind>-<prefix>-<offset>` (hex). Parsing fails on unknown
+/// kinds, and [`parse_as`](KindedId::parse_as) also fails on IDs of any other kind, so an API
+/// handed a session ID where it expects a job ID rejects it instead of looking it up:
+///
+/// ```
+/// use snowflake::{IdKind, KindedId};
+///
+/// #[derive(Copy, Clone, PartialEq, Debug)]
+/// enum Kind {
+///     Job,
+///     Session,
+/// }
+///
+/// impl IdKind for Kind {
+///     fn to_byte(self) -> u8 {
+///         self as u8
+///     }
+///
+///     fn from_byte(byte: u8) -> Option<Self> {
+///         [Kind::Job, Kind::Session].get(usize::from(byte)).copied()
+///     }
+///
+///     fn name(self) -> &'static str {
+///         match self {
+///             Kind::Job => "job",
+///             Kind::Session => "session",
+///         }
+///     }
+/// }
+///
+/// let session = KindedId::new(Kind::Session);
+/// let s = session.to_string();
+/// assert!(s.starts_with("session-"));
+/// assert_eq!(s.parse(), Ok(session));
+/// assert_eq!(KindedId::parse_as(Kind::Session, &s), Ok(session));
+/// assert!(KindedId::parse_as(Kind::Job, &s).is_err());
+/// assert!("user-1-2".parse::<KindedId<Kind>>().is_err());
+/// ```
+///
+/// IDs sort by kind byte first and then by ID.
+#[derive(Copy, Clone)]
+pub struct KindedId<K> {
+    inner: CompositeId<Tag<K>>,
+}
+
+impl<K: IdKind> KindedId<K> {
+    /// Creates a new unique ID of the given kind.
+    ///
+    /// **panics** under the same conditions as [`ProcessUniqueId::new`], or if the thread's
+    /// prefix has grown past 56 bits.
+    #[cfg(feature = "std")]
+    pub fn new(kind: K) -> Self {
+        KindedId {
+            inner: CompositeId::new(Tag(kind)),
+        }
+    }
+
+    /// Tags an existing ID with `kind`.
+    ///
+    /// Fails if the ID's prefix doesn't fit in 56 bits.
+    pub fn from_parts(kind: K, id: ProcessUniqueId) -> Result<Self, IdOutOfRange> {
+        CompositeId::from_parts(Tag(kind), id).map(|inner| KindedId { inner })
+    }
+
+    /// Parses the display form, failing if the ID isn't of kind `kind`.
+    pub fn parse_as(kind: K, s: &str) -> Result<Self, ParseIdError> {
+        let id: Self = s.parse()?;
+        if id.kind() != kind {
+            return Err(ParseIdError::at(s, 0, kind.name()));
+        }
+        Ok(id)
+    }
+
+    /// The kind of thing the ID identifies.
+    #[inline]
+    pub fn kind(&self) -> K {
+        self.inner.discriminant().0
+    }
+
+    /// The ID without its kind.
+    #[inline]
+    pub fn id(&self) -> ProcessUniqueId {
+        self.inner.id()
+    }
+
+    /// Packs the ID into a `u128`: the kind's byte in the top 8 bits, then the prefix, then the
+    /// offset in the low 64 bits.
+    #[inline]
+    pub fn to_u128(&self) -> u128 {
+        self.inner.to_u128()
+    }
+
+    /// Unpacks an ID packed by [`to_u128`](KindedId::to_u128).
+    ///
+    /// Returns `None` if the top byte doesn't stand for a kind or the prefix doesn't fit in a
+    /// `usize`.
+    #[inline]
+    pub fn from_u128(value: u128) -> Option<Self> {
+        CompositeId::from_u128(value).map(|inner| KindedId { inner })
+    }
+
+    /// Writes the ID as 16 bytes: [`to_u128`](KindedId::to_u128) in big-endian.
+    #[inline]
+    pub fn to_bytes(&self) -> [u8; 16] {
+        self.inner.to_bytes()
+    }
+
+    /// Reads an ID written by [`to_bytes`](KindedId::to_bytes).
+    #[inline]
+    pub fn from_bytes(bytes: [u8; 16]) -> Result<Self, IdOutOfRange> {
+        CompositeId::from_bytes(bytes).map(|inner| KindedId { inner })
+    }
+}
+
+impl<K: IdKind> PartialEq for KindedId<K> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl<K: IdKind> Eq for KindedId<K> {}
+
+impl<K: IdKind> PartialOrd for KindedId<K> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: IdKind> Ord for KindedId<K> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.inner.cmp(&other.inner)
+    }
+}
+
+impl<K: IdKind> Hash for KindedId<K> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner.hash(state);
+    }
+}
+
+impl<K: IdKind> fmt::Display for KindedId<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let id = self.id();
+        pad_id(
+            f,
+            format_args!("{}-{:x}-", self.kind().name(), id.prefix),
+            format_args!("{:x}", id.offset),
+        )
+    }
+}
+
+impl<K: IdKind> fmt::Debug for KindedId<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "KindedId({})", self)
+    }
+}
+
+/// Parses the `<kind>-<prefix>-<offset>` form produced by `Display`, failing on unknown kinds.
+impl<K: IdKind> FromStr for KindedId<K> {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        // Split from the end, so kind names may contain dashes.
+        let offset_dash = s.rfind('-');
+        let prefix_dash = offset_dash.and_then(|dash| s[..dash].rfind('-'));
+        let (prefix_dash, offset_dash) = match (prefix_dash, offset_dash) {
+            (Some(prefix_dash), Some(offset_dash)) => (prefix_dash, offset_dash),
+            _ => return Err(ParseIdError::at(s, s.len(), "`-`")),
+        };
+        let kind = K::from_name(&s[..prefix_dash])
+            .ok_or_else(|| ParseIdError::at(s, 0, "a known kind of ID"))?;
+        let prefix = usize_hex_field(s, prefix_dash + 1, offset_dash)?;
+        let offset = hex_field(s, offset_dash + 1, s.len())?;
+        KindedId::from_parts(kind, ProcessUniqueId { prefix, offset })
+            .map_err(|_| ParseIdError::at(s, prefix_dash + 1, "a prefix of at most 56 bits"))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::{IdKind, KindedId};
+    use crate::ProcessUniqueId;
+
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    enum Kind {
+        Entity = 1,
+        BackgroundJob = 7,
+    }
+
+    impl IdKind for Kind {
+        fn to_byte(self) -> u8 {
+            self as u8
+        }
+
+        fn from_byte(byte: u8) -> Option<Self> {
+            match byte {
+                1 => Some(Kind::Entity),
+                7 => Some(Kind::BackgroundJob),
+                _ => None,
+            }
+        }
+
+        fn name(self) -> &'static str {
+            match self {
+                Kind::Entity => "entity",
+                Kind::BackgroundJob => "background-job",
+            }
+        }
+    }
+
+    #[test]
+    fn test_kinded_id() {
+        let id: ProcessUniqueId = "puid-3-2a".parse().unwrap();
+        let job = KindedId::from_parts(Kind::BackgroundJob, id).unwrap();
+        assert_eq!(job.to_u128(), 0x0700_0000_0000_0003_0000_0000_0000_002a);
+        assert_eq!(job.to_string(), "background-job-3-2a");
+        assert_eq!(format!("{:>22}", job), "   background-job-3-2a");
+        assert_eq!("background-job-3-2a".parse(), Ok(job));
+        assert_eq!(KindedId::from_bytes(job.to_bytes()), Ok(job));
+        assert!(KindedId::<Kind>::from_u128(0x0200 << 112).is_none());
+
+        assert_eq!(
+            KindedId::parse_as(Kind::BackgroundJob, "background-job-3-2a"),
+            Ok(job)
+        );
+        assert!(KindedId::parse_as(Kind::Entity, "background-job-3-2a").is_err());
+        assert!("job-3-2a".parse::<KindedId<Kind>>().is_err());
+        assert!("entity-3".parse::<KindedId<Kind>>().is_err());
+        assert!("entity-3-x".parse::<KindedId<Kind>>().is_err());
+
+        let wide: ProcessUniqueId = "puid-100000000000000-0".parse().unwrap();
+        assert!(KindedId::from_parts(Kind::Entity, wide).is_err());
+        assert!(KindedId::new(Kind::Entity) < KindedId::new(Kind::BackgroundJob));
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::convert::TryFrom;
+use core::fmt;
+use core::mem;
+use core::str::FromStr;
+
+use crate::display::pad_id;
+use crate::process_unique_id::{expect_tag, hex_field, hex_field_to_dash, ParseIdError};
+
+/// A Lamport timestamp: a logical counter and the ID of the node that issued it.
+///
+/// A `LamportId` doubles as a node's clock. [`tick`](LamportId::tick) issues the ID for a local
+/// event and [`observe`](LamportId::observe) merges an ID received from another node, so an event
+/// always orders after every event that could have caused it, without consulting a wall clock:
+///
+/// ```
+/// use snowflake::LamportId;
+///
+/// let mut a = LamportId::new(1);
+/// let mut b = LamportId::new(2);
+///
+/// let sent = a.tick();
+/// let received = b.observe(sent);
+/// assert!(received > sent);
+/// assert!(b.tick() > received);
+/// ```
+///
+/// IDs order by counter, then node ID, so IDs from different nodes never compare equal. Displayed
+/// as `lamport-<counter>-<node>` (hex).
+///
+/// # Memory layout
+///
+/// `#[repr(C)]`: the counter (a `u64`) then the node ID (a `u32`), in native byte order, aligned
+/// like a `u64`. That's 16 bytes on 64bit targets, the last four of which are padding; in C,
+/// `struct { uint64_t counter; uint32_t node; }`.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[repr(C)]
+pub struct LamportId {
+    counter: u64,
+    node: u32,
+}
+
+const _: () = {
+    assert!(mem::offset_of!(LamportId, counter) == 0);
+    assert!(mem::offset_of!(LamportId, node) == 8);
+    assert!(mem::align_of::<LamportId>() == mem::align_of::<u64>());
+    assert!(mem::size_of::<LamportId>() == 12usize.next_multiple_of(mem::align_of::<u64>()));
+};
+
+impl LamportId {
+    /// Creates the clock for `node`, starting at counter zero.
+    #[inline]
+    pub const fn new(node: u32) -> Self {
+        LamportId { counter: 0, node }
+    }
+
+    /// Creates an ID from its parts.
+    #[inline]
+    pub const fn from_parts(counter: u64, node: u32) -> Self {
+        LamportId { counter, node }
+    }
+
+    /// The logical counter.
+    #[inline]
+    pub fn counter(self) -> u64 {
+        self.counter
+    }
+
+    /// The ID of the node that issued the ID.
+    #[inline]
+    pub fn node(self) -> u32 {
+        self.node
+    }
+
+    /// Advances the clock for a local event (or a message about to be sent) and returns the
+    /// event's ID.
+    ///
+    /// **panics** if the counter overflows.
+    #[inline]
+    pub fn tick(&mut self) -> LamportId {
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .expect("Lamport counter overflow");
+        *self
+    }
+
+    /// Merges an ID received from another node and returns the ID of the receive event, which is
+    /// greater than both `remote` and every ID this clock issued before.
+    ///
+    /// **panics** if the counter overflows.
+    #[inline]
+    pub fn observe(&mut self, remote: LamportId) -> LamportId {
+        self.counter = self.counter.max(remote.counter);
+        self.tick()
+    }
+}
+
+impl fmt::Display for LamportId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(
+            f,
+            format_args!("lamport-{:x}-", self.counter),
+            format_args!("{:x}", self.node),
+        )
+    }
+}
+
+/// Parses the `lamport-<counter>-<node>` form produced by `Display`.
+impl FromStr for LamportId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        let start = expect_tag(s, "lamport-", "`lamport-`")?;
+        let (counter, start) = hex_field_to_dash(s, start)?;
+        let node = hex_field(s, start, s.len())?;
+        let node = u32::try_from(node)
+            .map_err(|_| ParseIdError::at(s, start, "a node ID of at most 32 bits"))?;
+        Ok(LamportId::from_parts(counter, node))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::LamportId;
+
+    #[test]
+    fn test_lamport_order() {
+        let mut a = LamportId::new(1);
+        let mut b = LamportId::new(2);
+        assert_eq!(a.tick(), LamportId::from_parts(1, 1));
+        assert_eq!(a.tick(), LamportId::from_parts(2, 1));
+        // Receiving from a node behind us still advances our clock.
+        let behind = b.tick();
+        assert_eq!(a.observe(behind), LamportId::from_parts(3, 1));
+        // Receiving from a node ahead of us jumps past it.
+        assert_eq!(
+            b.observe(LamportId::from_parts(10, 1)),
+            LamportId::from_parts(11, 2)
+        );
+        // Equal counters are ordered by node.
+        assert!(LamportId::from_parts(5, 1) < LamportId::from_parts(5, 2));
+
+        let id = LamportId::from_parts(0xab, 7);
+        assert_eq!(id.to_string(), "lamport-ab-7");
+        assert_eq!("lamport-ab-7".parse(), Ok(id));
+        assert!("lamport-ab-100000000".parse::<LamportId>().is_err());
+        assert!("lamport-ab".parse::<LamportId>().is_err());
+    }
+}
@@ -0,0 +1,411 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Time-limited node ID leases.
+//!
+//! A [`NodeIdLease`] claims a node ID from some coordination backend, keeps it alive with
+//! heartbeats, and notices when it has lapsed. The lease deadline is tracked locally and
+//! conservatively (from the moment a claim or renewal was *sent*), so a process that was paused
+//! past its deadline finds out the next time it checks, even if it never hears from the backend.
+//!
+//! A [`LeasedSnowflakeGenerator`] ties a generator to its lease, so that it stops issuing IDs
+//! the moment the lease is lost instead of relying on callers to check.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::snowflake_id::NODE_BITS;
+use crate::{SequenceExhausted, SnowflakeGenerator, SnowflakeId};
+
+/// A coordination service that hands out node IDs under time-limited leases.
+pub trait LeaseBackend {
+    /// Claims an unused node ID that fits in `bits` bits for `ttl`.
+    fn claim(&self, bits: u32, ttl: Duration) -> Result<u64, LeaseError>;
+
+    /// Extends the lease on `node_id` by `ttl` from now.
+    fn renew(&self, node_id: u64, ttl: Duration) -> Result<(), LeaseError>;
+
+    /// Gives up `node_id`.
+    fn release(&self, node_id: u64) -> Result<(), LeaseError>;
+}
+
+impl<B: LeaseBackend> LeaseBackend for &B {
+    fn claim(&self, bits: u32, ttl: Duration) -> Result<u64, LeaseError> {
+        (**self).claim(bits, ttl)
+    }
+
+    fn renew(&self, node_id: u64, ttl: Duration) -> Result<(), LeaseError> {
+        (**self).renew(node_id, ttl)
+    }
+
+    fn release(&self, node_id: u64) -> Result<(), LeaseError> {
+        (**self).release(node_id)
+    }
+}
+
+/// An error produced by a lease operation.
+#[derive(Debug)]
+pub enum LeaseError {
+    /// Every node ID is currently leased.
+    Unavailable,
+    /// The lease expired or was taken over by someone else.
+    Lost,
+    /// The backend failed.
+    Backend(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for LeaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LeaseError::Unavailable => f.write_str("no node IDs are available"),
+            LeaseError::Lost => f.write_str("the node ID lease has been lost"),
+            LeaseError::Backend(e) => write!(f, "lease backend error: {}", e),
+        }
+    }
+}
+
+impl Error for LeaseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LeaseError::Backend(e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
+
+type LostCallback = Box<dyn FnOnce(u64) + Send>;
+
+struct LeaseState {
+    deadline: Instant,
+    lost: bool,
+    callbacks: Vec<LostCallback>,
+}
+
+/// A node ID held under a lease.
+///
+/// Call [`heartbeat`](NodeIdLease::heartbeat) well within the TTL and check
+/// [`is_valid`](NodeIdLease::is_valid) (or register an [`on_lost`](NodeIdLease::on_lost)
+/// callback) before issuing IDs under the node ID, or hand the lease to a
+/// [`LeasedSnowflakeGenerator`], which checks for you. Once lost, a lease never becomes valid
+/// again; claim a new one.
+///
+/// The lease is released when dropped.
+pub struct NodeIdLease<B: LeaseBackend> {
+    backend: B,
+    node_id: u64,
+    ttl: Duration,
+    state: Mutex<LeaseState>,
+}
+
+impl<B: LeaseBackend> NodeIdLease<B> {
+    /// Claims a node ID fitting in `bits` bits from `backend`, valid for `ttl`.
+    pub fn claim(backend: B, bits: u32, ttl: Duration) -> Result<Self, LeaseError> {
+        let sent = Instant::now();
+        let node_id = backend.claim(bits, ttl)?;
+        Ok(NodeIdLease {
+            backend,
+            node_id,
+            ttl,
+            state: Mutex::new(LeaseState {
+                deadline: sent + ttl,
+                lost: false,
+                callbacks: Vec::new(),
+            }),
+        })
+    }
+
+    /// The leased node ID.
+    #[inline]
+    pub fn node_id(&self) -> u64 {
+        self.node_id
+    }
+
+    /// The lease duration.
+    #[inline]
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Returns true if the lease is still held, revoking it if it has expired.
+    pub fn is_valid(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        if !state.lost && Instant::now() >= state.deadline {
+            self.revoke(state);
+            return false;
+        }
+        !state.lost
+    }
+
+    /// Renews the lease.
+    ///
+    /// If the lease already expired or the backend reports it lost, the lease is revoked and
+    /// `LeaseError::Lost` is returned. Other backend errors leave the lease untouched (it may still
+    /// be renewed before the deadline).
+    pub fn heartbeat(&self) -> Result<(), LeaseError> {
+        if !self.is_valid() {
+            return Err(LeaseError::Lost);
+        }
+        let sent = Instant::now();
+        match self.backend.renew(self.node_id, self.ttl) {
+            Ok(()) => {
+                let mut state = self.state.lock().unwrap();
+                if state.lost {
+                    return Err(LeaseError::Lost);
+                }
+                state.deadline = sent + self.ttl;
+                Ok(())
+            }
+            Err(LeaseError::Lost) => {
+                self.revoke(self.state.lock().unwrap());
+                Err(LeaseError::Lost)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Registers a callback to be invoked (with the node ID) when the lease is lost.
+    ///
+    /// If the lease is already lost, the callback is invoked immediately.
+    pub fn on_lost<F>(&self, callback: F)
+    where
+        F: FnOnce(u64) + Send + 'static,
+    {
+        let mut state = self.state.lock().unwrap();
+        if state.lost {
+            drop(state);
+            callback(self.node_id);
+        } else {
+            state.callbacks.push(Box::new(callback));
+        }
+    }
+
+    /// Explicitly releases the lease, invoking any `on_lost` callbacks.
+    pub fn release(self) -> Result<(), LeaseError> {
+        let result = self.backend.release(self.node_id);
+        self.revoke(self.state.lock().unwrap());
+        result
+    }
+
+    fn revoke(&self, mut state: MutexGuard<'_, LeaseState>) {
+        if state.lost {
+            return;
+        }
+        state.lost = true;
+        let callbacks = std::mem::take(&mut state.callbacks);
+        drop(state);
+        for callback in callbacks {
+            callback(self.node_id);
+        }
+    }
+}
+
+impl<B: LeaseBackend> Drop for NodeIdLease<B> {
+    fn drop(&mut self) {
+        let state = self.state.get_mut().unwrap_or_else(|e| e.into_inner());
+        if !state.lost {
+            state.lost = true;
+            let _ = self.backend.release(self.node_id);
+        }
+    }
+}
+
+impl<B: LeaseBackend> fmt::Debug for NodeIdLease<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NodeIdLease")
+            .field("node_id", &self.node_id)
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+/// A [`SnowflakeGenerator`] bound to the [`NodeIdLease`] its node ID came from.
+///
+/// [`try_next`](LeasedSnowflakeGenerator::try_next) checks the lease for every ID and fails with
+/// `LeaseError::Lost` once the lease has expired or been revoked, even if nobody polled it in the
+/// meantime. Keep the lease alive by calling [`heartbeat`](NodeIdLease::heartbeat) on
+/// [`lease`](LeasedSnowflakeGenerator::lease).
+///
+/// ```
+/// use snowflake::lease::{LeasedSnowflakeGenerator, MemoryLeaseBackend};
+/// use std::time::Duration;
+///
+/// let backend = MemoryLeaseBackend::new();
+/// let gen = LeasedSnowflakeGenerator::claim(&backend, Duration::from_secs(30)).unwrap();
+/// let id = gen.try_next().unwrap();
+/// assert_eq!(id.node_id(), gen.lease().node_id());
+/// gen.lease().heartbeat().unwrap();
+/// ```
+pub struct LeasedSnowflakeGenerator<B: LeaseBackend> {
+    lease: NodeIdLease<B>,
+    gen: SnowflakeGenerator,
+}
+
+impl<B: LeaseBackend> LeasedSnowflakeGenerator<B> {
+    /// Claims a 10-bit node ID from `backend`, valid for `ttl`, and creates a generator for it.
+    pub fn claim(backend: B, ttl: Duration) -> Result<Self, LeaseError> {
+        NodeIdLease::claim(backend, NODE_BITS, ttl).map(LeasedSnowflakeGenerator::new)
+    }
+
+    /// Creates a generator issuing IDs under `lease`'s node ID for as long as the lease is held.
+    ///
+    /// **panics** if the node ID doesn't fit in 10 bits.
+    pub fn new(lease: NodeIdLease<B>) -> Self {
+        let node_id = u16::try_from(lease.node_id()).expect("invalid snowflake node ID");
+        LeasedSnowflakeGenerator {
+            gen: SnowflakeGenerator::new(node_id),
+            lease,
+        }
+    }
+
+    /// The lease the generator's node ID is held under.
+    #[inline]
+    pub fn lease(&self) -> &NodeIdLease<B> {
+        &self.lease
+    }
+
+    /// Generates a new ID, waiting for the next millisecond if the current one's sequence is
+    /// exhausted.
+    ///
+    /// Fails with `LeaseError::Lost`, revoking the lease, if it has expired or been lost. The
+    /// lease is checked after the ID is taken, so every ID returned was issued while the lease
+    /// was held.
+    ///
+    /// **panics** under the same conditions as [`SnowflakeGenerator::next`].
+    pub fn try_next(&self) -> Result<SnowflakeId, LeaseError> {
+        loop {
+            match self.gen.try_next() {
+                Ok(id) if self.lease.is_valid() => return Ok(id),
+                Ok(_) => return Err(LeaseError::Lost),
+                Err(SequenceExhausted) if self.lease.is_valid() => thread::yield_now(),
+                Err(SequenceExhausted) => return Err(LeaseError::Lost),
+            }
+        }
+    }
+
+    /// Releases the lease, after which no more IDs can be issued under its node ID.
+    pub fn release(self) -> Result<(), LeaseError> {
+        self.lease.release()
+    }
+}
+
+impl<B: LeaseBackend> fmt::Debug for LeasedSnowflakeGenerator<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LeasedSnowflakeGenerator")
+            .field("lease", &self.lease)
+            .field("gen", &self.gen)
+            .finish()
+    }
+}
+
+/// An in-process lease backend.
+///
+/// Useful for tests and for coordinating several generators within a single process.
+#[derive(Default, Debug)]
+pub struct MemoryLeaseBackend {
+    leases: Mutex<HashMap<u64, Instant>>,
+}
+
+impl MemoryLeaseBackend {
+    /// Creates a backend with no outstanding leases.
+    pub fn new() -> Self {
+        MemoryLeaseBackend::default()
+    }
+}
+
+impl LeaseBackend for MemoryLeaseBackend {
+    fn claim(&self, bits: u32, ttl: Duration) -> Result<u64, LeaseError> {
+        let now = Instant::now();
+        let mut leases = self.leases.lock().unwrap();
+        let limit = if bits >= 64 {
+            u64::MAX
+        } else {
+            (1 << bits) - 1
+        };
+        let node_id = (0..=limit)
+            .find(|id| leases.get(id).is_none_or(|&deadline| deadline <= now))
+            .ok_or(LeaseError::Unavailable)?;
+        leases.insert(node_id, now + ttl);
+        Ok(node_id)
+    }
+
+    fn renew(&self, node_id: u64, ttl: Duration) -> Result<(), LeaseError> {
+        let now = Instant::now();
+        match self.leases.lock().unwrap().get_mut(&node_id) {
+            Some(deadline) if *deadline > now => {
+                *deadline = now + ttl;
+                Ok(())
+            }
+            _ => Err(LeaseError::Lost),
+        }
+    }
+
+    fn release(&self, node_id: u64) -> Result<(), LeaseError> {
+        self.leases.lock().unwrap().remove(&node_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LeaseError, LeasedSnowflakeGenerator, MemoryLeaseBackend, NodeIdLease};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_claim_release() {
+        let backend = MemoryLeaseBackend::new();
+        let a = NodeIdLease::claim(&backend, 1, Duration::from_secs(60)).unwrap();
+        let b = NodeIdLease::claim(&backend, 1, Duration::from_secs(60)).unwrap();
+        assert_ne!(a.node_id(), b.node_id());
+        assert!(matches!(
+            NodeIdLease::claim(&backend, 1, Duration::from_secs(60)),
+            Err(LeaseError::Unavailable)
+        ));
+        let freed = a.node_id();
+        a.release().unwrap();
+        let c = NodeIdLease::claim(&backend, 1, Duration::from_secs(60)).unwrap();
+        assert_eq!(c.node_id(), freed);
+        assert!(b.heartbeat().is_ok());
+    }
+
+    #[test]
+    fn test_expiry_revokes() {
+        let backend = MemoryLeaseBackend::new();
+        let lease = NodeIdLease::claim(&backend, 4, Duration::from_millis(10)).unwrap();
+        let lost = Arc::new(AtomicU64::new(u64::MAX));
+        let lost2 = lost.clone();
+        lease.on_lost(move |id| lost2.store(id, Ordering::SeqCst));
+        assert!(lease.is_valid());
+        thread::sleep(Duration::from_millis(20));
+        assert!(!lease.is_valid());
+        assert_eq!(lost.load(Ordering::SeqCst), lease.node_id());
+        assert!(matches!(lease.heartbeat(), Err(LeaseError::Lost)));
+    }
+
+    #[test]
+    fn test_leased_generator_stops_on_expiry() {
+        let backend = MemoryLeaseBackend::new();
+        let gen = LeasedSnowflakeGenerator::claim(&backend, Duration::from_millis(10)).unwrap();
+        let a = gen.try_next().unwrap();
+        let b = gen.try_next().unwrap();
+        assert!(a < b);
+        assert_eq!(a.node_id(), gen.lease().node_id());
+        // Nothing polls the lease while it lapses.
+        thread::sleep(Duration::from_millis(20));
+        assert!(matches!(gen.try_next(), Err(LeaseError::Lost)));
+        assert!(!gen.lease().is_valid());
+        assert!(matches!(gen.lease().heartbeat(), Err(LeaseError::Lost)));
+    }
+}
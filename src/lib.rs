@@ -8,13 +8,382 @@
 
 //! A crate for quickly generating unique IDs with guaranteed properties.
 //!
-//! This crate currently includes guaranteed process unique IDs but may include new ID types in the
-//! future.
+//! This crate currently includes guaranteed process unique IDs and time-ordered, node-unique
+//! snowflake, Sonyflake, TSID, MongoDB ObjectId, xid, 128-bit flake, and Instagram-style sharded
+//! IDs, as well as Firebase-style push IDs, Lamport IDs, host-ordered IDs, and hybrid logical
+//! clock timestamps, but may include new ID types in the future.
+//!
+//! # Features
+//!
+//! - `std` (default): thread local and clock based generators. Without it the crate is `no_std`
+//!   and provides the ID types along with their formatting, parsing, and serialization, and
+//!   `ProcessUniqueId::new()`, which then takes IDs from a single prefix shared by all callers.
+//! - `serde_support`: serde `Serialize`/`Deserialize` implementations and the `serde` module.
+//! - `tokio`, `async-std`: `AsyncSnowflakeGenerator`. With `tokio`, also
+//!   `context::scope_correlation_id`, which keeps a correlation ID in a task local, and the
+//!   `task_ids` module, which associates Tokio tasks with process unique IDs.
+//! - `futures`: `IdStreamAsync`, a `futures::Stream` of IDs from any generator, including ones
+//!   that have to wait (with `tokio` or `async-std`, `RateLimitedGen` and
+//!   `AsyncSnowflakeGenerator`).
+//! - `defmt`: `defmt::Format` implementations for embedded logging.
+//! - `portable-atomic`: use the `portable-atomic` crate's atomics even where native ones are
+//!   available. Targets without native 64-bit atomics always use them, and targets without any
+//!   atomic CAS (e.g. thumbv6m, riscv32imc) fall back on `critical-section`, so the platform has
+//!   to provide a `critical-section` implementation (most HALs do).
+//! - `embedded`: the `embedded` module, with a process unique ID generator for targets without
+//!   threads or thread local storage (e.g. embassy executors), built on `critical-section`.
+//! - `shm`: `cross_process::SharedMemoryCounter`, a host-wide prefix counter in shared memory.
+//!   Not available together with `safe`.
+//! - `id-service` (Unix only): the `id_service` module, a small daemon handing out host-wide
+//!   prefixes over a Unix domain socket, and its client.
+//! - `etcd`: the `etcd` module, a node ID provider that leases node IDs from etcd and surrenders
+//!   them when the lease can't be renewed. Building `etcd-client` requires `protoc`.
+//! - `zookeeper`: the `zookeeper` module, a node ID provider that claims node IDs with ephemeral
+//!   sequential znodes and surrenders them when the session is disconnected.
+//! - `ticket`: the `ticket` module, a client for central (Flickr-style) ticket servers that
+//!   fetches IDs in blocks over TCP or HTTP and prefetches the next block in the background.
+//! - `cli`: the `snowflake-cli` binary, which mints, inspects, and converts IDs.
+//! - `opaque`: the `opaque` module, which encrypts IDs into tokens that can be handed to
+//!   untrusted parties.
+//! - `nanoid`: the `nanoid` module, which generates random, unguessable string IDs.
+//! - `secure`: `SecureUniqueId`, a process unique ID paired with 64 random bits, for IDs that
+//!   must be both unique and unguessable (session handles, capability tokens).
+//! - `opentelemetry`: the `otel` module, with an OpenTelemetry SDK `IdGenerator` that derives
+//!   trace and span IDs from process unique IDs instead of a random number generator.
+//! - `http`: the `request_id` module, which checks request ID headers from callers (ULIDs,
+//!   UUIDs, vendor-prefixed IDs, ...) and generates new ones.
+//! - `tonic`: the `tonic` module, with a gRPC interceptor that stamps requests with correlation
+//!   IDs and propagates incoming ones.
+//! - `tower`: the `tower` module, with middleware that gives every HTTP request an ID (reusing
+//!   valid incoming `x-request-id` headers), e.g. for axum. Implies `http`.
+//! - `trace-context`: the `trace_context` module, which generates W3C Trace Context trace and
+//!   span IDs and formats and parses `traceparent` headers.
+//! - `uuid`: conversions between process unique IDs and version 8 UUIDs, and between flake IDs
+//!   and UUIDs. With `std`, also `UuidClockSequence`, a node ID and clock sequence for version 1
+//!   and 6 UUIDs that never collide within the process, and `TimeUuidGenerator`, which creates
+//!   version 1 UUIDs for Cassandra and ScyllaDB `timeuuid` columns.
+//! - `windows`: conversions between the 128-bit IDs (process unique, flake and host-ordered IDs)
+//!   and `windows::core::GUID`s, for COM and WinRT APIs, with the same bits as the UUID
+//!   conversions. Builds on any platform.
+//! - `bson`: conversions between `ObjectId` and the `bson` crate's `ObjectId`.
+//! - `roaring`: `IdBitSet`, a set of process unique IDs kept as roaring bitmaps, for
+//!   membership tests over millions of IDs in a fraction of the memory of a `HashSet`.
+//! - `redis`: the `redis` module; the ID types implement redis-rs's `ToRedisArgs` and
+//!   `FromRedisValue`, in their display or binary forms.
+//! - `dynamodb`: conversions between snowflake and flake IDs and the AWS SDK's DynamoDB
+//!   `AttributeValue`, alongside the key helpers in the `dynamodb` module.
+//! - `prost`: the `proto` module, with a protobuf message for process unique IDs.
+//! - `bytemuck`, `zerocopy`: cast slices of IDs to and from bytes. IDs for which every bit
+//!   pattern is valid implement `Pod`/`FromBytes`; `SnowflakeId` and `SonyflakeId` can only be
+//!   cast to bytes. `ProcessUniqueId` is only supported on 64bit targets.
+//! - `bitcode`, `speedy`: fixed-size binary encodings of the ID types, for snapshotting large
+//!   amounts of state without going through serde. With `bitcode`, only the IDs for which every
+//!   bit pattern is valid implement `Encode` and `Decode`; `speedy` checks the others as it reads
+//!   them.
+//! - `rocket`, `actix-web`, `axum`: the `web` module, for taking IDs from URL paths and
+//!   answering `400 Bad Request` when they don't parse.
+//! - `clap`: the `clap` module; the ID types implement clap's `ValueParserFactory`, so they can be
+//!   taken as command line arguments with the parse error in the usage message.
+//! - `config`: the `config` module; builds a generator from a serde-deserializable
+//!   `GeneratorConfig` naming the kind of ID, its epoch and bit layout, where the node ID comes
+//!   from and what to do when the clock goes backwards.
+//! - `async-graphql`: the ID types are GraphQL scalars (of the same name) in async-graphql, in
+//!   their display forms.
+//! - `fake`: the ID types implement the `fake` crate's `Dummy<Faker>`, so test fixtures deriving
+//!   `Dummy` fill their ID fields with valid IDs from this crate's generators, which never
+//!   repeat.
+//! - `leak-report`: print the `OwnedUniqueId`s still alive when the process exits to standard
+//!   error. Does nothing together with `safe`.
+//! - `bevy`: Bevy `Component` and `Reflect` implementations for the ID types, and the `bevy`
+//!   module.
+//! - `wasm-bindgen`: the `wasm` module, which exports process unique IDs and the snowflake
+//!   generator to JavaScript.
+//! - `egui`: a conversion from `ProcessUniqueId` to `egui::Id`, for giving dynamically created
+//!   widgets their own identity.
+//! - `metrics`: record prefix usage, sequence exhaustion, and clock rollbacks with the `metrics`
+//!   crate, e.g. for Prometheus; see `describe_metrics`.
+//! - `tracing`: log `tracing` warnings when a prefix is used up, when the number of allocated
+//!   `ProcessUniqueId` prefixes crosses a threshold, and when a time based generator sees the
+//!   clock go backwards. With `tonic`, also record correlation IDs in the current span.
+//! - `thread-stats`: `all_thread_stats`, which reports how many process unique IDs each live
+//!   thread has used, for finding runaway consumers. Each thread registers itself the first time
+//!   it claims a block of IDs.
+//! - `test-util`: the `test_util` module, for resetting the process unique ID generators in
+//!   tests and running them down to their limits to test exhaustion handling.
+//! - `audit`: the `audit` module, which keeps a ring buffer of recent ID block allocations (IDs,
+//!   thread, time) for tracking down where an ID came from.
+//! - `durable`: the `durable` module, which persists the prefix counter and snowflake generator
+//!   timestamps across restarts.
+//! - `replay`: the `replay` module, which records which ID blocks each thread claims and replays
+//!   them in a later run, for deterministic replay and simulation testing.
+//! - `snapshot`: the `snapshot` module, which keeps processes restored from a memory image
+//!   (CRIU, VM snapshots) from handing out the same IDs as the imaged process.
+//! - `no-panic`: check at link time, with the `no-panic` crate, that `ProcessUniqueId::try_new`
+//!   and `IdHandle::try_next` can't panic. The check only runs in optimized builds without debug
+//!   assertions, and may fail to link together with features that hook into claiming blocks
+//!   (`audit`, `durable`, `metrics`, `replay`, `thread-stats`, `tracing`), which lock or do I/O
+//!   there.
+//! - `nightly` (nightly compilers only): keep the thread local state of `ProcessUniqueId::new()`
+//!   in a `#[thread_local]` static instead of going through `thread_local!`. Requires a target
+//!   with native thread local storage.
+//! - `simd`: encode hex digits 16 bytes at a time with SSE2 in `format_ids_into`, on x86_64.
+//!   Ignored on other targets and together with `safe`.
+//! - `safe`: keep thread local state in a `Cell` instead of an `UnsafeCell` and build the crate
+//!   with `#![forbid(unsafe_code)]`. In a release build on x86_64, `ProcessUniqueId::new()` took
+//!   about 1.15ns either way; the difference was within measurement noise. Mapping shared memory
+//!   (`shm`) and registering an exit handler (`leak-report`) can't be done without `unsafe`, so
+//!   `SharedMemoryCounter` isn't available and no leak report is printed.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "safe", forbid(unsafe_code))]
+#![cfg_attr(feature = "nightly", feature(thread_local))]
 
 #[cfg(feature = "serde_support")]
 #[macro_use]
 extern crate serde_derive;
 
+#[cfg(feature = "std")]
+mod actor_id_allocator;
+mod any_id;
+#[cfg(all(feature = "std", any(feature = "tokio", feature = "async-std")))]
+mod async_snowflake;
+mod atomic;
+#[cfg(feature = "audit")]
+pub mod audit;
+#[cfg(feature = "std")]
+mod base62;
+#[cfg(feature = "bevy")]
+pub mod bevy;
+#[cfg(feature = "std")]
+mod bulk_format;
+#[cfg(feature = "clap")]
+pub mod clap;
+#[cfg(feature = "std")]
+pub mod codec;
+mod composite_id;
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod context;
+#[cfg(feature = "std")]
+pub mod cross_process;
+#[cfg(feature = "std")]
+mod dense_prefix_map;
+mod display;
+#[cfg(feature = "durable")]
+pub mod durable;
+#[cfg(feature = "std")]
+pub mod dynamodb;
+#[cfg(feature = "embedded")]
+pub mod embedded;
+mod error;
+#[cfg(feature = "etcd")]
+pub mod etcd;
+#[cfg(feature = "fake")]
+mod fake;
+mod flake;
+#[cfg(feature = "std")]
+mod generator_registry;
+mod generic_unique_id;
+#[cfg(feature = "async-graphql")]
+mod graphql;
+pub mod hlc;
+#[cfg(feature = "std")]
+mod host_ordered;
+#[cfg(feature = "std")]
+mod id_arena;
+#[cfg(feature = "roaring")]
+mod id_bit_set;
+#[cfg(feature = "std")]
+mod id_gen;
+#[cfg(feature = "std")]
+mod id_handle;
+#[cfg(feature = "std")]
+mod id_map;
+#[cfg(feature = "std")]
+mod id_path;
+#[cfg(feature = "std")]
+mod id_pool;
+#[cfg(feature = "std")]
+mod id_range;
+#[cfg(all(feature = "id-service", unix))]
+pub mod id_service;
+#[cfg(feature = "std")]
+mod id_stream;
+#[cfg(feature = "futures")]
+mod id_stream_async;
+#[cfg(feature = "std")]
+pub mod intern;
+mod kinded_id;
+mod lamport_id;
+#[cfg(feature = "std")]
+pub mod lease;
+#[cfg(feature = "std")]
+mod local_id_generator;
+#[cfg(feature = "nanoid")]
+pub mod nanoid;
+#[cfg(feature = "std")]
+pub mod node;
+pub mod obfuscate;
+mod object_id;
+#[cfg(feature = "opaque")]
+pub mod opaque;
+#[cfg(feature = "opentelemetry")]
+pub mod otel;
+#[cfg(feature = "std")]
+mod owned_unique_id;
 mod process_unique_id;
+#[cfg(feature = "std")]
+mod proquint;
+#[cfg(feature = "prost")]
+pub mod proto;
+mod push_id;
+#[cfg(feature = "std")]
+mod rate_limited;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(feature = "replay")]
+pub mod replay;
+#[cfg(feature = "http")]
+pub mod request_id;
+#[cfg(feature = "std")]
+mod scoped_unique_id;
+#[cfg(feature = "secure")]
+mod secure_unique_id;
+#[cfg(feature = "serde_support")]
+pub mod serde;
+mod sharded_id;
+#[cfg(feature = "std")]
+pub mod short_code;
+mod small_unique_id;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+mod snowflake_id;
+mod sonyflake;
+#[cfg(feature = "speedy")]
+mod speedy;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "tokio")]
+pub mod task_ids;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "std")]
+mod thread_prefix;
+#[cfg(feature = "std")]
+mod thread_stats;
+#[cfg(feature = "ticket")]
+pub mod ticket;
+#[cfg(feature = "std")]
+mod timestamped;
+#[cfg(feature = "tonic")]
+pub mod tonic;
+#[cfg(feature = "tower")]
+pub mod tower;
+#[cfg(feature = "trace-context")]
+pub mod trace_context;
+mod tsid;
+#[cfg(all(feature = "uuid", feature = "std"))]
+mod uuid_clock;
+mod varint;
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
+#[cfg(any(feature = "rocket", feature = "actix-web", feature = "axum"))]
+pub mod web;
+mod xid;
+#[cfg(feature = "zookeeper")]
+pub mod zookeeper;
 
-pub use crate::process_unique_id::ProcessUniqueId;
+#[cfg(feature = "std")]
+pub use crate::actor_id_allocator::ActorIdAllocator;
+pub use crate::any_id::AnyId;
+#[cfg(all(feature = "std", any(feature = "tokio", feature = "async-std")))]
+pub use crate::async_snowflake::AsyncSnowflakeGenerator;
+#[cfg(feature = "std")]
+pub use crate::bulk_format::{format_ids_into, FormatId};
+pub use crate::composite_id::{CompositeId, Discriminant};
+#[cfg(feature = "std")]
+pub use crate::cross_process::{CrossProcessGenerator, HostUniqueId};
+#[cfg(feature = "std")]
+pub use crate::dense_prefix_map::DensePrefixMap;
+pub use crate::display::DisplayAs;
+pub use crate::error::Error;
+#[cfg(feature = "std")]
+pub use crate::flake::FlakeGenerator;
+pub use crate::flake::FlakeId;
+#[cfg(feature = "std")]
+pub use crate::generator_registry::GeneratorRegistry;
+pub use crate::generic_unique_id::{Counter, GenericUniqueId};
+#[cfg(feature = "std")]
+pub use crate::host_ordered::HostOrderedId;
+#[cfg(feature = "std")]
+pub use crate::id_arena::{ArenaId, IdArena, IdRemap};
+#[cfg(feature = "roaring")]
+pub use crate::id_bit_set::IdBitSet;
+#[cfg(feature = "std")]
+pub use crate::id_gen::{DynIdGen, IdGen};
+#[cfg(feature = "std")]
+pub use crate::id_handle::IdHandle;
+#[cfg(feature = "std")]
+pub use crate::id_map::{BuildIdHasher, IdHasher, IdMap, IdSet};
+#[cfg(feature = "std")]
+pub use crate::id_path::{Ancestors, IdPath};
+#[cfg(feature = "std")]
+pub use crate::id_pool::{IdPool, PooledId};
+#[cfg(feature = "std")]
+pub use crate::id_range::IdRange;
+#[cfg(feature = "std")]
+pub use crate::id_stream::IdStream;
+#[cfg(feature = "futures")]
+pub use crate::id_stream_async::IdStreamAsync;
+pub use crate::kinded_id::{IdKind, KindedId};
+pub use crate::lamport_id::LamportId;
+#[cfg(feature = "std")]
+pub use crate::local_id_generator::LocalIdGenerator;
+pub use crate::object_id::ObjectId;
+#[cfg(feature = "std")]
+pub use crate::owned_unique_id::OwnedUniqueId;
+pub use crate::process_unique_id::IdUnavailable;
+#[cfg(feature = "std")]
+pub use crate::process_unique_id::{AtomicPrefixCounter, PrefixCounterInUse};
+pub use crate::process_unique_id::{LenientParseError, ParseIdError, ProcessUniqueId};
+pub use crate::push_id::PushId;
+#[cfg(feature = "std")]
+pub use crate::push_id::PushIdGenerator;
+#[cfg(feature = "std")]
+pub use crate::rate_limited::{RateLimited, RateLimitedGen};
+#[cfg(feature = "std")]
+pub use crate::scoped_unique_id::ScopedUniqueId;
+#[cfg(feature = "secure")]
+pub use crate::secure_unique_id::SecureUniqueId;
+pub use crate::sharded_id::ShardedId;
+#[cfg(feature = "std")]
+pub use crate::sharded_id::ShardedIdGenerator;
+pub use crate::small_unique_id::SmallUniqueId;
+#[cfg(feature = "std")]
+pub use crate::snowflake_id::SnowflakeGenerator;
+pub use crate::snowflake_id::{IdOutOfRange, SequenceExhausted, SnowflakeBatch, SnowflakeId};
+#[cfg(feature = "std")]
+pub use crate::sonyflake::SonyflakeGenerator;
+pub use crate::sonyflake::SonyflakeId;
+#[cfg(feature = "metrics")]
+pub use crate::stats::describe_metrics;
+#[cfg(feature = "std")]
+pub use crate::stats::{stats, GeneratorStats, Stats};
+#[cfg(feature = "std")]
+pub use crate::thread_prefix::ThreadPrefix;
+#[cfg(feature = "thread-stats")]
+pub use crate::thread_stats::all_thread_stats;
+#[cfg(feature = "std")]
+pub use crate::thread_stats::{thread_stats, ThreadStats};
+#[cfg(feature = "std")]
+pub use crate::timestamped::Timestamped;
+pub use crate::tsid::Tsid;
+#[cfg(feature = "std")]
+pub use crate::tsid::TsidGenerator;
+#[cfg(all(feature = "uuid", feature = "std"))]
+pub use crate::uuid_clock::{TimeUuidGenerator, UuidClockSequence};
+pub use crate::varint::VarintError;
+pub use crate::xid::Xid;
+#[cfg(feature = "std")]
+pub use crate::xid::XidGenerator;
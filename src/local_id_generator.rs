@@ -0,0 +1,109 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+use crate::{IdRange, ProcessUniqueId};
+
+/// A single-threaded generator handing out the IDs of a reserved [`IdRange`].
+///
+/// The generator's state is a pair of `Cell`s, so creating an ID touches neither atomics nor
+/// thread local storage. It's meant for hot loops on one thread, and is neither `Send` nor
+/// `Sync`:
+///
+/// ```
+/// use snowflake::LocalIdGenerator;
+///
+/// let gen = LocalIdGenerator::reserve(1000);
+/// let a = gen.next().unwrap();
+/// let b = gen.next().unwrap();
+/// assert!(a < b);
+/// assert_eq!(gen.remaining(), 998);
+/// ```
+///
+/// Once the range runs out, [`next`](LocalIdGenerator::next) returns `None`.
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<snowflake::LocalIdGenerator>();
+/// ```
+#[derive(Debug)]
+pub struct LocalIdGenerator {
+    prefix: usize,
+    next: Cell<u64>,
+    remaining: Cell<u64>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl LocalIdGenerator {
+    /// Creates a generator handing out the IDs in `range`.
+    pub fn from_range(range: IdRange) -> Self {
+        let (prefix, next) = range.get(0).map_or((0, 0), |id| (id.prefix, id.offset));
+        LocalIdGenerator {
+            prefix,
+            next: Cell::new(next),
+            remaining: Cell::new(range.len()),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Reserves `n` IDs (see [`IdRange::reserve`]) and creates a generator handing them out.
+    ///
+    /// **panics** under the same conditions as [`ProcessUniqueId::new`].
+    #[inline]
+    pub fn reserve(n: u64) -> Self {
+        LocalIdGenerator::from_range(IdRange::reserve(n))
+    }
+
+    /// Returns the next ID in the range, or `None` if the range is used up.
+    #[inline]
+    pub fn next(&self) -> Option<ProcessUniqueId> {
+        let remaining = self.remaining.get();
+        if remaining == 0 {
+            return None;
+        }
+        let offset = self.next.get();
+        self.remaining.set(remaining - 1);
+        // Doesn't overflow unless the range is now used up.
+        self.next.set(offset.wrapping_add(1));
+        Some(ProcessUniqueId {
+            prefix: self.prefix,
+            offset,
+        })
+    }
+
+    /// The number of IDs left.
+    #[inline]
+    pub fn remaining(&self) -> u64 {
+        self.remaining.get()
+    }
+}
+
+impl From<IdRange> for LocalIdGenerator {
+    #[inline]
+    fn from(range: IdRange) -> Self {
+        LocalIdGenerator::from_range(range)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LocalIdGenerator;
+    use crate::IdRange;
+
+    #[test]
+    fn test_local_generator() {
+        let range = IdRange::reserve(3);
+        let gen = LocalIdGenerator::from(range.clone());
+        let ids: Vec<_> = std::iter::from_fn(|| gen.next()).collect();
+        assert!(ids.into_iter().eq(range));
+        assert_eq!(gen.remaining(), 0);
+        assert_eq!(gen.next(), None);
+        assert_eq!(LocalIdGenerator::reserve(0).next(), None);
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Random string IDs in the style of NanoID.
+//!
+//! Unlike the other IDs in this crate, NanoIDs carry no time or ordering information: every
+//! character is drawn from the operating system's secure random number generator. Use them where
+//! IDs must be unguessable (invite links, API keys' public halves) rather than sortable.
+//!
+//! ```
+//! use snowflake::nanoid::NanoId;
+//!
+//! let id = NanoId::default().generate();
+//! assert_eq!(id.len(), 21);
+//!
+//! let pins = NanoId::new("0123456789", 6).unwrap();
+//! assert!(pins.generate().bytes().all(|c| c.is_ascii_digit()));
+//! ```
+
+use crate::short_code::InvalidAlphabet;
+
+/// Generates random IDs of a fixed length from a fixed alphabet.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NanoId {
+    alphabet: Vec<u8>,
+    len: usize,
+    // The smallest `2^n - 1` covering every index into the alphabet.
+    mask: u8,
+}
+
+impl NanoId {
+    /// NanoID's default alphabet: URL-safe letters, digits, `_`, and `-`.
+    pub const DEFAULT_ALPHABET: &'static str =
+        "useandom-26T198340PX75pxJACKVERYMINDBUSHWOLF_GQZbfghjklqvwyzrict";
+
+    /// NanoID's default length, which gives about as many random bits (126) as a v4 UUID.
+    pub const DEFAULT_LEN: usize = 21;
+
+    /// Creates a generator of `len`-character IDs drawn from `alphabet` (ASCII, at least two
+    /// distinct characters).
+    pub fn new(alphabet: &str, len: usize) -> Result<Self, InvalidAlphabet> {
+        if !alphabet.is_ascii() {
+            return Err(InvalidAlphabet::NotAscii);
+        }
+        let alphabet = alphabet.as_bytes().to_vec();
+        if alphabet.len() < 2 {
+            return Err(InvalidAlphabet::TooShort);
+        }
+        let mut seen = [false; 128];
+        for &c in &alphabet {
+            if seen[c as usize] {
+                return Err(InvalidAlphabet::Duplicate(c as char));
+            }
+            seen[c as usize] = true;
+        }
+        // At most 128 characters, so the mask fits in 7 bits.
+        let bits = usize::BITS - (alphabet.len() - 1).leading_zeros();
+        let mask = ((1u32 << bits) - 1) as u8;
+        Ok(NanoId {
+            alphabet,
+            len,
+            mask,
+        })
+    }
+
+    /// The length of generated IDs.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if this generator produces empty IDs.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Generates a new ID.
+    ///
+    /// **panics** if the operating system's random number generator fails.
+    pub fn generate(&self) -> String {
+        let mut id = Vec::with_capacity(self.len);
+        // Masking and rejecting out-of-range bytes keeps every character equally likely. Request
+        // enough bytes that one batch is usually enough (the same factor NanoID uses).
+        let step = (self.len * usize::from(self.mask) * 8 / (5 * self.alphabet.len())).max(1);
+        let mut bytes = vec![0u8; step];
+        while id.len() < self.len {
+            getrandom::fill(&mut bytes)
+                .expect("failed to read from the OS random number generator");
+            for &b in &bytes {
+                if let Some(&c) = self.alphabet.get(usize::from(b & self.mask)) {
+                    id.push(c);
+                    if id.len() == self.len {
+                        break;
+                    }
+                }
+            }
+        }
+        // The alphabet is ASCII.
+        String::from_utf8(id).unwrap()
+    }
+}
+
+impl Default for NanoId {
+    /// A generator of 21-character IDs from [`DEFAULT_ALPHABET`](NanoId::DEFAULT_ALPHABET).
+    fn default() -> Self {
+        NanoId::new(NanoId::DEFAULT_ALPHABET, NanoId::DEFAULT_LEN).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::NanoId;
+    use crate::short_code::InvalidAlphabet;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_generate() {
+        let gen = NanoId::default();
+        let ids: HashSet<_> = (0..1000).map(|_| gen.generate()).collect();
+        assert_eq!(ids.len(), 1000);
+        for id in &ids {
+            assert_eq!(id.len(), 21);
+            assert!(id
+                .bytes()
+                .all(|c| NanoId::DEFAULT_ALPHABET.as_bytes().contains(&c)));
+        }
+
+        // Every character of an alphabet that isn't a power of two in size gets used.
+        let gen = NanoId::new("abc", 300).unwrap();
+        let id = gen.generate();
+        assert_eq!(id.len(), 300);
+        for c in "abc".chars() {
+            assert!(id.contains(c));
+        }
+        assert_eq!(NanoId::new("ab", 0).unwrap().generate(), "");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(NanoId::new("a", 5), Err(InvalidAlphabet::TooShort));
+        assert_eq!(NanoId::new("aba", 5), Err(InvalidAlphabet::Duplicate('a')));
+        assert_eq!(NanoId::new("ab\u{e9}", 5), Err(InvalidAlphabet::NotAscii));
+    }
+}
@@ -0,0 +1,337 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Node ID providers for distributed ID schemes.
+//!
+//! Time-based IDs (snowflakes and friends) need a node ID that is unique across all running
+//! generators. A [`NodeIdProvider`] supplies one and checks that it fits in the number of bits the
+//! ID layout reserves for it.
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+
+/// A source of node IDs.
+pub trait NodeIdProvider {
+    /// Returns this node's ID, failing if it doesn't fit in `bits` bits.
+    fn node_id(&self, bits: u32) -> Result<u64, NodeIdError>;
+}
+
+/// An error produced when a node ID can't be determined.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum NodeIdError {
+    /// None of the places the provider looks for a node ID were set.
+    Missing,
+    /// A node ID source was set but couldn't be interpreted.
+    Invalid {
+        /// Where the value came from (e.g., the environment variable name).
+        source: String,
+        /// The offending value.
+        value: String,
+    },
+    /// The node ID doesn't fit in the number of bits reserved for it.
+    TooWide {
+        /// The node ID.
+        node_id: u64,
+        /// The number of bits available.
+        bits: u32,
+    },
+    /// A coordination service couldn't hand out a node ID (it was unreachable, or every node ID
+    /// was taken).
+    Unavailable(String),
+}
+
+impl fmt::Display for NodeIdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NodeIdError::Missing => f.write_str("no node ID source is available"),
+            NodeIdError::Invalid { source, value } => {
+                write!(f, "invalid node ID {:?} from {}", value, source)
+            }
+            NodeIdError::TooWide { node_id, bits } => {
+                write!(f, "node ID {} does not fit in {} bits", node_id, bits)
+            }
+            NodeIdError::Unavailable(reason) => write!(f, "no node ID available: {}", reason),
+        }
+    }
+}
+
+impl Error for NodeIdError {}
+
+/// Checks that `node_id` fits in `bits` bits.
+pub(crate) fn check_width(node_id: u64, bits: u32) -> Result<u64, NodeIdError> {
+    if bits >= 64 || node_id >> bits == 0 {
+        Ok(node_id)
+    } else {
+        Err(NodeIdError::TooWide { node_id, bits })
+    }
+}
+
+/// A runtime for providers built on async clients, with one worker thread to keep leases or
+/// sessions alive in the background.
+#[cfg(any(feature = "etcd", feature = "zookeeper"))]
+pub(crate) fn background_runtime(name: &str) -> Result<tokio::runtime::Runtime, NodeIdError> {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name(name)
+        .enable_all()
+        .build()
+        .map_err(|e| NodeIdError::Unavailable(e.to_string()))
+}
+
+/// Runs `future` to completion on `runtime`, from a thread of its own so that this also works
+/// when called from async code.
+#[cfg(any(feature = "etcd", feature = "zookeeper"))]
+pub(crate) fn block_on<F>(runtime: &tokio::runtime::Runtime, future: F) -> F::Output
+where
+    F: std::future::Future + Send,
+    F::Output: Send,
+{
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| runtime.block_on(future))
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e))
+    })
+}
+
+/// A fixed, explicitly configured node ID.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct StaticNodeId(pub u64);
+
+impl NodeIdProvider for StaticNodeId {
+    fn node_id(&self, bits: u32) -> Result<u64, NodeIdError> {
+        check_width(self.0, bits)
+    }
+}
+
+/// Derives the node ID from the pod's identity when running in Kubernetes.
+///
+/// The following sources are tried, in order:
+///
+/// 1. The pod index environment variable (`POD_INDEX` by default), typically populated from the
+///    `apps.kubernetes.io/pod-index` label via the downward API.
+/// 2. The pod name environment variable (`POD_NAME` by default), typically populated from
+///    `metadata.name` via the downward API. StatefulSet pods are named `<set>-<ordinal>`.
+/// 3. `HOSTNAME`, which Kubernetes sets to the pod name.
+///
+/// The configured offset is added to the ordinal so that several StatefulSets can share one node
+/// ID space without overlapping.
+#[derive(Clone, Debug)]
+pub struct KubernetesNodeId {
+    index_var: String,
+    pod_name_var: String,
+    offset: u64,
+}
+
+impl Default for KubernetesNodeId {
+    fn default() -> Self {
+        KubernetesNodeId::new()
+    }
+}
+
+impl KubernetesNodeId {
+    /// Creates a provider using the default environment variables and no offset.
+    pub fn new() -> Self {
+        KubernetesNodeId {
+            index_var: "POD_INDEX".into(),
+            pod_name_var: "POD_NAME".into(),
+            offset: 0,
+        }
+    }
+
+    /// Reads the pod index from `name` instead of `POD_INDEX`.
+    pub fn index_var(mut self, name: impl Into<String>) -> Self {
+        self.index_var = name.into();
+        self
+    }
+
+    /// Reads the pod name from `name` instead of `POD_NAME`.
+    pub fn pod_name_var(mut self, name: impl Into<String>) -> Self {
+        self.pod_name_var = name.into();
+        self
+    }
+
+    /// Adds `offset` to the pod ordinal.
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    fn resolve<F>(&self, bits: u32, lookup: F) -> Result<u64, NodeIdError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let ordinal = if let Some(value) = lookup(&self.index_var) {
+            parse_ordinal(&self.index_var, &value, value.trim())?
+        } else if let Some(value) = lookup(&self.pod_name_var) {
+            parse_ordinal(&self.pod_name_var, &value, pod_ordinal(&value))?
+        } else if let Some(value) = lookup("HOSTNAME") {
+            parse_ordinal("HOSTNAME", &value, pod_ordinal(&value))?
+        } else {
+            return Err(NodeIdError::Missing);
+        };
+        let node_id = ordinal
+            .checked_add(self.offset)
+            .ok_or(NodeIdError::TooWide {
+                node_id: u64::MAX,
+                bits,
+            })?;
+        check_width(node_id, bits)
+    }
+}
+
+impl NodeIdProvider for KubernetesNodeId {
+    fn node_id(&self, bits: u32) -> Result<u64, NodeIdError> {
+        self.resolve(bits, |name| env::var(name).ok())
+    }
+}
+
+/// Uses the lower 16 bits of this host's private IPv4 address as the node ID, like Sonyflake's
+/// default machine ID.
+///
+/// Hosts in the same private /16 network get distinct IDs. Link-local addresses (169.254/16,
+/// common on cloud metadata interfaces) are accepted too, for hosts that have no other. The
+/// address is discovered by asking the OS which local address it would use to reach a public
+/// address, or failing that the link-local metadata address 169.254.169.254 (no packets are sent);
+/// use [`with_addr`](PrivateIpNodeId::with_addr) if that picks the wrong interface.
+#[derive(Clone, Debug, Default)]
+pub struct PrivateIpNodeId {
+    addr: Option<Ipv4Addr>,
+}
+
+impl PrivateIpNodeId {
+    /// Creates a provider that discovers the host's private address.
+    pub fn new() -> Self {
+        PrivateIpNodeId { addr: None }
+    }
+
+    /// Creates a provider that uses `addr` instead of discovering it.
+    pub fn with_addr(addr: Ipv4Addr) -> Self {
+        PrivateIpNodeId { addr: Some(addr) }
+    }
+
+    fn discover() -> Option<Ipv4Addr> {
+        let mut found = None;
+        for target in [Ipv4Addr::new(8, 8, 8, 8), Ipv4Addr::new(169, 254, 169, 254)] {
+            let addr = PrivateIpNodeId::local_addr_to(target);
+            if addr.is_some_and(usable) {
+                return addr;
+            }
+            found = found.or(addr);
+        }
+        found
+    }
+
+    /// The local address the OS would send from to reach `target`.
+    fn local_addr_to(target: Ipv4Addr) -> Option<Ipv4Addr> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+        socket.connect((target, 53)).ok()?;
+        match socket.local_addr().ok()?.ip() {
+            IpAddr::V4(addr) => Some(addr),
+            IpAddr::V6(_) => None,
+        }
+    }
+}
+
+fn usable(addr: Ipv4Addr) -> bool {
+    addr.is_private() || addr.is_link_local()
+}
+
+impl NodeIdProvider for PrivateIpNodeId {
+    fn node_id(&self, bits: u32) -> Result<u64, NodeIdError> {
+        let addr = self
+            .addr
+            .or_else(PrivateIpNodeId::discover)
+            .ok_or(NodeIdError::Missing)?;
+        if !usable(addr) {
+            return Err(NodeIdError::Invalid {
+                source: "private or link-local IPv4 address".into(),
+                value: addr.to_string(),
+            });
+        }
+        let [_, _, high, low] = addr.octets();
+        check_width(u64::from(u16::from_be_bytes([high, low])), bits)
+    }
+}
+
+/// Returns the trailing `-<ordinal>` component of a StatefulSet pod name.
+fn pod_ordinal(name: &str) -> &str {
+    let name = name.trim();
+    name.rsplit('-').next().unwrap_or(name)
+}
+
+fn parse_ordinal(source: &str, value: &str, ordinal: &str) -> Result<u64, NodeIdError> {
+    ordinal.parse().map_err(|_| NodeIdError::Invalid {
+        source: source.into(),
+        value: value.into(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{KubernetesNodeId, NodeIdError, NodeIdProvider, PrivateIpNodeId, StaticNodeId};
+    use std::net::Ipv4Addr;
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    #[test]
+    fn test_static_width() {
+        assert_eq!(StaticNodeId(1023).node_id(10), Ok(1023));
+        assert_eq!(
+            StaticNodeId(1024).node_id(10),
+            Err(NodeIdError::TooWide {
+                node_id: 1024,
+                bits: 10
+            })
+        );
+    }
+
+    #[test]
+    fn test_kubernetes_sources() {
+        let k8s = KubernetesNodeId::new();
+        assert_eq!(
+            k8s.resolve(10, env(&[("POD_INDEX", "4"), ("POD_NAME", "web-7")])),
+            Ok(4)
+        );
+        assert_eq!(k8s.resolve(10, env(&[("POD_NAME", "web-7")])), Ok(7));
+        assert_eq!(k8s.resolve(10, env(&[("HOSTNAME", "my-app-12")])), Ok(12));
+        assert_eq!(k8s.resolve(10, env(&[])), Err(NodeIdError::Missing));
+        assert!(matches!(
+            k8s.resolve(10, env(&[("POD_NAME", "web-abc")])),
+            Err(NodeIdError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn test_kubernetes_offset() {
+        let k8s = KubernetesNodeId::new().offset(1000);
+        assert_eq!(k8s.resolve(10, env(&[("POD_INDEX", "23")])), Ok(1023));
+        assert!(k8s.resolve(10, env(&[("POD_INDEX", "24")])).is_err());
+    }
+
+    #[test]
+    fn test_private_ip() {
+        let ip = PrivateIpNodeId::with_addr(Ipv4Addr::new(10, 1, 2, 3));
+        assert_eq!(ip.node_id(16), Ok(0x0203));
+        assert!(ip.node_id(8).is_err());
+        let link_local = PrivateIpNodeId::with_addr(Ipv4Addr::new(169, 254, 7, 9));
+        assert_eq!(link_local.node_id(16), Ok(0x0709));
+        assert!(matches!(
+            PrivateIpNodeId::with_addr(Ipv4Addr::new(8, 8, 4, 4)).node_id(16),
+            Err(NodeIdError::Invalid { .. })
+        ));
+    }
+}
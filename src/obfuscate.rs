@@ -0,0 +1,118 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Keyed permutations that hide the order of sequential 64-bit IDs.
+//!
+//! Sequential IDs leak how many things were created and when. An [`Obfuscator`] maps every `u64`
+//! to a different, random-looking `u64` (and back), so the external form of an ID reveals neither
+//! while the internal form stays sequential and compact.
+//!
+//! ```
+//! use snowflake::obfuscate::Obfuscator;
+//!
+//! let obfuscator = Obfuscator::new(0x5eed_5eed_5eed_5eed);
+//! let external = obfuscator.obfuscate(42);
+//! assert_ne!(external, 42);
+//! assert_eq!(obfuscator.deobfuscate(external), 42);
+//! ```
+//!
+//! **This is not encryption.** The permutation is a small Feistel network with a fast,
+//! non-cryptographic round function: it stops casual observers from reading order and volume off
+//! of IDs, but a determined attacker with many (internal, external) pairs can likely recover the
+//! permutation. Use it to hide metrics, not to protect secrets, and keep the key stable: changing
+//! it changes every external ID.
+
+/// The number of Feistel rounds.
+const ROUNDS: usize = 8;
+
+/// A keyed bijection on `u64`s.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Obfuscator {
+    round_keys: [u32; ROUNDS],
+}
+
+impl Obfuscator {
+    /// Creates an obfuscator from a (secret) key.
+    pub fn new(key: u64) -> Self {
+        let mut state = key;
+        let mut round_keys = [0; ROUNDS];
+        for round_key in &mut round_keys {
+            *round_key = splitmix64(&mut state) as u32;
+        }
+        Obfuscator { round_keys }
+    }
+
+    /// Maps an internal ID to its external form.
+    #[inline]
+    pub fn obfuscate(&self, id: u64) -> u64 {
+        let (mut left, mut right) = ((id >> 32) as u32, id as u32);
+        for &key in &self.round_keys {
+            let next = left ^ round(right, key);
+            left = right;
+            right = next;
+        }
+        (u64::from(left) << 32) | u64::from(right)
+    }
+
+    /// Maps an external ID back to its internal form.
+    #[inline]
+    pub fn deobfuscate(&self, id: u64) -> u64 {
+        let (mut left, mut right) = ((id >> 32) as u32, id as u32);
+        for &key in self.round_keys.iter().rev() {
+            let prev = right ^ round(left, key);
+            right = left;
+            left = prev;
+        }
+        (u64::from(left) << 32) | u64::from(right)
+    }
+}
+
+/// The Feistel round function. It doesn't need to be invertible, just well mixed.
+#[inline]
+fn round(half: u32, key: u32) -> u32 {
+    let mut state = u64::from(half ^ key) | (u64::from(key) << 32);
+    (splitmix64(&mut state) >> 32) as u32
+}
+
+/// One step of the SplitMix64 generator.
+#[inline]
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::Obfuscator;
+
+    #[test]
+    fn test_round_trip() {
+        let obfuscator = Obfuscator::new(12345);
+        for &id in &[0, 1, 2, 1 << 32, u64::MAX - 1, u64::MAX] {
+            assert_eq!(obfuscator.deobfuscate(obfuscator.obfuscate(id)), id);
+        }
+    }
+
+    #[test]
+    fn test_hides_order() {
+        let a = Obfuscator::new(1);
+        let b = Obfuscator::new(2);
+        let mut external: Vec<_> = (0..1000).map(|id| a.obfuscate(id)).collect();
+        // Consecutive IDs map to unrelated values...
+        assert!(external.windows(2).filter(|w| w[0] < w[1]).count() < 600);
+        // ...that depend on the key...
+        assert_ne!(a.obfuscate(7), b.obfuscate(7));
+        // ...and never collide.
+        external.sort();
+        external.dedup();
+        assert_eq!(external.len(), 1000);
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::fmt;
+use core::mem;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, Hasher};
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "bevy")]
+use bevy_ecs::reflect::ReflectComponent;
+
+#[cfg(feature = "std")]
+use crate::atomic::{AtomicU32, Ordering};
+use crate::display::pad_id;
+#[cfg(feature = "std")]
+use crate::obfuscate::splitmix64;
+use crate::process_unique_id::{expect_len, ParseIdError};
+
+#[cfg(feature = "std")]
+const COUNTER_MASK: u32 = (1 << 24) - 1;
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+/// The random value and counter shared by every ID this process creates.
+#[cfg(feature = "std")]
+struct ProcessState {
+    random: [u8; 5],
+    counter: AtomicU32,
+}
+
+#[cfg(feature = "std")]
+static PROCESS_STATE: OnceLock<ProcessState> = OnceLock::new();
+
+#[cfg(feature = "std")]
+fn process_state() -> &'static ProcessState {
+    PROCESS_STATE.get_or_init(|| {
+        let mut seed = RandomState::new().build_hasher().finish();
+        let random = splitmix64(&mut seed).to_be_bytes();
+        ProcessState {
+            random: [random[0], random[1], random[2], random[3], random[4]],
+            counter: AtomicU32::new(splitmix64(&mut seed) as u32 & COUNTER_MASK),
+        }
+    })
+}
+
+/// Returns the next value of the process-wide 24-bit counter, which starts at a random value and
+/// wraps around.
+#[cfg(feature = "std")]
+pub(crate) fn next_counter() -> u32 {
+    process_state().counter.fetch_add(1, Ordering::Relaxed) & COUNTER_MASK
+}
+
+/// A 12-byte ID in the layout of MongoDB's `ObjectId`.
+///
+/// A 4-byte big-endian timestamp in seconds since the UNIX epoch, a 5-byte random value chosen
+/// once per process, and a 3-byte big-endian counter that starts at a random value. IDs created
+/// by `new()` are unique within the process (up to 2^24 per second) and, with high probability,
+/// across processes and hosts. They sort by creation time to the second.
+///
+/// Displayed as 24 lowercase hex digits, like MongoDB's drivers.
+///
+/// # Memory layout
+///
+/// `#[repr(transparent)]` over its 12 bytes (as in [`bytes`](ObjectId::bytes)), with an alignment
+/// of 1.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(
+        zerocopy::FromBytes,
+        zerocopy::IntoBytes,
+        zerocopy::Immutable,
+        zerocopy::KnownLayout
+    )
+)]
+#[cfg_attr(feature = "bitcode", derive(bitcode::Encode, bitcode::Decode))]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_ecs::component::Component, bevy_reflect::Reflect),
+    reflect(opaque, Component, Debug, PartialEq, Hash, Clone)
+)]
+#[repr(transparent)]
+pub struct ObjectId([u8; 12]);
+
+const _: () = assert!(mem::size_of::<ObjectId>() == 12 && mem::align_of::<ObjectId>() == 1);
+
+impl ObjectId {
+    /// Creates a new ObjectId.
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as u32);
+        ObjectId::new_at(secs)
+    }
+
+    #[cfg(feature = "std")]
+    fn new_at(secs: u32) -> Self {
+        let mut bytes = [0u8; 12];
+        bytes[..4].copy_from_slice(&secs.to_be_bytes());
+        bytes[4..9].copy_from_slice(&process_state().random);
+        bytes[9..].copy_from_slice(&next_counter().to_be_bytes()[1..]);
+        ObjectId(bytes)
+    }
+
+    /// Creates an ObjectId from its raw bytes.
+    #[inline]
+    pub const fn from_bytes(bytes: [u8; 12]) -> Self {
+        ObjectId(bytes)
+    }
+
+    /// The raw bytes of this ID.
+    #[inline]
+    pub const fn bytes(self) -> [u8; 12] {
+        self.0
+    }
+
+    /// Seconds since the UNIX epoch at which this ID was created.
+    #[inline]
+    pub fn timestamp(self) -> u32 {
+        u32::from_be_bytes([self.0[0], self.0[1], self.0[2], self.0[3]])
+    }
+
+    /// The per-process random value.
+    #[inline]
+    pub fn process_random(self) -> [u8; 5] {
+        [self.0[4], self.0[5], self.0[6], self.0[7], self.0[8]]
+    }
+
+    /// The 24-bit counter.
+    #[inline]
+    pub fn counter(self) -> u32 {
+        u32::from_be_bytes([0, self.0[9], self.0[10], self.0[11]])
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for ObjectId {
+    #[inline]
+    fn default() -> Self {
+        ObjectId::new()
+    }
+}
+
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut buf = [0u8; 24];
+        for (i, b) in self.0.iter().enumerate() {
+            buf[2 * i] = HEX[usize::from(b >> 4)];
+            buf[2 * i + 1] = HEX[usize::from(b & 0xf)];
+        }
+        // Hex digits are ASCII.
+        let s = core::str::from_utf8(&buf).unwrap();
+        pad_id(f, format_args!(""), format_args!("{}", s))
+    }
+}
+
+/// Parses the 24-digit hex form produced by `Display`, in either case.
+impl FromStr for ObjectId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        fn decode(c: u8) -> Option<u8> {
+            (c as char).to_digit(16).map(|d| d as u8)
+        }
+
+        let mut bytes = [0u8; 12];
+        for (i, c) in s.bytes().enumerate().take(24) {
+            let digit = decode(c).ok_or_else(|| ParseIdError::at(s, i, "a hex digit"))?;
+            bytes[i / 2] = bytes[i / 2] << 4 | digit;
+        }
+        expect_len(s, 24, "a hex digit")?;
+        Ok(ObjectId(bytes))
+    }
+}
+
+#[cfg(feature = "bson")]
+impl From<ObjectId> for bson::oid::ObjectId {
+    #[inline]
+    fn from(id: ObjectId) -> Self {
+        bson::oid::ObjectId::from_bytes(id.0)
+    }
+}
+
+#[cfg(feature = "bson")]
+impl From<bson::oid::ObjectId> for ObjectId {
+    #[inline]
+    fn from(id: bson::oid::ObjectId) -> Self {
+        ObjectId(id.bytes())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::ObjectId;
+
+    #[test]
+    fn test_layout() {
+        let a = ObjectId::new_at(0x6500_0000);
+        let b = ObjectId::new_at(0x6500_0000);
+        assert_eq!(a.timestamp(), 0x6500_0000);
+        assert_eq!(a.process_random(), b.process_random());
+        // The counter is shared with other generators, which may run on other test threads.
+        assert_ne!(b.counter(), a.counter());
+        assert_eq!(ObjectId::from_bytes(a.bytes()), a);
+        assert!(ObjectId::new().timestamp() > 1_600_000_000);
+    }
+
+    #[test]
+    fn test_string_form() {
+        let id: ObjectId = "507f1f77bcf86cd799439011".parse().unwrap();
+        assert_eq!(id.timestamp(), 0x507f_1f77);
+        assert_eq!(id.counter(), 0x43_9011);
+        assert_eq!(id.to_string(), "507f1f77bcf86cd799439011");
+        assert_eq!("507F1F77BCF86CD799439011".parse(), Ok(id));
+        for bad in &[
+            "",
+            "507f1f77bcf86cd79943901",
+            "507f1f77bcf86cd79943901g",
+            "+07f1f77bcf86cd799439011",
+        ] {
+            assert!(bad.parse::<ObjectId>().is_err(), "{}", bad);
+        }
+    }
+
+    #[cfg(feature = "bson")]
+    #[test]
+    fn test_bson() {
+        let id = ObjectId::new();
+        let oid = bson::oid::ObjectId::from(id);
+        assert_eq!(oid.to_hex(), id.to_string());
+        assert_eq!(ObjectId::from(oid), id);
+    }
+}
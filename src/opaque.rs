@@ -0,0 +1,307 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Encrypted, URL-safe tokens that carry an ID across a trust boundary.
+//!
+//! An [`OpaqueKey`] seals an ID, an optional expiry, and an application-chosen type tag into a
+//! token with XChaCha20-Poly1305. Tokens reveal nothing about the ID (not even whether two tokens
+//! hold the same one), and any modification, wrong key, wrong tag, or wrong ID type is rejected
+//! when the token is opened.
+//!
+//! ```
+//! use snowflake::opaque::OpaqueKey;
+//! use snowflake::ProcessUniqueId;
+//!
+//! let key = OpaqueKey::new(&[7; 32]);
+//! let id = ProcessUniqueId::new();
+//! let token = key.seal(id, "order", None);
+//! assert_eq!(key.open::<ProcessUniqueId>(&token, "order"), Ok(id));
+//! assert!(key.open::<ProcessUniqueId>(&token, "invoice").is_err());
+//! ```
+//!
+//! # Token format
+//!
+//! A token is the unpadded URL-safe base64 encoding of a random 24-byte nonce followed by the
+//! encrypted payload: a version byte (`1`), the ID (16 bytes, big-endian), and the expiry in
+//! seconds since the UNIX epoch (8 bytes, big-endian, zero for "never"). The ID type's
+//! [`KIND`](OpaqueId::KIND) and the tag are authenticated but not stored.
+
+use std::error::Error;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+
+use crate::{ProcessUniqueId, SmallUniqueId, SnowflakeId};
+
+const VERSION: u8 = 1;
+const NONCE_LEN: usize = 24;
+const PAYLOAD_LEN: usize = 1 + 16 + 8;
+
+/// An ID that can be sealed into an opaque token.
+pub trait OpaqueId: Sized {
+    /// Identifies the ID type, so a token sealed for one type can't be opened as another.
+    const KIND: &'static str;
+
+    /// Packs the ID into a `u128`.
+    fn to_u128(&self) -> u128;
+
+    /// Unpacks an ID packed by `to_u128`, returning `None` if `value` isn't a valid ID.
+    fn from_u128(value: u128) -> Option<Self>;
+}
+
+impl OpaqueId for ProcessUniqueId {
+    const KIND: &'static str = "puid";
+
+    #[inline]
+    fn to_u128(&self) -> u128 {
+        ProcessUniqueId::to_u128(*self)
+    }
+
+    #[inline]
+    fn from_u128(value: u128) -> Option<Self> {
+        ProcessUniqueId::from_u128(value)
+    }
+}
+
+impl OpaqueId for SmallUniqueId {
+    const KIND: &'static str = "spuid";
+
+    #[inline]
+    fn to_u128(&self) -> u128 {
+        self.0.into()
+    }
+
+    #[inline]
+    fn from_u128(value: u128) -> Option<Self> {
+        if value > u128::from(u64::MAX) {
+            return None;
+        }
+        Some(SmallUniqueId(value as u64))
+    }
+}
+
+impl OpaqueId for SnowflakeId {
+    const KIND: &'static str = "snowflake";
+
+    #[inline]
+    fn to_u128(&self) -> u128 {
+        self.0.into()
+    }
+
+    #[inline]
+    fn from_u128(value: u128) -> Option<Self> {
+        if value >> 63 != 0 {
+            return None;
+        }
+        Some(SnowflakeId(value as u64))
+    }
+}
+
+/// An error returned by [`OpaqueKey::open`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum OpaqueError {
+    /// The token isn't well formed (bad base64, wrong length).
+    Malformed,
+    /// The token was sealed with a different key, tag, or ID type, or has been tampered with.
+    Invalid,
+    /// The token's expiry has passed.
+    Expired,
+}
+
+impl fmt::Display for OpaqueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            OpaqueError::Malformed => "malformed opaque ID token",
+            OpaqueError::Invalid => "invalid opaque ID token",
+            OpaqueError::Expired => "expired opaque ID token",
+        })
+    }
+}
+
+impl Error for OpaqueError {}
+
+/// Seals IDs into opaque tokens and opens them again.
+#[derive(Clone)]
+pub struct OpaqueKey {
+    cipher: XChaCha20Poly1305,
+}
+
+impl fmt::Debug for OpaqueKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("OpaqueKey(..)")
+    }
+}
+
+impl OpaqueKey {
+    /// Creates a key from 32 secret bytes.
+    pub fn new(key: &[u8; 32]) -> Self {
+        OpaqueKey {
+            cipher: XChaCha20Poly1305::new(Key::from_slice(key)),
+        }
+    }
+
+    /// Seals `id` into a token that can only be opened with this key and the same `tag`.
+    ///
+    /// If `expires` is set, [`open`](OpaqueKey::open) rejects the token from then on. Expiries are
+    /// stored with one-second precision.
+    pub fn seal<I: OpaqueId>(&self, id: I, tag: &str, expires: Option<SystemTime>) -> String {
+        let expires = expires.map_or(0, |t| {
+            // Truncating means the token never outlives the requested expiry; an expiry at (or
+            // before) the epoch must not turn into "never".
+            let since_epoch = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+            since_epoch.as_secs().max(1)
+        });
+        let mut payload = [0u8; PAYLOAD_LEN];
+        payload[0] = VERSION;
+        payload[1..17].copy_from_slice(&id.to_u128().to_be_bytes());
+        payload[17..].copy_from_slice(&expires.to_be_bytes());
+
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = associated_data::<I>(tag);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &payload,
+                    aad: &aad,
+                },
+            )
+            .expect("encrypting a fixed-size payload can't fail");
+        let mut token = nonce.to_vec();
+        token.extend_from_slice(&ciphertext);
+        URL_SAFE_NO_PAD.encode(token)
+    }
+
+    /// Opens a token sealed by [`seal`](OpaqueKey::seal) with the same `tag` and ID type.
+    pub fn open<I: OpaqueId>(&self, token: &str, tag: &str) -> Result<I, OpaqueError> {
+        self.open_at(token, tag, SystemTime::now())
+    }
+
+    fn open_at<I: OpaqueId>(
+        &self,
+        token: &str,
+        tag: &str,
+        now: SystemTime,
+    ) -> Result<I, OpaqueError> {
+        let token = URL_SAFE_NO_PAD
+            .decode(token)
+            .map_err(|_| OpaqueError::Malformed)?;
+        if token.len() <= NONCE_LEN {
+            return Err(OpaqueError::Malformed);
+        }
+        let (nonce, ciphertext) = token.split_at(NONCE_LEN);
+        let aad = associated_data::<I>(tag);
+        let payload = self
+            .cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| OpaqueError::Invalid)?;
+        if payload.len() != PAYLOAD_LEN || payload[0] != VERSION {
+            return Err(OpaqueError::Invalid);
+        }
+
+        let mut expires = [0u8; 8];
+        expires.copy_from_slice(&payload[17..]);
+        let expires = u64::from_be_bytes(expires);
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        if expires != 0 && now >= expires {
+            return Err(OpaqueError::Expired);
+        }
+
+        let mut id = [0u8; 16];
+        id.copy_from_slice(&payload[1..17]);
+        I::from_u128(u128::from_be_bytes(id)).ok_or(OpaqueError::Invalid)
+    }
+}
+
+/// Binds a token to its ID type and tag. The kind can't contain a NUL, so the encoding is
+/// unambiguous.
+fn associated_data<I: OpaqueId>(tag: &str) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(I::KIND.len() + 1 + tag.len());
+    aad.extend_from_slice(I::KIND.as_bytes());
+    aad.push(0);
+    aad.extend_from_slice(tag.as_bytes());
+    aad
+}
+
+#[cfg(test)]
+mod test {
+    use super::{OpaqueError, OpaqueKey};
+    use crate::{ProcessUniqueId, SmallUniqueId};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_round_trip() {
+        let key = OpaqueKey::new(&[1; 32]);
+        let id = ProcessUniqueId::new();
+        let a = key.seal(id, "order", None);
+        let b = key.seal(id, "order", None);
+        assert_ne!(a, b);
+        assert!(a
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_'));
+        assert_eq!(key.open(&a, "order"), Ok(id));
+        assert_eq!(key.open(&b, "order"), Ok(id));
+    }
+
+    #[test]
+    fn test_rejects() {
+        let key = OpaqueKey::new(&[1; 32]);
+        let token = key.seal(ProcessUniqueId::new(), "order", None);
+        assert_eq!(
+            key.open::<ProcessUniqueId>(&token, "user"),
+            Err(OpaqueError::Invalid)
+        );
+        assert_eq!(
+            key.open::<SmallUniqueId>(&token, "order"),
+            Err(OpaqueError::Invalid)
+        );
+        assert_eq!(
+            OpaqueKey::new(&[2; 32]).open::<ProcessUniqueId>(&token, "order"),
+            Err(OpaqueError::Invalid)
+        );
+        let mut tampered = token.into_bytes();
+        tampered[40] = if tampered[40] == b'A' { b'B' } else { b'A' };
+        let tampered = String::from_utf8(tampered).unwrap();
+        assert_eq!(
+            key.open::<ProcessUniqueId>(&tampered, "order"),
+            Err(OpaqueError::Invalid)
+        );
+        assert_eq!(
+            key.open::<ProcessUniqueId>("not a token!", "order"),
+            Err(OpaqueError::Malformed)
+        );
+        assert_eq!(
+            key.open::<ProcessUniqueId>("AAAA", "order"),
+            Err(OpaqueError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_expiry() {
+        let key = OpaqueKey::new(&[1; 32]);
+        let id = SmallUniqueId::new();
+        let now = SystemTime::now();
+        let token = key.seal(id, "", Some(now + Duration::from_secs(60)));
+        assert_eq!(key.open_at(&token, "", now), Ok(id));
+        assert_eq!(
+            key.open_at::<SmallUniqueId>(&token, "", now + Duration::from_secs(61)),
+            Err(OpaqueError::Expired)
+        );
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! OpenTelemetry integration.
+//!
+//! Requires the `opentelemetry` feature. [`CounterIdGenerator`] implements the SDK's
+//! [`IdGenerator`] on top of process unique IDs instead of a random number generator:
+//!
+//! ```
+//! use opentelemetry_sdk::trace::SdkTracerProvider;
+//! use snowflake::otel::CounterIdGenerator;
+//!
+//! let provider = SdkTracerProvider::builder()
+//!     .with_id_generator(CounterIdGenerator::new())
+//!     .build();
+//! # drop(provider);
+//! ```
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use opentelemetry::trace::{SpanId, TraceId};
+use opentelemetry_sdk::trace::IdGenerator;
+
+use crate::obfuscate::splitmix64;
+use crate::ProcessUniqueId;
+
+/// An [`IdGenerator`] that derives trace and span IDs from process unique IDs.
+///
+/// Each ID starts from a fresh [`ProcessUniqueId`] (a thread local counter bump) and scrambles
+/// its prefix and offset with a bijective mix keyed by random values chosen when the generator is
+/// created. Trace IDs are therefore unique within the process, not just unlikely to collide, and
+/// look random, including their rightmost 7 bytes as Trace Context recommends; across processes
+/// they're as unlikely to collide as random IDs. Span IDs fold the prefix and offset into 64 bits,
+/// so they're unique within the process until a thread hands out 2^40 IDs from one prefix.
+///
+/// The IDs are not unpredictable: anyone who sees enough of them can work out the keys and guess
+/// future IDs. Use the SDK's `RandomIdGenerator` where that matters.
+#[derive(Clone, Debug)]
+pub struct CounterIdGenerator {
+    keys: [u64; 3],
+}
+
+impl CounterIdGenerator {
+    /// Creates a generator with freshly chosen keys.
+    pub fn new() -> Self {
+        let mut seed = RandomState::new().build_hasher().finish();
+        CounterIdGenerator {
+            keys: [
+                splitmix64(&mut seed),
+                splitmix64(&mut seed),
+                splitmix64(&mut seed),
+            ],
+        }
+    }
+}
+
+impl Default for CounterIdGenerator {
+    #[inline]
+    fn default() -> Self {
+        CounterIdGenerator::new()
+    }
+}
+
+/// A bijection on `u64`s, keyed by `key`.
+#[inline]
+fn mix(value: u64, key: u64) -> u64 {
+    let mut state = value ^ key;
+    splitmix64(&mut state)
+}
+
+impl IdGenerator for CounterIdGenerator {
+    fn new_trace_id(&self) -> TraceId {
+        loop {
+            let id = ProcessUniqueId::new();
+            let high = mix(id.prefix as u64, self.keys[0]);
+            let low = mix(id.offset, self.keys[1]);
+            // The all-zero ID is invalid; skip the one ID that maps to it.
+            if high | low != 0 {
+                return TraceId::from(u128::from(high) << 64 | u128::from(low));
+            }
+        }
+    }
+
+    fn new_span_id(&self) -> SpanId {
+        loop {
+            let id = ProcessUniqueId::new();
+            let span = mix((id.prefix as u64) << 40 ^ id.offset, self.keys[2]);
+            if span != 0 {
+                return SpanId::from(span);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CounterIdGenerator;
+    use opentelemetry_sdk::trace::IdGenerator;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_otel_ids_unique() {
+        let gen = CounterIdGenerator::new();
+        let traces: HashSet<_> = (0..10_000).map(|_| gen.new_trace_id()).collect();
+        let spans: HashSet<_> = (0..10_000).map(|_| gen.new_span_id()).collect();
+        assert_eq!((traces.len(), spans.len()), (10_000, 10_000));
+        // Consecutive IDs differ in their low bytes, not just by one.
+        let (a, b) = (gen.new_span_id(), gen.new_span_id());
+        let diff = u64::from_be_bytes(a.to_bytes()) ^ u64::from_be_bytes(b.to_bytes());
+        assert!(diff.count_ones() > 8);
+    }
+}
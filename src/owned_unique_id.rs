@@ -0,0 +1,172 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::panic::Location;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::ProcessUniqueId;
+
+// Every live `OwnedUniqueId` and where it was created.
+static LIVE: Mutex<BTreeMap<ProcessUniqueId, &'static Location<'static>>> =
+    Mutex::new(BTreeMap::new());
+
+fn live() -> MutexGuard<'static, BTreeMap<ProcessUniqueId, &'static Location<'static>>> {
+    LIVE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A [`ProcessUniqueId`] that tracks its own lifetime, for finding leaked resources.
+///
+/// Creating an `OwnedUniqueId` registers it, along with the location of the call to
+/// [`new`](OwnedUniqueId::new), in a process-wide registry; dropping it unregisters it. Use it
+/// as the key of a logical resource and [`live_ids`](OwnedUniqueId::live_ids) shows which
+/// resources are still around and who created them:
+///
+/// ```
+/// use snowflake::OwnedUniqueId;
+///
+/// let conn = OwnedUniqueId::new();
+/// let id = conn.id();
+/// assert!(OwnedUniqueId::live_ids().iter().any(|(live, _)| *live == id));
+///
+/// drop(conn);
+/// assert!(!OwnedUniqueId::live_ids().iter().any(|(live, _)| *live == id));
+/// ```
+///
+/// Registration takes a global lock, so this is meant for debugging and for resources that
+/// aren't created at a very high rate.
+///
+/// With the `leak-report` feature, the IDs still alive when the process exits are printed to
+/// standard error, along with where they were created. The report isn't printed if the process
+/// exits through a path that skips `atexit` handlers, e.g. `std::process::abort`, or with the `safe`
+/// feature, which rules out the `unsafe` code registering the handler takes.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OwnedUniqueId {
+    id: ProcessUniqueId,
+}
+
+impl OwnedUniqueId {
+    /// Creates and registers a new unique ID.
+    ///
+    /// **panics** under the same conditions as [`ProcessUniqueId::new`].
+    #[track_caller]
+    pub fn new() -> Self {
+        #[cfg(all(feature = "leak-report", not(feature = "safe")))]
+        register_leak_report();
+        let id = ProcessUniqueId::new();
+        live().insert(id, Location::caller());
+        OwnedUniqueId { id }
+    }
+
+    /// The ID.
+    #[inline]
+    pub fn id(&self) -> ProcessUniqueId {
+        self.id
+    }
+
+    /// Where this ID was created.
+    pub fn location(&self) -> &'static Location<'static> {
+        live()[&self.id]
+    }
+
+    /// All live `OwnedUniqueId`s, in order, along with where they were created.
+    pub fn live_ids() -> Vec<(ProcessUniqueId, &'static Location<'static>)> {
+        live().iter().map(|(&id, &loc)| (id, loc)).collect()
+    }
+
+    /// Writes the live IDs to `out`, one per line, and returns how many there were. Writes
+    /// nothing if there are none.
+    pub fn report_leaks<W: Write>(out: &mut W) -> io::Result<usize> {
+        let live = Self::live_ids();
+        if !live.is_empty() {
+            writeln!(out, "{} OwnedUniqueId(s) still alive:", live.len())?;
+            for (id, loc) in &live {
+                writeln!(out, "  {} created at {}", id, loc)?;
+            }
+        }
+        Ok(live.len())
+    }
+}
+
+impl Drop for OwnedUniqueId {
+    fn drop(&mut self) {
+        live().remove(&self.id);
+    }
+}
+
+impl fmt::Display for OwnedUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.id.fmt(f)
+    }
+}
+
+impl fmt::Debug for OwnedUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("OwnedUniqueId").field(&self.id).finish()
+    }
+}
+
+impl Default for OwnedUniqueId {
+    #[inline]
+    #[track_caller]
+    fn default() -> Self {
+        OwnedUniqueId::new()
+    }
+}
+
+#[cfg(all(feature = "leak-report", not(feature = "safe")))]
+fn register_leak_report() {
+    use std::os::raw::c_int;
+    use std::sync::Once;
+
+    extern "C" {
+        fn atexit(callback: extern "C" fn()) -> c_int;
+    }
+
+    extern "C" fn report() {
+        let _ = OwnedUniqueId::report_leaks(&mut io::stderr());
+    }
+
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        // SAFETY: `atexit` is part of the C standard library and `report` never unwinds.
+        unsafe {
+            atexit(report);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::OwnedUniqueId;
+
+    #[test]
+    fn test_live_ids() {
+        let a = OwnedUniqueId::new();
+        let line = line!() - 1;
+        let b = OwnedUniqueId::default();
+        let (a_id, b_id) = (a.id(), b.id());
+        assert_eq!(a.location().line(), line);
+        assert_eq!(a.location().file(), file!());
+
+        let live = |id| {
+            OwnedUniqueId::live_ids()
+                .iter()
+                .any(|(live, _)| *live == id)
+        };
+        assert!(live(a_id) && live(b_id));
+        drop(a);
+        assert!(!live(a_id) && live(b_id));
+
+        let mut report = Vec::new();
+        assert!(OwnedUniqueId::report_leaks(&mut report).unwrap() >= 1);
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.contains(&format!("{} created at {}:", b_id, file!())));
+    }
+}
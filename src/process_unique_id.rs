@@ -5,102 +5,1800 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+#[cfg(all(feature = "std", any(feature = "safe", feature = "snapshot")))]
+use core::cell::Cell;
+#[cfg(all(feature = "std", not(feature = "safe")))]
 use std::cell::UnsafeCell;
+#[cfg(feature = "std")]
+use std::mem::MaybeUninit;
 
-use std::default::Default;
-use std::fmt;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use core::convert::TryFrom;
+use core::error::Error;
+use core::fmt;
+use core::str::FromStr;
 
-static GLOBAL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+#[cfg(feature = "std")]
+use core::ptr;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
 
-fn next_global() -> usize {
-    let mut prev = GLOBAL_COUNTER.load(Ordering::Relaxed);
+#[cfg(feature = "bevy")]
+use bevy_ecs::reflect::ReflectComponent;
+
+#[cfg(feature = "std")]
+use crate::atomic::fence;
+use crate::atomic::{AtomicBool, AtomicU64, AtomicUsize, CachePadded, Ordering};
+use crate::display::{pad_id, DisplayAs};
+use crate::IdOutOfRange;
+
+/// The bits of a UUIDv8 not taken up by the version and variant.
+#[cfg(any(feature = "uuid", feature = "windows"))]
+const UUID_CUSTOM_BITS: u32 = 122;
+
+// Threads that start at the same time all go for the counter and the shared prefix, so each gets
+// a cache line of its own instead of bouncing the neighboring statics around with it.
+static GLOBAL_COUNTER: CachePadded<AtomicUsize> = CachePadded(AtomicUsize::new(0));
+
+// Another copy of this crate's counter, once `use_prefix_counter` has been called.
+#[cfg(feature = "std")]
+static SHARED_COUNTER: OnceLock<&'static AtomicUsize> = OnceLock::new();
+
+// Set while `use_prefix_counter` is putting another copy's counter in place of this one's.
+#[cfg(feature = "std")]
+static SWITCHING: AtomicBool = AtomicBool::new(false);
+
+/// Whether prefixes are claimed from another copy's counter, see `use_prefix_counter`.
+#[cfg(feature = "durable")]
+pub(crate) fn uses_shared_counter() -> bool {
+    SHARED_COUNTER.get().is_some()
+}
+
+/// The counter prefixes are claimed from: `GLOBAL_COUNTER`, unless another copy of this crate's
+/// is being shared.
+#[inline]
+pub(crate) fn global_counter() -> &'static AtomicUsize {
+    #[cfg(feature = "std")]
+    if let Some(counter) = SHARED_COUNTER.get() {
+        return counter;
+    }
+    &GLOBAL_COUNTER.0
+}
+
+static WRAP_ON_EXHAUSTION: AtomicBool = AtomicBool::new(false);
+
+#[cold]
+fn exhausted() -> ! {
+    panic!("Snow Crash: Go home and reevaluate your threading model!")
+}
+
+#[cfg(any(
+    feature = "embedded",
+    feature = "test-util",
+    all(feature = "std", feature = "uuid"),
+    kani
+))]
+pub(crate) fn next_global() -> usize {
+    try_next_global().unwrap_or_else(|| exhausted())
+}
+
+// Below this, prefixes are claimed with a single `fetch_add`. Threads only add to the counter
+// after seeing it below the limit, so it could only run past `usize::MAX` if a quarter of the
+// prefix space worth of threads were between the two at once.
+const FETCH_ADD_LIMIT: usize = usize::MAX / 4 * 3;
+
+/// Like [`next_global`], but returns `None` instead of panicking when the prefixes run out.
+#[inline]
+fn try_next_global() -> Option<usize> {
+    let prefix = loop {
+        // A CAS loop degrades badly when many threads claim prefixes at once; `fetch_add` doesn't.
+        let counter = global_counter();
+        let prefix = if counter.load(Ordering::Relaxed) < FETCH_ADD_LIMIT {
+            counter.fetch_add(1, Ordering::Relaxed)
+        } else {
+            try_next_global_near_end(counter)?
+        };
+        // `use_prefix_counter` parks this copy's counter at `FETCH_ADD_LIMIT`, so a prefix this
+        // high may have come from a counter that's no longer in use.
+        if prefix < FETCH_ADD_LIMIT || !switched_away(counter) {
+            break prefix;
+        }
+    };
+    #[cfg(feature = "durable")]
+    crate::durable::on_prefix_allocated(prefix);
+    #[cfg(feature = "metrics")]
+    crate::stats::on_prefix_allocated(prefix + 1);
+    #[cfg(feature = "tracing")]
+    check_prefix_thresholds(prefix + 1);
+    Some(prefix)
+}
+
+/// Claims a prefix with a CAS loop, which can stop at `usize::MAX` (or wrap around).
+// Inlinable despite being cold so the `no-panic` check can see through it from other crates.
+#[cold]
+#[inline]
+fn try_next_global_near_end(counter: &AtomicUsize) -> Option<usize> {
+    let mut prev = counter.load(Ordering::Relaxed);
     loop {
-        assert!(
-            prev < usize::MAX,
-            "Snow Crash: Go home and reevaluate your threading model!"
+        let (prefix, next) = if prev < usize::MAX {
+            (prev, prev + 1)
+        } else if WRAP_ON_EXHAUSTION.load(Ordering::Relaxed) {
+            (0, 1)
+        } else {
+            return None;
+        };
+        match counter.compare_exchange_weak(prev, next, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => return Some(prefix),
+            Err(actual) => prev = actual,
+        }
+    }
+}
+
+/// Whether `counter` has been replaced by `use_prefix_counter`, once any switch in progress has
+/// finished.
+#[cfg(feature = "std")]
+#[cold]
+#[inline]
+fn switched_away(counter: &AtomicUsize) -> bool {
+    // Pairs with the parking CAS, so a parked counter means `SWITCHING` is seen set, or cleared
+    // with the new counter in place.
+    fence(Ordering::Acquire);
+    while SWITCHING.load(Ordering::Acquire) {
+        core::hint::spin_loop();
+    }
+    !ptr::eq(counter, global_counter())
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+fn switched_away(_counter: &AtomicUsize) -> bool {
+    false
+}
+
+// Without `std` there's no thread local storage to keep a block in, so every ID comes from one
+// prefix shared by all callers, claimed the first time it's needed, and its offsets are handed out
+// with a single atomic operation each.
+#[cfg(not(feature = "std"))]
+const UNCLAIMED: usize = usize::MAX;
+
+#[cfg(not(feature = "std"))]
+static GLOBAL_PREFIX: AtomicUsize = AtomicUsize::new(UNCLAIMED);
+
+#[cfg(not(feature = "std"))]
+static GLOBAL_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(not(feature = "std"))]
+#[inline]
+fn try_next_id() -> Option<ProcessUniqueId> {
+    let mut prefix = GLOBAL_PREFIX.load(Ordering::Relaxed);
+    if prefix == UNCLAIMED {
+        // Callers racing to claim the prefix all claim one, but only the first is used. (The
+        // offsets keep counting up across prefixes, so the last prefix, which looks unclaimed,
+        // doesn't cause duplicates either.)
+        let claimed = try_next_global()?;
+        prefix = match GLOBAL_PREFIX.compare_exchange(
+            UNCLAIMED,
+            claimed,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => claimed,
+            Err(actual) => actual,
+        };
+    }
+    let offset = GLOBAL_OFFSET
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |offset| {
+            offset.checked_add(1)
+        })
+        .ok()?;
+    Some(ProcessUniqueId { prefix, offset })
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn try_next_id() -> Option<ProcessUniqueId> {
+    try_with_local(|local| try_next_in(local, try_next_block_lock_free)).flatten()
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+fn next_id() -> ProcessUniqueId {
+    try_next_id().unwrap_or_else(|| exhausted())
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn next_id() -> ProcessUniqueId {
+    with_local(next_in)
+}
+
+// The numbers of allocated prefixes at which to log a warning, sorted. `None` until configured,
+// meaning half and 90% of the prefix space.
+#[cfg(feature = "tracing")]
+static PREFIX_WARNINGS: Mutex<Option<Vec<usize>>> = Mutex::new(None);
+
+#[cfg(feature = "tracing")]
+#[cold]
+fn check_prefix_thresholds(allocated: usize) {
+    let thresholds = PREFIX_WARNINGS.lock().unwrap_or_else(|e| e.into_inner());
+    let crossed = match &*thresholds {
+        Some(thresholds) => thresholds.binary_search(&allocated).is_ok(),
+        None => allocated == usize::MAX / 2 || allocated == usize::MAX / 10 * 9,
+    };
+    if crossed {
+        tracing::warn!(
+            allocated,
+            remaining = usize::MAX - allocated,
+            "ProcessUniqueId prefix space filling up"
         );
+    }
+}
+
+/// Called when the current thread hands out the last ID of `prefix`.
+#[cfg(feature = "tracing")]
+#[cold]
+fn prefix_used_up(prefix: usize) {
+    tracing::warn!(
+        prefix,
+        "ProcessUniqueId prefix used up, moving on to a new one"
+    );
+}
+
+/// The number of offsets a thread claims at a time unless configured otherwise.
+#[cfg(feature = "std")]
+const DEFAULT_BLOCK_SIZE: u64 = 1 << 32;
+
+#[cfg(feature = "std")]
+static BLOCK_SIZE: AtomicU64 = AtomicU64::new(DEFAULT_BLOCK_SIZE);
+
+// The prefix blocks are currently being carved out of, and the first offset not yet claimed.
+// `None` when there is no such prefix yet, or it has been used up. Only touched when a thread
+// needs a new block, so a mutex is fine.
+#[cfg(feature = "std")]
+pub(crate) static SHARED_PREFIX: CachePadded<Mutex<Option<(usize, u64)>>> =
+    CachePadded(Mutex::new(None));
+
+/// A range of offsets claimed by a thread: `next` is the next ID to hand out, `last` the offset
+/// of the last ID in the block.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) struct Block {
+    next: ProcessUniqueId,
+    last: u64,
+}
+
+#[cfg(feature = "std")]
+impl Block {
+    /// The number of IDs left in the block, saturating at `u64::MAX`.
+    pub(crate) fn remaining(&self) -> u64 {
+        if self.next.offset > self.last {
+            0
+        } else {
+            (self.last - self.next.offset).saturating_add(1)
+        }
+    }
+
+    /// The prefix of the IDs in the block, or `None` if it has none left.
+    pub(crate) fn prefix(&self) -> Option<usize> {
+        if self.next.offset > self.last {
+            None
+        } else {
+            Some(self.next.prefix)
+        }
+    }
+}
+
+/// Carves a block of `size` (at least one) offsets out of `shared`, moving on to a new prefix if
+/// it's missing. The last block of a prefix may be short.
+#[cfg(feature = "std")]
+fn claim_block(shared: &mut Option<(usize, u64)>, size: u64) -> Block {
+    try_claim_block(shared, size).unwrap_or_else(|| exhausted())
+}
+
+#[cfg(feature = "std")]
+fn try_claim_block(shared: &mut Option<(usize, u64)>, size: u64) -> Option<Block> {
+    let (prefix, first) = match *shared {
+        Some(current) => current,
+        None => (try_next_global()?, 0),
+    };
+    let last = first.saturating_add(size.max(1) - 1);
+    *shared = if last == u64::MAX {
+        None
+    } else {
+        Some((prefix, last + 1))
+    };
+    Some(Block {
+        next: ProcessUniqueId {
+            prefix,
+            offset: first,
+        },
+        last,
+    })
+}
+
+/// A block with no IDs left, which makes the thread claim a new block on its next ID.
+#[cfg(feature = "std")]
+pub(crate) const EMPTY_BLOCK: Block = Block {
+    next: ProcessUniqueId {
+        prefix: 0,
+        offset: 1,
+    },
+    last: 0,
+};
+
+#[cfg(feature = "std")]
+fn next_block() -> Block {
+    try_next_block().unwrap_or_else(|| exhausted())
+}
+
+#[cfg(feature = "std")]
+fn try_next_block() -> Option<Block> {
+    reclaim_on_exit();
+    claim_with(|| {
+        let mut shared = SHARED_PREFIX.lock().unwrap_or_else(|e| e.into_inner());
+        let size = BLOCK_SIZE.load(Ordering::Relaxed);
+        let mut reclaimed = RECLAIMED.lock().unwrap_or_else(|e| e.into_inner());
+        take_reclaimed(&mut reclaimed, size).or_else(|| try_claim_block(&mut shared, size))
+    })
+}
+
+// What was left of the blocks of threads that have exited, handed out again before any new
+// blocks are carved out of the shared prefix. Only locked while holding `SHARED_PREFIX`, so
+// whatever resets the shared prefix can reset this along with it.
+#[cfg(feature = "std")]
+pub(crate) static RECLAIMED: Mutex<Vec<Block>> = Mutex::new(Vec::new());
+
+/// Takes up to `size` (at least one) IDs from the front of a reclaimed block, putting the rest
+/// back.
+#[cfg(feature = "std")]
+fn take_reclaimed(reclaimed: &mut Vec<Block>, size: u64) -> Option<Block> {
+    let block = reclaimed.pop()?;
+    let size = size.max(1);
+    if block.remaining() <= size {
+        return Some(block);
+    }
+    let last = block.next.offset + (size - 1);
+    reclaimed.push(Block {
+        next: ProcessUniqueId {
+            prefix: block.next.prefix,
+            offset: last + 1,
+        },
+        last: block.last,
+    });
+    Some(Block {
+        next: block.next,
+        last,
+    })
+}
+
+/// Moves what's left of `local`'s block to `reclaimed`.
+#[cfg(feature = "std")]
+fn reclaim(local: &LocalBlock, reclaimed: &mut Vec<Block>) {
+    let block = local.get();
+    local.set(EMPTY_BLOCK);
+    if block.remaining() > 0 {
+        reclaimed.push(block);
+    }
+}
+
+// Returns the thread's block to `RECLAIMED` when the thread exits. Kept apart from the block
+// itself so that `new()` never has to register a destructor: threads only do so when they claim
+// a block from the shared state.
+#[cfg(feature = "std")]
+struct ReclaimOnExit;
+
+#[cfg(feature = "std")]
+impl Drop for ReclaimOnExit {
+    fn drop(&mut self) {
+        let _shared = SHARED_PREFIX.lock().unwrap_or_else(|e| e.into_inner());
+        let mut reclaimed = RECLAIMED.lock().unwrap_or_else(|e| e.into_inner());
+        try_with_local(|local| reclaim(local, &mut reclaimed));
+    }
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static RECLAIM_ON_EXIT: ReclaimOnExit = const { ReclaimOnExit };
+}
+
+/// Makes sure the current thread returns its block when it exits. Does nothing during thread
+/// teardown, once that's no longer possible.
+#[cfg(feature = "std")]
+#[inline]
+fn reclaim_on_exit() {
+    let _ = RECLAIM_ON_EXIT.try_with(|_| ());
+}
+
+/// Claims a whole prefix as a block, without taking the shared state's mutex, so real-time
+/// threads never block (or panic) on it. Used by the `try_` constructors.
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn try_next_block_lock_free() -> Option<Block> {
+    claim_with(|| {
+        Some(Block {
+            next: ProcessUniqueId {
+                prefix: try_next_global()?,
+                offset: 0,
+            },
+            last: u64::MAX,
+        })
+    })
+}
+
+/// Claims a block with `claim`, unless replaying, and records it.
+#[cfg(feature = "std")]
+#[inline(always)]
+fn claim_with(claim: impl FnOnce() -> Option<Block>) -> Option<Block> {
+    #[cfg(feature = "replay")]
+    if let Some((prefix, first, last)) = crate::replay::replayed_block() {
+        let block = Block {
+            next: ProcessUniqueId {
+                prefix,
+                offset: first,
+            },
+            last,
+        };
+        on_claimed(&block);
+        return Some(block);
+    }
+    let block = claim()?;
+    #[cfg(feature = "replay")]
+    crate::replay::record_block(block.next.prefix, block.next.offset, block.last);
+    #[cfg(feature = "audit")]
+    crate::audit::record(block.next, block.last);
+    on_claimed(&block);
+    Some(block)
+}
+
+/// Counts `block` towards the current thread's [`thread_stats`](crate::thread_stats).
+#[cfg(feature = "std")]
+#[inline(always)]
+fn on_claimed(block: &Block) {
+    let len = u128::from(block.last - block.next.offset) + 1;
+    crate::thread_stats::on_block_claimed(block.next.prefix, len);
+}
+
+// A thread's block, in a thread local or an `IdHandle`.
+//
+// NOTE: We could use a Cell (not unsafe) but this is slightly faster. The `safe` feature does
+// anyways.
+#[cfg(feature = "std")]
+pub(crate) struct LocalBlock {
+    #[cfg(not(feature = "safe"))]
+    block: UnsafeCell<Block>,
+    #[cfg(feature = "safe")]
+    block: Cell<Block>,
+    // The restore generation the block was claimed in; see `snapshot::restore_state`.
+    #[cfg(feature = "snapshot")]
+    generation: Cell<u64>,
+}
+
+#[cfg(feature = "std")]
+impl LocalBlock {
+    #[inline]
+    pub(crate) const fn new(block: Block) -> Self {
+        LocalBlock {
+            #[cfg(not(feature = "safe"))]
+            block: UnsafeCell::new(block),
+            #[cfg(feature = "safe")]
+            block: Cell::new(block),
+            #[cfg(feature = "snapshot")]
+            generation: Cell::new(0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get(&self) -> Block {
+        // A block claimed before the process image was restored may be handed out by other
+        // restores of the same image too, so drop it and claim a new one.
+        #[cfg(feature = "snapshot")]
+        if crate::snapshot::is_stale(&self.generation) {
+            return EMPTY_BLOCK;
+        }
+        // SAFETY: `UnsafeCell` makes the block `!Sync`, so only one thread can access it at a
+        // time, and we never hand out references into it.
+        #[cfg(not(feature = "safe"))]
+        let block = unsafe { *self.block.get() };
+        #[cfg(feature = "safe")]
+        let block = self.block.get();
+        block
+    }
+
+    #[inline]
+    pub(crate) fn set(&self, block: Block) {
+        // SAFETY: as above.
+        #[cfg(not(feature = "safe"))]
+        unsafe {
+            *self.block.get() = block
+        }
+        #[cfg(feature = "safe")]
+        self.block.set(block);
+    }
+}
+
+// Starting out empty (rather than claiming a block on first access) lets the thread local be
+// const-initialized, so accessing it never has to check whether it has been initialized yet, and
+// `LocalBlock` has no destructor to register.
+#[cfg(all(feature = "std", not(feature = "nightly")))]
+thread_local! {
+    static NEXT_LOCAL_UNIQUE_ID: LocalBlock = const { LocalBlock::new(EMPTY_BLOCK) }
+}
+
+// With `#[thread_local]`, the access compiles down to a plain TLS-relative load.
+#[cfg(all(feature = "std", feature = "nightly"))]
+#[thread_local]
+static NEXT_LOCAL_UNIQUE_ID: LocalBlock = LocalBlock::new(EMPTY_BLOCK);
+
+#[cfg(all(feature = "std", not(feature = "nightly")))]
+#[inline(always)]
+pub(crate) fn with_local<R>(f: impl FnOnce(&LocalBlock) -> R) -> R {
+    NEXT_LOCAL_UNIQUE_ID.with(f)
+}
+
+#[cfg(all(feature = "std", feature = "nightly"))]
+#[inline(always)]
+pub(crate) fn with_local<R>(f: impl FnOnce(&LocalBlock) -> R) -> R {
+    f(&NEXT_LOCAL_UNIQUE_ID)
+}
+
+/// Like [`with_local`], but returns `None` instead of panicking if the thread local has already
+/// been destroyed (i.e., when called from another thread local's destructor).
+#[cfg(all(feature = "std", not(feature = "nightly")))]
+#[inline(always)]
+fn try_with_local<R>(f: impl FnOnce(&LocalBlock) -> R) -> Option<R> {
+    NEXT_LOCAL_UNIQUE_ID.try_with(f).ok()
+}
+
+#[cfg(all(feature = "std", feature = "nightly"))]
+#[inline(always)]
+fn try_with_local<R>(f: impl FnOnce(&LocalBlock) -> R) -> Option<R> {
+    Some(f(&NEXT_LOCAL_UNIQUE_ID))
+}
+
+/// Hands out the next ID in `unique_id`'s block, claiming a new block if it's empty.
+#[cfg(feature = "std")]
+#[inline(always)]
+pub(crate) fn next_in(unique_id: &LocalBlock) -> ProcessUniqueId {
+    try_next_in(unique_id, try_next_block).unwrap_or_else(|| exhausted())
+}
+
+/// Like [`next_in`], but claims new blocks with `refill` and returns `None` if it fails.
+#[cfg(feature = "std")]
+#[inline(always)]
+pub(crate) fn try_next_in<F>(unique_id: &LocalBlock, refill: F) -> Option<ProcessUniqueId>
+where
+    F: FnOnce() -> Option<Block>,
+{
+    // NOTE: Checked ops are slower than manually checking... (WTF?)
+    let block = unique_id.get();
+    let next_unique_id = block.next;
+    // Also true for an empty block, so a fresh thread takes the same single branch.
+    if next_unique_id.offset >= block.last {
+        return take_last(unique_id, block, refill);
+    }
+    unique_id.set(Block {
+        next: ProcessUniqueId {
+            prefix: next_unique_id.prefix,
+            offset: next_unique_id.offset + 1,
+        },
+        last: block.last,
+    });
+    Some(next_unique_id)
+}
+
+/// Hands out the last ID in `block`, or claims a new block first if `block` is empty.
+#[cfg(feature = "std")]
+#[cold]
+fn take_last<F>(local: &LocalBlock, block: Block, refill: F) -> Option<ProcessUniqueId>
+where
+    F: FnOnce() -> Option<Block>,
+{
+    let block = if block.next.offset > block.last {
+        refill()?
+    } else {
+        block
+    };
+    #[cfg(feature = "tracing")]
+    if block.next.offset == u64::MAX {
+        prefix_used_up(block.next.prefix);
+    }
+    local.set(if block.next.offset == block.last {
+        EMPTY_BLOCK
+    } else {
+        Block {
+            next: ProcessUniqueId {
+                prefix: block.next.prefix,
+                offset: block.next.offset + 1,
+            },
+            last: block.last,
+        }
+    });
+    Some(block.next)
+}
+
+/// Process unique IDs are guaranteed to be unique within the current process, for the lifetime of
+/// the current process.
+///
+/// 1. ID creation should be highly performant even on highly concurrent systems. It's MUCH faster
+///    than using random/time based IDs (but, on the other hand, only unique within a process).
+/// 2. While this crate can run out of process unique IDs, this is very unlikely assuming a sane
+///    threading model and will panic rather than potentially reusing unique IDs.
+///
+/// # Limits
+///
+/// The unique ID's are `sizeof(usize) + 64` bits wide: a `usize` global prefix and a 64bit offset.
+/// Each thread that calls `new()` claims a block of offsets within the current prefix (2^32 by
+/// default, see [`set_block_size`](ProcessUniqueId::set_block_size)) and only goes back to the
+/// shared state when the block runs out. A prefix is retired once all 2^64 of its offsets have
+/// been handed out in blocks, so the only way to run out of IDs is to claim
+/// `usize::MAX * (2^64 / block size)` blocks. With the default block size, that's about 18
+/// quintillion threads even on a 32bit system.
+///
+/// When a thread exits, whatever is left of its block is handed to the next thread that needs a
+/// block, so short-lived threads only use up the IDs they actually created. Blocks claimed by
+/// [`try_new`](ProcessUniqueId::try_new) (which never registers a thread local destructor) or for
+/// an [`IdHandle`](crate::IdHandle) aren't handed back.
+///
+/// # Memory layout
+///
+/// `#[repr(C)]`: the prefix (a `usize`) followed by the offset (a `u64`), in native byte order,
+/// aligned like a `u64`; in C, `struct { uintptr_t prefix; uint64_t offset; }`. On 64bit targets
+/// that's 16 bytes with no padding. On 32bit targets the offset starts at byte 4 or 8, depending
+/// on how the target aligns a `u64`. The layout is checked at compile time and is part of the
+/// API, so IDs can be placed in shared memory and FFI structs.
+///
+/// The `bytemuck` and `zerocopy` implementations are only available on 64bit targets, where
+/// there's no padding between the two. For bytes that mean the same thing on every platform, use
+/// [`to_bytes`](ProcessUniqueId::to_bytes).
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize))]
+#[cfg_attr(
+    all(feature = "bytemuck", target_pointer_width = "64"),
+    derive(bytemuck::Pod, bytemuck::Zeroable)
+)]
+#[cfg_attr(
+    all(feature = "zerocopy", target_pointer_width = "64"),
+    derive(
+        zerocopy::FromBytes,
+        zerocopy::IntoBytes,
+        zerocopy::Immutable,
+        zerocopy::KnownLayout
+    )
+)]
+#[cfg_attr(feature = "bitcode", derive(bitcode::Encode, bitcode::Decode))]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_ecs::component::Component, bevy_reflect::Reflect),
+    reflect(opaque, Component, Debug, PartialEq, Hash, Clone)
+)]
+#[repr(C)]
+pub struct ProcessUniqueId {
+    pub(crate) prefix: usize,
+    pub(crate) offset: u64,
+}
+
+const _: () = {
+    use core::mem::{align_of, offset_of, size_of};
+
+    assert!(offset_of!(ProcessUniqueId, prefix) == 0);
+    let offset = if align_of::<u64>() > size_of::<usize>() {
+        align_of::<u64>()
+    } else {
+        size_of::<usize>()
+    };
+    assert!(offset_of!(ProcessUniqueId, offset) == offset);
+    assert!(size_of::<ProcessUniqueId>() == offset + 8);
+    assert!(align_of::<ProcessUniqueId>() == align_of::<u64>());
+    #[cfg(target_pointer_width = "64")]
+    assert!(size_of::<ProcessUniqueId>() == 16);
+};
+
+/// The URL-safe base64 alphabet (RFC 4648 §5).
+#[cfg(feature = "std")]
+const BASE64_URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// The length of [`ProcessUniqueId::to_base64_url`]: 128 bits at 6 bits per character.
+const BASE64_URL_LEN: usize = 22;
+
+/// Displays the ID as `puid-<prefix>-<offset>` (hex).
+///
+/// Like every ID type in this crate, honors the width, fill, alignment, and precision flags as a
+/// string would, so `{:>24}` lines IDs up in columns. The `0` flag pads the offset with zeros
+/// instead: `{:012}` gives e.g. `puid-0-00002a`.
+impl fmt::Display for ProcessUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(
+            f,
+            format_args!("puid-{:x}-", self.prefix),
+            format_args!("{:x}", self.offset),
+        )
+    }
+}
+
+/// Logs the same `puid-<prefix>-<offset>` form as `Display`, but the formatting happens on the host.
+#[cfg(feature = "defmt")]
+impl defmt::Format for ProcessUniqueId {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "puid-{=usize:x}-{=u64:x}", self.prefix, self.offset)
+    }
+}
+
+/// An error returned when parsing an ID fails, saying where and what was wrong.
+///
+/// Its `Display` form is written for the people who typed or pasted the ID, so it can be shown to
+/// them as is:
+///
+/// ```
+/// use snowflake::ProcessUniqueId;
+///
+/// let err = "puid-1-2x".parse::<ProcessUniqueId>().unwrap_err();
+/// assert_eq!(err.offset(), 8);
+/// assert_eq!(err.expected(), "a hex digit");
+/// assert_eq!(err.found(), Some('x'));
+/// assert_eq!(err.to_string(), "invalid ID at byte 8: expected a hex digit, found 'x'");
+///
+/// let err = "puid-1".parse::<ProcessUniqueId>().unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "invalid ID at byte 6: expected a hex digit or `-`, found the end of the input"
+/// );
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseIdError {
+    offset: usize,
+    expected: &'static str,
+    found: Found,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Found {
+    Char(char),
+    End,
+    // The input as a whole was wrong (e.g., not text at all), not something at a position in it.
+    #[cfg_attr(not(any(feature = "dynamodb", feature = "http")), allow(dead_code))]
+    Nothing,
+}
+
+impl ParseIdError {
+    /// An error at byte `offset` of `input`, where `expected` should have been.
+    pub(crate) fn at(input: &str, offset: usize, expected: &'static str) -> Self {
+        let mut start = offset.min(input.len());
+        while !input.is_char_boundary(start) {
+            start -= 1;
+        }
+        ParseIdError {
+            offset,
+            expected,
+            found: input[start..]
+                .chars()
+                .next()
+                .map_or(Found::End, Found::Char),
+        }
+    }
+
+    /// An error about the input as a whole, which should have been `expected`.
+    #[cfg_attr(not(any(feature = "dynamodb", feature = "http")), allow(dead_code))]
+    pub(crate) fn invalid(expected: &'static str) -> Self {
+        ParseIdError {
+            offset: 0,
+            expected,
+            found: Found::Nothing,
+        }
+    }
+
+    /// The same error, for input that started `by` bytes further on in a larger string.
+    #[cfg_attr(not(feature = "trace-context"), allow(dead_code))]
+    pub(crate) fn shifted(mut self, by: usize) -> Self {
+        self.offset += by;
+        self
+    }
+
+    /// The byte offset in the input at which parsing failed.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// What was expected at [`offset`](ParseIdError::offset), e.g. "a hex digit".
+    #[inline]
+    pub fn expected(&self) -> &'static str {
+        self.expected
+    }
+
+    /// The character found at [`offset`](ParseIdError::offset) instead, or `None` if the input
+    /// ended there (or the input as a whole was wrong).
+    #[inline]
+    pub fn found(&self) -> Option<char> {
+        match self.found {
+            Found::Char(c) => Some(c),
+            Found::End | Found::Nothing => None,
+        }
+    }
+}
+
+impl fmt::Display for ParseIdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.found {
+            Found::Char(c) => write!(
+                f,
+                "invalid ID at byte {}: expected {}, found {:?}",
+                self.offset, self.expected, c
+            ),
+            Found::End => write!(
+                f,
+                "invalid ID at byte {}: expected {}, found the end of the input",
+                self.offset, self.expected
+            ),
+            Found::Nothing => write!(f, "invalid ID: expected {}", self.expected),
+        }
+    }
+}
+
+impl Error for ParseIdError {}
+
+/// An error returned by [`ProcessUniqueId::try_new`] and [`IdHandle::try_next`] when no ID can be
+/// created.
+///
+/// [`IdHandle::try_next`]: crate::IdHandle::try_next
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct IdUnavailable {
+    _private: (),
+}
+
+impl IdUnavailable {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        IdUnavailable { _private: () }
+    }
+}
+
+impl fmt::Display for IdUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("no more unique IDs available")
+    }
+}
+
+impl Error for IdUnavailable {}
+
+/// The type of the counter prefixes are claimed from, shared between copies of this crate by
+/// [`ProcessUniqueId::use_prefix_counter`]: `std::sync::atomic::AtomicUsize`, or
+/// `portable_atomic::AtomicUsize` with the `portable-atomic` feature.
+#[cfg(feature = "std")]
+pub type AtomicPrefixCounter = AtomicUsize;
+
+/// An error returned by [`ProcessUniqueId::use_prefix_counter`] when this copy of the crate has
+/// already claimed prefixes from another counter.
+#[cfg(feature = "std")]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PrefixCounterInUse {
+    _private: (),
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for PrefixCounterInUse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("prefixes were already claimed from another counter")
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for PrefixCounterInUse {}
+
+/// An error returned by [`ProcessUniqueId::parse_lenient`], saying what was wrong with the input.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum LenientParseError {
+    /// There was nothing but whitespace and brackets.
+    Empty,
+    /// The ID is tagged as something other than a process unique ID (e.g., `spuid-1-2`).
+    WrongTag,
+    /// The input isn't of the form `[puid-]<prefix>-<offset>` or `puid:<integer>`.
+    Malformed,
+    /// A field has a character that isn't a digit.
+    InvalidDigit,
+    /// A field is too large: the prefix doesn't fit in a `usize` or the offset in a `u64`.
+    OutOfRange,
+}
+
+impl fmt::Display for LenientParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            LenientParseError::Empty => "no unique ID given",
+            LenientParseError::WrongTag => "not a process unique ID (expected a `puid` tag)",
+            LenientParseError::Malformed => {
+                "expected a unique ID of the form `puid-<prefix>-<offset>` or `puid:<integer>`"
+            }
+            LenientParseError::InvalidDigit => "invalid digit in unique ID",
+            LenientParseError::OutOfRange => "unique ID field out of range",
+        })
+    }
+}
+
+impl Error for LenientParseError {}
+
+/// Strips whitespace and any number of matching brackets or quotes from around `s`.
+fn strip_wrapping(mut s: &str) -> &str {
+    const PAIRS: [(char, char); 7] = [
+        ('(', ')'),
+        ('[', ']'),
+        ('{', '}'),
+        ('<', '>'),
+        ('"', '"'),
+        ('\'', '\''),
+        ('`', '`'),
+    ];
+    loop {
+        s = s.trim();
+        let inner = PAIRS.iter().find_map(|&(open, close)| {
+            s.strip_prefix(open)
+                .and_then(|rest| rest.strip_suffix(close))
+        });
+        match inner {
+            Some(inner) => s = inner,
+            None => return s,
+        }
+    }
+}
+
+/// Parses an integer field of a leniently parsed ID, in `radix`.
+fn parse_lenient_field(s: &str, radix: u32) -> Result<u128, LenientParseError> {
+    if s.is_empty() {
+        return Err(LenientParseError::Malformed);
+    }
+    if !s.chars().all(|c| c.is_digit(radix)) {
+        return Err(LenientParseError::InvalidDigit);
+    }
+    u128::from_str_radix(s, radix).map_err(|_| LenientParseError::OutOfRange)
+}
+
+/// Checks that `input` is `len` bytes long, `expected` being what should have come after a short
+/// input.
+pub(crate) fn expect_len(
+    input: &str,
+    len: usize,
+    expected: &'static str,
+) -> Result<(), ParseIdError> {
+    if input.len() < len {
+        Err(ParseIdError::at(input, input.len(), expected))
+    } else if input.len() > len {
+        Err(ParseIdError::at(input, len, "the end of the input"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that `input` starts with `tag`, returning the offset after it. `expected` describes the
+/// tag for the error.
+pub(crate) fn expect_tag(
+    input: &str,
+    tag: &str,
+    expected: &'static str,
+) -> Result<usize, ParseIdError> {
+    if input.starts_with(tag) {
+        return Ok(tag.len());
+    }
+    let matching = input
+        .bytes()
+        .zip(tag.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    Err(ParseIdError::at(input, matching, expected))
+}
+
+/// Parses the hex field starting at `start` and the `-` after it, returning the field and the
+/// offset after the dash.
+pub(crate) fn hex_field_to_dash(input: &str, start: usize) -> Result<(u64, usize), ParseIdError> {
+    let digits = input[start..]
+        .bytes()
+        .take_while(u8::is_ascii_hexdigit)
+        .count();
+    let end = start + digits;
+    match input.as_bytes().get(end) {
+        Some(b'-') if digits > 0 => Ok((hex_field(input, start, end)?, end + 1)),
+        _ if digits == 0 => Err(ParseIdError::at(input, end, "a hex digit")),
+        _ => Err(ParseIdError::at(input, end, "a hex digit or `-`")),
+    }
+}
+
+/// Parses `input[start..end]`, a non-empty field of hex digits (without the sign
+/// `from_str_radix` accepts), that has to fit in 64 bits.
+pub(crate) fn hex_field(input: &str, start: usize, end: usize) -> Result<u64, ParseIdError> {
+    let field = &input[start..end];
+    if let Some(i) = field.bytes().position(|b| !b.is_ascii_hexdigit()) {
+        return Err(ParseIdError::at(input, start + i, "a hex digit"));
+    }
+    if field.is_empty() {
+        return Err(ParseIdError::at(input, start, "a hex digit"));
+    }
+    u64::from_str_radix(field, 16)
+        .map_err(|_| ParseIdError::at(input, start, "a value that fits in 64 bits"))
+}
+
+/// Parses `input`, a non-empty string of decimal digits that has to fit in 64 bits.
+pub(crate) fn decimal_u64(input: &str) -> Result<u64, ParseIdError> {
+    if let Some(i) = input.bytes().position(|b| !b.is_ascii_digit()) {
+        return Err(ParseIdError::at(input, i, "a decimal digit"));
+    }
+    if input.is_empty() {
+        return Err(ParseIdError::at(input, 0, "a decimal digit"));
+    }
+    input
+        .parse()
+        .map_err(|_| ParseIdError::at(input, 0, "a value that fits in 64 bits"))
+}
+
+/// Parses `input[start..end]` as a hex field that fits in a `usize`.
+pub(crate) fn usize_hex_field(
+    input: &str,
+    start: usize,
+    end: usize,
+) -> Result<usize, ParseIdError> {
+    usize::try_from(hex_field(input, start, end)?)
+        .map_err(|_| ParseIdError::at(input, start, "a value that fits in a usize"))
+}
+
+/// Parses the `<tag>-<prefix>-<offset>` form (prefix and offset in hex) shared by the
+/// prefix/offset ID types, with `tag` including the first dash (e.g. `puid-`). Returns the
+/// prefix and offset, and where the prefix starts.
+pub(crate) fn parse_tagged(
+    s: &str,
+    tag: &str,
+    expected: &'static str,
+) -> Result<(u64, u64, usize), ParseIdError> {
+    let start = expect_tag(s, tag, expected)?;
+    let (prefix, rest) = hex_field_to_dash(s, start)?;
+    let offset = hex_field(s, rest, s.len())?;
+    Ok((prefix, offset, start))
+}
+
+/// Parses the `puid-<prefix>-<offset>` form produced by `Display`.
+impl FromStr for ProcessUniqueId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        let (prefix, offset, start) = parse_tagged(s, "puid-", "`puid-`")?;
+        Ok(ProcessUniqueId {
+            prefix: usize::try_from(prefix)
+                .map_err(|_| ParseIdError::at(s, start, "a prefix that fits in a usize"))?,
+            offset,
+        })
+    }
+}
+
+impl ProcessUniqueId {
+    /// Parses an ID the way a person might have copied it, for command line tools and admin
+    /// interfaces. [`FromStr`] only accepts the exact `Display` form.
+    ///
+    /// On top of that form, this accepts:
+    ///
+    /// - surrounding whitespace, brackets, and quotes: `[puid-1-2]`, `"puid-1-2"`;
+    /// - the tag in any case, or no tag at all: `PUID-1-2`, `1-2`;
+    /// - the packed [`to_u128`](ProcessUniqueId::to_u128) form, in decimal or `0x`-prefixed hex:
+    ///   `puid:18446744073709551618`, `puid:0x10000000000000002`.
+    ///
+    /// ```
+    /// use snowflake::{LenientParseError, ProcessUniqueId};
+    ///
+    /// let id: ProcessUniqueId = "puid-1-2a".parse().unwrap();
+    /// assert_eq!(ProcessUniqueId::parse_lenient(" [PUID-1-2A] "), Ok(id));
+    /// assert_eq!(ProcessUniqueId::parse_lenient("1-2a"), Ok(id));
+    /// assert_eq!(ProcessUniqueId::parse_lenient("puid:0x1000000000000002a"), Ok(id));
+    /// assert_eq!(
+    ///     ProcessUniqueId::parse_lenient("spuid-1-2a"),
+    ///     Err(LenientParseError::WrongTag)
+    /// );
+    /// ```
+    pub fn parse_lenient(s: &str) -> Result<Self, LenientParseError> {
+        let s = strip_wrapping(s);
+        if s.is_empty() {
+            return Err(LenientParseError::Empty);
+        }
+
+        let packed = s
+            .get(..5)
+            .filter(|tag| tag.eq_ignore_ascii_case("puid:"))
+            .map(|_| &s[5..]);
+        if let Some(packed) = packed {
+            let value = match packed
+                .strip_prefix("0x")
+                .or_else(|| packed.strip_prefix("0X"))
+            {
+                Some(hex) => parse_lenient_field(hex, 16)?,
+                None => parse_lenient_field(packed, 10)?,
+            };
+            return ProcessUniqueId::from_u128(value).ok_or(LenientParseError::OutOfRange);
+        }
+
+        let mut fields = s.split('-');
+        let (prefix, offset) = match (fields.next(), fields.next(), fields.next(), fields.next()) {
+            (Some(tag), Some(prefix), Some(offset), None) => {
+                if !tag.eq_ignore_ascii_case("puid") {
+                    return Err(LenientParseError::WrongTag);
+                }
+                (prefix, offset)
+            }
+            (Some(prefix), Some(offset), None, None) => (prefix, offset),
+            _ => return Err(LenientParseError::Malformed),
+        };
+        let prefix = parse_lenient_field(prefix, 16)?;
+        let offset = parse_lenient_field(offset, 16)?;
+        Ok(ProcessUniqueId {
+            prefix: usize::try_from(prefix).map_err(|_| LenientParseError::OutOfRange)?,
+            offset: u64::try_from(offset).map_err(|_| LenientParseError::OutOfRange)?,
+        })
+    }
+
+    /// Packs the ID into a `u128`: the prefix in the high 64 bits, the offset in the low 64 bits.
+    ///
+    /// The packed form sorts the same way as the ID itself.
+    #[inline]
+    pub fn to_u128(self) -> u128 {
+        ((self.prefix as u128) << 64) | u128::from(self.offset)
+    }
+
+    /// Unpacks an ID packed by [`to_u128`](ProcessUniqueId::to_u128).
+    ///
+    /// Returns `None` if the prefix doesn't fit in a `usize` (i.e., the ID can't have been created
+    /// by a process on this platform).
+    ///
+    /// Note: an ID created in another process is *not* unique in this one. Only unpack IDs that
+    /// were created by the current process.
+    #[inline]
+    pub fn from_u128(value: u128) -> Option<Self> {
+        let prefix = (value >> 64) as u64;
+        if prefix > usize::MAX as u64 {
+            return None;
+        }
+        Some(ProcessUniqueId {
+            prefix: prefix as usize,
+            offset: value as u64,
+        })
+    }
+
+    /// Writes the ID as 16 bytes: the prefix as a big-endian `u64` followed by the offset as a
+    /// big-endian `u64` (i.e., [`to_u128`](ProcessUniqueId::to_u128) in big-endian).
+    ///
+    /// The layout is the same on every platform, and the bytes sort the same way as the IDs.
+    #[inline]
+    pub fn to_bytes(self) -> [u8; 16] {
+        self.to_u128().to_be_bytes()
+    }
+
+    /// Reads an ID written by [`to_bytes`](ProcessUniqueId::to_bytes).
+    ///
+    /// Fails if the prefix doesn't fit in a `usize`. As with `from_u128`, only IDs created by the
+    /// current process are unique in it.
+    #[inline]
+    pub fn from_bytes(bytes: [u8; 16]) -> Result<Self, IdOutOfRange> {
+        ProcessUniqueId::from_u128(u128::from_be_bytes(bytes)).ok_or_else(IdOutOfRange::new)
+    }
+
+    /// Maps this ID to a version 8 (custom) UUID.
+    ///
+    /// The prefix and offset are packed into the 122 bits UUIDv8 leaves free, so UUIDs sort the
+    /// same way as the IDs they came from. That leaves 58 bits for the prefix, which takes 2^58
+    /// threads to exhaust; `None` is only returned for IDs that didn't come from this crate's
+    /// generator (e.g., ones built with [`from_u128`](ProcessUniqueId::from_u128)).
+    #[cfg(feature = "uuid")]
+    #[inline]
+    pub fn to_uuid_v8(self) -> Option<uuid::Uuid> {
+        self.to_v8_bits().map(uuid::Uuid::from_u128)
+    }
+
+    /// The bits of [`to_uuid_v8`](ProcessUniqueId::to_uuid_v8)'s UUID.
+    #[cfg(any(feature = "uuid", feature = "windows"))]
+    fn to_v8_bits(self) -> Option<u128> {
+        let packed = self.to_u128();
+        if packed >> UUID_CUSTOM_BITS != 0 {
+            return None;
+        }
+        Some(
+            ((packed >> 74) << 80)
+                | (0x8 << 76)
+                | (((packed >> 62) & 0xfff) << 64)
+                | (0b10 << 62)
+                | (packed & ((1 << 62) - 1)),
+        )
+    }
+
+    /// Recovers an ID from a UUID produced by [`to_uuid_v8`](ProcessUniqueId::to_uuid_v8).
+    ///
+    /// Returns `None` if `uuid` isn't an RFC 4122 version 8 UUID or its prefix doesn't fit in a
+    /// `usize`. As with `from_u128`, only IDs created by the current process are unique in it.
+    #[cfg(feature = "uuid")]
+    #[inline]
+    pub fn from_uuid_v8(uuid: uuid::Uuid) -> Option<Self> {
+        ProcessUniqueId::from_v8_bits(uuid.as_u128())
+    }
+
+    /// Recovers an ID from the bits of a UUID made by `to_v8_bits`.
+    #[cfg(any(feature = "uuid", feature = "windows"))]
+    fn from_v8_bits(uuid: u128) -> Option<Self> {
+        // The version (8) and the RFC 4122 variant (`10`).
+        if (uuid >> 76) & 0xf != 8 || (uuid >> 62) & 0b11 != 0b10 {
+            return None;
+        }
+        let packed =
+            ((uuid >> 80) << 74) | (((uuid >> 64) & 0xfff) << 62) | (uuid & ((1 << 62) - 1));
+        ProcessUniqueId::from_u128(packed)
+    }
+
+    /// Formats the ID as a fixed-width string that sorts byte-wise in the same order as the IDs,
+    /// for use as keys in string-ordered stores (RocksDB, S3, ...).
+    ///
+    /// The prefix and offset are zero-padded to 16 hex digits each: `puid-<prefix>-<offset>`, 38
+    /// bytes in all. The `Display` form doesn't sort correctly (`puid-0-10` comes before
+    /// `puid-0-9`), but `FromStr` parses both.
+    ///
+    /// ```
+    /// use snowflake::ProcessUniqueId;
+    ///
+    /// let a = ProcessUniqueId::new();
+    /// let b = ProcessUniqueId::new();
+    /// assert!(a.to_sortable_string() < b.to_sortable_string());
+    /// assert_eq!(a.to_sortable_string().len(), 38);
+    /// assert_eq!(a.to_sortable_string().parse(), Ok(a));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn to_sortable_string(self) -> String {
+        format!("puid-{:016x}-{:016x}", self.prefix, self.offset)
+    }
+
+    /// Encodes the [`to_u128`](ProcessUniqueId::to_u128) form as 22 characters of unpadded
+    /// URL-safe base64 (RFC 4648 §5), for embedding in URLs, query strings, and JWT claims.
+    ///
+    /// ```
+    /// use snowflake::ProcessUniqueId;
+    ///
+    /// let id = ProcessUniqueId::new();
+    /// let encoded = id.to_base64_url();
+    /// assert_eq!(encoded.len(), 22);
+    /// assert_eq!(ProcessUniqueId::from_base64_url(&encoded), Ok(id));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn to_base64_url(self) -> String {
+        let value = self.to_u128();
+        let mut encoded = String::with_capacity(BASE64_URL_LEN);
+        for i in 0..BASE64_URL_LEN - 1 {
+            let digit = (value >> (122 - 6 * i)) as usize & 0x3f;
+            encoded.push(BASE64_URL[digit] as char);
+        }
+        // The last character holds the two remaining bits.
+        encoded.push(BASE64_URL[(value as usize & 0x3) << 4] as char);
+        encoded
+    }
+
+    /// Decodes the form produced by [`to_base64_url`](ProcessUniqueId::to_base64_url).
+    ///
+    /// Only the canonical encoding is accepted: exactly 22 characters, no padding, and no bits
+    /// set past the end of the 128-bit value. As with `from_u128`, only IDs created by the
+    /// current process are unique in it.
+    pub fn from_base64_url(s: &str) -> Result<Self, ParseIdError> {
+        fn digit(c: u8) -> Option<u128> {
+            let digit = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'-' => 62,
+                b'_' => 63,
+                _ => return None,
+            };
+            Some(u128::from(digit))
+        }
 
-        let old_value = match GLOBAL_COUNTER.compare_exchange(
-            prev,
-            prev + 1,
-            Ordering::Relaxed,
-            Ordering::Relaxed,
-        ) {
-            Ok(value) => value,
-            Err(value) => value,
-        };
+        expect_len(s, BASE64_URL_LEN, "a base64url digit")?;
+        let mut value = 0u128;
+        for (i, c) in s.bytes().enumerate() {
+            let digit = digit(c).ok_or_else(|| ParseIdError::at(s, i, "a base64url digit"))?;
+            if i < BASE64_URL_LEN - 1 {
+                value = value << 6 | digit;
+            } else if digit & 0xf != 0 {
+                // The last character holds the two remaining bits.
+                return Err(ParseIdError::at(s, i, "one of `A`, `Q`, `g`, or `w`"));
+            } else {
+                value = value << 2 | digit >> 4;
+            }
+        }
+        ProcessUniqueId::from_u128(value)
+            .ok_or_else(|| ParseIdError::at(s, 0, "a prefix that fits in a usize"))
+    }
+
+    /// Returns an adapter that displays this ID with `label` in place of `puid`.
+    ///
+    /// ```
+    /// use snowflake::ProcessUniqueId;
+    ///
+    /// let id = ProcessUniqueId::new();
+    /// let job = id.display_as("job").to_string();
+    /// assert!(job.starts_with("job-"));
+    /// assert_eq!(&job[3..], &id.to_string()[4..]);
+    /// ```
+    #[inline]
+    pub fn display_as(self, label: &str) -> DisplayAs<'_> {
+        DisplayAs::new(label, self.prefix as u64, self.offset)
+    }
+
+    /// Create a new unique ID.
+    ///
+    /// **panics** if there are no more unique IDs available. If this happens, go home and
+    /// reevaluate your threading model!
+    ///
+    /// Without the `std` feature there are no thread locals to keep blocks of IDs in: every
+    /// caller takes the next offset of a single shared prefix, with one atomic operation.
+    #[inline]
+    pub fn new() -> Self {
+        next_id()
+    }
+
+    /// Like [`new`](ProcessUniqueId::new), but returns an error instead of panicking when there
+    /// are no more unique IDs available, or when called during thread teardown (from a thread
+    /// local's destructor) after the thread's ID state has been destroyed.
+    ///
+    /// It never blocks either: when the thread's block runs out, it claims a whole prefix as the
+    /// new block with a single atomic operation instead of going through the shared state (a
+    /// mutex), so it's safe to call from real-time threads. With the `no-panic` feature,
+    /// optimized builds check at link time that it never panics.
+    ///
+    /// Without the `std` feature, it fails once the shared prefix's offsets run out.
+    ///
+    /// ```
+    /// use snowflake::ProcessUniqueId;
+    ///
+    /// let a = ProcessUniqueId::try_new().unwrap();
+    /// let b = ProcessUniqueId::try_new().unwrap();
+    /// assert!(a < b);
+    /// ```
+    #[cfg_attr(all(feature = "no-panic", not(debug_assertions)), no_panic::no_panic)]
+    #[inline]
+    pub fn try_new() -> Result<Self, IdUnavailable> {
+        try_next_id().ok_or_else(IdUnavailable::new)
+    }
+
+    /// Claims the current thread's block of IDs now, if it doesn't have one, without creating an
+    /// ID.
+    ///
+    /// A thread's first `new()` goes to the shared state (a mutex) to claim a block; latency
+    /// sensitive threads can call this at startup to get that out of the way of their first
+    /// request.
+    ///
+    /// **panics** under the same conditions as `new()`.
+    #[cfg(feature = "std")]
+    pub fn warm_up() {
+        with_local(|local| {
+            if local.get().remaining() == 0 {
+                local.set(next_block());
+            }
+        });
+    }
+
+    /// Fills `ids` with new unique IDs, in the order `new()` would have created them.
+    ///
+    /// Faster than calling `new()` for each element: the thread's block is only looked up once.
+    ///
+    /// ```
+    /// use snowflake::ProcessUniqueId;
+    ///
+    /// let mut ids = vec![ProcessUniqueId::default(); 1000];
+    /// ProcessUniqueId::new_fill(&mut ids);
+    /// assert!(ids.windows(2).all(|w| w[0] < w[1]));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn new_fill(ids: &mut [ProcessUniqueId]) {
+        fill_local(ids, |slot, id| *slot = id);
+    }
+
+    /// Like [`new_fill`](ProcessUniqueId::new_fill), but for uninitialized memory (e.g., the spare
+    /// capacity of a `Vec`). Every element of `ids` is initialized.
+    ///
+    /// ```
+    /// use snowflake::ProcessUniqueId;
+    ///
+    /// let mut ids = Vec::with_capacity(1000);
+    /// ProcessUniqueId::new_fill_uninit(&mut ids.spare_capacity_mut()[..1000]);
+    /// // SAFETY: the first 1000 elements were just initialized.
+    /// unsafe { ids.set_len(1000) };
+    /// assert!(ids.windows(2).all(|w| w[0] < w[1]));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn new_fill_uninit(ids: &mut [MaybeUninit<ProcessUniqueId>]) {
+        fill_local(ids, |slot, id| {
+            slot.write(id);
+        });
+    }
+
+    /// Sets the number of offsets a thread claims at a time (2^32 by default).
+    ///
+    /// Smaller blocks waste fewer IDs when threads are short lived, at the cost of threads going
+    /// back to the shared state (a mutex) more often. The new size applies to blocks claimed from
+    /// now on; `u64::MAX` restores the old behavior of a prefix per thread.
+    ///
+    /// **panics** if `size` is zero.
+    #[cfg(feature = "std")]
+    pub fn set_block_size(size: u64) {
+        assert!(size > 0, "the block size must not be zero");
+        BLOCK_SIZE.store(size, Ordering::Relaxed);
+    }
 
-        if old_value == prev {
-            return prev;
+    /// Makes the prefix counter wrap around to zero when it runs out, instead of panicking.
+    ///
+    /// **This gives up the uniqueness guarantee.** After wrapping, new IDs reuse prefixes from
+    /// the start of the process, and any ID created since then that's still in use can be handed
+    /// out again. Only turn this on if a duplicate ID is genuinely less harmful than a crash
+    /// (e.g., in a soft real-time system that would rather glitch than stop) and nothing relies on
+    /// IDs being unique for correctness or security.
+    ///
+    /// It's off by default and should stay off almost everywhere: running out of prefixes takes
+    /// `usize::MAX` blocks (see [Limits](ProcessUniqueId#limits)), so the panic it replaces
+    /// practically never fires on 64bit targets. It applies to every thread from the next prefix
+    /// allocation on, and can be turned off again. It doesn't affect [`SmallUniqueId`] or
+    /// [`ScopedUniqueId`], which have their own setting.
+    ///
+    /// With the `durable` feature, the persisted high-water mark stays at the end of the prefix
+    /// space, so after a restart the counter starts out exhausted (and wraps right away if this
+    /// is turned on again).
+    ///
+    /// [`SmallUniqueId`]: crate::SmallUniqueId
+    /// [`ScopedUniqueId`]: crate::ScopedUniqueId
+    pub fn set_wrap_on_exhaustion(wrap: bool) {
+        WRAP_ON_EXHAUSTION.store(wrap, Ordering::Relaxed);
+    }
+
+    /// The counter this copy of the crate claims prefixes from, for sharing with other copies
+    /// loaded into the same process. See
+    /// [`use_prefix_counter`](ProcessUniqueId::use_prefix_counter).
+    ///
+    /// Don't share it after calling `durable::init`: the high-water mark wouldn't cover the
+    /// prefixes other copies claim from it.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn prefix_counter() -> &'static AtomicPrefixCounter {
+        global_counter()
+    }
+
+    /// Claims prefixes from `counter` (another copy of this crate's
+    /// [`prefix_counter`](ProcessUniqueId::prefix_counter)) instead of this copy's own.
+    ///
+    /// Each dynamic library that links this crate statically (e.g., each plugin of a host
+    /// application) has its own copy of it, with its own counter, so two copies hand out the same
+    /// IDs. Having the host pass its counter to every plugin, which then calls this before it
+    /// creates any IDs, makes all of them claim prefixes from the one counter, and so create IDs
+    /// that are unique across the whole process:
+    ///
+    /// ```
+    /// use snowflake::{AtomicPrefixCounter, ProcessUniqueId};
+    /// use std::sync::atomic::Ordering;
+    ///
+    /// // The plugin's entry point, called by the host with `ProcessUniqueId::prefix_counter()`.
+    /// extern "C" fn plugin_init(counter: *const AtomicPrefixCounter) {
+    ///     // SAFETY: the host never unloads its own counter.
+    ///     let counter = unsafe { &*counter };
+    ///     ProcessUniqueId::use_prefix_counter(counter).expect("plugin created IDs too early");
+    /// }
+    ///
+    /// // Stands in for the host's counter, which has handed out two prefixes.
+    /// static HOST: AtomicPrefixCounter = AtomicPrefixCounter::new(2);
+    /// plugin_init(&HOST);
+    /// assert_eq!(ProcessUniqueId::new().to_string(), "puid-2-0");
+    /// assert_eq!(HOST.load(Ordering::Relaxed), 3);
+    ///
+    /// // From then on, the plugin's counter is the host's.
+    /// assert!(std::ptr::eq(ProcessUniqueId::prefix_counter(), &HOST));
+    /// static OTHER: AtomicPrefixCounter = AtomicPrefixCounter::new(0);
+    /// assert!(ProcessUniqueId::use_prefix_counter(&OTHER).is_err());
+    /// ```
+    ///
+    /// Sharing only covers `ProcessUniqueId`s and the IDs built from them. [`SmallUniqueId`] and
+    /// [`ScopedUniqueId`] keep counters of their own in each copy. All copies must be built with
+    /// the same atomics: with or without the `portable-atomic` feature.
+    ///
+    /// Fails if this copy has already claimed a prefix from its own counter, has been given a
+    /// different counter before, or has made its IDs durable with `durable::init`, whose
+    /// high-water mark wouldn't cover the shared counter's prefixes. IDs created on other threads while the counter is being
+    /// switched wait for the switch to finish.
+    ///
+    /// [`SmallUniqueId`]: crate::SmallUniqueId
+    /// [`ScopedUniqueId`]: crate::ScopedUniqueId
+    #[cfg(feature = "std")]
+    pub fn use_prefix_counter(
+        counter: &'static AtomicPrefixCounter,
+    ) -> Result<(), PrefixCounterInUse> {
+        let same_as = |current: &AtomicUsize| {
+            if ptr::eq(current, counter) {
+                Ok(())
+            } else {
+                Err(PrefixCounterInUse { _private: () })
+            }
+        };
+        #[cfg(feature = "durable")]
+        if crate::durable::is_initialized() {
+            return Err(PrefixCounterInUse { _private: () });
+        }
+        // Only one switch at a time; any other caller waits to see what it switched to.
+        while SWITCHING.swap(true, Ordering::Acquire) {
+            while SWITCHING.load(Ordering::Relaxed) {
+                core::hint::spin_loop();
+            }
+        }
+        let result = if let Some(shared) = SHARED_COUNTER.get() {
+            same_as(shared)
+        } else if ptr::eq(counter, &GLOBAL_COUNTER.0) {
+            Ok(())
         } else {
-            prev = old_value;
+            // Parks this copy's counter where claiming a prefix from it goes through
+            // `switched_away`, which waits for `counter` to be in place. (Parking it at
+            // `usize::MAX` would make it look exhausted in the meantime.)
+            GLOBAL_COUNTER
+                .compare_exchange(0, FETCH_ADD_LIMIT, Ordering::Release, Ordering::Relaxed)
+                .map(|_| {
+                    let _ = SHARED_COUNTER.set(counter);
+                })
+                .map_err(|_| PrefixCounterInUse { _private: () })
+        };
+        SWITCHING.store(false, Ordering::Release);
+        result
+    }
+
+    /// Sets the numbers of allocated prefixes at which a `tracing` warning is logged, replacing
+    /// the default of half and 90% of the prefix space.
+    ///
+    /// Requires the `tracing` feature.
+    #[cfg(feature = "tracing")]
+    pub fn set_prefix_warning_thresholds<I: IntoIterator<Item = usize>>(thresholds: I) {
+        let mut thresholds: Vec<_> = thresholds.into_iter().collect();
+        thresholds.sort_unstable();
+        thresholds.dedup();
+        *PREFIX_WARNINGS.lock().unwrap_or_else(|e| e.into_inner()) = Some(thresholds);
+    }
+}
+
+/// Reserves up to `n` (at least one) consecutive IDs from the current thread's block.
+///
+/// Returns the first reserved ID and the number of IDs reserved. Fewer than `n` IDs are reserved
+/// only when the thread's block runs out, in which case the thread moves on to a new block.
+#[cfg(feature = "std")]
+pub(crate) fn reserve_local(n: u64) -> (ProcessUniqueId, u64) {
+    with_local(|unique_id| reserve_in(unique_id, n))
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn reserve_in(unique_id: &LocalBlock, n: u64) -> (ProcessUniqueId, u64) {
+    let mut block = unique_id.get();
+    if block.next.offset > block.last {
+        block = next_block();
+    }
+    let first = block.next;
+    let after_first = block.last - first.offset;
+    if n.saturating_sub(1) < after_first {
+        let n = n.max(1);
+        unique_id.set(Block {
+            next: ProcessUniqueId {
+                prefix: first.prefix,
+                offset: first.offset + n,
+            },
+            last: block.last,
+        });
+        (first, n)
+    } else {
+        #[cfg(feature = "tracing")]
+        if block.last == u64::MAX {
+            prefix_used_up(first.prefix);
         }
+        unique_id.set(EMPTY_BLOCK);
+        (first, after_first + 1)
     }
 }
 
-// NOTE: We could use a Cell (not unsafe) but this is slightly faster.
-thread_local! {
-    static NEXT_LOCAL_UNIQUE_ID: UnsafeCell<ProcessUniqueId> = UnsafeCell::new(ProcessUniqueId {
-        prefix: next_global(),
-        offset: 0
+/// Reserves `n` (at least one) consecutive offsets under a single prefix, returning the first ID:
+/// from the thread's block if it has more than that left, otherwise straight from the shared
+/// prefix.
+#[cfg(feature = "std")]
+pub(crate) fn reserve_exact(n: u64) -> ProcessUniqueId {
+    let n = n.max(1);
+    let local = with_local(|unique_id| {
+        let block = unique_id.get();
+        if block.next.offset > block.last || n > block.last - block.next.offset {
+            return None;
+        }
+        unique_id.set(Block {
+            next: ProcessUniqueId {
+                prefix: block.next.prefix,
+                offset: block.next.offset + n,
+            },
+            last: block.last,
+        });
+        Some(block.next)
+    });
+    if let Some(first) = local {
+        return first;
+    }
+    let mut shared = SHARED_PREFIX.lock().unwrap_or_else(|e| e.into_inner());
+    loop {
+        // Claimed through `claim_with` like every other block, so it's audited and recorded (or
+        // replayed).
+        let block = claim_with(|| Some(claim_block(&mut shared, n))).unwrap_or_else(|| exhausted());
+        #[cfg(feature = "tracing")]
+        if block.last == u64::MAX {
+            prefix_used_up(block.next.prefix);
+        }
+        // The last block of a prefix may be too short, in which case its IDs go unused.
+        if block.last - block.next.offset == n - 1 {
+            return block.next;
+        }
+    }
+}
+
+/// Fills `slots` with new IDs, in order, with a single thread local access.
+#[cfg(feature = "std")]
+#[inline]
+fn fill_local<T>(slots: &mut [T], mut write: impl FnMut(&mut T, ProcessUniqueId)) {
+    with_local(|unique_id| {
+        let mut rest = slots;
+        while !rest.is_empty() {
+            let (first, count) = reserve_in(unique_id, rest.len() as u64);
+            let (head, tail) = rest.split_at_mut(count as usize);
+            for (i, slot) in head.iter_mut().enumerate() {
+                write(
+                    slot,
+                    ProcessUniqueId {
+                        prefix: first.prefix,
+                        offset: first.offset + i as u64,
+                    },
+                );
+            }
+            rest = tail;
+        }
     })
 }
 
-/// Process unique IDs are guaranteed to be unique within the current process, for the lifetime of
-/// the current process.
-///
-/// 1. ID creation should be highly performant even on highly concurrent systems. It's MUCH faster
-///    than using random/time based IDs (but, on the other hand, only unique within a process).
-/// 2. While this crate can run out of process unique IDs, this is very unlikely assuming a sane
-///    threading model and will panic rather than potentially reusing unique IDs.
-///
-/// # Limits
+/// The number of prefixes handed out, and the number of IDs in all blocks claimed from them.
+#[cfg(feature = "std")]
+pub(crate) fn claimed() -> (usize, u128) {
+    let shared = SHARED_PREFIX.lock().unwrap_or_else(|e| e.into_inner());
+    let prefixes = global_counter().load(Ordering::Relaxed);
+    let ids = match *shared {
+        // The current prefix is partially claimed, the ones before it fully.
+        Some((_, next)) => ((prefixes - 1) as u128) << 64 | u128::from(next),
+        None => (prefixes as u128) << 64,
+    };
+    (prefixes, ids)
+}
+
+/// Generator state saved by [`reset_state`].
+#[cfg(feature = "test-util")]
+pub(crate) struct SavedState {
+    global: usize,
+    shared: Option<(usize, u64)>,
+    block_size: u64,
+    reclaimed: Vec<Block>,
+    local: Block,
+}
+
+/// This copy's own prefix counter, for the test helpers to rewind or exhaust.
 ///
-/// The unique ID's are `sizeof(usize) + 64` bits wide and are generated by combining a `usize`
-/// global counter value with a 64bit thread local offset. This is important because each thread
-/// that calls `new()` at least once will reserve at least 2^64 IDs. So, the only way to run out of
-/// IDs in a reasonable amount of time is to run a 32bit system, spawn 2^32 threads, and claim one
-/// ID on each thread. You might be able to do this on a 64bit system but it would take a while...
-/// TL; DR: Don't create unique IDs from over 4 billion different threads on a 32bit system.
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
-#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
-pub struct ProcessUniqueId {
-    prefix: usize,
-    offset: u64,
+/// **panics** if prefixes are claimed from another copy's counter instead (see
+/// [`ProcessUniqueId::use_prefix_counter`]): changing that one would affect every copy sharing it.
+#[cfg(feature = "test-util")]
+fn own_counter() -> &'static AtomicUsize {
+    assert!(
+        SHARED_COUNTER.get().is_none(),
+        "can't reset a prefix counter shared with other copies of the crate"
+    );
+    &GLOBAL_COUNTER.0
 }
 
-impl fmt::Display for ProcessUniqueId {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "puid-{:x}-{:x}", self.prefix, self.offset)
+/// Saves the global and current thread's generator state, and resets it to that of a freshly
+/// started process.
+#[cfg(feature = "test-util")]
+pub(crate) fn reset_state() -> SavedState {
+    let mut shared = SHARED_PREFIX.lock().unwrap_or_else(|e| e.into_inner());
+    let saved = SavedState {
+        global: own_counter().swap(0, Ordering::Relaxed),
+        shared: shared.take(),
+        block_size: BLOCK_SIZE.swap(DEFAULT_BLOCK_SIZE, Ordering::Relaxed),
+        reclaimed: std::mem::take(&mut *RECLAIMED.lock().unwrap_or_else(|e| e.into_inner())),
+        local: with_local(|local| local.get()),
+    };
+    with_local(|local| local.set(EMPTY_BLOCK));
+    saved
+}
+
+/// Puts back state saved by [`reset_state`].
+#[cfg(feature = "test-util")]
+pub(crate) fn restore_state(saved: SavedState) {
+    let mut shared = SHARED_PREFIX.lock().unwrap_or_else(|e| e.into_inner());
+    // The saved count is this copy's own, whatever counter is in use now.
+    GLOBAL_COUNTER.store(saved.global, Ordering::Relaxed);
+    *shared = saved.shared;
+    BLOCK_SIZE.store(saved.block_size, Ordering::Relaxed);
+    // Blocks reclaimed since the reset may repeat IDs from before it.
+    *RECLAIMED.lock().unwrap_or_else(|e| e.into_inner()) = saved.reclaimed;
+    let local = saved.local;
+    with_local(|unique_id| unique_id.set(local));
+}
+
+/// Makes the prefix counter run out after `n` more prefixes, dropping the shared prefix and any
+/// reclaimed blocks so the next block comes from a new one.
+#[cfg(feature = "test-util")]
+pub(crate) fn set_prefixes_left(n: usize) {
+    let mut shared = SHARED_PREFIX.lock().unwrap_or_else(|e| e.into_inner());
+    *shared = None;
+    RECLAIMED.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    own_counter().store(usize::MAX - n, Ordering::Relaxed);
+}
+
+/// Gives the current thread a block of the last `n` offsets of a new prefix.
+#[cfg(feature = "test-util")]
+pub(crate) fn set_offsets_left(n: u64) {
+    let block = match n {
+        0 => EMPTY_BLOCK,
+        n => Block {
+            next: ProcessUniqueId {
+                prefix: next_global(),
+                offset: u64::MAX - (n - 1),
+            },
+            last: u64::MAX,
+        },
+    };
+    with_local(|local| local.set(block));
+}
+
+/// Hashes the packed [`to_u128`](ProcessUniqueId::to_u128) form rather than going through
+/// `Hash`, so an ID maps to the same `egui::Id` on every platform. Like any 64-bit hash,
+/// two distinct IDs can collide, but only with a chance of about 2^-64 per pair.
+#[cfg(feature = "egui")]
+impl From<ProcessUniqueId> for egui::Id {
+    #[inline]
+    fn from(id: ProcessUniqueId) -> egui::Id {
+        egui::Id::new(id.to_u128())
     }
 }
 
-impl ProcessUniqueId {
-    /// Create a new unique ID.
-    ///
-    /// **panics** if there are no more unique IDs available. If this happens, go home and
-    /// reevaluate your threading model!
+/// Same as [`to_uuid_v8`](ProcessUniqueId::to_uuid_v8), failing where it returns `None`.
+#[cfg(feature = "uuid")]
+impl TryFrom<ProcessUniqueId> for uuid::Uuid {
+    type Error = IdOutOfRange;
+
     #[inline]
-    pub fn new() -> Self {
-        NEXT_LOCAL_UNIQUE_ID.with(|unique_id| {
-            unsafe {
-                // NOTE: Checked ops are slower than manually checking... (WTF?)
-                let next_unique_id = *unique_id.get();
-                (*unique_id.get()) = if next_unique_id.offset == u64::MAX {
-                    ProcessUniqueId {
-                        prefix: next_global(),
-                        offset: 0,
-                    }
-                } else {
-                    ProcessUniqueId {
-                        prefix: next_unique_id.prefix,
-                        offset: next_unique_id.offset + 1,
-                    }
-                };
-                next_unique_id
-            }
-        })
+    fn try_from(id: ProcessUniqueId) -> Result<uuid::Uuid, IdOutOfRange> {
+        id.to_uuid_v8().ok_or_else(IdOutOfRange::new)
+    }
+}
+
+/// Same as [`from_uuid_v8`](ProcessUniqueId::from_uuid_v8), failing where it returns `None`.
+#[cfg(feature = "uuid")]
+impl TryFrom<uuid::Uuid> for ProcessUniqueId {
+    type Error = IdOutOfRange;
+
+    #[inline]
+    fn try_from(uuid: uuid::Uuid) -> Result<Self, IdOutOfRange> {
+        ProcessUniqueId::from_uuid_v8(uuid).ok_or_else(IdOutOfRange::new)
+    }
+}
+
+/// The GUID with the same bits as [`to_uuid_v8`](ProcessUniqueId::to_uuid_v8)'s UUID, failing
+/// where it returns `None`.
+#[cfg(feature = "windows")]
+impl TryFrom<ProcessUniqueId> for windows_core::GUID {
+    type Error = IdOutOfRange;
+
+    #[inline]
+    fn try_from(id: ProcessUniqueId) -> Result<windows_core::GUID, IdOutOfRange> {
+        id.to_v8_bits()
+            .map(windows_core::GUID::from_u128)
+            .ok_or_else(IdOutOfRange::new)
+    }
+}
+
+/// Recovers an ID from a GUID made by the conversion above, failing where
+/// [`from_uuid_v8`](ProcessUniqueId::from_uuid_v8) returns `None`.
+#[cfg(feature = "windows")]
+impl TryFrom<windows_core::GUID> for ProcessUniqueId {
+    type Error = IdOutOfRange;
+
+    #[inline]
+    fn try_from(guid: windows_core::GUID) -> Result<Self, IdOutOfRange> {
+        ProcessUniqueId::from_v8_bits(guid.to_u128()).ok_or_else(IdOutOfRange::new)
     }
 }
 
@@ -111,54 +1809,528 @@ impl Default for ProcessUniqueId {
     }
 }
 
-#[cfg(test)]
+// Proof harnesses for the prefix and offset bookkeeping, checked with `cargo kani`. Together they
+// show that an ID is never handed out twice: blocks never overlap, a thread only hands out IDs
+// from its block in order, and running out of prefixes can't silently wrap around.
+#[cfg(all(kani, feature = "std"))]
+mod verification {
+    use super::{
+        claim_block, next_global, reserve_in, with_local, Block, LocalBlock, ProcessUniqueId,
+        EMPTY_BLOCK, GLOBAL_COUNTER,
+    };
+    use crate::atomic::Ordering;
+
+    /// A block with at least one ID left.
+    fn any_block() -> Block {
+        let block = Block {
+            next: ProcessUniqueId {
+                prefix: kani::any(),
+                offset: kani::any(),
+            },
+            last: kani::any(),
+        };
+        kani::assume(block.next.offset <= block.last);
+        block
+    }
+
+    /// Consecutive blocks claimed from the shared prefix are never empty and never overlap, even
+    /// when the first one uses up its prefix.
+    #[kani::proof]
+    fn claim_block_disjoint() {
+        let counter: usize = kani::any();
+        kani::assume(counter < usize::MAX - 1);
+        GLOBAL_COUNTER.store(counter, Ordering::Relaxed);
+        let mut shared: Option<(usize, u64)> = kani::any();
+        if let Some((prefix, _)) = shared {
+            // The shared prefix was handed out by the counter.
+            kani::assume(prefix < counter);
+        }
+
+        let a = claim_block(&mut shared, kani::any());
+        let b = claim_block(&mut shared, kani::any());
+        assert!(a.next.offset <= a.last);
+        assert!(b.next.offset <= b.last);
+        assert!(a.next.prefix != b.next.prefix || a.last < b.next.offset);
+    }
+
+    /// Reserving IDs from a thread's block takes them from the front of the block, and leaves
+    /// only later IDs (or none) behind.
+    #[kani::proof]
+    fn reserve_in_stays_in_block() {
+        let block = any_block();
+        let local = LocalBlock::new(block);
+        let (first, count) = reserve_in(&local, kani::any());
+        assert_eq!(first, block.next);
+        assert!(count >= 1 && count - 1 <= block.last - first.offset);
+
+        let rest = local.get();
+        assert!(
+            rest == EMPTY_BLOCK
+                || (rest.next.prefix == first.prefix
+                    && rest.next.offset == first.offset + count
+                    && rest.last == block.last)
+        );
+    }
+
+    /// `new()` hands out the next ID in the thread's block and moves past it without ever
+    /// wrapping the offset around.
+    #[kani::proof]
+    fn new_advances_block() {
+        let block = any_block();
+        with_local(|local| local.set(block));
+        let id = ProcessUniqueId::new();
+        assert_eq!(id, block.next);
+
+        let rest = with_local(|local| local.get());
+        assert!(
+            rest == EMPTY_BLOCK
+                || (rest.next.prefix == id.prefix
+                    && rest.next.offset == id.offset + 1
+                    && rest.last == block.last)
+        );
+    }
+
+    /// Running out of prefixes panics unless wrapping around was explicitly allowed.
+    #[kani::proof]
+    #[kani::should_panic]
+    fn next_global_exhaustion() {
+        GLOBAL_COUNTER.store(usize::MAX, Ordering::Relaxed);
+        next_global();
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
 mod test {
     use super::ProcessUniqueId;
     use std::thread;
 
     // Glass box tests.
 
+    #[test]
+    fn test_try_new() {
+        thread::spawn(|| {
+            // A fresh thread claims a whole prefix without touching the shared state.
+            let first = ProcessUniqueId::try_new().unwrap();
+            assert_eq!(first.offset, 0);
+            let block = super::with_local(|local| local.get());
+            assert_eq!(block.remaining(), u64::MAX);
+            // `new()` carries on with the same block.
+            let next = ProcessUniqueId::new();
+            assert_eq!((next.prefix, next.offset), (first.prefix, 1));
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_padded_statics() {
+        use core::mem::align_of_val;
+        assert!(align_of_val(&super::GLOBAL_COUNTER) >= 64);
+        assert!(align_of_val(&super::SHARED_PREFIX) >= 64);
+    }
+
+    #[test]
+    fn test_warm_up() {
+        thread::spawn(|| {
+            ProcessUniqueId::warm_up();
+            let block = super::with_local(|local| local.get());
+            assert!(block.remaining() > 0);
+            // Warming up again keeps the block, and `new()` hands out its first ID.
+            ProcessUniqueId::warm_up();
+            assert_eq!(ProcessUniqueId::new(), block.next);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_reclaim() {
+        use super::{reclaim, take_reclaimed, Block, LocalBlock, EMPTY_BLOCK};
+
+        let first = ProcessUniqueId {
+            prefix: 7,
+            offset: 10,
+        };
+        let local = LocalBlock::new(Block {
+            next: first,
+            last: 19,
+        });
+        let mut reclaimed = Vec::new();
+        reclaim(&local, &mut reclaimed);
+        assert_eq!(local.get(), EMPTY_BLOCK);
+        reclaim(&local, &mut reclaimed);
+        assert_eq!(reclaimed.len(), 1);
+
+        // Handed out in blocks of the requested size, from the front.
+        let block = take_reclaimed(&mut reclaimed, 4).unwrap();
+        assert_eq!((block.next, block.last), (first, 13));
+        let rest = take_reclaimed(&mut reclaimed, 100).unwrap();
+        assert_eq!((rest.next.offset, rest.last), (14, 19));
+        assert_eq!(take_reclaimed(&mut reclaimed, 100), None);
+
+        // A thread's block outlives it.
+        let (id, remaining) = thread::spawn(|| {
+            let id = ProcessUniqueId::new();
+            (id, super::with_local(|local| local.get().remaining()))
+        })
+        .join()
+        .unwrap();
+        let shared = super::SHARED_PREFIX.lock().unwrap();
+        let reclaimed = super::RECLAIMED.lock().unwrap();
+        let next = ProcessUniqueId {
+            prefix: id.prefix,
+            offset: id.offset + 1,
+        };
+        // Unless another test's thread has taken it already.
+        if let Some(block) = reclaimed.iter().find(|block| block.next == next) {
+            assert_eq!(block.remaining(), remaining);
+        }
+        drop((reclaimed, shared));
+    }
+
     #[test]
     fn test_unique_id_unthreaded() {
-        let first_unique_id = ProcessUniqueId::new();
-        // Not going to be able to count to u64::MAX
+        // Not going to be able to count to the end of a block, so start near the end of a prefix
+        // of our own, which no other thread can be handed.
+        let prefix = super::try_next_global().unwrap();
         {
             // Ignore....
-            use super::NEXT_LOCAL_UNIQUE_ID;
-            NEXT_LOCAL_UNIQUE_ID
-                .with(|unique_id| unsafe { (*unique_id.get()).offset = u64::MAX - 10 });
+            use super::{with_local, Block};
+            with_local(|unique_id| {
+                unique_id.set(Block {
+                    next: ProcessUniqueId {
+                        prefix,
+                        offset: u64::MAX - 10,
+                    },
+                    last: u64::MAX,
+                })
+            });
         } // Ignore...
 
         for i in (u64::MAX - 11)..(u64::MAX) {
             assert!(
                 ProcessUniqueId::new()
                     == ProcessUniqueId {
-                        prefix: first_unique_id.prefix,
-                        offset: i + 1,
+                        prefix,
+                        offset: i + 1
                     }
             );
         }
         let next = ProcessUniqueId::new();
-        assert!(next.prefix != first_unique_id.prefix);
-        assert!(next.offset == 0);
+        assert!(next.prefix != prefix);
         assert!(
             ProcessUniqueId::new()
                 == ProcessUniqueId {
                     prefix: next.prefix,
-                    offset: 1,
+                    offset: next.offset + 1,
                 }
         );
     }
 
+    #[test]
+    fn test_claim_block() {
+        use super::claim_block;
+        let mut shared = None;
+        let a = claim_block(&mut shared, 1 << 62);
+        assert_eq!((a.next.offset, a.last), (0, (1 << 62) - 1));
+        let b = claim_block(&mut shared, 1 << 62);
+        assert_eq!(b.next.prefix, a.next.prefix);
+        assert_eq!((b.next.offset, b.last), (1 << 62, (1 << 63) - 1));
+        // The last block of a prefix is cut short, then the next block starts a new prefix.
+        let c = claim_block(&mut shared, u64::MAX);
+        assert_eq!(
+            (c.next.prefix, c.next.offset, c.last),
+            (a.next.prefix, 1 << 63, u64::MAX)
+        );
+        assert_eq!(shared, None);
+        let d = claim_block(&mut shared, 0);
+        assert_ne!(d.next.prefix, a.next.prefix);
+        assert_eq!((d.next.offset, d.last), (0, 0));
+        assert_eq!(shared, Some((d.next.prefix, 1)));
+    }
+
+    #[test]
+    fn test_parse() {
+        let id = ProcessUniqueId::new();
+        assert_eq!(id.to_string().parse(), Ok(id));
+        let id = ProcessUniqueId {
+            prefix: 0xab,
+            offset: u64::MAX,
+        };
+        assert_eq!("puid-ab-ffffffffffffffff".parse(), Ok(id));
+        assert_eq!(ProcessUniqueId::from_u128(id.to_u128()), Some(id));
+        let bytes = id.to_bytes();
+        assert_eq!(&bytes[..8], &[0, 0, 0, 0, 0, 0, 0, 0xab]);
+        assert_eq!(&bytes[8..], &[0xff; 8]);
+        assert_eq!(ProcessUniqueId::from_bytes(bytes), Ok(id));
+        for bad in &[
+            "",
+            "puid-",
+            "puid-1",
+            "puid--1",
+            "puid-1-",
+            "puid-+1-1",
+            "puid-1-1-1",
+            "uid-1-1",
+            "puid-1-10000000000000000",
+        ] {
+            assert!(bad.parse::<ProcessUniqueId>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |s: &str| {
+            let err = s.parse::<ProcessUniqueId>().unwrap_err();
+            (err.offset(), err.expected(), err.found())
+        };
+        assert_eq!(err(""), (0, "`puid-`", None));
+        assert_eq!(err("pu1d-1-1"), (2, "`puid-`", Some('1')));
+        assert_eq!(err("puid--1"), (5, "a hex digit", Some('-')));
+        assert_eq!(err("puid-1-1-1"), (8, "a hex digit", Some('-')));
+        assert_eq!(err("puid-1-\u{e9}"), (7, "a hex digit", Some('\u{e9}')));
+        assert_eq!(
+            err("puid-1-10000000000000000"),
+            (7, "a value that fits in 64 bits", Some('1'))
+        );
+        assert_eq!(
+            "puid-1-"
+                .parse::<ProcessUniqueId>()
+                .unwrap_err()
+                .to_string(),
+            "invalid ID at byte 7: expected a hex digit, found the end of the input"
+        );
+    }
+
+    #[test]
+    fn test_sortable_string() {
+        let mut ids: Vec<_> = [
+            (0, 9),
+            (0, 0x10),
+            (1, 0),
+            (0xa, 2),
+            (0x10, 1),
+            (0, u64::MAX),
+        ]
+        .iter()
+        .map(|&(prefix, offset)| ProcessUniqueId { prefix, offset })
+        .collect();
+        ids.sort();
+        let strings: Vec<_> = ids.iter().map(|id| id.to_sortable_string()).collect();
+        assert!(strings.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(strings[0], "puid-0000000000000000-0000000000000009");
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        use super::LenientParseError::*;
+
+        let id: ProcessUniqueId = "puid-ab-ff".parse().unwrap();
+        for good in &[
+            "puid-ab-ff",
+            "Puid-AB-FF",
+            "ab-ff",
+            "\t( [ 'puid-ab-ff' ] )\n",
+            "<`ab-ff`>",
+            "puid:3154393236604333326591",
+            "PUID:0xab00000000000000ff",
+        ] {
+            assert_eq!(ProcessUniqueId::parse_lenient(good), Ok(id), "{}", good);
+        }
+        for (bad, err) in &[
+            ("", Empty),
+            (" [ ] ", Empty),
+            ("spuid-ab-ff", WrongTag),
+            ("ab", Malformed),
+            ("puid-ab-ff-1", Malformed),
+            ("puid-ab-", Malformed),
+            ("puid:", Malformed),
+            ("puid-xy-ff", InvalidDigit),
+            ("puid-ab-+ff", InvalidDigit),
+            ("puid:0xg", InvalidDigit),
+            ("puid-ab-10000000000000000", OutOfRange),
+            ("puid:0x1000000000000000000000000000000000", OutOfRange),
+        ] {
+            assert_eq!(
+                ProcessUniqueId::parse_lenient(bad),
+                Err(err.clone()),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_base64_url() {
+        let id = ProcessUniqueId::from_u128(1 << 64 | 0xfffe_fdfc_fbfa_f9f8).unwrap();
+        assert_eq!(id.to_base64_url(), "AAAAAAAAAAH__v38-_r5-A");
+        assert_eq!(
+            ProcessUniqueId::from_base64_url("AAAAAAAAAAH__v38-_r5-A"),
+            Ok(id)
+        );
+        let zero = ProcessUniqueId::from_u128(0).unwrap();
+        assert_eq!(zero.to_base64_url(), "AAAAAAAAAAAAAAAAAAAAAA");
+
+        // Bits past the end, padding, the standard alphabet, and wrong lengths are rejected.
+        assert!(ProcessUniqueId::from_base64_url("AAAAAAAAAAH__v38-_r5-B").is_err());
+        assert!(ProcessUniqueId::from_base64_url("AAAAAAAAAAH__v38-_r5-A==").is_err());
+        assert!(ProcessUniqueId::from_base64_url("AAAAAAAAAAH//v38+/r5+A").is_err());
+        assert!(ProcessUniqueId::from_base64_url("AAAAAAAAAAH__v38-_r5-").is_err());
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn test_uuid_v8() {
+        use core::convert::TryFrom;
+
+        let id = ProcessUniqueId::new();
+        let uuid = id.to_uuid_v8().unwrap();
+        assert_eq!(uuid.get_version_num(), 8);
+        assert_eq!(ProcessUniqueId::from_uuid_v8(uuid), Some(id));
+
+        let low = ProcessUniqueId::from_u128(1 << 64 | u128::from(u64::MAX)).unwrap();
+        let high = ProcessUniqueId::from_u128(2 << 64).unwrap();
+        assert!(low.to_uuid_v8() < high.to_uuid_v8());
+        let max = ProcessUniqueId::from_u128((1 << 122) - 1).unwrap();
+        assert_eq!(
+            ProcessUniqueId::from_uuid_v8(max.to_uuid_v8().unwrap()),
+            Some(max)
+        );
+        assert!(ProcessUniqueId::from_u128(1 << 122)
+            .unwrap()
+            .to_uuid_v8()
+            .is_none());
+
+        assert!(ProcessUniqueId::from_uuid_v8(uuid::Uuid::new_v4()).is_none());
+
+        assert_eq!(uuid::Uuid::try_from(id), Ok(uuid));
+        assert_eq!(ProcessUniqueId::try_from(uuid), Ok(id));
+        assert!(ProcessUniqueId::try_from(uuid::Uuid::nil()).is_err());
+    }
+
+    #[cfg(feature = "windows")]
+    #[test]
+    fn test_guid() {
+        use core::convert::TryFrom;
+        use windows_core::GUID;
+
+        let id = ProcessUniqueId::new();
+        let guid = GUID::try_from(id).unwrap();
+        assert_eq!(guid.data3 >> 12, 8);
+        assert_eq!(ProcessUniqueId::try_from(guid), Ok(id));
+        #[cfg(feature = "uuid")]
+        assert_eq!(guid.to_u128(), id.to_uuid_v8().unwrap().as_u128());
+
+        assert!(GUID::try_from(ProcessUniqueId::from_u128(1 << 122).unwrap()).is_err());
+        assert!(ProcessUniqueId::try_from(GUID::zeroed()).is_err());
+    }
+
+    #[cfg(feature = "egui")]
+    #[test]
+    fn test_egui_id() {
+        let (a, b) = (ProcessUniqueId::new(), ProcessUniqueId::new());
+        assert_eq!(egui::Id::from(a), egui::Id::new(a.to_u128()));
+        assert_ne!(egui::Id::from(a), egui::Id::from(b));
+    }
+
+    #[cfg(all(
+        feature = "bytemuck",
+        feature = "zerocopy",
+        target_pointer_width = "64"
+    ))]
+    #[test]
+    fn test_cast_bytes() {
+        use zerocopy::{FromBytes, IntoBytes};
+
+        let ids = [ProcessUniqueId::new(), ProcessUniqueId::new()];
+        let bytes: &[u8] = bytemuck::cast_slice(&ids);
+        assert_eq!(bytes.len(), 32);
+        assert_eq!(bytes, ids.as_bytes());
+        assert_eq!(&bytes[8..16], &ids[0].offset.to_ne_bytes());
+        assert_eq!(bytemuck::cast_slice::<u8, ProcessUniqueId>(bytes), &ids);
+        assert_eq!(<[ProcessUniqueId]>::ref_from_bytes(bytes).unwrap(), &ids);
+    }
+
+    #[cfg(feature = "bitcode")]
+    #[test]
+    fn test_bitcode() {
+        use crate::{ObjectId, Tsid};
+
+        let ids: Vec<_> = (0..100).map(|_| ProcessUniqueId::new()).collect();
+        let bytes = bitcode::encode(&ids);
+        let decoded: Vec<ProcessUniqueId> = bitcode::decode(&bytes).unwrap();
+        assert_eq!(decoded, ids);
+        let other = (Tsid::from(7u64), ObjectId::from_bytes([3; 12]));
+        let decoded: (Tsid, ObjectId) = bitcode::decode(&bitcode::encode(&other)).unwrap();
+        assert_eq!(decoded, other);
+    }
+
+    #[test]
+    fn test_reserve_local_rollover() {
+        use super::{reserve_local, try_next_global, with_local, Block};
+        let (first, count) = reserve_local(10);
+        assert_eq!(count, 10);
+        assert_eq!(ProcessUniqueId::new().offset, first.offset + 10);
+        let prefix = try_next_global().unwrap();
+        with_local(|unique_id| {
+            unique_id.set(Block {
+                next: ProcessUniqueId {
+                    prefix,
+                    offset: u64::MAX - 3,
+                },
+                last: u64::MAX,
+            })
+        });
+        let (tail, count) = reserve_local(10);
+        assert_eq!((tail.prefix, tail.offset, count), (prefix, u64::MAX - 3, 4));
+        assert_ne!(ProcessUniqueId::new().prefix, prefix);
+    }
+
+    #[test]
+    fn test_new_fill() {
+        use super::{with_local, Block};
+        let first = ProcessUniqueId::new();
+        let mut ids = [first; 10];
+        ProcessUniqueId::new_fill(&mut ids);
+        assert_eq!(ids[0].offset, first.offset + 1);
+        assert!(ids.windows(2).all(|w| w[1].offset == w[0].offset + 1));
+
+        // Crossing into a new block.
+        let prefix = super::try_next_global().unwrap();
+        with_local(|unique_id| {
+            unique_id.set(Block {
+                next: ProcessUniqueId {
+                    prefix,
+                    offset: u64::MAX - 3,
+                },
+                last: u64::MAX,
+            })
+        });
+        ProcessUniqueId::new_fill(&mut ids);
+        assert_eq!((ids[3].prefix, ids[3].offset), (prefix, u64::MAX));
+        assert_ne!(ids[4].prefix, prefix);
+        assert!(ids[4..].windows(2).all(|w| w[1].offset == w[0].offset + 1));
+        assert_eq!(ProcessUniqueId::new().offset, ids[9].offset + 1);
+    }
+
     #[test]
     fn test_unique_id_threaded() {
+        use std::sync::{Arc, Barrier};
+        // Exiting threads hand the rest of their blocks on, so keep them all alive until every
+        // one has claimed its block.
+        let claimed = Arc::new(Barrier::new(128));
         let threads: Vec<_> = (0..128)
             .map(|_| {
+                let claimed = claimed.clone();
                 thread::spawn(move || {
                     thread::park();
+                    ProcessUniqueId::warm_up();
+                    let block = super::with_local(|unique_id| unique_id.get());
                     let unique_id = ProcessUniqueId::new();
-                    assert_eq!(unique_id.offset, 0);
-                    unique_id.prefix
+                    assert_eq!(unique_id, block.next);
+                    assert_eq!(ProcessUniqueId::new().offset, unique_id.offset + 1);
+                    claimed.wait();
+                    (unique_id.prefix, unique_id.offset, block.last)
                 })
             })
             .collect();
@@ -168,11 +2340,13 @@ mod test {
             thread.thread().unpark();
         }
 
+        // Threads share prefixes, but no two threads' blocks overlap.
         let mut results: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
         results.sort();
-        let old_len = results.len();
-        results.dedup();
-        assert_eq!(old_len, results.len());
+        for pair in results.windows(2) {
+            let ((prefix, _, last), (next_prefix, next_offset, _)) = (pair[0], pair[1]);
+            assert!(prefix != next_prefix || last < next_offset);
+        }
     }
 
     // #[bench]
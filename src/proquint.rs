@@ -0,0 +1,197 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::convert::TryFrom;
+
+use crate::process_unique_id::{expect_len, ParseIdError};
+use crate::{FlakeId, ProcessUniqueId, SmallUniqueId, SnowflakeId, SonyflakeId, Tsid};
+
+// From "A Proposal for Proquints" (arXiv:0901.4016): letters picked to be told apart when
+// spoken, in alphabetical order, so that quints sort the same way as the values.
+const CONSONANTS: &[u8; 16] = b"bdfghjklmnprstvz";
+const VOWELS: &[u8; 4] = b"aiou";
+
+/// Writes the low `words * 16` bits of `value` as `words` five-letter quints (consonant, vowel,
+/// consonant, vowel, consonant) separated by `-`, most significant first.
+fn encode(value: u128, words: usize) -> String {
+    let mut code = String::with_capacity(words * 6 - 1);
+    for i in (0..words).rev() {
+        let word = (value >> (16 * i)) as u16;
+        if i + 1 != words {
+            code.push('-');
+        }
+        code.push(char::from(CONSONANTS[usize::from(word >> 12)]));
+        code.push(char::from(VOWELS[usize::from(word >> 10 & 0x3)]));
+        code.push(char::from(CONSONANTS[usize::from(word >> 6 & 0xf)]));
+        code.push(char::from(VOWELS[usize::from(word >> 4 & 0x3)]));
+        code.push(char::from(CONSONANTS[usize::from(word & 0xf)]));
+    }
+    code
+}
+
+/// What belongs at byte `i` of a code.
+fn expected_at(i: usize) -> &'static str {
+    match i % 6 {
+        1 | 3 => "a proquint vowel",
+        5 => "`-`",
+        _ => "a proquint consonant",
+    }
+}
+
+/// Reads a value written by `encode` with `words` quints, in either case.
+fn decode(code: &str, words: usize) -> Result<u128, ParseIdError> {
+    let len = words * 6 - 1;
+    let mut value = 0u128;
+    for (i, c) in code.bytes().enumerate().take(len) {
+        let c = c.to_ascii_lowercase();
+        let (digit, bits) = match i % 6 {
+            1 | 3 => (VOWELS.iter().position(|&v| v == c), 2),
+            5 if c == b'-' => continue,
+            5 => (None, 0),
+            _ => (CONSONANTS.iter().position(|&v| v == c), 4),
+        };
+        let digit = digit.ok_or_else(|| ParseIdError::at(code, i, expected_at(i)))?;
+        value = (value << bits) | digit as u128;
+    }
+    expect_len(code, len, expected_at(code.len()))?;
+    Ok(value)
+}
+
+fn decode_u64(code: &str) -> Result<u64, ParseIdError> {
+    // Four quints are exactly 64 bits.
+    decode(code, 4).map(|value| value as u64)
+}
+
+impl ProcessUniqueId {
+    /// Encodes this ID (its [`to_u128`](ProcessUniqueId::to_u128) form) as eight proquints:
+    /// pronounceable five-letter words, for reading IDs aloud (e.g., to support over the phone).
+    ///
+    /// ```
+    /// use snowflake::ProcessUniqueId;
+    ///
+    /// let id = ProcessUniqueId::from_u128(42).unwrap();
+    /// assert_eq!(id.to_proquint(), "babab-babab-babab-babab-babab-babab-babab-babop");
+    ///
+    /// let id = ProcessUniqueId::new();
+    /// assert_eq!(ProcessUniqueId::from_proquint(&id.to_proquint()), Ok(id));
+    /// ```
+    ///
+    /// Each quint stands for 16 bits, and the letters are chosen to be hard to mishear. Smaller
+    /// ID types take four quints.
+    #[inline]
+    pub fn to_proquint(self) -> String {
+        encode(self.to_u128(), 8)
+    }
+
+    /// Decodes the form produced by [`to_proquint`](ProcessUniqueId::to_proquint), in either
+    /// case. As with `from_u128`, only IDs created by the current process are unique in it.
+    pub fn from_proquint(code: &str) -> Result<Self, ParseIdError> {
+        ProcessUniqueId::from_u128(decode(code, 8)?)
+            .ok_or_else(|| ParseIdError::at(code, 0, "a prefix that fits in a usize"))
+    }
+}
+
+impl SmallUniqueId {
+    /// Encodes this ID as four proquints. See [`ProcessUniqueId::to_proquint`].
+    #[inline]
+    pub fn to_proquint(self) -> String {
+        encode(u64::from(self).into(), 4)
+    }
+
+    /// Decodes the form produced by [`to_proquint`](SmallUniqueId::to_proquint).
+    pub fn from_proquint(code: &str) -> Result<Self, ParseIdError> {
+        decode_u64(code).map(SmallUniqueId::from)
+    }
+}
+
+impl SnowflakeId {
+    /// Encodes this ID as four proquints. See [`ProcessUniqueId::to_proquint`].
+    #[inline]
+    pub fn to_proquint(self) -> String {
+        encode(u64::from(self).into(), 4)
+    }
+
+    /// Decodes the form produced by [`to_proquint`](SnowflakeId::to_proquint).
+    pub fn from_proquint(code: &str) -> Result<Self, ParseIdError> {
+        SnowflakeId::try_from(decode_u64(code)?)
+            .map_err(|_| ParseIdError::at(code, 0, "a value below 2^63"))
+    }
+}
+
+impl SonyflakeId {
+    /// Encodes this ID as four proquints. See [`ProcessUniqueId::to_proquint`].
+    #[inline]
+    pub fn to_proquint(self) -> String {
+        encode(u64::from(self).into(), 4)
+    }
+
+    /// Decodes the form produced by [`to_proquint`](SonyflakeId::to_proquint).
+    pub fn from_proquint(code: &str) -> Result<Self, ParseIdError> {
+        SonyflakeId::try_from(decode_u64(code)?)
+            .map_err(|_| ParseIdError::at(code, 0, "a value below 2^63"))
+    }
+}
+
+impl Tsid {
+    /// Encodes this ID as four proquints. See [`ProcessUniqueId::to_proquint`].
+    #[inline]
+    pub fn to_proquint(self) -> String {
+        encode(u64::from(self).into(), 4)
+    }
+
+    /// Decodes the form produced by [`to_proquint`](Tsid::to_proquint).
+    pub fn from_proquint(code: &str) -> Result<Self, ParseIdError> {
+        decode_u64(code).map(Tsid::from)
+    }
+}
+
+impl FlakeId {
+    /// Encodes this ID as eight proquints. See [`ProcessUniqueId::to_proquint`].
+    #[inline]
+    pub fn to_proquint(self) -> String {
+        encode(u128::from(self), 8)
+    }
+
+    /// Decodes the form produced by [`to_proquint`](FlakeId::to_proquint).
+    pub fn from_proquint(code: &str) -> Result<Self, ParseIdError> {
+        decode(code, 8).map(FlakeId::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, encode};
+    use crate::{SnowflakeId, Tsid};
+
+    #[test]
+    fn test_proquint() {
+        // The examples from the proposal: IPv4 addresses as two quints.
+        assert_eq!(encode(0x7f00_0001, 2), "lusab-babad");
+        assert_eq!(encode(0x3f54_dcc1, 2), "gutih-tugad");
+        assert_eq!(decode("LUSAB-babad", 2), Ok(0x7f00_0001));
+
+        for &value in &[0, 1, u128::from(u64::MAX), u128::MAX] {
+            assert_eq!(decode(&encode(value, 8), 8), Ok(value));
+        }
+        assert_eq!(encode(u128::MAX, 8).len(), 47);
+
+        let err = decode("lusab-babad", 4).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid ID at byte 11: expected `-`, found the end of the input"
+        );
+        let err = decode("lusab-baxad", 2).unwrap_err();
+        assert_eq!(err.offset(), 8);
+        assert!(decode("lusab_babad", 2).is_err());
+        assert!(decode("lusab-babad-", 2).is_err());
+        assert!(decode("lusab-babadd", 2).is_err());
+
+        let id = Tsid::from(u64::MAX);
+        assert_eq!(Tsid::from_proquint(&id.to_proquint()), Ok(id));
+        assert!(SnowflakeId::from_proquint(&encode(1 << 63, 4)).is_err());
+    }
+}
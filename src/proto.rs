@@ -0,0 +1,93 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Protobuf messages for IDs, for use with `prost`.
+//!
+//! [`ProcessUniqueIdProto`] matches the `snowflake.ProcessUniqueIdProto` message in
+//! `proto/snowflake.proto` (shipped with this crate), so other languages can generate compatible
+//! code. Import that file from your own `.proto` files and map the type to this one, e.g. with
+//! `prost_build::Config::extern_path(".snowflake", "::snowflake::proto")`.
+//!
+//! To carry IDs in a `string` field instead (or a `google.protobuf.StringValue`), use the
+//! `puid-<prefix>-<offset>` form: `id.to_string()` and `s.parse::<ProcessUniqueId>()`.
+//!
+//! ```
+//! use prost::Message;
+//! use snowflake::proto::ProcessUniqueIdProto;
+//! use snowflake::ProcessUniqueId;
+//! use std::convert::TryFrom;
+//!
+//! let id = ProcessUniqueId::new();
+//! let bytes = ProcessUniqueIdProto::from(id).encode_to_vec();
+//! let proto = ProcessUniqueIdProto::decode(&bytes[..]).unwrap();
+//! assert_eq!(ProcessUniqueId::try_from(proto), Ok(id));
+//! ```
+
+use std::convert::TryFrom;
+
+use crate::{IdOutOfRange, ProcessUniqueId};
+
+/// The protobuf form of a [`ProcessUniqueId`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ProcessUniqueIdProto {
+    /// The process-wide prefix.
+    #[prost(uint64, tag = "1")]
+    pub prefix: u64,
+    /// The offset within the prefix.
+    #[prost(uint64, tag = "2")]
+    pub offset: u64,
+}
+
+impl From<ProcessUniqueId> for ProcessUniqueIdProto {
+    #[inline]
+    fn from(id: ProcessUniqueId) -> Self {
+        ProcessUniqueIdProto {
+            prefix: id.prefix as u64,
+            offset: id.offset,
+        }
+    }
+}
+
+/// Fails if the prefix doesn't fit in a `usize`.
+///
+/// Note: as with deserialization, an ID created in another process is *not* unique in this one.
+impl TryFrom<ProcessUniqueIdProto> for ProcessUniqueId {
+    type Error = IdOutOfRange;
+
+    #[inline]
+    fn try_from(proto: ProcessUniqueIdProto) -> Result<Self, IdOutOfRange> {
+        Ok(ProcessUniqueId {
+            prefix: usize::try_from(proto.prefix).map_err(|_| IdOutOfRange::new())?,
+            offset: proto.offset,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ProcessUniqueIdProto;
+    use crate::ProcessUniqueId;
+    use prost::Message;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_round_trip() {
+        let id = ProcessUniqueId::new();
+        let proto = ProcessUniqueIdProto::from(id);
+        let decoded = ProcessUniqueIdProto::decode(&proto.encode_to_vec()[..]).unwrap();
+        assert_eq!(decoded, proto);
+        assert_eq!(ProcessUniqueId::try_from(decoded), Ok(id));
+
+        // Field numbers are part of the wire format.
+        let proto = ProcessUniqueIdProto {
+            prefix: 1,
+            offset: 2,
+        };
+        assert_eq!(proto.encode_to_vec(), [8, 1, 16, 2]);
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::fmt;
+use core::mem;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, Hasher};
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "bevy")]
+use bevy_ecs::reflect::ReflectComponent;
+
+use crate::display::pad_id;
+#[cfg(feature = "std")]
+use crate::obfuscate::splitmix64;
+use crate::process_unique_id::{expect_len, ParseIdError};
+
+const ENCODED_LEN: usize = 20;
+const TIME_LEN: usize = 8;
+#[cfg(feature = "std")]
+const RANDOM_LEN: usize = ENCODED_LEN - TIME_LEN;
+
+/// Firebase's alphabet, in ASCII order so that IDs sort as strings.
+const PUSH_CHARS: &[u8; 64] = b"-0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ_abcdefghijklmnopqrstuvwxyz";
+
+fn digit(c: u8) -> Option<u8> {
+    PUSH_CHARS.iter().position(|&d| d == c).map(|d| d as u8)
+}
+
+/// A 20-character ID in the format of Firebase's push IDs.
+///
+/// Eight characters of timestamp (milliseconds since the UNIX epoch) followed by twelve random
+/// characters, from a 64-character alphabet ordered so that IDs sort as strings in creation order.
+///
+/// # Memory layout
+///
+/// `#[repr(transparent)]` over its 20 ASCII characters, with an alignment of 1.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_ecs::component::Component, bevy_reflect::Reflect),
+    reflect(opaque, Component, Debug, PartialEq, Hash, Clone)
+)]
+#[repr(transparent)]
+pub struct PushId([u8; ENCODED_LEN]);
+
+const _: () = assert!(mem::size_of::<PushId>() == 20 && mem::align_of::<PushId>() == 1);
+
+impl PushId {
+    /// Milliseconds since the UNIX epoch.
+    pub fn timestamp(&self) -> u64 {
+        self.0[..TIME_LEN].iter().fold(0, |ts, &c| {
+            // Only valid characters are ever stored.
+            (ts << 6) | u64::from(digit(c).unwrap())
+        })
+    }
+
+    /// The ID as a string slice.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        // The alphabet is ASCII.
+        core::str::from_utf8(&self.0).unwrap()
+    }
+}
+
+impl fmt::Display for PushId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(f, format_args!(""), format_args!("{}", self.as_str()))
+    }
+}
+
+/// Parses a 20-character push ID.
+///
+/// Push IDs serialize as strings (with the `serde_support` feature).
+impl FromStr for PushId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        if let Some(i) = s.bytes().take(ENCODED_LEN).position(|c| digit(c).is_none()) {
+            return Err(ParseIdError::at(s, i, "a push ID character"));
+        }
+        expect_len(s, ENCODED_LEN, "a push ID character")?;
+        let mut bytes = [0u8; ENCODED_LEN];
+        bytes.copy_from_slice(s.as_bytes());
+        Ok(PushId(bytes))
+    }
+}
+
+#[cfg(feature = "serde_support")]
+impl serde::Serialize for PushId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        crate::serde::as_string::serialize(self, serializer)
+    }
+}
+
+#[cfg(feature = "serde_support")]
+impl<'de> serde::Deserialize<'de> for PushId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        crate::serde::as_string::deserialize(deserializer)
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct PushState {
+    last_ms: u64,
+    // The last ID's random characters, as digits.
+    last_random: [u8; RANDOM_LEN],
+    rng: u64,
+}
+
+/// Generates [`PushId`]s with Firebase's algorithm.
+///
+/// Within a millisecond, instead of drawing new random characters, the generator increments the
+/// previous ID's random part so that IDs created in quick succession still sort in creation
+/// order. If the clock goes backwards, it keeps using the last time it saw.
+///
+/// The random characters make IDs from different clients unlikely to collide; they are not
+/// suitable as secrets.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct PushIdGenerator {
+    state: Mutex<PushState>,
+}
+
+#[cfg(feature = "std")]
+impl Default for PushIdGenerator {
+    fn default() -> Self {
+        PushIdGenerator::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl PushIdGenerator {
+    /// Creates a new generator.
+    pub fn new() -> Self {
+        PushIdGenerator {
+            state: Mutex::new(PushState {
+                last_ms: 0,
+                last_random: [0; RANDOM_LEN],
+                rng: RandomState::new().build_hasher().finish(),
+            }),
+        }
+    }
+
+    /// Generates a new push ID.
+    pub fn next(&self) -> PushId {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        self.next_at(now)
+    }
+
+    fn next_at(&self, now: u64) -> PushId {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if now > state.last_ms {
+            state.last_ms = now;
+            let mut rng = state.rng;
+            for d in state.last_random.iter_mut() {
+                *d = (splitmix64(&mut rng) & 0x3f) as u8;
+            }
+            state.rng = rng;
+        } else {
+            // Increment the random part, carrying from the right. Firebase doesn't handle the
+            // (astronomically unlikely) overflow; move on to the next millisecond instead.
+            let mut carry = true;
+            for d in state.last_random.iter_mut().rev() {
+                if *d == 0x3f {
+                    *d = 0;
+                } else {
+                    *d += 1;
+                    carry = false;
+                    break;
+                }
+            }
+            if carry {
+                state.last_ms += 1;
+            }
+        }
+
+        let mut bytes = [0u8; ENCODED_LEN];
+        for (i, c) in bytes[..TIME_LEN].iter_mut().enumerate() {
+            *c = PUSH_CHARS[((state.last_ms >> (6 * (TIME_LEN - 1 - i))) & 0x3f) as usize];
+        }
+        for (c, &d) in bytes[TIME_LEN..].iter_mut().zip(&state.last_random) {
+            *c = PUSH_CHARS[usize::from(d)];
+        }
+        PushId(bytes)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::{PushId, PushIdGenerator};
+
+    #[test]
+    fn test_format() {
+        let gen = PushIdGenerator::new();
+        let id = gen.next_at(1_500_000_000_000);
+        assert_eq!(id.as_str().len(), 20);
+        assert_eq!(&id.as_str()[..8], "-KoyxtV-");
+        assert_eq!(id.timestamp(), 1_500_000_000_000);
+        assert_eq!(id.to_string().parse(), Ok(id));
+        assert!("-KoyxtV------------".parse::<PushId>().is_err());
+        assert!("-KoyxtV------------+".parse::<PushId>().is_err());
+    }
+
+    #[test]
+    fn test_ordering() {
+        let gen = PushIdGenerator::new();
+        let mut ids = vec![gen.next_at(1000)];
+        for now in &[1000, 1000, 999, 1001, 1001] {
+            ids.push(gen.next_at(*now));
+        }
+        assert!(ids.windows(2).all(|w| w[0].as_str() < w[1].as_str()));
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+
+        // Within a millisecond, the random part is incremented.
+        gen.state.lock().unwrap().last_random = [0; 12];
+        assert!(gen.next_at(0).as_str().ends_with("-----------0"));
+
+        let a = gen.next();
+        assert!(gen.next() > a);
+    }
+
+    #[test]
+    fn test_carry() {
+        let gen = PushIdGenerator::new();
+        gen.next_at(1000);
+        gen.state.lock().unwrap().last_random = [0x3f; 12];
+        let id = gen.next_at(1000);
+        assert_eq!(id.timestamp(), 1001);
+        assert!(id.as_str().ends_with("------------"));
+    }
+}
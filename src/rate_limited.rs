@@ -0,0 +1,169 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::error::Error;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::atomic::{AtomicU64, Ordering};
+use crate::IdGen;
+
+/// An error returned by [`RateLimitedGen::try_next`] when issuing an ID now would exceed the
+/// rate limit.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RateLimited {
+    retry_after: Duration,
+}
+
+impl RateLimited {
+    /// How long until an ID can be issued (assuming no one else takes it first).
+    #[inline]
+    pub fn retry_after(&self) -> Duration {
+        self.retry_after
+    }
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ID rate limit exceeded, retry after {:?}",
+            self.retry_after
+        )
+    }
+}
+
+impl Error for RateLimited {}
+
+/// An [`IdGen`] that issues at most a given number of IDs per second, for using ID issuance as
+/// an admission-control point.
+///
+/// The limit is a token bucket: up to `burst` IDs can be issued at once, and the bucket refills
+/// at `per_second` IDs per second. [`try_next`](RateLimitedGen::try_next) fails when the bucket
+/// is empty, while [`IdGen::next`] sleeps until it isn't:
+///
+/// ```
+/// use snowflake::{IdGen, RateLimitedGen, SnowflakeGenerator};
+///
+/// let gen = RateLimitedGen::new(SnowflakeGenerator::new(1), 1000, 2);
+/// assert!(gen.try_next().is_ok());
+/// assert!(gen.try_next().is_ok());
+/// let limited = gen.try_next().unwrap_err();
+/// assert!(limited.retry_after().as_millis() <= 1);
+/// gen.next(); // Waits for the bucket to refill.
+/// ```
+///
+/// Like the generators, the limiter is lock-free: its whole state is a single `AtomicU64`
+/// updated by CAS (using the generic cell rate algorithm).
+#[derive(Debug)]
+pub struct RateLimitedGen<G> {
+    inner: G,
+    // Nanoseconds between IDs at the sustained rate.
+    interval: u64,
+    // How far (in nanoseconds) the schedule may run ahead of the clock: `burst - 1` intervals.
+    tolerance: u64,
+    start: Instant,
+    // The time (in nanoseconds since `start`) at which the bucket will be full again.
+    full_at: AtomicU64,
+}
+
+impl<G: IdGen> RateLimitedGen<G> {
+    /// Limits `inner` to `per_second` IDs per second, in bursts of up to `burst` IDs.
+    ///
+    /// **panics** if `per_second` or `burst` is zero.
+    pub fn new(inner: G, per_second: u32, burst: u32) -> Self {
+        assert!(per_second > 0, "the rate limit must be positive");
+        assert!(burst > 0, "the burst size must be positive");
+        let interval = 1_000_000_000 / u64::from(per_second);
+        RateLimitedGen {
+            inner,
+            interval,
+            tolerance: interval * u64::from(burst - 1),
+            start: Instant::now(),
+            full_at: AtomicU64::new(0),
+        }
+    }
+
+    /// Generates an ID if the rate limit allows it.
+    pub fn try_next(&self) -> Result<G::Id, RateLimited> {
+        self.acquire()?;
+        Ok(self.inner.next())
+    }
+
+    /// The wrapped generator. IDs generated with it directly don't count towards the limit.
+    #[inline]
+    pub fn inner(&self) -> &G {
+        &self.inner
+    }
+
+    /// Unwraps the generator.
+    #[inline]
+    pub fn into_inner(self) -> G {
+        self.inner
+    }
+
+    fn acquire(&self) -> Result<(), RateLimited> {
+        let now = self.start.elapsed().as_nanos() as u64;
+        let mut current = self.full_at.load(Ordering::Relaxed);
+        loop {
+            let full_at = current.max(now);
+            if full_at - now > self.tolerance {
+                return Err(RateLimited {
+                    retry_after: Duration::from_nanos(full_at - now - self.tolerance),
+                });
+            }
+            match self.full_at.compare_exchange_weak(
+                current,
+                full_at + self.interval,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Ok(()),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+/// Waits for the rate limit instead of failing.
+impl<G: IdGen> IdGen for RateLimitedGen<G> {
+    type Id = G::Id;
+
+    fn next(&self) -> G::Id {
+        loop {
+            match self.try_next() {
+                Ok(id) => return id,
+                Err(limited) => thread::sleep(limited.retry_after()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RateLimitedGen;
+    use crate::{IdGen, ProcessUniqueId};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_rate_limit() {
+        let gen = RateLimitedGen::new(ProcessUniqueId::new, 100, 3);
+        for _ in 0..3 {
+            gen.try_next().unwrap();
+        }
+        let limited = gen.try_next().unwrap_err();
+        assert!(limited.retry_after() <= Duration::from_millis(10));
+
+        let start = Instant::now();
+        for _ in 0..5 {
+            gen.next();
+        }
+        // The bucket was empty, so five more IDs take about five intervals.
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
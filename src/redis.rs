@@ -0,0 +1,181 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! redis-rs integration.
+//!
+//! Requires the `redis` feature. The ID types implement `ToRedisArgs` and `FromRedisValue`, so
+//! they can be passed to redis-rs as keys, values, and set members, and read back from replies.
+//! They're written in their display form, which keeps keys readable in `redis-cli`; wrap them in
+//! [`Binary`] to write their (shorter, fixed-width) binary form instead:
+//!
+//! ```no_run
+//! use redis::Commands;
+//! use snowflake::redis::Binary;
+//! use snowflake::{SnowflakeGenerator, SnowflakeId};
+//!
+//! # fn main() -> redis::RedisResult<()> {
+//! let mut con = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+//! let id = SnowflakeGenerator::new(1).next();
+//! con.set::<_, _, ()>(id, "pending")?;
+//! con.sadd::<_, _, ()>("jobs", Binary(id))?;
+//! let members: Vec<Binary<SnowflakeId>> = con.smembers("jobs")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The binary forms are big-endian, so they sort the same way as the IDs: the IDs' integer
+//! values for [`ProcessUniqueId`] (as in `to_bytes`), [`SnowflakeId`], [`FlakeId`],
+//! [`SonyflakeId`], [`Tsid`] and [`SmallUniqueId`], and their 12 bytes for [`ObjectId`] and
+//! [`Xid`]. [`PushId`]s and [`LamportId`]s only have the display form.
+//!
+//! Note: as with deserialization, an ID read back in a *different* process is not unique in that
+//! process.
+use core::convert::TryFrom;
+use std::str;
+
+use redis::{FromRedisValue, ParsingError, RedisWrite, ToRedisArgs, ToSingleRedisArg, Value};
+
+use crate::{
+    FlakeId, LamportId, ObjectId, ProcessUniqueId, PushId, SmallUniqueId, SnowflakeId, SonyflakeId,
+    Tsid, Xid,
+};
+
+/// An ID written to and read from redis in its binary form; see the
+/// [module documentation](self).
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Binary<T>(pub T);
+
+/// The string in a reply, if it is one.
+fn reply_str(value: &Value) -> Result<&str, ParsingError> {
+    match value {
+        Value::BulkString(bytes) => Ok(str::from_utf8(bytes)?),
+        Value::SimpleString(s) | Value::VerbatimString { text: s, .. } => Ok(s),
+        _ => Err(format!("expected an ID string, got {:?}", value).into()),
+    }
+}
+
+/// The bytes in a reply, if it has `N` of them.
+fn reply_bytes<const N: usize>(value: &Value) -> Result<[u8; N], ParsingError> {
+    match value {
+        Value::BulkString(bytes) => <[u8; N]>::try_from(&bytes[..])
+            .map_err(|_| format!("expected a {}-byte ID, got {} bytes", N, bytes.len()).into()),
+        _ => Err(format!("expected a binary ID, got {:?}", value).into()),
+    }
+}
+
+macro_rules! impl_string {
+    ($($ty:ident),*) => {$(
+        impl ToRedisArgs for $ty {
+            fn write_redis_args<W: ?Sized + RedisWrite>(&self, out: &mut W) {
+                out.write_arg_fmt(self)
+            }
+        }
+
+        impl ToSingleRedisArg for $ty {}
+
+        impl FromRedisValue for $ty {
+            fn from_redis_value_ref(value: &Value) -> Result<Self, ParsingError> {
+                let s = reply_str(value)?;
+                s.parse()
+                    .map_err(|_| format!("{:?} is not a valid {}", s, stringify!($ty)).into())
+            }
+
+            fn from_redis_value(value: Value) -> Result<Self, ParsingError> {
+                Self::from_redis_value_ref(&value)
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_binary {
+    ($($ty:ident: $len:literal, $to:expr, $from:expr;)*) => {$(
+        impl ToRedisArgs for Binary<$ty> {
+            fn write_redis_args<W: ?Sized + RedisWrite>(&self, out: &mut W) {
+                let to: fn($ty) -> [u8; $len] = $to;
+                out.write_arg(&to(self.0))
+            }
+        }
+
+        impl ToSingleRedisArg for Binary<$ty> {}
+
+        impl FromRedisValue for Binary<$ty> {
+            fn from_redis_value_ref(value: &Value) -> Result<Self, ParsingError> {
+                let from: fn([u8; $len]) -> Option<$ty> = $from;
+                from(reply_bytes(value)?).map(Binary).ok_or_else(|| {
+                    concat!("ID out of range for ", stringify!($ty)).to_owned().into()
+                })
+            }
+
+            fn from_redis_value(value: Value) -> Result<Self, ParsingError> {
+                Self::from_redis_value_ref(&value)
+            }
+        }
+    )*};
+}
+
+impl_string!(
+    ProcessUniqueId,
+    SnowflakeId,
+    FlakeId,
+    SonyflakeId,
+    Tsid,
+    SmallUniqueId,
+    ObjectId,
+    Xid,
+    PushId,
+    LamportId
+);
+
+impl_binary! {
+    ProcessUniqueId: 16, ProcessUniqueId::to_bytes, |b| ProcessUniqueId::from_bytes(b).ok();
+    SnowflakeId: 8, |id| u64::from(id).to_be_bytes(),
+        |b| SnowflakeId::try_from(u64::from_be_bytes(b)).ok();
+    FlakeId: 16, |id| u128::from(id).to_be_bytes(), |b| Some(FlakeId::from(u128::from_be_bytes(b)));
+    SonyflakeId: 8, |id| u64::from(id).to_be_bytes(),
+        |b| SonyflakeId::try_from(u64::from_be_bytes(b)).ok();
+    Tsid: 8, |id| u64::from(id).to_be_bytes(), |b| Some(Tsid::from(u64::from_be_bytes(b)));
+    SmallUniqueId: 8, |id| u64::from(id).to_be_bytes(),
+        |b| Some(SmallUniqueId::from(u64::from_be_bytes(b)));
+    ObjectId: 12, ObjectId::bytes, |b| Some(ObjectId::from_bytes(b));
+    Xid: 12, Xid::bytes, |b| Some(Xid::from_bytes(b));
+}
+
+#[cfg(test)]
+mod test {
+    use super::Binary;
+    use crate::{ProcessUniqueId, SnowflakeId};
+    use core::convert::TryFrom;
+    use redis::{FromRedisValue, ToRedisArgs, Value};
+
+    #[test]
+    fn test_redis_round_trip() {
+        let id = ProcessUniqueId::new();
+        let args = id.to_redis_args();
+        assert_eq!(args, [id.to_string().into_bytes()]);
+        let value = Value::BulkString(args[0].clone());
+        assert_eq!(ProcessUniqueId::from_redis_value(value), Ok(id));
+        let value = Value::SimpleString(id.to_string());
+        assert_eq!(ProcessUniqueId::from_redis_value(value), Ok(id));
+
+        let binary = Binary(id).to_redis_args();
+        assert_eq!(binary, [id.to_bytes().to_vec()]);
+        let value = Value::BulkString(binary[0].clone());
+        assert_eq!(Binary::from_redis_value(value), Ok(Binary(id)));
+
+        assert!(ProcessUniqueId::from_redis_value(Value::Int(1)).is_err());
+        assert!(
+            Binary::<ProcessUniqueId>::from_redis_value(Value::BulkString(vec![0; 8])).is_err()
+        );
+        // The sign bit is never set in a snowflake ID.
+        let value = Value::BulkString(vec![0xff; 8]);
+        assert!(Binary::<SnowflakeId>::from_redis_value(value).is_err());
+        let id = SnowflakeId::try_from(1u64 << 40).unwrap();
+        let value = Value::BulkString(Binary(id).to_redis_args().remove(0));
+        assert_eq!(Binary::from_redis_value(value), Ok(Binary(id)));
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Recording and replaying process unique ID allocation, for deterministic replay and
+//! simulation testing.
+//!
+//! Requires the `replay` feature. Which IDs a thread gets depends on the order in which threads
+//! happen to claim their blocks. [`record`] logs every block claimed, along with the name of the
+//! claiming thread; [`replay`] makes a later run hand each thread the blocks it got in the
+//! recorded run, in the same order, so every thread creates the same IDs again:
+//!
+//! ```
+//! use snowflake::{replay, ProcessUniqueId};
+//! use std::sync::{Arc, Mutex};
+//!
+//! # #[derive(Clone)]
+//! # struct Shared(Arc<Mutex<Vec<u8>>>);
+//! # impl std::io::Write for Shared {
+//! #     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+//! #         self.0.lock().unwrap().write(buf)
+//! #     }
+//! #     fn flush(&mut self) -> std::io::Result<()> {
+//! #         Ok(())
+//! #     }
+//! # }
+//! # let log = Shared(Arc::new(Mutex::new(Vec::new())));
+//! # let spawn = || {
+//! #     std::thread::Builder::new()
+//! #         .name("worker".into())
+//! #         .spawn(ProcessUniqueId::new)
+//! #         .unwrap()
+//! #         .join()
+//! #         .unwrap()
+//! # };
+//! // First run: record (to a file, in practice).
+//! replay::record(log.clone());
+//! let recorded = spawn();
+//! replay::stop().unwrap();
+//!
+//! // Later run: replay.
+//! let bytes = log.0.lock().unwrap().clone();
+//! replay::replay(&bytes[..]).unwrap();
+//! assert_eq!(spawn(), recorded);
+//! replay::stop().unwrap();
+//! ```
+//!
+//! Threads are told apart by name, so every thread that creates IDs needs a name that's unique
+//! and the same in every run (unnamed threads all share one, which only works if at most one of
+//! them creates IDs). When a thread claims a block that wasn't recorded, the replay has diverged
+//! and the thread panics. Only blocks of process unique IDs (including those of `IdHandle`s,
+//! `IdRange`s and the other types built on them) are recorded; time based IDs aren't:
+//!
+//! ```
+//! use snowflake::{replay, IdRange};
+//!
+//! # #[derive(Clone)]
+//! # struct Shared(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+//! # impl std::io::Write for Shared {
+//! #     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+//! #         self.0.lock().unwrap().write(buf)
+//! #     }
+//! #     fn flush(&mut self) -> std::io::Result<()> {
+//! #         Ok(())
+//! #     }
+//! # }
+//! # let log = Shared(Default::default());
+//! let reserve = || {
+//!     std::thread::Builder::new()
+//!         .name("batch".into())
+//!         // More IDs than a thread's block holds, so they come straight from the shared prefix.
+//!         .spawn(|| IdRange::reserve(1 << 40))
+//!         .unwrap()
+//!         .join()
+//!         .unwrap()
+//! };
+//! replay::record(log.clone());
+//! let recorded = reserve();
+//! replay::stop().unwrap();
+//!
+//! let bytes = log.0.lock().unwrap().clone();
+//! replay::replay(&bytes[..]).unwrap();
+//! assert_eq!(reserve(), recorded);
+//! replay::stop().unwrap();
+//! ```
+//!
+//! # Format
+//!
+//! One line per block: the prefix, the first offset, and the last offset in hex, then the thread
+//! name, separated by spaces.
+
+use core::convert::TryFrom;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::atomic::Ordering;
+use crate::process_unique_id::{global_counter, RECLAIMED, SHARED_PREFIX};
+
+// The name recorded for threads without one.
+const UNNAMED: &str = "<unnamed>";
+
+type Blocks = HashMap<String, VecDeque<(usize, u64, u64)>>;
+
+enum Mode {
+    Off,
+    Recording(Box<dyn Write + Send>),
+    Replaying(Blocks),
+}
+
+static MODE: Mutex<Mode> = Mutex::new(Mode::Off);
+
+fn mode() -> std::sync::MutexGuard<'static, Mode> {
+    MODE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn thread_name() -> String {
+    thread::current().name().unwrap_or(UNNAMED).to_owned()
+}
+
+/// Starts writing every block claimed to `log`, replacing any recording or replay in progress.
+pub fn record<W: Write + Send + 'static>(log: W) {
+    *mode() = Mode::Recording(Box::new(log));
+}
+
+/// Starts handing threads the blocks recorded in `log`, replacing any recording or replay in
+/// progress.
+///
+/// The prefix counter moves past every recorded prefix (and blocks handed back by exited threads
+/// are dropped) first, so no block claimed outside the replay overlaps a replayed one. Fails with
+/// [`io::ErrorKind::InvalidData`] if `log` isn't a recording.
+pub fn replay<R: Read>(log: R) -> io::Result<()> {
+    let blocks = parse(BufReader::new(log))?;
+    if let Some(max) = blocks
+        .values()
+        .flatten()
+        .map(|&(prefix, _, _)| prefix)
+        .max()
+    {
+        let mut shared = SHARED_PREFIX.lock().unwrap_or_else(|e| e.into_inner());
+        *shared = None;
+        RECLAIMED.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        global_counter().fetch_max(max.saturating_add(1), Ordering::Relaxed);
+    }
+    *mode() = Mode::Replaying(blocks);
+    Ok(())
+}
+
+/// Stops recording or replaying. A recording's log is flushed and dropped.
+pub fn stop() -> io::Result<()> {
+    match std::mem::replace(&mut *mode(), Mode::Off) {
+        Mode::Recording(mut log) => log.flush(),
+        _ => Ok(()),
+    }
+}
+
+/// Logs a block claimed by the current thread, if recording.
+///
+/// **panics** if writing to the log fails: the recording would be useless.
+pub(crate) fn record_block(prefix: usize, first: u64, last: u64) {
+    if let Mode::Recording(log) = &mut *mode() {
+        writeln!(log, "{:x} {:x} {:x} {}", prefix, first, last, thread_name())
+            .unwrap_or_else(|e| panic!("failed to record an ID block: {}", e));
+    }
+}
+
+/// Returns the current thread's next recorded block, if replaying.
+///
+/// **panics** if the thread has no recorded blocks left.
+pub(crate) fn replayed_block() -> Option<(usize, u64, u64)> {
+    match &mut *mode() {
+        Mode::Replaying(blocks) => {
+            let name = thread_name();
+            let block = blocks.get_mut(&name).and_then(VecDeque::pop_front);
+            Some(block.unwrap_or_else(|| {
+                panic!(
+                    "ID replay diverged: thread {:?} claimed more blocks than it did when recording",
+                    name
+                )
+            }))
+        }
+        _ => None,
+    }
+}
+
+fn parse<R: BufRead>(log: R) -> io::Result<Blocks> {
+    fn invalid(line: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("malformed ID replay log line: {:?}", line),
+        )
+    }
+
+    let mut blocks = Blocks::new();
+    for line in log.lines() {
+        let line = line?;
+        let mut fields = line.splitn(4, ' ');
+        let mut hex = || -> Option<u64> { u64::from_str_radix(fields.next()?, 16).ok() };
+        let (prefix, first, last) = match (hex(), hex(), hex()) {
+            (Some(prefix), Some(first), Some(last)) if first <= last => (prefix, first, last),
+            _ => return Err(invalid(&line)),
+        };
+        let prefix = usize::try_from(prefix).map_err(|_| invalid(&line))?;
+        let name = fields.next().ok_or_else(|| invalid(&line))?;
+        blocks
+            .entry(name.to_owned())
+            .or_default()
+            .push_back((prefix, first, last));
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_parse_log() {
+        let blocks =
+            parse(&b"0 0 ffffffff main\n1 0 ff worker 1\n0 100000000 1ffffffff main\n"[..])
+                .unwrap();
+        assert_eq!(
+            blocks["main"].iter().copied().collect::<Vec<_>>(),
+            [(0, 0, 0xffff_ffff), (0, 0x1_0000_0000, 0x1_ffff_ffff)]
+        );
+        assert_eq!(blocks["worker 1"][0], (1, 0, 0xff));
+
+        for bad in ["0 0 main\n", "0 10 f main\n", "0 0 g main\n", "0 0 f\n"] {
+            assert_eq!(
+                parse(bad.as_bytes()).unwrap_err().kind(),
+                ErrorKind::InvalidData,
+                "{:?}",
+                bad
+            );
+        }
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Helpers for request ID headers such as `X-Request-Id`.
+//!
+//! Requires the `http` feature. Services that accept request IDs from their callers need to
+//! check them before logging them or passing them on: [`parse`] accepts the common formats
+//! (ULIDs, UUIDs, process unique IDs, vendor-prefixed IDs like `req_8f3k2`) as well as other
+//! short tokens of URL-safe characters, and [`accept_or_generate`] falls back to a new
+//! [`ProcessUniqueId`] when there's no usable ID:
+//!
+//! ```
+//! use http::HeaderValue;
+//! use snowflake::request_id::{self, Format};
+//!
+//! let uuid = HeaderValue::from_static("67e55044-10b1-426f-9247-bb680e5fe0c8");
+//! assert_eq!(request_id::parse(&uuid), Ok(Format::Uuid));
+//! assert_eq!(request_id::accept_or_generate(Some(&uuid)), uuid);
+//!
+//! // Not something to put in a log line; use our own.
+//! let bad = HeaderValue::from_static("a b\"c");
+//! assert!(!request_id::is_valid(&bad));
+//! let ours = request_id::accept_or_generate(Some(&bad));
+//! assert_eq!(request_id::parse(&ours), Ok(Format::ProcessUniqueId));
+//! ```
+use http::HeaderValue;
+
+use crate::{ParseIdError, ProcessUniqueId};
+
+/// The longest request ID [`parse`] accepts, in bytes.
+pub const MAX_LEN: usize = 128;
+
+/// The longest prefix of a vendor-prefixed ID, in bytes.
+const MAX_PREFIX_LEN: usize = 32;
+
+/// The format of a request ID, as recognized by [`parse`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Format {
+    /// A ULID: 26 Crockford base32 characters.
+    Ulid,
+    /// A UUID in its hyphenated form, in either case.
+    Uuid,
+    /// A [`ProcessUniqueId`], as formatted by `Display`.
+    ProcessUniqueId,
+    /// An ID with a lowercase vendor prefix, like `req_8f3k2` or `txn_01H8XGJWBWBAQ4ZT7DPT5Z1ZS6`.
+    Prefixed,
+    /// Some other token of URL-safe characters (letters, digits, and `-._~`).
+    Other,
+}
+
+/// Checks a request ID and returns its format.
+///
+/// Fails if the ID is empty, longer than [`MAX_LEN`], or has characters other than ASCII
+/// letters, digits, and `-._~`. Those are all a request ID should need, and keep IDs from
+/// callers safe to put in log lines and headers.
+pub fn parse(value: &HeaderValue) -> Result<Format, ParseIdError> {
+    parse_str(
+        value
+            .to_str()
+            .map_err(|_| ParseIdError::invalid("a header value of visible ASCII characters"))?,
+    )
+}
+
+fn parse_str(s: &str) -> Result<Format, ParseIdError> {
+    if let Some(i) = s.bytes().position(|b| !is_token_byte(b)) {
+        return Err(ParseIdError::at(s, i, "a letter, digit, or one of `-._~`"));
+    }
+    if s.is_empty() {
+        return Err(ParseIdError::at(s, 0, "a request ID"));
+    }
+    if s.len() > MAX_LEN {
+        return Err(ParseIdError::at(s, MAX_LEN, "the end of the input"));
+    }
+    Ok(if is_ulid(s) {
+        Format::Ulid
+    } else if is_uuid(s) {
+        Format::Uuid
+    } else if s.parse::<ProcessUniqueId>().is_ok() {
+        Format::ProcessUniqueId
+    } else if split_prefix(s).is_some() {
+        Format::Prefixed
+    } else {
+        Format::Other
+    })
+}
+
+/// Returns true if [`parse`] accepts the ID.
+#[inline]
+pub fn is_valid(value: &HeaderValue) -> bool {
+    parse(value).is_ok()
+}
+
+/// Creates a new request ID from a [`ProcessUniqueId`].
+pub fn generate() -> HeaderValue {
+    to_header_value(ProcessUniqueId::new().to_string())
+}
+
+/// Creates a new vendor-prefixed request ID, `<prefix>_<process unique ID>`.
+///
+/// ```
+/// use snowflake::request_id::{self, Format};
+///
+/// let id = request_id::generate_prefixed("req");
+/// assert!(id.to_str().unwrap().starts_with("req_puid-"));
+/// assert_eq!(request_id::parse(&id), Ok(Format::Prefixed));
+/// ```
+///
+/// **panics** if `prefix` isn't a lowercase ASCII letter followed by up to 31 lowercase ASCII
+/// letters and digits.
+pub fn generate_prefixed(prefix: &str) -> HeaderValue {
+    assert!(is_prefix(prefix), "invalid request ID prefix: {:?}", prefix);
+    to_header_value(format!("{}_{}", prefix, ProcessUniqueId::new()))
+}
+
+/// Returns `value` if [`parse`] accepts it, and a new ID from [`generate`] otherwise (including
+/// when there's no value at all).
+pub fn accept_or_generate(value: Option<&HeaderValue>) -> HeaderValue {
+    match value {
+        Some(value) if is_valid(value) => value.clone(),
+        _ => generate(),
+    }
+}
+
+/// Splits a vendor-prefixed ID into its prefix and the rest.
+///
+/// ```
+/// use snowflake::request_id::split_prefix;
+///
+/// assert_eq!(split_prefix("req_8f3k2"), Some(("req", "8f3k2")));
+/// assert_eq!(split_prefix("8f3k2"), None);
+/// ```
+pub fn split_prefix(s: &str) -> Option<(&str, &str)> {
+    let (prefix, rest) = s.split_once('_')?;
+    if is_prefix(prefix) && !rest.is_empty() {
+        Some((prefix, rest))
+    } else {
+        None
+    }
+}
+
+fn to_header_value(s: String) -> HeaderValue {
+    HeaderValue::from_str(&s).expect("generated request IDs are valid header values")
+}
+
+#[inline]
+fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~')
+}
+
+fn is_prefix(s: &str) -> bool {
+    let s = s.as_bytes();
+    matches!(s.first(), Some(b'a'..=b'z'))
+        && s.len() <= MAX_PREFIX_LEN
+        && s.iter()
+            .all(|&b| b.is_ascii_lowercase() || b.is_ascii_digit())
+}
+
+fn is_ulid(s: &str) -> bool {
+    // The first character only has 3 bits to fill, so it can't be over 7.
+    s.len() == 26
+        && matches!(s.as_bytes()[0], b'0'..=b'7')
+        && s.bytes().all(|b| {
+            let b = b.to_ascii_uppercase();
+            b.is_ascii_digit()
+                || (b.is_ascii_uppercase() && !matches!(b, b'I' | b'L' | b'O' | b'U'))
+        })
+}
+
+fn is_uuid(s: &str) -> bool {
+    s.len() == 36
+        && s.bytes().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_str, Format};
+
+    #[test]
+    fn test_parse_formats() {
+        for (id, format) in [
+            ("01ARZ3NDEKTSV4RRFFQ69G5FAV", Format::Ulid),
+            ("01arz3ndektsv4rrffq69g5fav", Format::Ulid),
+            ("67E55044-10B1-426F-9247-BB680E5FE0C8", Format::Uuid),
+            ("puid-1-2a", Format::ProcessUniqueId),
+            ("req_8f3k2", Format::Prefixed),
+            ("txn_01ARZ3NDEKTSV4RRFFQ69G5FAV", Format::Prefixed),
+            // Not quite ULIDs, UUIDs, or prefixes.
+            ("81ARZ3NDEKTSV4RRFFQ69G5FAV", Format::Other),
+            ("01ARZ3NDEKTSV4RRFFQ69G5FAU", Format::Other),
+            ("67e5504410b1426f9247bb680e5fe0c8", Format::Other),
+            ("Req_8f3k2", Format::Other),
+            ("req_", Format::Other),
+            ("_8f3k2", Format::Other),
+            ("a.b~c-d", Format::Other),
+        ] {
+            assert_eq!(parse_str(id), Ok(format), "{}", id);
+        }
+        for bad in ["", "a b", "a/b", "a\"b", "a:b", &"a".repeat(129)] {
+            assert!(parse_str(bad).is_err(), "{:?}", bad);
+        }
+        assert!(parse_str(&"a".repeat(128)).is_ok());
+    }
+}
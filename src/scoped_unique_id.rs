@@ -0,0 +1,224 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem;
+use std::sync::Mutex;
+
+#[cfg(feature = "bevy")]
+use bevy_ecs::reflect::ReflectComponent;
+
+use crate::display::pad_id;
+
+// (next free prefix, wrap on exhaustion) for each namespace. Only touched when a thread starts
+// using a namespace (or exhausts its 2^64 offsets), so a mutex is fine.
+static NAMESPACE_PREFIXES: Mutex<BTreeMap<u32, (usize, bool)>> = Mutex::new(BTreeMap::new());
+
+fn next_namespace_prefix(namespace: u32) -> usize {
+    let mut prefixes = NAMESPACE_PREFIXES.lock().unwrap_or_else(|e| e.into_inner());
+    let (next, wrap) = prefixes.entry(namespace).or_default();
+    if *next == usize::MAX {
+        assert!(
+            *wrap,
+            "Snow Crash: Go home and reevaluate your threading model!"
+        );
+        *next = 0;
+    }
+    let prefix = *next;
+    *next += 1;
+    prefix
+}
+
+// (namespace, prefix, next offset) for each namespace this thread has used. Programs use a
+// handful of namespaces so a linear scan beats hashing.
+thread_local! {
+    static NEXT_LOCAL_SCOPED_IDS: RefCell<Vec<(u32, usize, u64)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A process unique ID belonging to the namespace `NS`.
+///
+/// Each namespace has its own independent counters, and IDs from different namespaces are
+/// different types so they can't be mixed up. This is handy for giving subsystems (textures,
+/// entities, jobs, ...) their own IDs without defining a marker type for each:
+///
+/// ```
+/// use snowflake::ScopedUniqueId;
+///
+/// type TextureId = ScopedUniqueId<1>;
+/// type JobId = ScopedUniqueId<2>;
+///
+/// let texture = TextureId::new();
+/// let job = JobId::new();
+/// assert_eq!(texture.to_string(), "puid-ns1-0-0");
+/// assert_eq!(job.to_string(), "puid-ns2-0-0");
+/// ```
+///
+/// The uniqueness guarantees and limits are the same as those of
+/// [`ProcessUniqueId`](crate::ProcessUniqueId), per namespace.
+///
+/// # Memory layout
+///
+/// The same as [`ProcessUniqueId`](crate::ProcessUniqueId)'s, for every namespace: the namespace
+/// is only part of the type.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_ecs::component::Component, bevy_reflect::Reflect),
+    reflect(opaque, Component, Debug, PartialEq, Hash, Clone)
+)]
+#[repr(C)]
+pub struct ScopedUniqueId<const NS: u32> {
+    prefix: usize,
+    offset: u64,
+}
+
+const _: () = {
+    assert!(mem::size_of::<ScopedUniqueId<0>>() == mem::size_of::<crate::ProcessUniqueId>());
+    assert!(mem::align_of::<ScopedUniqueId<0>>() == mem::align_of::<crate::ProcessUniqueId>());
+    assert!(
+        mem::offset_of!(ScopedUniqueId<0>, offset)
+            == mem::offset_of!(crate::ProcessUniqueId, offset)
+    );
+};
+
+impl<const NS: u32> fmt::Display for ScopedUniqueId<NS> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(
+            f,
+            format_args!("puid-ns{}-{:x}-", NS, self.prefix),
+            format_args!("{:x}", self.offset),
+        )
+    }
+}
+
+impl<const NS: u32> ScopedUniqueId<NS> {
+    /// This ID type's namespace.
+    pub const NAMESPACE: u32 = NS;
+
+    /// Create a new unique ID in namespace `NS`.
+    ///
+    /// **panics** if there are no more unique IDs available in this namespace.
+    pub fn new() -> Self {
+        NEXT_LOCAL_SCOPED_IDS.with(|ids| {
+            let mut ids = ids.borrow_mut();
+            let entry = match ids.iter_mut().find(|(ns, _, _)| *ns == NS) {
+                Some(entry) => entry,
+                None => {
+                    ids.push((NS, next_namespace_prefix(NS), 0));
+                    ids.last_mut().unwrap()
+                }
+            };
+            let id = ScopedUniqueId {
+                prefix: entry.1,
+                offset: entry.2,
+            };
+            if entry.2 == u64::MAX {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    namespace = NS,
+                    prefix = entry.1,
+                    "ScopedUniqueId prefix used up, moving on to a new one"
+                );
+                entry.1 = next_namespace_prefix(NS);
+                entry.2 = 0;
+            } else {
+                entry.2 += 1;
+            }
+            id
+        })
+    }
+
+    /// This ID's namespace.
+    #[inline]
+    pub fn namespace(&self) -> u32 {
+        NS
+    }
+
+    /// Makes namespace `NS`'s prefix counter wrap around to zero when it runs out, instead of
+    /// panicking.
+    ///
+    /// **This gives up the uniqueness guarantee**, in the same way and for the same (rare) use
+    /// cases as [`ProcessUniqueId::set_wrap_on_exhaustion`](crate::ProcessUniqueId::set_wrap_on_exhaustion):
+    /// after wrapping, IDs still in use can be handed out again. Off by default. Only this
+    /// namespace is affected; the others keep their own setting.
+    pub fn set_wrap_on_exhaustion(wrap: bool) {
+        let mut prefixes = NAMESPACE_PREFIXES.lock().unwrap_or_else(|e| e.into_inner());
+        prefixes.entry(NS).or_default().1 = wrap;
+    }
+}
+
+/// Generator state saved by [`reset_state`]: the namespace prefixes and wrap settings, and the
+/// current thread's counters.
+#[cfg(feature = "test-util")]
+pub(crate) type SavedState = (BTreeMap<u32, (usize, bool)>, Vec<(u32, usize, u64)>);
+
+/// Saves the global and current thread's generator state, and resets it to that of a freshly
+/// started process.
+#[cfg(feature = "test-util")]
+pub(crate) fn reset_state() -> SavedState {
+    let mut prefixes = NAMESPACE_PREFIXES.lock().unwrap_or_else(|e| e.into_inner());
+    let local = NEXT_LOCAL_SCOPED_IDS.with(|ids| std::mem::take(&mut *ids.borrow_mut()));
+    (std::mem::take(&mut *prefixes), local)
+}
+
+/// Puts back state saved by [`reset_state`].
+#[cfg(feature = "test-util")]
+pub(crate) fn restore_state((prefixes, local): SavedState) {
+    *NAMESPACE_PREFIXES.lock().unwrap_or_else(|e| e.into_inner()) = prefixes;
+    NEXT_LOCAL_SCOPED_IDS.with(|ids| *ids.borrow_mut() = local);
+}
+
+impl<const NS: u32> Default for ScopedUniqueId<NS> {
+    #[inline]
+    fn default() -> Self {
+        ScopedUniqueId::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{next_namespace_prefix, ScopedUniqueId, NAMESPACE_PREFIXES};
+    use std::panic;
+    use std::thread;
+
+    #[test]
+    fn test_independent_namespaces() {
+        let a1 = ScopedUniqueId::<1001>::new();
+        let b1 = ScopedUniqueId::<1002>::new();
+        let a2 = ScopedUniqueId::<1001>::new();
+        assert_eq!((a1.prefix, a1.offset), (b1.prefix, b1.offset));
+        assert_eq!((a2.prefix, a2.offset), (a1.prefix, a1.offset + 1));
+        assert_eq!(a1.namespace(), 1001);
+        assert_eq!(b1.to_string(), format!("puid-ns1002-{:x}-0", b1.prefix));
+    }
+
+    #[test]
+    fn test_threaded_prefixes() {
+        let threads: Vec<_> = (0..16)
+            .map(|_| thread::spawn(ScopedUniqueId::<1003>::new))
+            .collect();
+        let mut ids: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 16);
+    }
+
+    #[test]
+    fn test_wrap_per_namespace() {
+        ScopedUniqueId::<1004>::set_wrap_on_exhaustion(true);
+        {
+            let mut prefixes = NAMESPACE_PREFIXES.lock().unwrap_or_else(|e| e.into_inner());
+            prefixes.entry(1004).or_default().0 = usize::MAX;
+            prefixes.entry(1005).or_default().0 = usize::MAX;
+        }
+        assert_eq!(next_namespace_prefix(1004), 0);
+        assert!(panic::catch_unwind(|| next_namespace_prefix(1005)).is_err());
+    }
+}
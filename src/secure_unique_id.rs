@@ -0,0 +1,197 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::display::pad_id;
+use crate::process_unique_id::{expect_len, expect_tag, hex_field_to_dash, ParseIdError};
+use crate::{IdOutOfRange, ProcessUniqueId};
+
+/// A [`ProcessUniqueId`] paired with 64 bits from the operating system's secure random number
+/// generator, for session handles, capability tokens, and other IDs that must not be guessable.
+///
+/// The counter half makes IDs unique within the process, with no chance of collision, but it's
+/// sequential: knowing one ID gives away the next. The random half makes each ID unguessable
+/// anyway, taking about 2^63 tries on average to hit one that was actually handed out.
+///
+/// Displayed as `secid-<prefix>-<offset>-<entropy>` (hex), with the entropy as 16 digits.
+///
+/// ```
+/// use snowflake::SecureUniqueId;
+///
+/// let a = SecureUniqueId::new();
+/// let b = SecureUniqueId::new();
+/// assert_ne!(a.id(), b.id());
+/// assert_eq!(a.to_string().parse(), Ok(a));
+/// ```
+///
+/// IDs sort by their process unique ID, then their entropy. As with `ProcessUniqueId`, only IDs
+/// created by the current process are unique in it: look tokens handed back by clients up
+/// among the ones that were issued rather than trusting them.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SecureUniqueId {
+    id: ProcessUniqueId,
+    entropy: u64,
+}
+
+impl SecureUniqueId {
+    /// Creates a new ID, reading the entropy from the operating system (one call per ID).
+    ///
+    /// **panics** under the same conditions as [`ProcessUniqueId::new`], or if the operating
+    /// system's random number generator fails.
+    pub fn new() -> Self {
+        let mut entropy = [0u8; 8];
+        getrandom::fill(&mut entropy).expect("failed to read from the OS random number generator");
+        SecureUniqueId {
+            id: ProcessUniqueId::new(),
+            entropy: u64::from_ne_bytes(entropy),
+        }
+    }
+
+    /// Pairs `id` with `entropy`, e.g. to rebuild an ID from a database row.
+    ///
+    /// IDs built this way are only as unguessable as `entropy` is random.
+    #[inline]
+    pub fn from_parts(id: ProcessUniqueId, entropy: u64) -> Self {
+        SecureUniqueId { id, entropy }
+    }
+
+    /// The process unique ID, which makes this ID unique.
+    #[inline]
+    pub fn id(self) -> ProcessUniqueId {
+        self.id
+    }
+
+    /// The random half, which makes this ID unguessable.
+    #[inline]
+    pub fn entropy(self) -> u64 {
+        self.entropy
+    }
+
+    /// Writes the ID as 24 bytes: the process unique ID's
+    /// [`to_bytes`](ProcessUniqueId::to_bytes) form followed by the entropy as a big-endian
+    /// `u64`. The bytes sort the same way as the IDs.
+    #[inline]
+    pub fn to_bytes(self) -> [u8; 24] {
+        let mut bytes = [0u8; 24];
+        bytes[..16].copy_from_slice(&self.id.to_bytes());
+        bytes[16..].copy_from_slice(&self.entropy.to_be_bytes());
+        bytes
+    }
+
+    /// Reads an ID written by [`to_bytes`](SecureUniqueId::to_bytes).
+    ///
+    /// Fails if the prefix doesn't fit in a `usize`.
+    #[inline]
+    pub fn from_bytes(bytes: [u8; 24]) -> Result<Self, IdOutOfRange> {
+        let (id, entropy) = bytes.split_at(16);
+        Ok(SecureUniqueId {
+            id: ProcessUniqueId::from_bytes(id.try_into().unwrap())?,
+            entropy: u64::from_be_bytes(entropy.try_into().unwrap()),
+        })
+    }
+}
+
+impl Default for SecureUniqueId {
+    #[inline]
+    fn default() -> Self {
+        SecureUniqueId::new()
+    }
+}
+
+impl fmt::Display for SecureUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(
+            f,
+            format_args!("secid-{:x}-{:x}-", self.id.prefix, self.id.offset),
+            format_args!("{:016x}", self.entropy),
+        )
+    }
+}
+
+/// Parses the `secid-<prefix>-<offset>-<entropy>` form produced by `Display`. The entropy must
+/// be exactly 16 hex digits.
+impl FromStr for SecureUniqueId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        let start = expect_tag(s, "secid-", "`secid-`")?;
+        let (prefix, rest) = hex_field_to_dash(s, start)?;
+        let (offset, rest) = hex_field_to_dash(s, rest)?;
+        if let Some(i) = s[rest..]
+            .bytes()
+            .take(16)
+            .position(|b| !b.is_ascii_hexdigit())
+        {
+            return Err(ParseIdError::at(s, rest + i, "a hex digit"));
+        }
+        expect_len(s, rest + 16, "a hex digit")?;
+        let entropy = u64::from_str_radix(&s[rest..], 16)
+            .map_err(|_| ParseIdError::at(s, rest, "a hex digit"))?;
+        let prefix = usize::try_from(prefix)
+            .map_err(|_| ParseIdError::at(s, start, "a prefix that fits in a usize"))?;
+        Ok(SecureUniqueId {
+            id: ProcessUniqueId { prefix, offset },
+            entropy,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SecureUniqueId;
+    use crate::ProcessUniqueId;
+
+    #[test]
+    fn test_secure_unique_id() {
+        let ids: Vec<SecureUniqueId> = (0..100).map(|_| SecureUniqueId::new()).collect();
+        assert!(ids.windows(2).all(|pair| pair[0].id() < pair[1].id()));
+        // 100 draws of 64 bits all landing on zero, or on the same value, means the RNG is broken.
+        assert!(ids.iter().any(|id| id.entropy() != 0));
+        assert!(ids
+            .windows(2)
+            .any(|pair| pair[0].entropy() != pair[1].entropy()));
+
+        for &id in &ids[..3] {
+            assert_eq!(id.to_string().parse(), Ok(id));
+            assert_eq!(SecureUniqueId::from_bytes(id.to_bytes()), Ok(id));
+        }
+
+        let id = SecureUniqueId::from_parts(ProcessUniqueId::from_u128(0x2a).unwrap(), 0xbeef);
+        assert_eq!(id.to_string(), "secid-0-2a-000000000000beef");
+        assert_eq!(&id.to_bytes()[..16], &id.id().to_bytes());
+        assert_eq!(
+            "SECID-0-2a-000000000000beef"
+                .parse::<SecureUniqueId>()
+                .unwrap_err()
+                .offset(),
+            0
+        );
+        assert_eq!(
+            "secid-0-2a-beef"
+                .parse::<SecureUniqueId>()
+                .unwrap_err()
+                .offset(),
+            15
+        );
+        assert_eq!(
+            "secid-0-2a-000000000000beefa"
+                .parse::<SecureUniqueId>()
+                .unwrap_err()
+                .offset(),
+            27
+        );
+        assert!("secid-0-000000000000beef"
+            .parse::<SecureUniqueId>()
+            .is_err());
+        assert!("secid-0-2a-00000000000xbeef"
+            .parse::<SecureUniqueId>()
+            .is_err());
+    }
+}
@@ -0,0 +1,574 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Alternative serde representations for IDs.
+//!
+//! By default IDs serialize as a `{prefix, offset}` struct. The modules here can be used with
+//! `#[serde(with = "...")]` to pick a different wire representation per field:
+//!
+//! ```
+//! use serde_derive::{Deserialize, Serialize};
+//! use snowflake::ProcessUniqueId;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Job {
+//!     #[serde(with = "snowflake::serde::as_string")]
+//!     id: ProcessUniqueId,
+//!     #[serde(with = "snowflake::serde::as_u128")]
+//!     parent: ProcessUniqueId,
+//! }
+//! ```
+//!
+//! In human readable formats (like JSON), `ProcessUniqueId`'s own `Deserialize` accepts the
+//! string form as well as the struct form. To move stored data over to strings without a
+//! migration, switch the field to `serialize_with = "snowflake::serde::as_string::serialize"`
+//! and keep deserializing it the default way: both old and new records will load.
+
+use core::fmt;
+
+use ::serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use ::serde::de::{self, Deserialize, Deserializer, Visitor};
+
+use crate::ProcessUniqueId;
+
+/// Serializes IDs as their `puid-<prefix>-<offset>` display form.
+pub mod as_string {
+    use core::fmt;
+    use core::marker::PhantomData;
+    use core::str::FromStr;
+
+    use ::serde::de::{self, Deserializer, Visitor};
+    use ::serde::ser::Serializer;
+
+    /// Serializes `id` as a string.
+    pub fn serialize<T, S>(id: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: fmt::Display,
+        S: Serializer,
+    {
+        serializer.collect_str(id)
+    }
+
+    /// Deserializes an ID from a string.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(FromStrVisitor(PhantomData))
+    }
+
+    struct FromStrVisitor<T>(PhantomData<T>);
+
+    impl<'de, T> Visitor<'de> for FromStrVisitor<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        type Value = T;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an ID string")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+            v.parse().map_err(E::custom)
+        }
+    }
+}
+
+/// Serializes maps keyed by IDs with the keys in their display form, so that they can be JSON
+/// objects.
+///
+/// JSON object keys have to be strings, so serializing a `HashMap<ProcessUniqueId, _>` with
+/// `serde_json` fails: IDs serialize as structs by default. Use this on the map instead:
+///
+/// ```
+/// use serde_derive::{Deserialize, Serialize};
+/// use snowflake::ProcessUniqueId;
+/// use std::collections::HashMap;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Jobs {
+///     #[serde(with = "snowflake::serde::as_key")]
+///     owners: HashMap<ProcessUniqueId, String>,
+/// }
+///
+/// let id = ProcessUniqueId::new();
+/// let jobs = Jobs { owners: vec![(id, "alice".to_owned())].into_iter().collect() };
+/// let json = serde_json::to_string(&jobs).unwrap();
+/// assert_eq!(json, format!(r#"{{"owners":{{"{}":"alice"}}}}"#, id));
+/// let jobs: Jobs = serde_json::from_str(&json).unwrap();
+/// assert_eq!(jobs.owners[&id], "alice");
+/// ```
+///
+/// Works with `HashMap`s (including [`IdMap`](crate::IdMap)s) and `BTreeMap`s keyed by any type
+/// with `Display` and `FromStr`, which all the ID types in this crate have. Requires the `std`
+/// feature.
+#[cfg(feature = "std")]
+pub mod as_key {
+    use core::fmt;
+    use core::hash::{BuildHasher, Hash};
+    use core::marker::PhantomData;
+    use core::str::FromStr;
+    use std::collections::{BTreeMap, HashMap};
+
+    use ::serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+    use ::serde::ser::{Serialize, Serializer};
+
+    /// A map that can be serialized with [`as_key`](self).
+    pub trait KeyedMap: Default {
+        /// The key type.
+        type Key;
+        /// The value type.
+        type Value;
+
+        /// The map's entries.
+        fn entries(&self) -> Box<dyn Iterator<Item = (&Self::Key, &Self::Value)> + '_>;
+
+        /// Adds an entry to the map.
+        fn insert_entry(&mut self, key: Self::Key, value: Self::Value);
+    }
+
+    impl<K: Eq + Hash, V, H: BuildHasher + Default> KeyedMap for HashMap<K, V, H> {
+        type Key = K;
+        type Value = V;
+
+        fn entries(&self) -> Box<dyn Iterator<Item = (&K, &V)> + '_> {
+            Box::new(self.iter())
+        }
+
+        fn insert_entry(&mut self, key: K, value: V) {
+            self.insert(key, value);
+        }
+    }
+
+    impl<K: Ord, V> KeyedMap for BTreeMap<K, V> {
+        type Key = K;
+        type Value = V;
+
+        fn entries(&self) -> Box<dyn Iterator<Item = (&K, &V)> + '_> {
+            Box::new(self.iter())
+        }
+
+        fn insert_entry(&mut self, key: K, value: V) {
+            self.insert(key, value);
+        }
+    }
+
+    struct Key<'a, K>(&'a K);
+
+    impl<K: fmt::Display> Serialize for Key<'_, K> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self.0)
+        }
+    }
+
+    struct ParsedKey<K>(K);
+
+    impl<'de, K> Deserialize<'de> for ParsedKey<K>
+    where
+        K: FromStr,
+        K::Err: fmt::Display,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            super::as_string::deserialize(deserializer).map(ParsedKey)
+        }
+    }
+
+    /// Serializes `map` with its keys as strings.
+    pub fn serialize<M, S>(map: &M, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        M: KeyedMap,
+        M::Key: fmt::Display,
+        M::Value: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_map(map.entries().map(|(k, v)| (Key(k), v)))
+    }
+
+    /// Deserializes a map whose keys are ID strings.
+    pub fn deserialize<'de, M, D>(deserializer: D) -> Result<M, D::Error>
+    where
+        M: KeyedMap,
+        M::Key: FromStr,
+        <M::Key as FromStr>::Err: fmt::Display,
+        M::Value: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(MapVisitor(PhantomData))
+    }
+
+    struct MapVisitor<M>(PhantomData<M>);
+
+    impl<'de, M> Visitor<'de> for MapVisitor<M>
+    where
+        M: KeyedMap,
+        M::Key: FromStr,
+        <M::Key as FromStr>::Err: fmt::Display,
+        M::Value: Deserialize<'de>,
+    {
+        type Value = M;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a map keyed by ID strings")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<M, A::Error> {
+            let mut map = M::default();
+            while let Some((ParsedKey(k), v)) = access.next_entry()? {
+                map.insert_entry(k, v);
+            }
+            Ok(map)
+        }
+    }
+}
+
+/// Serializes IDs as a single `u128`, prefix in the high 64 bits and offset in the low 64 bits.
+pub mod as_u128 {
+    use ::serde::de::{Deserialize, Deserializer, Error};
+    use ::serde::ser::Serializer;
+
+    use crate::ProcessUniqueId;
+
+    /// Serializes `id` as a `u128`.
+    pub fn serialize<S: Serializer>(
+        id: &ProcessUniqueId,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u128(id.to_u128())
+    }
+
+    /// Deserializes an ID from a `u128`.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<ProcessUniqueId, D::Error> {
+        let value = u128::deserialize(deserializer)?;
+        ProcessUniqueId::from_u128(value)
+            .ok_or_else(|| D::Error::custom("process unique ID prefix out of range"))
+    }
+}
+
+/// Serializes IDs as 16 bytes: the `as_u128` representation in big-endian byte order.
+pub mod as_bytes {
+    use ::serde::de::Deserializer;
+    use ::serde::ser::Serializer;
+
+    use super::BytesVisitor;
+    use crate::ProcessUniqueId;
+
+    /// Serializes `id` as bytes.
+    pub fn serialize<S: Serializer>(
+        id: &ProcessUniqueId,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&id.to_u128().to_be_bytes())
+    }
+
+    /// Deserializes an ID from bytes (or a sequence of 16 integers, for formats without a native
+    /// byte string type).
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<ProcessUniqueId, D::Error> {
+        deserializer.deserialize_bytes(BytesVisitor)
+    }
+}
+
+/// Serializes 64-bit IDs ([`SnowflakeId`](crate::SnowflakeId),
+/// [`SmallUniqueId`](crate::SmallUniqueId)) so they survive JavaScript's `JSON.parse`.
+///
+/// JavaScript numbers are doubles, so integers above [`MAX_SAFE_INTEGER`](js_safe::MAX_SAFE_INTEGER)
+/// (2^53 - 1) silently lose precision in browsers. IDs up to that bound are serialized as numbers
+/// and larger ones as decimal strings. Both forms (and strings for small IDs) are accepted when
+/// deserializing.
+pub mod js_safe {
+    use core::convert::TryFrom;
+    use core::fmt;
+    use core::marker::PhantomData;
+
+    use ::serde::de::{self, Deserializer, Visitor};
+    use ::serde::ser::Serializer;
+
+    /// The largest integer JavaScript can represent exactly, `Number.MAX_SAFE_INTEGER`.
+    pub const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+    /// Returns true if `id` survives a round trip through a JavaScript number.
+    #[inline]
+    pub fn is_safe<T: Copy + Into<u64>>(id: &T) -> bool {
+        (*id).into() <= MAX_SAFE_INTEGER
+    }
+
+    /// Serializes `id` as a number if it's safe, a string otherwise.
+    pub fn serialize<T, S>(id: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Copy + Into<u64>,
+        S: Serializer,
+    {
+        let value: u64 = (*id).into();
+        if value <= MAX_SAFE_INTEGER {
+            serializer.serialize_u64(value)
+        } else {
+            serializer.collect_str(&value)
+        }
+    }
+
+    /// Deserializes an ID from a number or a decimal string.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: TryFrom<u64>,
+        T::Error: fmt::Display,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(JsSafeVisitor(PhantomData))
+    }
+
+    struct JsSafeVisitor<T>(PhantomData<T>);
+
+    impl<'de, T> Visitor<'de> for JsSafeVisitor<T>
+    where
+        T: TryFrom<u64>,
+        T::Error: fmt::Display,
+    {
+        type Value = T;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an ID as an integer or a decimal string")
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+            T::try_from(v).map_err(E::custom)
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+            let v =
+                u64::try_from(v).map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))?;
+            self.visit_u64(v)
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+            if v.is_empty() || !v.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(E::invalid_value(de::Unexpected::Str(v), &self));
+            }
+            let v = v
+                .parse()
+                .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))?;
+            self.visit_u64(v)
+        }
+    }
+}
+
+/// The struct form `ProcessUniqueId` serializes as.
+#[derive(Deserialize)]
+#[serde(rename = "ProcessUniqueId")]
+struct Fields {
+    prefix: usize,
+    offset: u64,
+}
+
+impl From<Fields> for ProcessUniqueId {
+    #[inline]
+    fn from(fields: Fields) -> Self {
+        ProcessUniqueId {
+            prefix: fields.prefix,
+            offset: fields.offset,
+        }
+    }
+}
+
+/// Reads the `{prefix, offset}` struct written by `Serialize` and, in human readable formats, the
+/// `puid-<prefix>-<offset>` string written by [`as_string`].
+impl<'de> Deserialize<'de> for ProcessUniqueId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(StructOrStrVisitor)
+        } else {
+            // Binary formats aren't self-describing, so the struct form is the only option.
+            Fields::deserialize(deserializer).map(ProcessUniqueId::from)
+        }
+    }
+}
+
+struct StructOrStrVisitor;
+
+impl<'de> Visitor<'de> for StructOrStrVisitor {
+    type Value = ProcessUniqueId;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a process unique ID struct or string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<ProcessUniqueId, E> {
+        v.parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<ProcessUniqueId, A::Error> {
+        Fields::deserialize(MapAccessDeserializer::new(map)).map(ProcessUniqueId::from)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<ProcessUniqueId, A::Error> {
+        Fields::deserialize(SeqAccessDeserializer::new(seq)).map(ProcessUniqueId::from)
+    }
+}
+
+struct BytesVisitor;
+
+impl BytesVisitor {
+    fn finish<E: de::Error>(bytes: [u8; 16]) -> Result<ProcessUniqueId, E> {
+        ProcessUniqueId::from_u128(u128::from_be_bytes(bytes))
+            .ok_or_else(|| E::custom("process unique ID prefix out of range"))
+    }
+}
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = ProcessUniqueId;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("16 bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<ProcessUniqueId, E> {
+        let mut bytes = [0u8; 16];
+        if v.len() != bytes.len() {
+            return Err(E::invalid_length(v.len(), &self));
+        }
+        bytes.copy_from_slice(v);
+        BytesVisitor::finish(bytes)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<ProcessUniqueId, A::Error> {
+        let mut bytes = [0u8; 16];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+        }
+        if seq.next_element::<u8>()?.is_some() {
+            return Err(de::Error::invalid_length(17, &self));
+        }
+        BytesVisitor::finish(bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ProcessUniqueId, SmallUniqueId, SnowflakeId};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Record {
+        #[serde(with = "crate::serde::as_string")]
+        a: ProcessUniqueId,
+        #[serde(with = "crate::serde::as_u128")]
+        b: ProcessUniqueId,
+        #[serde(with = "crate::serde::as_bytes")]
+        c: ProcessUniqueId,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct JsRecord {
+        #[serde(with = "crate::serde::js_safe")]
+        small: SmallUniqueId,
+        #[serde(with = "crate::serde::js_safe")]
+        snowflake: SnowflakeId,
+    }
+
+    #[test]
+    fn test_js_safe() {
+        use core::convert::TryFrom;
+
+        let record = JsRecord {
+            small: SmallUniqueId::from(1u64 << 53),
+            snowflake: SnowflakeId::try_from((1u64 << 53) - 1).unwrap(),
+        };
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            json,
+            r#"{"small":"9007199254740992","snowflake":9007199254740991}"#
+        );
+        assert_eq!(serde_json::from_str::<JsRecord>(&json).unwrap(), record);
+        assert_eq!(
+            serde_json::from_str::<JsRecord>(r#"{"small":1,"snowflake":"2"}"#).unwrap(),
+            JsRecord {
+                small: SmallUniqueId::from(1u64),
+                snowflake: SnowflakeId::try_from(2u64).unwrap(),
+            }
+        );
+        assert!(serde_json::from_str::<JsRecord>(r#"{"small":-1,"snowflake":2}"#).is_err());
+        assert!(serde_json::from_str::<JsRecord>(
+            r#"{"small":1,"snowflake":"9223372036854775808"}"#
+        )
+        .is_err());
+        // Plain deserialization rejects the sign bit too.
+        assert!(serde_json::from_str::<SnowflakeId>("9223372036854775808").is_err());
+        assert!(serde_json::from_str::<crate::SonyflakeId>("9223372036854775808").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let record = Record {
+            a: ProcessUniqueId::new(),
+            b: ProcessUniqueId::new(),
+            c: ProcessUniqueId::new(),
+        };
+        let json = serde_json::to_string(&record).unwrap();
+        assert!(json.contains(&format!("\"{}\"", record.a)));
+        assert!(json.contains(&format!("{}", record.b.to_u128())));
+        assert_eq!(serde_json::from_str::<Record>(&json).unwrap(), record);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_as_key() {
+        use core::convert::TryFrom;
+        use std::collections::{BTreeMap, HashMap};
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Maps {
+            #[serde(with = "crate::serde::as_key")]
+            puids: HashMap<ProcessUniqueId, u32>,
+            #[serde(with = "crate::serde::as_key")]
+            snowflakes: BTreeMap<SnowflakeId, bool>,
+        }
+
+        let (a, b) = (ProcessUniqueId::new(), ProcessUniqueId::new());
+        let maps = Maps {
+            puids: vec![(a, 1), (b, 2)].into_iter().collect(),
+            snowflakes: vec![(SnowflakeId::try_from(7u64).unwrap(), true)]
+                .into_iter()
+                .collect(),
+        };
+        let json = serde_json::to_string(&maps).unwrap();
+        assert!(json.contains(&format!("\"{}\":1", a)));
+        assert!(json.contains(r#""snowflakes":{"7":true}"#));
+        assert_eq!(serde_json::from_str::<Maps>(&json).unwrap(), maps);
+        assert!(serde_json::from_str::<Maps>(r#"{"puids":{"x":1},"snowflakes":{}}"#).is_err());
+    }
+
+    #[test]
+    fn test_struct_or_string() {
+        let id = ProcessUniqueId::new();
+        let legacy = serde_json::to_string(&id).unwrap();
+        assert!(legacy.starts_with("{\"prefix\":"));
+        assert_eq!(
+            serde_json::from_str::<ProcessUniqueId>(&legacy).unwrap(),
+            id
+        );
+        let string = serde_json::to_string(&id.to_string()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ProcessUniqueId>(&string).unwrap(),
+            id
+        );
+        let seq = format!("[{},{}]", id.prefix, id.offset);
+        assert_eq!(serde_json::from_str::<ProcessUniqueId>(&seq).unwrap(), id);
+
+        assert!(serde_json::from_str::<ProcessUniqueId>("\"spuid-1-2\"").is_err());
+        assert!(serde_json::from_str::<ProcessUniqueId>("{\"prefix\":1}").is_err());
+        assert!(serde_json::from_str::<ProcessUniqueId>("7").is_err());
+    }
+}
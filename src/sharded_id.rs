@@ -0,0 +1,353 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::convert::TryFrom;
+use core::fmt;
+use core::mem;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "std")]
+use crate::atomic::{AtomicU64, Ordering};
+use crate::display::pad_id;
+use crate::process_unique_id::{decimal_u64, ParseIdError};
+#[cfg(feature = "std")]
+use crate::snowflake_id::TWITTER_EPOCH_MS;
+#[cfg(feature = "std")]
+use crate::stats;
+use crate::IdOutOfRange;
+
+/// The number of bits below the timestamp, shared between the shard and the sequence number.
+const LOW_BITS: u32 = 22;
+
+#[cfg(feature = "std")]
+const MAX_TIMESTAMP: u64 = (1 << 41) - 1;
+
+/// A 64-bit, roughly time-ordered ID that embeds a shard number, in the style of Instagram's IDs.
+///
+/// The layout is a zero sign bit, a 41-bit millisecond timestamp (relative to the generator's
+/// epoch), a `SHARD_BITS`-bit shard number, and a sequence number in the remaining
+/// `22 - SHARD_BITS` bits. The default of 13 shard bits matches Instagram's 8192 shards; keeping
+/// the sign bit clear leaves 512 IDs per millisecond, where Instagram's use all 64 bits for 1024.
+///
+/// Because the shard is part of the ID, storage routing can be computed from the ID alone:
+///
+/// ```
+/// use snowflake::{ShardedId, ShardedIdGenerator};
+///
+/// let gen = ShardedIdGenerator::<13>::new();
+/// let shard = ShardedId::<13>::shard_of(b"customer-42");
+/// let id = gen.next(shard);
+/// assert_eq!(id.shard(), shard);
+/// ```
+///
+/// Displayed as a decimal integer.
+///
+/// # Memory layout
+///
+/// `#[repr(transparent)]` over a `u64`, whatever `SHARD_BITS` is.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(Serialize, Deserialize),
+    serde(try_from = "u64")
+)]
+#[repr(transparent)]
+pub struct ShardedId<const SHARD_BITS: u32 = 13>(u64);
+
+const _: () = assert!(
+    mem::size_of::<ShardedId>() == 8 && mem::align_of::<ShardedId>() == mem::align_of::<u64>()
+);
+
+impl<const SHARD_BITS: u32> ShardedId<SHARD_BITS> {
+    const SEQUENCE_BITS: u32 = LOW_BITS - SHARD_BITS;
+
+    /// Milliseconds since the generator's epoch.
+    #[inline]
+    pub fn timestamp(self) -> u64 {
+        self.0 >> LOW_BITS
+    }
+
+    /// The shard number.
+    #[inline]
+    pub fn shard(self) -> u32 {
+        ((self.0 >> Self::SEQUENCE_BITS) & ((1 << SHARD_BITS) - 1)) as u32
+    }
+
+    /// The per-millisecond sequence number.
+    #[inline]
+    pub fn sequence(self) -> u64 {
+        self.0 & ((1 << Self::SEQUENCE_BITS) - 1)
+    }
+
+    /// Maps `key` (e.g., a customer ID) to a shard.
+    ///
+    /// Uses 64-bit FNV-1a, so the mapping is the same in every process and on every platform.
+    pub fn shard_of(key: &[u8]) -> u32 {
+        let hash = key.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, &b| {
+            (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+        });
+        (hash % (1 << SHARD_BITS)) as u32
+    }
+
+    /// Maps a numeric key (e.g., a user ID) to a shard: `key % 2^SHARD_BITS`, as Instagram does.
+    ///
+    /// Consecutive keys land on consecutive shards, which spreads sequentially assigned keys
+    /// evenly.
+    #[inline]
+    pub fn shard_of_u64(key: u64) -> u32 {
+        (key % (1 << SHARD_BITS)) as u32
+    }
+
+    /// Which of `nodes` storage nodes holds this ID's shard, when the shards are split into
+    /// `nodes` contiguous ranges of (nearly) equal size.
+    ///
+    /// ```
+    /// use snowflake::ShardedId;
+    ///
+    /// // 8192 shards over 4 nodes: shards 0-2047 on node 0, 2048-4095 on node 1, ...
+    /// let id = ShardedId::<13>::from_parts(0, 5000, 0);
+    /// assert_eq!(id.node(4), 2);
+    /// ```
+    ///
+    /// **panics** if `nodes` is zero.
+    #[inline]
+    pub fn node(self, nodes: u32) -> u32 {
+        assert!(nodes > 0, "no nodes to route to");
+        ((u64::from(self.shard()) * u64::from(nodes)) >> SHARD_BITS) as u32
+    }
+
+    /// Assembles an ID from its parts, e.g. to build the bounds of a range query over one shard.
+    ///
+    /// **panics** if a part doesn't fit in its bits.
+    pub fn from_parts(timestamp: u64, shard: u32, sequence: u64) -> Self {
+        assert!(timestamp >> 41 == 0, "sharded ID timestamp out of range");
+        assert!(
+            u64::from(shard) >> SHARD_BITS == 0,
+            "shard {} doesn't fit in {} bits",
+            shard,
+            SHARD_BITS
+        );
+        assert!(
+            sequence >> Self::SEQUENCE_BITS == 0,
+            "sharded ID sequence out of range"
+        );
+        ShardedId((timestamp << LOW_BITS) | (u64::from(shard) << Self::SEQUENCE_BITS) | sequence)
+    }
+}
+
+impl<const SHARD_BITS: u32> fmt::Display for ShardedId<SHARD_BITS> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(f, format_args!(""), format_args!("{}", self.0))
+    }
+}
+
+/// Parses the decimal form produced by `Display`.
+impl<const SHARD_BITS: u32> FromStr for ShardedId<SHARD_BITS> {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        ShardedId::try_from(decimal_u64(s)?)
+            .map_err(|_| ParseIdError::at(s, 0, "a value below 2^63"))
+    }
+}
+
+impl<const SHARD_BITS: u32> From<ShardedId<SHARD_BITS>> for u64 {
+    #[inline]
+    fn from(id: ShardedId<SHARD_BITS>) -> u64 {
+        id.0
+    }
+}
+
+/// Converting to `i64` always succeeds and the result is never negative.
+impl<const SHARD_BITS: u32> From<ShardedId<SHARD_BITS>> for i64 {
+    #[inline]
+    fn from(id: ShardedId<SHARD_BITS>) -> i64 {
+        id.0 as i64
+    }
+}
+
+/// Fails if the top (sign) bit is set.
+impl<const SHARD_BITS: u32> TryFrom<u64> for ShardedId<SHARD_BITS> {
+    type Error = IdOutOfRange;
+
+    #[inline]
+    fn try_from(value: u64) -> Result<Self, IdOutOfRange> {
+        if value >> 63 != 0 {
+            return Err(IdOutOfRange::new());
+        }
+        Ok(ShardedId(value))
+    }
+}
+
+/// Generates [`ShardedId`]s.
+///
+/// All shards share the generator's sequence, so a generator issues up to `2^(22 - SHARD_BITS)`
+/// IDs per millisecond in total; past that, it moves on to the next millisecond early. IDs are
+/// unique as long as each shard's IDs come from a single generator at a time (e.g., the shard's
+/// storage node, or whichever process owns the shard).
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ShardedIdGenerator<const SHARD_BITS: u32 = 13> {
+    epoch_ms: u64,
+    // The next free position, `timestamp << SEQUENCE_BITS | sequence`.
+    state: AtomicU64,
+    clock: stats::ClockWatch,
+}
+
+#[cfg(feature = "std")]
+impl<const SHARD_BITS: u32> ShardedIdGenerator<SHARD_BITS> {
+    /// Creates a generator using the default (Twitter) epoch.
+    ///
+    /// **panics** if `SHARD_BITS` is greater than 20.
+    pub fn new() -> Self {
+        assert!(SHARD_BITS <= 20, "at most 20 shard bits are supported");
+        ShardedIdGenerator {
+            epoch_ms: TWITTER_EPOCH_MS,
+            state: AtomicU64::new(0),
+            clock: stats::ClockWatch::new(),
+        }
+    }
+
+    /// Measures timestamps from `epoch` instead of the default (Twitter) epoch.
+    ///
+    /// **panics** if `epoch` is before the UNIX epoch.
+    pub fn with_epoch(mut self, epoch: SystemTime) -> Self {
+        self.epoch_ms = epoch
+            .duration_since(UNIX_EPOCH)
+            .expect("sharded ID epoch before the UNIX epoch")
+            .as_millis() as u64;
+        self
+    }
+
+    /// The generator's epoch.
+    pub fn epoch(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.epoch_ms)
+    }
+
+    /// Generates a new ID on `shard`.
+    ///
+    /// **panics** if `shard` doesn't fit in `SHARD_BITS` bits, or if the clock is past the end of
+    /// the 41-bit timestamp range.
+    pub fn next(&self, shard: u32) -> ShardedId<SHARD_BITS> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64)
+            .saturating_sub(self.epoch_ms);
+        self.next_at(now, shard)
+    }
+
+    /// Generates a new ID on the shard for `key`; see [`ShardedId::shard_of`].
+    ///
+    /// ```
+    /// use snowflake::{ShardedId, ShardedIdGenerator};
+    ///
+    /// let gen = ShardedIdGenerator::<13>::new();
+    /// let photo = gen.next_for_u64(31_337);
+    /// // Photos are stored with their owner, and found again from the ID alone.
+    /// assert_eq!(photo.shard(), ShardedId::<13>::shard_of_u64(31_337));
+    /// assert_eq!(gen.next_for(b"customer-42").shard(), ShardedId::<13>::shard_of(b"customer-42"));
+    /// ```
+    ///
+    /// **panics** if the clock is past the end of the 41-bit timestamp range.
+    #[inline]
+    pub fn next_for(&self, key: &[u8]) -> ShardedId<SHARD_BITS> {
+        self.next(ShardedId::<SHARD_BITS>::shard_of(key))
+    }
+
+    /// Generates a new ID on the shard for the numeric `key`; see [`ShardedId::shard_of_u64`].
+    ///
+    /// **panics** if the clock is past the end of the 41-bit timestamp range.
+    #[inline]
+    pub fn next_for_u64(&self, key: u64) -> ShardedId<SHARD_BITS> {
+        self.next(ShardedId::<SHARD_BITS>::shard_of_u64(key))
+    }
+
+    fn next_at(&self, now: u64, shard: u32) -> ShardedId<SHARD_BITS> {
+        assert!(
+            u64::from(shard) >> SHARD_BITS == 0,
+            "shard {} doesn't fit in {} bits",
+            shard,
+            SHARD_BITS
+        );
+        let sequence_bits = ShardedId::<SHARD_BITS>::SEQUENCE_BITS;
+        let sequence_mask = (1 << sequence_bits) - 1;
+        self.clock.observe(now, &stats::SHARDED);
+        let mut current = self.state.load(Ordering::Relaxed);
+        loop {
+            let position = current.max(now << sequence_bits);
+            let timestamp = position >> sequence_bits;
+            assert!(timestamp <= MAX_TIMESTAMP, "sharded ID timestamp overflow");
+            match self.state.compare_exchange_weak(
+                current,
+                position + 1,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    stats::SHARDED.issued(1);
+                    if timestamp > now && position & sequence_mask == 0 {
+                        stats::SHARDED.exhausted();
+                    }
+                    return ShardedId(
+                        (timestamp << LOW_BITS)
+                            | (u64::from(shard) << sequence_bits)
+                            | (position & sequence_mask),
+                    );
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<const SHARD_BITS: u32> Default for ShardedIdGenerator<SHARD_BITS> {
+    #[inline]
+    fn default() -> Self {
+        ShardedIdGenerator::new()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::{ShardedId, ShardedIdGenerator};
+
+    #[test]
+    fn test_sharded_layout() {
+        let gen = ShardedIdGenerator::<13>::new();
+        let a = gen.next_at(1000, 8191);
+        assert_eq!((a.timestamp(), a.shard(), a.sequence()), (1000, 8191, 0));
+        let b = gen.next_at(1000, 3);
+        assert_eq!((b.timestamp(), b.shard(), b.sequence()), (1000, 3, 1));
+        assert_eq!(a.to_string().parse(), Ok(a));
+        assert!("9223372036854775808".parse::<ShardedId>().is_err());
+
+        // With 20 shard bits there are only four sequence numbers per millisecond.
+        let narrow = ShardedIdGenerator::<20>::new();
+        let ids: Vec<_> = (0..5).map(|_| narrow.next_at(7, 1)).collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!((ids[4].timestamp(), ids[4].sequence()), (8, 0));
+
+        assert_eq!(
+            ShardedId::<4>::shard_of(b"a"),
+            ShardedId::<4>::shard_of(b"a")
+        );
+        assert!(ShardedId::<4>::shard_of(b"customer") < 16);
+        assert_eq!(ShardedId::<4>::shard_of_u64(35), 3);
+
+        let id = ShardedId::<13>::from_parts(1000, 8191, 511);
+        assert_eq!(
+            (id.timestamp(), id.shard(), id.sequence()),
+            (1000, 8191, 511)
+        );
+        assert_eq!(id.node(1), 0);
+        assert_eq!(id.node(3), 2);
+        assert_eq!(ShardedId::<13>::from_parts(0, 0, 0).node(3), 0);
+    }
+}
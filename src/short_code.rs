@@ -0,0 +1,243 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Short, URL-friendly codes for IDs, in the style of Hashids/Sqids.
+//!
+//! A [`ShortCodec`] writes an ID as a number in base `alphabet.len()`, using the alphabet shuffled
+//! by a salt. Different salts give different codes for the same ID, so codes from one service
+//! can't be decoded with another's codec by accident.
+//!
+//! ```
+//! use snowflake::short_code::ShortCodec;
+//! use snowflake::ProcessUniqueId;
+//!
+//! let codec = ShortCodec::new(ShortCodec::DEFAULT_ALPHABET, "orders").unwrap();
+//! let id = ProcessUniqueId::new();
+//! let code = id.to_short_code(&codec);
+//! assert_eq!(ProcessUniqueId::from_short_code(&code, &codec), Ok(id));
+//! ```
+//!
+//! Like the salted codes they're modeled on, short codes only obscure IDs; see the
+//! [`obfuscate`](crate::obfuscate) module to also hide their order, and don't rely on either for
+//! secrecy.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::obfuscate::splitmix64;
+use crate::process_unique_id::ParseIdError;
+use crate::{ProcessUniqueId, SmallUniqueId};
+
+const NOT_IN_ALPHABET: u8 = u8::MAX;
+
+/// An error returned by [`ShortCodec::new`] when the alphabet can't be used.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum InvalidAlphabet {
+    /// The alphabet has fewer than two characters.
+    TooShort,
+    /// The alphabet contains a non-ASCII character.
+    NotAscii,
+    /// The alphabet contains the same character more than once.
+    Duplicate(char),
+}
+
+impl fmt::Display for InvalidAlphabet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvalidAlphabet::TooShort => f.write_str("alphabet must have at least two characters"),
+            InvalidAlphabet::NotAscii => f.write_str("alphabet must be ASCII"),
+            InvalidAlphabet::Duplicate(c) => write!(f, "duplicate character in alphabet: {:?}", c),
+        }
+    }
+}
+
+impl Error for InvalidAlphabet {}
+
+/// Encodes and decodes short codes with a salted alphabet.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ShortCodec {
+    // The salted alphabet, digit to character.
+    alphabet: Vec<u8>,
+    // Character to digit, `NOT_IN_ALPHABET` for characters outside the alphabet.
+    digits: [u8; 128],
+}
+
+impl ShortCodec {
+    /// ASCII letters and digits.
+    pub const DEFAULT_ALPHABET: &'static str =
+        "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+    /// Creates a codec using `alphabet` (ASCII, at least two distinct characters) shuffled by
+    /// `salt`.
+    pub fn new(alphabet: &str, salt: &str) -> Result<Self, InvalidAlphabet> {
+        if !alphabet.is_ascii() {
+            return Err(InvalidAlphabet::NotAscii);
+        }
+        let mut alphabet = alphabet.as_bytes().to_vec();
+        if alphabet.len() < 2 {
+            return Err(InvalidAlphabet::TooShort);
+        }
+
+        // Seed a Fisher-Yates shuffle with an FNV-1a hash of the salt.
+        let mut state = salt.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, b| {
+            (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+        });
+        for i in (1..alphabet.len()).rev() {
+            let j = (splitmix64(&mut state) % (i as u64 + 1)) as usize;
+            alphabet.swap(i, j);
+        }
+
+        let mut digits = [NOT_IN_ALPHABET; 128];
+        for (digit, &c) in alphabet.iter().enumerate() {
+            if digits[c as usize] != NOT_IN_ALPHABET {
+                return Err(InvalidAlphabet::Duplicate(c as char));
+            }
+            digits[c as usize] = digit as u8;
+        }
+        Ok(ShortCodec { alphabet, digits })
+    }
+
+    /// Encodes `value` as a short code.
+    pub fn encode(&self, mut value: u128) -> String {
+        let base = self.alphabet.len() as u128;
+        let mut code = Vec::new();
+        loop {
+            code.push(self.alphabet[(value % base) as usize]);
+            value /= base;
+            if value == 0 {
+                break;
+            }
+        }
+        code.reverse();
+        // The alphabet is ASCII.
+        String::from_utf8(code).unwrap()
+    }
+
+    /// Decodes a short code produced by [`encode`](ShortCodec::encode).
+    ///
+    /// Only the exact code `encode` produces is accepted; in particular, codes with extra leading
+    /// "zero" characters are rejected so that every value has a single code.
+    pub fn decode(&self, code: &str) -> Result<u128, ParseIdError> {
+        let base = self.alphabet.len() as u128;
+        let mut value: u128 = 0;
+        for (i, c) in code.bytes().enumerate() {
+            let digit = match self.digits.get(c as usize) {
+                Some(&digit) if digit != NOT_IN_ALPHABET => digit,
+                _ => return Err(ParseIdError::at(code, i, "a character of the alphabet")),
+            };
+            if i == 0 && digit == 0 && code.len() > 1 {
+                return Err(ParseIdError::at(code, 0, "a code without leading zeros"));
+            }
+            value = value
+                .checked_mul(base)
+                .and_then(|v| v.checked_add(u128::from(digit)))
+                .ok_or_else(|| ParseIdError::at(code, 0, "a value that fits in 128 bits"))?;
+        }
+        if code.is_empty() {
+            return Err(ParseIdError::at(code, 0, "a character of the alphabet"));
+        }
+        Ok(value)
+    }
+}
+
+impl Default for ShortCodec {
+    /// A codec using [`DEFAULT_ALPHABET`](ShortCodec::DEFAULT_ALPHABET) and no salt.
+    fn default() -> Self {
+        ShortCodec::new(ShortCodec::DEFAULT_ALPHABET, "").unwrap()
+    }
+}
+
+impl ProcessUniqueId {
+    /// Encodes this ID as a short code (of its [`to_u128`](ProcessUniqueId::to_u128) form).
+    #[inline]
+    pub fn to_short_code(self, codec: &ShortCodec) -> String {
+        codec.encode(self.to_u128())
+    }
+
+    /// Decodes an ID from a short code produced by
+    /// [`to_short_code`](ProcessUniqueId::to_short_code) with the same codec.
+    pub fn from_short_code(code: &str, codec: &ShortCodec) -> Result<Self, ParseIdError> {
+        ProcessUniqueId::from_u128(codec.decode(code)?)
+            .ok_or_else(|| ParseIdError::at(code, 0, "a prefix that fits in a usize"))
+    }
+}
+
+impl SmallUniqueId {
+    /// Encodes this ID as a short code.
+    #[inline]
+    pub fn to_short_code(self, codec: &ShortCodec) -> String {
+        codec.encode(self.0.into())
+    }
+
+    /// Decodes an ID from a short code produced by
+    /// [`to_short_code`](SmallUniqueId::to_short_code) with the same codec.
+    pub fn from_short_code(code: &str, codec: &ShortCodec) -> Result<Self, ParseIdError> {
+        let value = codec.decode(code)?;
+        if value > u128::from(u64::MAX) {
+            return Err(ParseIdError::at(code, 0, "a value that fits in 64 bits"));
+        }
+        Ok(SmallUniqueId(value as u64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InvalidAlphabet, ShortCodec};
+    use crate::{ProcessUniqueId, SmallUniqueId};
+
+    #[test]
+    fn test_round_trip() {
+        let codec = ShortCodec::new(ShortCodec::DEFAULT_ALPHABET, "orders").unwrap();
+        for &value in &[0, 1, 61, 62, 1 << 64, u128::MAX] {
+            assert_eq!(codec.decode(&codec.encode(value)), Ok(value));
+        }
+        let id = ProcessUniqueId::new();
+        assert_eq!(
+            ProcessUniqueId::from_short_code(&id.to_short_code(&codec), &codec),
+            Ok(id)
+        );
+        let id = SmallUniqueId::new();
+        assert_eq!(
+            SmallUniqueId::from_short_code(&id.to_short_code(&codec), &codec),
+            Ok(id)
+        );
+        assert!(SmallUniqueId::from_short_code(&codec.encode(1 << 64), &codec).is_err());
+    }
+
+    #[test]
+    fn test_salt() {
+        let a = ShortCodec::new("0123456789", "a").unwrap();
+        let b = ShortCodec::new("0123456789", "b").unwrap();
+        let codes_a: Vec<_> = (0..10).map(|i| a.encode(i)).collect();
+        let codes_b: Vec<_> = (0..10).map(|i| b.encode(i)).collect();
+        assert_ne!(codes_a, codes_b);
+        assert_eq!(a, ShortCodec::new("0123456789", "a").unwrap());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let codec = ShortCodec::new("01", "").unwrap();
+        let zero = codec.encode(0);
+        assert!(codec.decode("").is_err());
+        assert!(codec.decode("2").is_err());
+        assert!(codec
+            .decode(&format!("{}{}", zero, codec.encode(1)))
+            .is_err());
+        assert!(codec.decode(&codec.encode(1).repeat(129)).is_err());
+
+        assert_eq!(ShortCodec::new("a", ""), Err(InvalidAlphabet::TooShort));
+        assert_eq!(
+            ShortCodec::new("aba", ""),
+            Err(InvalidAlphabet::Duplicate('a'))
+        );
+        assert_eq!(
+            ShortCodec::new("ab\u{e9}", ""),
+            Err(InvalidAlphabet::NotAscii)
+        );
+    }
+}
@@ -0,0 +1,353 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::fmt;
+use core::mem;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::cell::Cell;
+
+#[cfg(feature = "bevy")]
+use bevy_ecs::reflect::ReflectComponent;
+
+#[cfg(feature = "std")]
+use crate::atomic::{AtomicBool, AtomicU32, Ordering};
+use crate::display::{pad_id, DisplayAs};
+use crate::process_unique_id::{parse_tagged, ParseIdError};
+
+#[cfg(feature = "std")]
+const PREFIX_BITS: u32 = 24;
+const OFFSET_BITS: u32 = 40;
+
+#[cfg(feature = "std")]
+const MAX_PREFIX: u32 = (1 << PREFIX_BITS) - 1;
+const MAX_OFFSET: u64 = (1 << OFFSET_BITS) - 1;
+
+#[cfg(feature = "std")]
+static SMALL_GLOBAL_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+#[cfg(feature = "std")]
+static SMALL_WRAP_ON_EXHAUSTION: AtomicBool = AtomicBool::new(false);
+
+/// The prefix counter's next value after `prev`, or `None` if it's run out. The prefix handed out
+/// is `prev & MAX_PREFIX`, so after the last prefix the counter stops at `MAX_PREFIX + 1` or, if
+/// wrapping, hands out prefix zero again.
+#[cfg(feature = "std")]
+fn advance_prefix(prev: u32, wrap: bool) -> Option<u32> {
+    if prev <= MAX_PREFIX {
+        Some(prev + 1)
+    } else if wrap {
+        Some(1)
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "std")]
+fn next_small_global() -> u64 {
+    let wrap = SMALL_WRAP_ON_EXHAUSTION.load(Ordering::Relaxed);
+    let prefix = SMALL_GLOBAL_COUNTER
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |prev| {
+            advance_prefix(prev, wrap)
+        })
+        .expect("Snow Crash: Go home and reevaluate your threading model!")
+        & MAX_PREFIX;
+    #[cfg(feature = "metrics")]
+    crate::stats::on_small_prefix_allocated(prefix + 1);
+    u64::from(prefix) << OFFSET_BITS
+}
+
+/// The number of prefixes handed out.
+#[cfg(feature = "std")]
+pub(crate) fn prefixes_allocated() -> u32 {
+    SMALL_GLOBAL_COUNTER.load(Ordering::Relaxed)
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static NEXT_LOCAL_SMALL_ID: Cell<u64> = Cell::new(next_small_global());
+}
+
+/// A process unique ID that fits in 64 bits.
+///
+/// Like [`ProcessUniqueId`](crate::ProcessUniqueId) but packed into a single `u64` for FFI
+/// boundaries, GPU buffers, and database columns that don't have room for more.
+///
+/// # Limits
+///
+/// The ID is a 24-bit global prefix followed by a 40-bit thread local offset. Each thread that
+/// calls `new()` reserves a prefix, good for 2^40 (about a trillion) IDs, and claims a fresh one
+/// when that runs out. The process can hand out 2^24 (about 16 million) prefixes in total, so
+/// unlike `ProcessUniqueId` this *can* run out in practice if you create IDs from millions of
+/// short-lived threads. When it does, `new()` panics rather than reusing IDs.
+///
+/// # Memory layout
+///
+/// `#[repr(transparent)]` over the packed `u64`, so it's passed to and from C as a `uint64_t`.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(
+        zerocopy::FromBytes,
+        zerocopy::IntoBytes,
+        zerocopy::Immutable,
+        zerocopy::KnownLayout
+    )
+)]
+#[cfg_attr(feature = "bitcode", derive(bitcode::Encode, bitcode::Decode))]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_ecs::component::Component, bevy_reflect::Reflect),
+    reflect(opaque, Component, Debug, PartialEq, Hash, Clone)
+)]
+#[repr(transparent)]
+pub struct SmallUniqueId(pub(crate) u64);
+
+const _: () = assert!(
+    mem::size_of::<SmallUniqueId>() == 8
+        && mem::align_of::<SmallUniqueId>() == mem::align_of::<u64>()
+);
+
+impl fmt::Display for SmallUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(
+            f,
+            format_args!("spuid-{:x}-", self.prefix()),
+            format_args!("{:x}", self.offset()),
+        )
+    }
+}
+
+/// Parses the `spuid-<prefix>-<offset>` form produced by `Display`.
+impl FromStr for SmallUniqueId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        let (prefix, offset, start) = parse_tagged(s, "spuid-", "`spuid-`")?;
+        if prefix >> (64 - OFFSET_BITS) != 0 {
+            return Err(ParseIdError::at(s, start, "a prefix of at most 24 bits"));
+        }
+        if offset > MAX_OFFSET {
+            let start = s.rfind('-').map_or(0, |dash| dash + 1);
+            return Err(ParseIdError::at(s, start, "an offset of at most 40 bits"));
+        }
+        Ok(SmallUniqueId((prefix << OFFSET_BITS) | offset))
+    }
+}
+
+/// The raw 64 bits of the ID.
+impl From<SmallUniqueId> for u64 {
+    #[inline]
+    fn from(id: SmallUniqueId) -> u64 {
+        id.0
+    }
+}
+
+/// The raw 64 bits of the ID, reinterpreted as a two's complement `i64`.
+///
+/// IDs with a prefix of 2^23 or more (i.e., created after about 8 million threads have claimed
+/// prefixes) become negative, so they don't sort after smaller IDs. The conversion is lossless
+/// either way: `SmallUniqueId::from(i64::from(id)) == id`.
+impl From<SmallUniqueId> for i64 {
+    #[inline]
+    fn from(id: SmallUniqueId) -> i64 {
+        id.0 as i64
+    }
+}
+
+/// Every `u64` is a valid small unique ID, but IDs created in another process aren't unique in
+/// this one; only convert IDs that came from the current process.
+impl From<u64> for SmallUniqueId {
+    #[inline]
+    fn from(value: u64) -> Self {
+        SmallUniqueId(value)
+    }
+}
+
+/// The inverse of `i64::from(id)`: negative values are reinterpreted as their two's complement
+/// bits. As with `u64`, only convert IDs that came from the current process.
+impl From<i64> for SmallUniqueId {
+    #[inline]
+    fn from(value: i64) -> Self {
+        SmallUniqueId(value as u64)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for SmallUniqueId {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "spuid-{=u32:x}-{=u64:x}", self.prefix(), self.offset())
+    }
+}
+
+impl SmallUniqueId {
+    /// Create a new unique ID.
+    ///
+    /// **panics** if there are no more unique IDs available.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn new() -> Self {
+        NEXT_LOCAL_SMALL_ID.with(|next| {
+            let id = next.get();
+            next.set(if id & MAX_OFFSET == MAX_OFFSET {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    prefix = id >> OFFSET_BITS,
+                    "SmallUniqueId prefix used up, moving on to a new one"
+                );
+                next_small_global()
+            } else {
+                id + 1
+            });
+            SmallUniqueId(id)
+        })
+    }
+
+    /// Makes the prefix counter wrap around to zero when all 2^24 prefixes have been handed out,
+    /// instead of panicking.
+    ///
+    /// **This gives up the uniqueness guarantee.** After wrapping, threads reuse prefixes from
+    /// the start of the process, so any ID created since then that's still in use can be handed
+    /// out again. Only turn this on if a duplicate ID is genuinely less harmful than a crash, as
+    /// in a soft real-time system that would rather glitch than stop, and nothing relies on IDs
+    /// being unique for correctness or security.
+    ///
+    /// Unlike with [`ProcessUniqueId`](crate::ProcessUniqueId), running out is a real
+    /// possibility for programs that create IDs from millions of short-lived threads (see
+    /// [Limits](SmallUniqueId#limits)). Prefer fewer, longer-lived threads, or a wider ID, if you
+    /// can. Off by default; applies from the next prefix allocation on and can be turned off
+    /// again.
+    #[cfg(feature = "std")]
+    pub fn set_wrap_on_exhaustion(wrap: bool) {
+        SMALL_WRAP_ON_EXHAUSTION.store(wrap, Ordering::Relaxed);
+    }
+
+    /// Returns an adapter that displays this ID with `label` in place of `spuid`.
+    #[inline]
+    pub fn display_as(self, label: &str) -> DisplayAs<'_> {
+        DisplayAs::new(label, self.prefix().into(), self.offset())
+    }
+
+    /// The 24-bit prefix shared by all IDs created on the same thread (until it runs out).
+    #[inline]
+    pub fn prefix(self) -> u32 {
+        (self.0 >> OFFSET_BITS) as u32
+    }
+
+    /// The 40-bit offset within the prefix.
+    #[inline]
+    pub fn offset(self) -> u64 {
+        self.0 & MAX_OFFSET
+    }
+}
+
+/// Saves the global and current thread's generator state, as `(global, local)`, and resets it to
+/// that of a freshly started process.
+#[cfg(feature = "test-util")]
+pub(crate) fn reset_state() -> (u32, u64) {
+    // Read the thread local first: initializing it claims a prefix.
+    let local = NEXT_LOCAL_SMALL_ID.with(Cell::get);
+    let global = SMALL_GLOBAL_COUNTER.swap(0, Ordering::Relaxed);
+    NEXT_LOCAL_SMALL_ID.with(|next| next.set(next_small_global()));
+    (global, local)
+}
+
+/// Puts back state saved by [`reset_state`].
+#[cfg(feature = "test-util")]
+pub(crate) fn restore_state((global, local): (u32, u64)) {
+    SMALL_GLOBAL_COUNTER.store(global, Ordering::Relaxed);
+    NEXT_LOCAL_SMALL_ID.with(|next| next.set(local));
+}
+
+#[cfg(feature = "std")]
+impl Default for SmallUniqueId {
+    #[inline]
+    fn default() -> Self {
+        SmallUniqueId::new()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::{
+        advance_prefix, SmallUniqueId, MAX_OFFSET, MAX_PREFIX, NEXT_LOCAL_SMALL_ID, OFFSET_BITS,
+    };
+    use std::thread;
+
+    #[test]
+    fn test_small_unique_id_rollover() {
+        let first = SmallUniqueId::new();
+        NEXT_LOCAL_SMALL_ID
+            .with(|next| next.set((u64::from(first.prefix()) << OFFSET_BITS) | (MAX_OFFSET - 1)));
+        assert_eq!(SmallUniqueId::new().offset(), MAX_OFFSET - 1);
+        assert_eq!(SmallUniqueId::new().offset(), MAX_OFFSET);
+        let next = SmallUniqueId::new();
+        assert_ne!(next.prefix(), first.prefix());
+        assert_eq!(next.offset(), 0);
+    }
+
+    #[test]
+    fn test_parse() {
+        let id = SmallUniqueId::new();
+        assert_eq!(id.to_string().parse(), Ok(id));
+        assert_eq!(
+            "spuid-ffffff-ffffffffff"
+                .parse::<SmallUniqueId>()
+                .map(|id| id.0),
+            Ok(u64::MAX)
+        );
+        for bad in &[
+            "spuid-1000000-0",
+            "spuid-0-10000000000",
+            "puid-1-1",
+            "spuid-1",
+        ] {
+            assert!(bad.parse::<SmallUniqueId>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_advance_prefix() {
+        assert_eq!(advance_prefix(MAX_PREFIX, false), Some(MAX_PREFIX + 1));
+        assert_eq!(advance_prefix(MAX_PREFIX + 1, false), None);
+        // Wrapping hands out prefix zero, then carries on from one.
+        assert_eq!((MAX_PREFIX + 1) & MAX_PREFIX, 0);
+        assert_eq!(advance_prefix(MAX_PREFIX + 1, true), Some(1));
+        assert_eq!(advance_prefix(1, true), Some(2));
+    }
+
+    #[test]
+    fn test_int_conversions() {
+        let id = SmallUniqueId::new();
+        assert_eq!(SmallUniqueId::from(u64::from(id)), id);
+        assert_eq!(SmallUniqueId::from(i64::from(id)), id);
+        let high = SmallUniqueId::from(u64::MAX);
+        assert_eq!(i64::from(high), -1);
+        assert_eq!(SmallUniqueId::from(-1i64), high);
+    }
+
+    #[test]
+    fn test_display_as() {
+        let id = SmallUniqueId::new();
+        assert_eq!(
+            id.display_as("job").to_string(),
+            format!("job-{:x}-{:x}", id.prefix(), id.offset())
+        );
+    }
+
+    #[test]
+    fn test_small_unique_id_threaded() {
+        let threads: Vec<_> = (0..32).map(|_| thread::spawn(SmallUniqueId::new)).collect();
+        let mut ids: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 32);
+        assert!(ids.iter().all(|id| id.offset() == 0));
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Process unique IDs across checkpoint/restore.
+//!
+//! Requires the `snapshot` feature. A process restored from a memory image (CRIU, VM snapshots,
+//! warmed serverless sandboxes) resumes with the image's prefix counter and every thread's block
+//! of IDs, so it hands out exactly the IDs that every other restore of the same image, and the
+//! imaged process itself if it kept running, hand out.
+//!
+//! [`restore_state`] breaks the replay: every thread drops its block on its next ID, and the
+//! prefix counter moves past the given [`IdState`]. Capture states with [`snapshot_state`], keep
+//! them outside the image (e.g. wherever the platform keeps per-restore metadata), and hand each
+//! restored process a state beyond any prefix an earlier restore could have used:
+//!
+//! ```
+//! use snowflake::snapshot::{restore_state, snapshot_state, IdState};
+//! use snowflake::ProcessUniqueId;
+//!
+//! let state = snapshot_state();
+//!
+//! // ... the image is written, and later restored ...
+//!
+//! // E.g., give the n-th restore a range of 2^16 prefixes of its own.
+//! let n = 3;
+//! let restored = IdState::from_next_prefix(state.next_prefix() + (n << 16));
+//! restore_state(&restored).unwrap();
+//! assert!(ProcessUniqueId::new().to_u128() >> 64 >= u128::from(restored.next_prefix()));
+//! ```
+//!
+//! [`restore_detected`] is a heuristic for platforms that don't tell the process it was restored:
+//! it compares how far the wall clock and the monotonic clock advanced since the last
+//! [`snapshot_state`]. Images typically sit on disk for a while, during which the restored
+//! monotonic clock doesn't move but the wall clock does.
+//!
+//! Enabling the feature adds a check of a global generation counter to every ID created by
+//! `ProcessUniqueId::new()` or an `IdHandle`. IDs already reserved in an `IdRange` or
+//! `LocalIdGenerator`, and blocks detached into a `ThreadPrefix`, aren't affected.
+
+use core::cell::Cell;
+use core::convert::TryFrom;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::atomic::{AtomicU64, Ordering};
+use crate::process_unique_id::{global_counter, RECLAIMED, SHARED_PREFIX};
+use crate::IdOutOfRange;
+
+/// How far the wall clock has to run ahead of the monotonic clock for [`restore_detected`] to
+/// report a restore.
+pub const RESTORE_SKEW: Duration = Duration::from_secs(1);
+
+// Bumped by every `restore_state`. Blocks claimed in an earlier generation are dropped.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// The clocks at the last `snapshot_state`.
+static SNAPSHOT_CLOCKS: Mutex<Option<(SystemTime, Instant)>> = Mutex::new(None);
+
+/// Returns true (and adopts the current generation) if `generation` is out of date.
+#[inline]
+pub(crate) fn is_stale(generation: &Cell<u64>) -> bool {
+    let current = GENERATION.load(Ordering::Acquire);
+    if generation.get() == current {
+        return false;
+    }
+    generation.set(current);
+    true
+}
+
+/// The process unique ID state captured by [`snapshot_state`].
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct IdState {
+    next_prefix: u64,
+}
+
+impl IdState {
+    /// A state in which every prefix below `next_prefix` has been used.
+    #[inline]
+    pub fn from_next_prefix(next_prefix: u64) -> Self {
+        IdState { next_prefix }
+    }
+
+    /// The first prefix that hadn't been handed out yet.
+    #[inline]
+    pub fn next_prefix(&self) -> u64 {
+        self.next_prefix
+    }
+
+    /// The state as 8 bytes (`next_prefix` in big-endian), for storing outside the image.
+    #[inline]
+    pub fn to_bytes(&self) -> [u8; 8] {
+        self.next_prefix.to_be_bytes()
+    }
+
+    /// Reads a state written by [`to_bytes`](IdState::to_bytes).
+    #[inline]
+    pub fn from_bytes(bytes: [u8; 8]) -> Self {
+        IdState::from_next_prefix(u64::from_be_bytes(bytes))
+    }
+}
+
+/// Captures the process unique ID state, and remembers the time for [`restore_detected`].
+pub fn snapshot_state() -> IdState {
+    *SNAPSHOT_CLOCKS.lock().unwrap_or_else(|e| e.into_inner()) =
+        Some((SystemTime::now(), Instant::now()));
+    IdState {
+        next_prefix: global_counter().load(Ordering::Relaxed) as u64,
+    }
+}
+
+/// Makes every thread drop its block of IDs and moves the prefix counter to at least
+/// `state.next_prefix()`, so every ID handed out from now on has a prefix of at least
+/// `state.next_prefix()`.
+///
+/// Fails (and changes nothing) if `state` is beyond the prefixes that fit in a `usize`.
+pub fn restore_state(state: &IdState) -> Result<(), IdOutOfRange> {
+    let next_prefix = usize::try_from(state.next_prefix).map_err(|_| IdOutOfRange::new())?;
+    // Holding the shared prefix lock keeps threads from claiming a block until the new
+    // generation is in place.
+    let mut shared = SHARED_PREFIX.lock().unwrap_or_else(|e| e.into_inner());
+    *shared = None;
+    // Blocks given back by exited threads are in the image too.
+    RECLAIMED.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    global_counter().fetch_max(next_prefix, Ordering::Relaxed);
+    GENERATION.fetch_add(1, Ordering::Release);
+    Ok(())
+}
+
+/// Returns true if the wall clock ran ahead of the monotonic clock by more than
+/// [`RESTORE_SKEW`] since the last [`snapshot_state`], which suggests the process was restored
+/// from an image in between. Always false before the first snapshot.
+pub fn restore_detected() -> bool {
+    let clocks = *SNAPSHOT_CLOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    clocks.is_some_and(|(wall, monotonic)| {
+        let wall_elapsed = SystemTime::now()
+            .duration_since(wall)
+            .unwrap_or(Duration::ZERO);
+        wall_elapsed.saturating_sub(monotonic.elapsed()) > RESTORE_SKEW
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_stale, snapshot_state, IdState, GENERATION};
+    use crate::atomic::Ordering;
+    use core::cell::Cell;
+
+    // `restore_state` itself is exercised by the module's doctest, which runs in its own process:
+    // here it would make other tests' threads drop their blocks at random points.
+    #[test]
+    fn test_snapshot_state() {
+        let state = snapshot_state();
+        assert!(!super::restore_detected());
+        assert_eq!(IdState::from_bytes(state.to_bytes()), state);
+
+        let current = GENERATION.load(Ordering::Relaxed);
+        let generation = Cell::new(current.wrapping_sub(1));
+        assert!(is_stale(&generation));
+        assert!(!is_stale(&generation));
+        assert_eq!(generation.get(), current);
+    }
+}
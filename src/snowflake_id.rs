@@ -0,0 +1,626 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::convert::TryFrom;
+use core::error::Error;
+use core::fmt;
+use core::mem;
+use core::ops::RangeInclusive;
+use core::str::FromStr;
+use core::time::Duration;
+#[cfg(feature = "durable")]
+use std::io;
+#[cfg(feature = "durable")]
+use std::path::Path;
+#[cfg(feature = "std")]
+use std::thread;
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "bevy")]
+use bevy_ecs::reflect::ReflectComponent;
+
+#[cfg(feature = "std")]
+use crate::atomic::{AtomicU64, Ordering};
+use crate::display::pad_id;
+#[cfg(feature = "std")]
+use crate::node::{check_width, NodeIdError, NodeIdProvider};
+use crate::process_unique_id::{decimal_u64, ParseIdError};
+#[cfg(feature = "std")]
+use crate::stats;
+
+const TIMESTAMP_BITS: u32 = 41;
+pub(crate) const NODE_BITS: u32 = 10;
+const SEQUENCE_BITS: u32 = 12;
+
+const MAX_TIMESTAMP: u64 = (1 << TIMESTAMP_BITS) - 1;
+const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
+const NODE_MASK: u64 = (1 << NODE_BITS) - 1;
+
+/// Milliseconds between the UNIX epoch and the default (Twitter) snowflake epoch, 2010-11-04.
+#[cfg(feature = "std")]
+pub(crate) const TWITTER_EPOCH_MS: u64 = 1_288_834_974_657;
+
+/// A 64-bit, roughly time-ordered, globally unique ID.
+///
+/// Snowflake IDs use the classic Twitter layout: a zero sign bit, a 41-bit millisecond timestamp
+/// (relative to the generator's epoch), a 10-bit node ID, and a 12-bit per-millisecond sequence
+/// number. IDs are unique as long as no two generators share a node ID.
+///
+/// Displayed as a decimal integer.
+///
+/// # Memory layout
+///
+/// `#[repr(transparent)]` over a `u64`: a C `uint64_t` across FFI or in shared memory.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(Serialize, Deserialize),
+    serde(try_from = "u64")
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::NoUninit))]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::IntoBytes, zerocopy::Immutable, zerocopy::KnownLayout)
+)]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_ecs::component::Component, bevy_reflect::Reflect),
+    reflect(opaque, Component, Debug, PartialEq, Hash, Clone)
+)]
+#[repr(transparent)]
+pub struct SnowflakeId(pub(crate) u64);
+
+const _: () = assert!(
+    mem::size_of::<SnowflakeId>() == 8 && mem::align_of::<SnowflakeId>() == mem::align_of::<u64>()
+);
+
+impl SnowflakeId {
+    #[inline]
+    fn from_parts(timestamp: u64, node_id: u64, sequence: u64) -> Self {
+        SnowflakeId(
+            (timestamp << (NODE_BITS + SEQUENCE_BITS)) | (node_id << SEQUENCE_BITS) | sequence,
+        )
+    }
+
+    /// Milliseconds since the generator's epoch.
+    #[inline]
+    pub fn timestamp(self) -> u64 {
+        self.0 >> (NODE_BITS + SEQUENCE_BITS)
+    }
+
+    /// The ID of the node that generated this ID.
+    #[inline]
+    pub fn node_id(self) -> u64 {
+        (self.0 >> SEQUENCE_BITS) & NODE_MASK
+    }
+
+    /// The per-millisecond sequence number.
+    #[inline]
+    pub fn sequence(self) -> u64 {
+        self.0 & MAX_SEQUENCE
+    }
+
+    /// The start of the time bucket the ID falls in, in milliseconds since the generator's epoch.
+    ///
+    /// Useful as a partition or shard key: all IDs issued in the same `bucket` (e.g., hour) share
+    /// it, and [`partition_range`](SnowflakeId::partition_range) gives the IDs in a partition.
+    ///
+    /// ```
+    /// use snowflake::SnowflakeId;
+    /// use std::time::Duration;
+    ///
+    /// let hour = Duration::from_secs(3600);
+    /// let id: SnowflakeId = "1541815603606036480".parse().unwrap();
+    /// let key = id.partition_key(hour);
+    /// assert_eq!(key % 3_600_000, 0);
+    /// assert!(SnowflakeId::partition_range(key, hour).contains(&id));
+    /// ```
+    ///
+    /// **panics** if `bucket` is shorter than a millisecond.
+    #[inline]
+    pub fn partition_key(self, bucket: Duration) -> u64 {
+        let bucket = bucket_ms(bucket);
+        self.timestamp() - self.timestamp() % bucket
+    }
+
+    /// The IDs in the partition starting at `key` (as returned by
+    /// [`partition_key`](SnowflakeId::partition_key) with the same `bucket`), for range scans.
+    ///
+    /// **panics** if `bucket` is shorter than a millisecond.
+    pub fn partition_range(key: u64, bucket: Duration) -> RangeInclusive<SnowflakeId> {
+        let last = key.saturating_add(bucket_ms(bucket) - 1);
+        SnowflakeId::time_range(key, last)
+    }
+
+    /// The IDs with timestamps from `first` to `last` inclusive (in milliseconds since the
+    /// generator's epoch), from every node, for range scans.
+    ///
+    /// Timestamps past the end of the 41-bit range are clamped to it. The range is empty if
+    /// `last` is before `first`.
+    pub fn time_range(first: u64, last: u64) -> RangeInclusive<SnowflakeId> {
+        let first = SnowflakeId::from_parts(first.min(MAX_TIMESTAMP), 0, 0);
+        let last = SnowflakeId::from_parts(last.min(MAX_TIMESTAMP), NODE_MASK, MAX_SEQUENCE);
+        first..=last
+    }
+}
+
+/// The length of a partition bucket in milliseconds.
+fn bucket_ms(bucket: Duration) -> u64 {
+    let ms = bucket.as_millis();
+    assert!(
+        ms > 0,
+        "partition buckets must be at least a millisecond long"
+    );
+    u64::try_from(ms).unwrap_or(u64::MAX)
+}
+
+impl fmt::Display for SnowflakeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(f, format_args!(""), format_args!("{}", self.0))
+    }
+}
+
+/// Parses the decimal form produced by `Display`.
+impl FromStr for SnowflakeId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        match decimal_u64(s)? {
+            // The sign bit is always zero.
+            value if value >> 63 == 0 => Ok(SnowflakeId(value)),
+            _ => Err(ParseIdError::at(s, 0, "a value below 2^63")),
+        }
+    }
+}
+
+/// Converting to `u64` always succeeds: the result is the raw 64-bit ID.
+impl From<SnowflakeId> for u64 {
+    #[inline]
+    fn from(id: SnowflakeId) -> u64 {
+        id.0
+    }
+}
+
+/// Converting to `i64` always succeeds and the result is never negative, because the sign bit of a
+/// snowflake ID is always zero. IDs sort the same way as their `i64` values, so they make good
+/// `BIGINT` primary keys.
+impl From<SnowflakeId> for i64 {
+    #[inline]
+    fn from(id: SnowflakeId) -> i64 {
+        id.0 as i64
+    }
+}
+
+/// Fails if the top (sign) bit is set.
+impl TryFrom<u64> for SnowflakeId {
+    type Error = IdOutOfRange;
+
+    #[inline]
+    fn try_from(value: u64) -> Result<Self, IdOutOfRange> {
+        if value >> 63 != 0 {
+            return Err(IdOutOfRange::new());
+        }
+        Ok(SnowflakeId(value))
+    }
+}
+
+/// Fails if `value` is negative.
+impl TryFrom<i64> for SnowflakeId {
+    type Error = IdOutOfRange;
+
+    #[inline]
+    fn try_from(value: i64) -> Result<Self, IdOutOfRange> {
+        if value < 0 {
+            return Err(IdOutOfRange::new());
+        }
+        Ok(SnowflakeId(value as u64))
+    }
+}
+
+/// An error returned when converting an integer that can't be a valid ID into one.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct IdOutOfRange {
+    _private: (),
+}
+
+impl IdOutOfRange {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        IdOutOfRange { _private: () }
+    }
+}
+
+impl fmt::Display for IdOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("integer out of range for this ID type")
+    }
+}
+
+impl Error for IdOutOfRange {}
+
+/// Returned by [`SnowflakeGenerator::try_next`] when every sequence number for the current
+/// millisecond has already been issued.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SequenceExhausted;
+
+impl fmt::Display for SequenceExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("snowflake sequence exhausted for the current millisecond")
+    }
+}
+
+impl Error for SequenceExhausted {}
+
+/// Generates [`SnowflakeId`]s for a single node.
+///
+/// Each generator can issue 4096 IDs per millisecond. If the system clock goes backwards, the
+/// generator keeps issuing IDs from the last timestamp it saw until the clock catches up.
+///
+/// The generator is lock-free: timestamp and sequence live in a single `AtomicU64` updated by CAS,
+/// so it can be shared between many threads without becoming a bottleneck.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct SnowflakeGenerator {
+    node_id: u64,
+    epoch_ms: u64,
+    // The next free position, `timestamp << SEQUENCE_BITS | sequence`. Packing both into one word
+    // lets us claim IDs with a single CAS instead of taking a lock.
+    state: AtomicU64,
+    clock: stats::ClockWatch,
+    #[cfg(feature = "durable")]
+    persisted: Option<crate::durable::TimestampMark>,
+}
+
+#[cfg(feature = "std")]
+impl SnowflakeGenerator {
+    /// Creates a generator for the given node using the default (Twitter) epoch.
+    ///
+    /// **panics** if `node_id` doesn't fit in 10 bits.
+    pub fn new(node_id: u16) -> Self {
+        let node_id = check_width(node_id.into(), NODE_BITS)
+            .unwrap_or_else(|e| panic!("invalid snowflake node ID: {}", e));
+        SnowflakeGenerator {
+            node_id,
+            epoch_ms: TWITTER_EPOCH_MS,
+            state: AtomicU64::new(0),
+            clock: stats::ClockWatch::new(),
+            #[cfg(feature = "durable")]
+            persisted: None,
+        }
+    }
+
+    /// Creates a generator using the node ID supplied by `provider`.
+    pub fn from_provider<P: NodeIdProvider + ?Sized>(provider: &P) -> Result<Self, NodeIdError> {
+        let node_id = provider.node_id(NODE_BITS)?;
+        Ok(SnowflakeGenerator::new(node_id as u16))
+    }
+
+    /// Measures timestamps from `epoch` instead of the default (Twitter) epoch.
+    ///
+    /// **panics** if `epoch` is before the UNIX epoch.
+    pub fn with_epoch(mut self, epoch: SystemTime) -> Self {
+        self.epoch_ms = epoch
+            .duration_since(UNIX_EPOCH)
+            .expect("snowflake epoch before the UNIX epoch")
+            .as_millis() as u64;
+        self
+    }
+
+    /// Records the generator's timestamps in the file at `path`, so that after a restart it never
+    /// issues IDs earlier than ones it may have issued before, even if the clock went backwards in
+    /// the meantime. See the [`durable`](crate::durable) module for details.
+    ///
+    /// Call this after [`with_epoch`](SnowflakeGenerator::with_epoch). Only one generator (in one
+    /// process) may use a given file at a time.
+    ///
+    /// Requires the `durable` feature. Once persisted, [`next`](SnowflakeGenerator::next) and
+    /// friends also **panic** if the file can't be updated.
+    #[cfg(feature = "durable")]
+    pub fn with_persistence<P: AsRef<Path>>(mut self, path: P) -> io::Result<Self> {
+        let mark = crate::durable::TimestampMark::open(path.as_ref())?;
+        let start = mark.start().saturating_sub(self.epoch_ms);
+        assert!(start <= MAX_TIMESTAMP, "snowflake timestamp overflow");
+        self.state = AtomicU64::new(start << SEQUENCE_BITS);
+        self.persisted = Some(mark);
+        Ok(self)
+    }
+
+    /// This generator's node ID.
+    #[inline]
+    pub fn node_id(&self) -> u64 {
+        self.node_id
+    }
+
+    /// The generator's epoch.
+    pub fn epoch(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.epoch_ms)
+    }
+
+    /// Generates a new ID, waiting for the next millisecond if the current one's sequence is
+    /// exhausted.
+    ///
+    /// **panics** if the clock is past the end of the 41-bit timestamp range (about 69 years after
+    /// the epoch).
+    pub fn next(&self) -> SnowflakeId {
+        loop {
+            match self.try_next() {
+                Ok(id) => return id,
+                Err(SequenceExhausted) => thread::yield_now(),
+            }
+        }
+    }
+
+    /// Generates a new ID, failing instead of waiting if the current millisecond's sequence is
+    /// exhausted.
+    ///
+    /// **panics** under the same conditions as [`next`](SnowflakeGenerator::next).
+    pub fn try_next(&self) -> Result<SnowflakeId, SequenceExhausted> {
+        self.try_next_at(self.now())
+    }
+
+    /// Reserves `n` consecutive IDs in a single atomic operation.
+    ///
+    /// If the batch doesn't fit in the current millisecond, it continues into the following ones
+    /// without waiting for the clock; later calls to [`next`](SnowflakeGenerator::next) pick up
+    /// after the batch. Borrowing from the future like this keeps IDs unique and ordered but means
+    /// the IDs' timestamps may run slightly ahead of the wall clock.
+    ///
+    /// **panics** under the same conditions as [`next`](SnowflakeGenerator::next).
+    pub fn next_batch(&self, n: usize) -> SnowflakeBatch {
+        self.next_batch_at(self.now(), n as u64)
+    }
+
+    fn next_batch_at(&self, now: u64, n: u64) -> SnowflakeBatch {
+        self.clock.observe(now, &stats::SNOWFLAKE);
+        let mut current = self.state.load(Ordering::Relaxed);
+        loop {
+            let start = current.max(now << SEQUENCE_BITS);
+            let end = start
+                .checked_add(n)
+                .filter(|&end| end <= (MAX_TIMESTAMP + 1) << SEQUENCE_BITS)
+                .expect("snowflake timestamp overflow");
+            match self.state.compare_exchange_weak(
+                current,
+                end,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    if n > 0 {
+                        self.persist((end - 1) >> SEQUENCE_BITS);
+                    }
+                    stats::SNOWFLAKE.issued(n);
+                    return SnowflakeBatch {
+                        node_id: self.node_id,
+                        next: start,
+                        end,
+                    };
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    fn try_next_at(&self, now: u64) -> Result<SnowflakeId, SequenceExhausted> {
+        self.clock.observe(now, &stats::SNOWFLAKE);
+        let now_position = now << SEQUENCE_BITS;
+        let mut current = self.state.load(Ordering::Relaxed);
+        loop {
+            let position = current.max(now_position);
+            // Moving on to a millisecond the clock hasn't reached yet means we've run out of
+            // sequence numbers. Running ahead *within* a millisecond is fine: that just means the
+            // clock went backwards and we're sticking with the last timestamp we saw.
+            if position > now_position && position & MAX_SEQUENCE == 0 {
+                stats::SNOWFLAKE.exhausted();
+                return Err(SequenceExhausted);
+            }
+            match self.state.compare_exchange_weak(
+                current,
+                position + 1,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    self.persist(position >> SEQUENCE_BITS);
+                    stats::SNOWFLAKE.issued(1);
+                    return Ok(SnowflakeId::from_parts(
+                        position >> SEQUENCE_BITS,
+                        self.node_id,
+                        position & MAX_SEQUENCE,
+                    ));
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Makes sure `timestamp` is covered by the persisted mark, if any, before IDs using it are
+    /// returned.
+    #[inline]
+    #[cfg_attr(not(feature = "durable"), allow(unused_variables))]
+    fn persist(&self, timestamp: u64) {
+        #[cfg(feature = "durable")]
+        if let Some(mark) = &self.persisted {
+            mark.ensure_covers(self.epoch_ms + timestamp);
+        }
+    }
+
+    fn now(&self) -> u64 {
+        #[cfg(all(feature = "wasm-bindgen", target_arch = "wasm32"))]
+        let unix_ms = crate::wasm::unix_ms();
+        #[cfg(not(all(feature = "wasm-bindgen", target_arch = "wasm32")))]
+        let unix_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let now = unix_ms.saturating_sub(self.epoch_ms);
+        assert!(
+            now <= MAX_TIMESTAMP,
+            "snowflake timestamp overflow: the epoch is too far in the past"
+        );
+        now
+    }
+}
+
+/// A run of IDs reserved by [`SnowflakeGenerator::next_batch`].
+#[derive(Clone, Debug)]
+pub struct SnowflakeBatch {
+    node_id: u64,
+    // Positions are `timestamp << SEQUENCE_BITS | sequence`.
+    next: u64,
+    end: u64,
+}
+
+impl Iterator for SnowflakeBatch {
+    type Item = SnowflakeId;
+
+    #[inline]
+    fn next(&mut self) -> Option<SnowflakeId> {
+        if self.next == self.end {
+            return None;
+        }
+        let position = self.next;
+        self.next += 1;
+        Some(SnowflakeId::from_parts(
+            position >> SEQUENCE_BITS,
+            self.node_id,
+            position & MAX_SEQUENCE,
+        ))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.end - self.next) as usize;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for SnowflakeBatch {}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::{SequenceExhausted, SnowflakeGenerator, SnowflakeId, MAX_SEQUENCE, NODE_MASK};
+    use crate::node::StaticNodeId;
+    use core::convert::TryFrom;
+    use core::time::Duration;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_layout() {
+        let gen = SnowflakeGenerator::new(513);
+        let id = gen.try_next_at(12345).unwrap();
+        assert_eq!(id.timestamp(), 12345);
+        assert_eq!(id.node_id(), 513);
+        assert_eq!(id.sequence(), 0);
+        assert_eq!(gen.try_next_at(12345).unwrap().sequence(), 1);
+        assert_eq!(id.to_string().parse(), Ok(id));
+        assert_eq!(SnowflakeId::try_from(u64::from(id)), Ok(id));
+        assert_eq!(SnowflakeId::try_from(i64::from(id)), Ok(id));
+        assert!(SnowflakeId::try_from(1u64 << 63).is_err());
+        assert!(SnowflakeId::try_from(-1i64).is_err());
+        for bad in &["", "+1", "-1", "0x10", "9223372036854775808"] {
+            assert!(bad.parse::<SnowflakeId>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_partitions() {
+        let minute = Duration::from_secs(60);
+        let id = SnowflakeId::from_parts(125_000, 3, 7);
+        assert_eq!(id.partition_key(minute), 120_000);
+        let range = SnowflakeId::partition_range(120_000, minute);
+        assert_eq!(*range.start(), SnowflakeId::from_parts(120_000, 0, 0));
+        assert_eq!(
+            *range.end(),
+            SnowflakeId::from_parts(179_999, NODE_MASK, MAX_SEQUENCE)
+        );
+        assert!(range.contains(&id));
+        assert!(!range.contains(&SnowflakeId::from_parts(180_000, 0, 0)));
+
+        assert!(SnowflakeId::time_range(5, 4).is_empty());
+        let all = SnowflakeId::time_range(0, u64::MAX);
+        assert!(SnowflakeId::try_from(u64::from(*all.end())).is_ok());
+    }
+
+    #[test]
+    fn test_try_next_exhaustion() {
+        let gen = SnowflakeGenerator::new(1);
+        let mut last = None;
+        for _ in 0..=MAX_SEQUENCE {
+            let id = gen.try_next_at(7).unwrap();
+            assert!(Some(id) > last);
+            last = Some(id);
+        }
+        assert_eq!(gen.try_next_at(7), Err(SequenceExhausted));
+        // Clock going backwards doesn't help.
+        assert_eq!(gen.try_next_at(6), Err(SequenceExhausted));
+        let id = gen.try_next_at(8).unwrap();
+        assert_eq!((id.timestamp(), id.sequence()), (8, 0));
+    }
+
+    #[test]
+    fn test_next_batch_spans_ticks() {
+        let gen = SnowflakeGenerator::new(2);
+        gen.try_next_at(100).unwrap();
+        let batch = gen.next_batch_at(100, 5000);
+        assert_eq!(batch.len(), 5000);
+        let ids: Vec<_> = batch.collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!((ids[0].timestamp(), ids[0].sequence()), (100, 1));
+        let last = ids[ids.len() - 1];
+        assert_eq!((last.timestamp(), last.sequence()), (101, 904));
+        // The generator continues after the batch even though the clock hasn't moved.
+        let next = gen.try_next_at(100).unwrap();
+        assert_eq!((next.timestamp(), next.sequence()), (101, 905));
+        assert_eq!(gen.next_batch_at(100, 0).len(), 0);
+    }
+
+    #[test]
+    fn test_next_batch_overflow() {
+        let gen = SnowflakeGenerator::new(2);
+        let first = gen.next();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| gen.next_batch(usize::MAX)));
+        let message = result.unwrap_err();
+        assert_eq!(
+            message.downcast_ref::<String>().map(String::as_str),
+            Some("snowflake timestamp overflow")
+        );
+        // Nothing was reserved.
+        let next = gen.next();
+        assert!(next > first);
+        assert!(next.timestamp() - first.timestamp() < 1000);
+    }
+
+    #[test]
+    fn test_threaded_unique() {
+        let gen = Arc::new(SnowflakeGenerator::new(4));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let gen = gen.clone();
+                thread::spawn(move || (0..10_000).map(|_| gen.next()).collect::<Vec<_>>())
+            })
+            .collect();
+        let mut ids: Vec<_> = threads
+            .into_iter()
+            .flat_map(|t| t.join().unwrap())
+            .collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 80_000);
+    }
+
+    #[test]
+    fn test_next_ordered() {
+        let gen = SnowflakeGenerator::from_provider(&StaticNodeId(3)).unwrap();
+        let ids: Vec<_> = (0..10_000).map(|_| gen.next()).collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert!(SnowflakeGenerator::from_provider(&StaticNodeId(1024)).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::convert::TryFrom;
+use core::fmt;
+use core::mem;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::thread;
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "bevy")]
+use bevy_ecs::reflect::ReflectComponent;
+
+#[cfg(feature = "std")]
+use crate::atomic::{AtomicU64, Ordering};
+use crate::display::pad_id;
+#[cfg(feature = "std")]
+use crate::node::{NodeIdError, NodeIdProvider};
+use crate::process_unique_id::{decimal_u64, ParseIdError};
+#[cfg(feature = "std")]
+use crate::stats;
+use crate::IdOutOfRange;
+
+#[cfg(feature = "std")]
+const TIME_BITS: u32 = 39;
+const SEQUENCE_BITS: u32 = 8;
+const MACHINE_BITS: u32 = 16;
+
+#[cfg(feature = "std")]
+const MAX_TIME: u64 = (1 << TIME_BITS) - 1;
+const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
+const MACHINE_MASK: u64 = (1 << MACHINE_BITS) - 1;
+
+/// Sonyflake measures time in units of 10 milliseconds.
+#[cfg(feature = "std")]
+pub(crate) const TIME_UNIT_MS: u64 = 10;
+
+/// Milliseconds between the UNIX epoch and Sonyflake's default start time, 2014-09-01T00:00:00Z.
+#[cfg(feature = "std")]
+pub(crate) const DEFAULT_START_MS: u64 = 1_409_529_600_000;
+
+/// A 64-bit ID in the layout used by Sony's Sonyflake.
+///
+/// A zero sign bit, a 39-bit timestamp in units of 10 milliseconds (relative to the generator's
+/// start time), an 8-bit sequence number, and a 16-bit machine ID. Compared to
+/// [`SnowflakeId`](crate::SnowflakeId), Sonyflake trades per-millisecond throughput (256 IDs per
+/// 10ms per machine) for a longer lifetime (about 174 years) and more machines.
+///
+/// Displayed as a decimal integer.
+///
+/// # Memory layout
+///
+/// `#[repr(transparent)]` over a `u64`, like [`SnowflakeId`](crate::SnowflakeId).
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(Serialize, Deserialize),
+    serde(try_from = "u64")
+)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::NoUninit))]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(zerocopy::IntoBytes, zerocopy::Immutable, zerocopy::KnownLayout)
+)]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_ecs::component::Component, bevy_reflect::Reflect),
+    reflect(opaque, Component, Debug, PartialEq, Hash, Clone)
+)]
+#[repr(transparent)]
+pub struct SonyflakeId(u64);
+
+const _: () = assert!(
+    mem::size_of::<SonyflakeId>() == 8 && mem::align_of::<SonyflakeId>() == mem::align_of::<u64>()
+);
+
+impl SonyflakeId {
+    /// Time since the generator's start time, in units of 10 milliseconds.
+    #[inline]
+    pub fn time(self) -> u64 {
+        self.0 >> (SEQUENCE_BITS + MACHINE_BITS)
+    }
+
+    /// The sequence number within the 10ms time unit.
+    #[inline]
+    pub fn sequence(self) -> u64 {
+        (self.0 >> MACHINE_BITS) & MAX_SEQUENCE
+    }
+
+    /// The ID of the machine that generated this ID.
+    #[inline]
+    pub fn machine_id(self) -> u16 {
+        (self.0 & MACHINE_MASK) as u16
+    }
+}
+
+impl fmt::Display for SonyflakeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pad_id(f, format_args!(""), format_args!("{}", self.0))
+    }
+}
+
+/// Parses the decimal form produced by `Display`.
+impl FromStr for SonyflakeId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        SonyflakeId::try_from(decimal_u64(s)?)
+            .map_err(|_| ParseIdError::at(s, 0, "a value below 2^63"))
+    }
+}
+
+impl From<SonyflakeId> for u64 {
+    #[inline]
+    fn from(id: SonyflakeId) -> u64 {
+        id.0
+    }
+}
+
+/// Never negative: the sign bit of a Sonyflake ID is always zero.
+impl From<SonyflakeId> for i64 {
+    #[inline]
+    fn from(id: SonyflakeId) -> i64 {
+        id.0 as i64
+    }
+}
+
+/// Fails if the top (sign) bit is set.
+impl TryFrom<u64> for SonyflakeId {
+    type Error = IdOutOfRange;
+
+    #[inline]
+    fn try_from(value: u64) -> Result<Self, IdOutOfRange> {
+        if value >> 63 != 0 {
+            return Err(IdOutOfRange::new());
+        }
+        Ok(SonyflakeId(value))
+    }
+}
+
+/// Fails if `value` is negative.
+impl TryFrom<i64> for SonyflakeId {
+    type Error = IdOutOfRange;
+
+    #[inline]
+    fn try_from(value: i64) -> Result<Self, IdOutOfRange> {
+        if value < 0 {
+            return Err(IdOutOfRange::new());
+        }
+        Ok(SonyflakeId(value as u64))
+    }
+}
+
+/// Generates [`SonyflakeId`]s, bit-for-bit compatible with Sonyflake's Go implementation.
+///
+/// Like the Go implementation, the generator sleeps when a time unit's 256 sequence numbers run
+/// out, and keeps counting from the last time it saw if the clock goes backwards. Use
+/// [`PrivateIpNodeId`](crate::node::PrivateIpNodeId) with
+/// [`from_provider`](SonyflakeGenerator::from_provider) to get Sonyflake's default machine ID.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct SonyflakeGenerator {
+    machine_id: u64,
+    start_ms: u64,
+    // The last ID's `time << SEQUENCE_BITS | sequence`.
+    state: AtomicU64,
+    clock: stats::ClockWatch,
+}
+
+#[cfg(feature = "std")]
+impl SonyflakeGenerator {
+    /// Creates a generator for the given machine using the default start time (2014-09-01).
+    pub fn new(machine_id: u16) -> Self {
+        SonyflakeGenerator {
+            machine_id: machine_id.into(),
+            start_ms: DEFAULT_START_MS,
+            // So that the first ID in the first time unit gets sequence number zero.
+            state: AtomicU64::new(MAX_SEQUENCE),
+            clock: stats::ClockWatch::new(),
+        }
+    }
+
+    /// Creates a generator using the machine ID supplied by `provider`.
+    pub fn from_provider<P: NodeIdProvider + ?Sized>(provider: &P) -> Result<Self, NodeIdError> {
+        let machine_id = provider.node_id(MACHINE_BITS)?;
+        Ok(SonyflakeGenerator::new(machine_id as u16))
+    }
+
+    /// Measures time from `start` instead of the default start time.
+    ///
+    /// **panics** if `start` is before the UNIX epoch.
+    pub fn with_start_time(mut self, start: SystemTime) -> Self {
+        self.start_ms = start
+            .duration_since(UNIX_EPOCH)
+            .expect("sonyflake start time before the UNIX epoch")
+            .as_millis() as u64;
+        self
+    }
+
+    /// This generator's machine ID.
+    #[inline]
+    pub fn machine_id(&self) -> u16 {
+        self.machine_id as u16
+    }
+
+    /// The generator's start time.
+    pub fn start_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.start_ms)
+    }
+
+    /// Generates a new ID, sleeping until the next time unit if the current one's sequence is
+    /// exhausted.
+    ///
+    /// **panics** if the clock is past the end of the 39-bit time range (about 174 years after the
+    /// start time).
+    pub fn next(&self) -> SonyflakeId {
+        let (id, sleep) = self.next_at(self.elapsed_ms());
+        if sleep > 0 {
+            thread::sleep(Duration::from_millis(sleep));
+        }
+        id
+    }
+
+    /// Claims the next ID at `now_ms` (milliseconds since the start time), returning it along
+    /// with how long to sleep before handing it out.
+    fn next_at(&self, now_ms: u64) -> (SonyflakeId, u64) {
+        self.clock.observe(now_ms, &stats::SONYFLAKE);
+        let now = now_ms / TIME_UNIT_MS;
+        let mut current = self.state.load(Ordering::Relaxed);
+        loop {
+            let (time, sequence) = (current >> SEQUENCE_BITS, current & MAX_SEQUENCE);
+            // Like the Go implementation, only sleep when borrowing a time unit because the
+            // sequence ran out, not when sticking with a time unit because the clock went back.
+            let (next, borrowed) = if time < now {
+                (now << SEQUENCE_BITS, false)
+            } else if sequence == MAX_SEQUENCE {
+                ((time + 1) << SEQUENCE_BITS, true)
+            } else {
+                (current + 1, false)
+            };
+            let time = next >> SEQUENCE_BITS;
+            assert!(time <= MAX_TIME, "sonyflake time overflow");
+            match self.state.compare_exchange_weak(
+                current,
+                next,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    let id = SonyflakeId((next << MACHINE_BITS) | self.machine_id);
+                    stats::SONYFLAKE.issued(1);
+                    let sleep = if borrowed {
+                        stats::SONYFLAKE.exhausted();
+                        (time * TIME_UNIT_MS).saturating_sub(now_ms)
+                    } else {
+                        0
+                    };
+                    return (id, sleep);
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+            .saturating_sub(self.start_ms)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::{SonyflakeGenerator, SonyflakeId, MAX_SEQUENCE};
+    use crate::node::{NodeIdProvider, PrivateIpNodeId};
+    use core::convert::TryFrom;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_layout() {
+        let gen = SonyflakeGenerator::new(0xbeef);
+        let (id, sleep) = gen.next_at(12_345);
+        assert_eq!(sleep, 0);
+        assert_eq!(id.time(), 1234);
+        assert_eq!(id.sequence(), 0);
+        assert_eq!(id.machine_id(), 0xbeef);
+        assert_eq!(u64::from(id), (1234 << 24) | 0xbeef);
+        assert_eq!(gen.next_at(12_349).0.sequence(), 1);
+        assert_eq!(id.to_string().parse(), Ok(id));
+        assert_eq!(SonyflakeId::try_from(i64::from(id)), Ok(id));
+        assert!(SonyflakeId::try_from(-1i64).is_err());
+    }
+
+    #[test]
+    fn test_sequence_overflow() {
+        let gen = SonyflakeGenerator::new(1);
+        for i in 0..=MAX_SEQUENCE {
+            let (id, sleep) = gen.next_at(100);
+            assert_eq!((id.time(), id.sequence(), sleep), (10, i, 0));
+        }
+        // Borrowed from the next time unit: the caller has to wait 10ms for it.
+        let (id, sleep) = gen.next_at(100);
+        assert_eq!((id.time(), id.sequence(), sleep), (11, 0, 10));
+        // The clock going backwards doesn't cause a sleep.
+        let (id, sleep) = gen.next_at(50);
+        assert_eq!((id.time(), id.sequence(), sleep), (11, 1, 0));
+    }
+
+    #[test]
+    fn test_private_ip_machine_id() {
+        let provider = PrivateIpNodeId::with_addr(Ipv4Addr::new(192, 168, 1, 2));
+        let gen = SonyflakeGenerator::from_provider(&provider).unwrap();
+        assert_eq!(gen.machine_id(), 0x0102);
+        assert_eq!(provider.node_id(16), Ok(0x0102));
+        let a = gen.next();
+        assert!(gen.next() > a);
+    }
+}
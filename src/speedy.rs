@@ -0,0 +1,126 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::convert::TryFrom;
+use std::mem;
+
+use speedy::{Context, Readable, Reader, Writable, Writer};
+
+use crate::{
+    FlakeId, ObjectId, ProcessUniqueId, SmallUniqueId, SnowflakeId, SonyflakeId, Tsid, Xid,
+};
+
+fn out_of_range<C: Context>(ty: &str) -> C::Error {
+    speedy::Error::custom(format!("ID out of range for {}", ty)).into()
+}
+
+// IDs that are integers are written as one, in the context's endianness.
+macro_rules! impl_int {
+    ($($ty:ident: $int:ident, $write:ident, $read:ident, $to:expr, $from:expr;)*) => {$(
+        impl<C: Context> Writable<C> for $ty {
+            #[inline]
+            fn write_to<W: ?Sized + Writer<C>>(&self, writer: &mut W) -> Result<(), C::Error> {
+                let to: fn($ty) -> $int = $to;
+                writer.$write(to(*self))
+            }
+
+            #[inline]
+            fn bytes_needed(&self) -> Result<usize, C::Error> {
+                Ok(mem::size_of::<$int>())
+            }
+        }
+
+        impl<'a, C: Context> Readable<'a, C> for $ty {
+            #[inline]
+            fn read_from<R: Reader<'a, C>>(reader: &mut R) -> Result<Self, C::Error> {
+                let from: fn($int) -> Option<$ty> = $from;
+                from(reader.$read()?).ok_or_else(|| out_of_range::<C>(stringify!($ty)))
+            }
+
+            #[inline]
+            fn minimum_bytes_needed() -> usize {
+                mem::size_of::<$int>()
+            }
+        }
+    )*};
+}
+
+// IDs that are byte strings are written as is.
+macro_rules! impl_bytes {
+    ($($ty:ident),*) => {$(
+        impl<C: Context> Writable<C> for $ty {
+            #[inline]
+            fn write_to<W: ?Sized + Writer<C>>(&self, writer: &mut W) -> Result<(), C::Error> {
+                writer.write_bytes(&self.bytes())
+            }
+
+            #[inline]
+            fn bytes_needed(&self) -> Result<usize, C::Error> {
+                Ok(12)
+            }
+        }
+
+        impl<'a, C: Context> Readable<'a, C> for $ty {
+            #[inline]
+            fn read_from<R: Reader<'a, C>>(reader: &mut R) -> Result<Self, C::Error> {
+                let mut bytes = [0; 12];
+                reader.read_bytes(&mut bytes)?;
+                Ok($ty::from_bytes(bytes))
+            }
+
+            #[inline]
+            fn minimum_bytes_needed() -> usize {
+                12
+            }
+        }
+    )*};
+}
+
+impl_int! {
+    ProcessUniqueId: u128, write_u128, read_u128, ProcessUniqueId::to_u128, ProcessUniqueId::from_u128;
+    SnowflakeId: u64, write_u64, read_u64, u64::from, |n| SnowflakeId::try_from(n).ok();
+    FlakeId: u128, write_u128, read_u128, u128::from, |n| Some(FlakeId::from(n));
+    SonyflakeId: u64, write_u64, read_u64, u64::from, |n| SonyflakeId::try_from(n).ok();
+    Tsid: u64, write_u64, read_u64, u64::from, |n| Some(Tsid::from(n));
+    SmallUniqueId: u64, write_u64, read_u64, u64::from, |n| Some(SmallUniqueId::from(n));
+}
+
+impl_bytes!(ObjectId, Xid);
+
+#[cfg(test)]
+mod test {
+    use crate::{ObjectId, ProcessUniqueId, SnowflakeId};
+    use core::convert::TryFrom;
+    use speedy::{BigEndian, LittleEndian, Readable, Writable};
+
+    #[test]
+    fn test_speedy() {
+        let ids = vec![ProcessUniqueId::new(), ProcessUniqueId::new()];
+        let bytes = ids.write_to_vec().unwrap();
+        // A 4-byte length, then 16 bytes per ID.
+        assert_eq!(bytes.len(), 4 + 2 * 16);
+        assert_eq!(
+            Vec::<ProcessUniqueId>::read_from_buffer(&bytes).unwrap(),
+            ids
+        );
+
+        let id = SnowflakeId::try_from(0x0102u64).unwrap();
+        let bytes = id.write_to_vec_with_ctx(BigEndian::default()).unwrap();
+        assert_eq!(bytes, [0, 0, 0, 0, 0, 0, 1, 2]);
+        assert_eq!(
+            SnowflakeId::read_from_buffer_with_ctx(BigEndian::default(), &bytes).unwrap(),
+            id
+        );
+        // The sign bit is never set in a snowflake ID.
+        assert!(
+            SnowflakeId::read_from_buffer_with_ctx(LittleEndian::default(), &[0xff; 8]).is_err()
+        );
+
+        let id = ObjectId::from_bytes(*b"abcdefghijkl");
+        assert_eq!(id.write_to_vec().unwrap(), b"abcdefghijkl");
+    }
+}
@@ -0,0 +1,317 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use crate::atomic::{AtomicU64, Ordering};
+use crate::{process_unique_id, small_unique_id};
+
+/// Process-wide counters for one kind of time based generator, summed over all its instances.
+#[derive(Debug)]
+pub(crate) struct Counters {
+    // The `generator` label of the generator's metrics and tracing events.
+    #[cfg_attr(not(any(feature = "metrics", feature = "tracing")), allow(dead_code))]
+    name: &'static str,
+    issued: AtomicU64,
+    exhausted: AtomicU64,
+    clock_rollbacks: AtomicU64,
+}
+
+impl Counters {
+    const fn new(name: &'static str) -> Self {
+        Counters {
+            name,
+            issued: AtomicU64::new(0),
+            exhausted: AtomicU64::new(0),
+            clock_rollbacks: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn issued(&self, n: u64) {
+        self.issued.fetch_add(n, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn exhausted(&self) {
+        self.exhausted.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!(SEQUENCE_EXHAUSTED, "generator" => self.name).increment(1);
+    }
+
+    fn clock_rollback(&self) {
+        self.clock_rollbacks.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!(CLOCK_ROLLBACKS, "generator" => self.name).increment(1);
+    }
+
+    fn get(&self) -> GeneratorStats {
+        GeneratorStats {
+            issued: self.issued.load(Ordering::Relaxed),
+            exhausted: self.exhausted.load(Ordering::Relaxed),
+            clock_rollbacks: self.clock_rollbacks.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub(crate) static SNOWFLAKE: Counters = Counters::new("snowflake");
+pub(crate) static SONYFLAKE: Counters = Counters::new("sonyflake");
+pub(crate) static TSID: Counters = Counters::new("tsid");
+pub(crate) static FLAKE: Counters = Counters::new("flake");
+pub(crate) static SHARDED: Counters = Counters::new("sharded");
+
+/// The latest clock reading a generator has seen, for noticing when the clock goes backwards.
+#[derive(Debug)]
+pub(crate) struct ClockWatch(AtomicU64);
+
+impl ClockWatch {
+    pub(crate) const fn new() -> Self {
+        ClockWatch(AtomicU64::new(0))
+    }
+
+    /// Records a clock reading, counting a rollback in `counters` if it's behind the latest one.
+    #[inline]
+    pub(crate) fn observe(&self, now: u64, counters: &Counters) {
+        let latest = self.0.load(Ordering::Relaxed);
+        if now > latest {
+            self.0.fetch_max(now, Ordering::Relaxed);
+        } else if now < latest {
+            self.rolled_back(latest, now, counters);
+        }
+    }
+
+    #[cold]
+    fn rolled_back(&self, latest: u64, now: u64, counters: &Counters) {
+        // Only the thread that moves the watch back counts the rollback, so it's counted once
+        // rather than for every ID created until the clock catches up.
+        if self
+            .0
+            .compare_exchange(latest, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                generator = counters.name,
+                behind_ms = latest - now,
+                "system clock went backwards"
+            );
+            counters.clock_rollback();
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+const PREFIXES_ALLOCATED: &str = "snowflake_puid_prefixes_allocated";
+#[cfg(feature = "metrics")]
+const SMALL_PREFIXES_ALLOCATED: &str = "snowflake_small_prefixes_allocated";
+#[cfg(feature = "metrics")]
+const SEQUENCE_EXHAUSTED: &str = "snowflake_sequence_exhausted_total";
+#[cfg(feature = "metrics")]
+const CLOCK_ROLLBACKS: &str = "snowflake_clock_rollbacks_total";
+
+/// Called by the `ProcessUniqueId` prefix counter with the number of prefixes handed out.
+#[cfg(feature = "metrics")]
+pub(crate) fn on_prefix_allocated(count: usize) {
+    metrics::gauge!(PREFIXES_ALLOCATED).set(count as f64);
+}
+
+/// Called by the `SmallUniqueId` prefix counter with the number of prefixes handed out.
+#[cfg(feature = "metrics")]
+pub(crate) fn on_small_prefix_allocated(count: u32) {
+    metrics::gauge!(SMALL_PREFIXES_ALLOCATED).set(count);
+}
+
+/// Registers descriptions of the metrics this crate records with the installed `metrics`
+/// recorder.
+///
+/// Requires the `metrics` feature. With it, the crate records:
+///
+/// - `snowflake_puid_prefixes_allocated` (gauge): `ProcessUniqueId` prefixes handed out.
+/// - `snowflake_small_prefixes_allocated` (gauge): `SmallUniqueId` prefixes handed out.
+/// - `snowflake_sequence_exhausted_total` (counter, labeled by `generator`): times a time based
+///   generator ran out of sequence numbers, see [`GeneratorStats::exhausted`].
+/// - `snowflake_clock_rollbacks_total` (counter, labeled by `generator`): times a time based
+///   generator saw the clock go backwards, see [`GeneratorStats::clock_rollbacks`].
+///
+/// The metrics go to whatever recorder the application installs, e.g.
+/// `metrics-exporter-prometheus` to expose them to Prometheus. Call this once after installing
+/// it; the metrics are recorded whether or not it's called.
+#[cfg(feature = "metrics")]
+pub fn describe_metrics() {
+    metrics::describe_gauge!(
+        PREFIXES_ALLOCATED,
+        "ProcessUniqueId prefixes handed out, out of usize::MAX."
+    );
+    metrics::describe_gauge!(
+        SMALL_PREFIXES_ALLOCATED,
+        "SmallUniqueId prefixes handed out, out of 2^24."
+    );
+    metrics::describe_counter!(
+        SEQUENCE_EXHAUSTED,
+        "Times a time based generator ran out of sequence numbers for the current time."
+    );
+    metrics::describe_counter!(
+        CLOCK_ROLLBACKS,
+        "Times a time based generator saw the system clock go backwards."
+    );
+}
+
+/// Counters for one kind of time based generator, summed over all its instances.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct GeneratorStats {
+    issued: u64,
+    exhausted: u64,
+    clock_rollbacks: u64,
+}
+
+impl GeneratorStats {
+    /// The number of IDs issued.
+    #[inline]
+    pub fn issued(&self) -> u64 {
+        self.issued
+    }
+
+    /// The number of times a generator ran out of sequence numbers for the current time and had
+    /// to wait, fail, or move on to the next millisecond (or time unit) early.
+    ///
+    /// A steadily growing count means the generator is running close to its capacity.
+    #[inline]
+    pub fn exhausted(&self) -> u64 {
+        self.exhausted
+    }
+
+    /// The number of times a generator saw the system clock go backwards.
+    ///
+    /// Generators keep issuing IDs with the last timestamp they used until the clock catches up,
+    /// so a rollback eats into the sequence numbers of that timestamp. A thread that's preempted
+    /// between reading the clock and creating its ID can occasionally be counted as a rollback
+    /// too.
+    #[inline]
+    pub fn clock_rollbacks(&self) -> u64 {
+        self.clock_rollbacks
+    }
+}
+
+/// A snapshot of the process's ID generators, returned by [`stats`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Stats {
+    prefixes_allocated: usize,
+    ids_claimed: u128,
+    small_prefixes_allocated: u32,
+    snowflake: GeneratorStats,
+    sonyflake: GeneratorStats,
+    tsid: GeneratorStats,
+    flake: GeneratorStats,
+    sharded: GeneratorStats,
+}
+
+impl Stats {
+    /// The number of [`ProcessUniqueId`](crate::ProcessUniqueId) prefixes handed out so far, out
+    /// of `usize::MAX`.
+    #[inline]
+    pub fn prefixes_allocated(&self) -> usize {
+        self.prefixes_allocated
+    }
+
+    /// Approximately how many process unique IDs have been issued.
+    ///
+    /// Threads claim IDs in blocks (see
+    /// [`set_block_size`](crate::ProcessUniqueId::set_block_size)), so this counts every ID in
+    /// every claimed block: an upper bound that overshoots by up to a block per thread.
+    #[inline]
+    pub fn ids_claimed(&self) -> u128 {
+        self.ids_claimed
+    }
+
+    /// The number of [`SmallUniqueId`](crate::SmallUniqueId) prefixes handed out so far, out of
+    /// 2^24.
+    #[inline]
+    pub fn small_prefixes_allocated(&self) -> u32 {
+        self.small_prefixes_allocated
+    }
+
+    /// Counters for all [`SnowflakeGenerator`](crate::SnowflakeGenerator)s.
+    #[inline]
+    pub fn snowflake(&self) -> GeneratorStats {
+        self.snowflake
+    }
+
+    /// Counters for all [`SonyflakeGenerator`](crate::SonyflakeGenerator)s.
+    #[inline]
+    pub fn sonyflake(&self) -> GeneratorStats {
+        self.sonyflake
+    }
+
+    /// Counters for all [`TsidGenerator`](crate::TsidGenerator)s.
+    #[inline]
+    pub fn tsid(&self) -> GeneratorStats {
+        self.tsid
+    }
+
+    /// Counters for all [`FlakeGenerator`](crate::FlakeGenerator)s.
+    #[inline]
+    pub fn flake(&self) -> GeneratorStats {
+        self.flake
+    }
+
+    /// Counters for all [`ShardedIdGenerator`](crate::ShardedIdGenerator)s.
+    #[inline]
+    pub fn sharded(&self) -> GeneratorStats {
+        self.sharded
+    }
+}
+
+/// Takes a snapshot of the process's ID generators, for capacity planning and monitoring.
+///
+/// ```
+/// use snowflake::{stats, ProcessUniqueId, SnowflakeGenerator};
+///
+/// ProcessUniqueId::new();
+/// SnowflakeGenerator::new(1).next();
+///
+/// let stats = stats();
+/// assert!(stats.prefixes_allocated() >= 1);
+/// assert!(stats.ids_claimed() >= 1);
+/// assert!(stats.snowflake().issued() >= 1);
+/// ```
+///
+/// The counters are read one at a time without stopping other threads, so a snapshot taken
+/// while IDs are being created may be slightly inconsistent.
+pub fn stats() -> Stats {
+    let (prefixes_allocated, ids_claimed) = process_unique_id::claimed();
+    Stats {
+        prefixes_allocated,
+        ids_claimed,
+        small_prefixes_allocated: small_unique_id::prefixes_allocated(),
+        snowflake: SNOWFLAKE.get(),
+        sonyflake: SONYFLAKE.get(),
+        tsid: TSID.get(),
+        flake: FLAKE.get(),
+        sharded: SHARDED.get(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::stats;
+    use crate::{FlakeGenerator, SnowflakeGenerator, SonyflakeGenerator, TsidGenerator};
+
+    #[test]
+    fn test_generator_stats() {
+        let before = stats();
+        let gen = SnowflakeGenerator::new(1);
+        gen.next();
+        gen.next_batch(10).for_each(drop);
+        SonyflakeGenerator::new(1).next();
+        TsidGenerator::new(1).next();
+        FlakeGenerator::new(1).next();
+        let after = stats();
+        assert!(after.snowflake().issued() >= before.snowflake().issued() + 11);
+        assert!(after.sonyflake().issued() > before.sonyflake().issued());
+        assert!(after.tsid().issued() > before.tsid().issued());
+        assert!(after.flake().issued() > before.flake().issued());
+        assert!(after.prefixes_allocated() >= before.prefixes_allocated());
+    }
+}
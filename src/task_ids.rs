@@ -0,0 +1,138 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Associates Tokio tasks with process unique IDs.
+//!
+//! Requires the `tokio` feature. Tokio identifies tasks by [`task::Id`]s, which only mean
+//! something to the runtime. Tasks spawned with [`spawn`] are also given a [`ProcessUniqueId`],
+//! which the application can log and attach to its own records; [`lookup`] maps the runtime's IDs
+//! (in a task dump, say) back to it for as long as the task exists:
+//!
+//! ```
+//! use snowflake::task_ids;
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let (handle, id) = task_ids::spawn(async { task_ids::current() });
+//! assert_eq!(task_ids::lookup(handle.id()), Some(id));
+//! assert_eq!(handle.await.unwrap(), Some(id));
+//! # });
+//! ```
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use tokio::task::{self, JoinHandle};
+
+use crate::ProcessUniqueId;
+
+static TASKS: Mutex<BTreeMap<task::Id, ProcessUniqueId>> = Mutex::new(BTreeMap::new());
+
+fn tasks() -> MutexGuard<'static, BTreeMap<task::Id, ProcessUniqueId>> {
+    TASKS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Where a spawned task is in its life. Only changed with `TASKS` locked, so a task that finishes
+// before `spawn` has registered it is never registered.
+enum State {
+    Spawning,
+    Registered(task::Id),
+    Finished,
+}
+
+// Unregisters the task when its future completes or is dropped (if it's aborted, or the runtime
+// shuts down), whether or not it was ever polled.
+struct Unregister(Arc<Mutex<State>>);
+
+impl Drop for Unregister {
+    fn drop(&mut self) {
+        let mut tasks = tasks();
+        let mut state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let State::Registered(id) = std::mem::replace(&mut *state, State::Finished) {
+            tasks.remove(&id);
+        }
+    }
+}
+
+/// Spawns `future` on the current Tokio runtime, like [`tokio::spawn`], with a new process unique
+/// ID. Returns the task's handle and ID.
+///
+/// **panics** if called outside a Tokio runtime.
+pub fn spawn<F>(future: F) -> (JoinHandle<F::Output>, ProcessUniqueId)
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let id = ProcessUniqueId::new();
+    (spawn_with_id(id, future), id)
+}
+
+/// Spawns `future` on the current Tokio runtime, like [`tokio::spawn`], associating it with `id`.
+///
+/// **panics** if called outside a Tokio runtime.
+pub fn spawn_with_id<F>(id: ProcessUniqueId, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let state = Arc::new(Mutex::new(State::Spawning));
+    let unregister = Unregister(state.clone());
+    let handle = tokio::spawn(async move {
+        let _unregister = unregister;
+        future.await
+    });
+    let mut tasks = tasks();
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    if let State::Spawning = *state {
+        *state = State::Registered(handle.id());
+        tasks.insert(handle.id(), id);
+    }
+    handle
+}
+
+/// The ID of the task with the runtime's ID `task`, if it was spawned with [`spawn`] or
+/// [`spawn_with_id`] and hasn't finished yet.
+pub fn lookup(task: task::Id) -> Option<ProcessUniqueId> {
+    tasks().get(&task).copied()
+}
+
+/// The ID of the task this is called from, if it was spawned with [`spawn`] or
+/// [`spawn_with_id`].
+pub fn current() -> Option<ProcessUniqueId> {
+    lookup(task::try_id()?)
+}
+
+/// All running tasks spawned with [`spawn`] or [`spawn_with_id`], by the runtime's ID.
+pub fn all() -> Vec<(task::Id, ProcessUniqueId)> {
+    tasks().iter().map(|(&task, &id)| (task, id)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{lookup, spawn};
+
+    #[tokio::test]
+    async fn test_task_ids_unregistered() {
+        let (done, id) = spawn(async {});
+        let task = done.id();
+        done.await.unwrap();
+        assert_eq!(lookup(task), None);
+
+        // Aborted before it ever ran.
+        let (pending, _) = spawn(std::future::pending::<()>());
+        let task = pending.id();
+        assert!(lookup(task).is_some());
+        pending.abort();
+        assert!(pending.await.unwrap_err().is_cancelled());
+        assert_eq!(lookup(task), None);
+
+        let (running, other) = spawn(std::future::pending::<()>());
+        assert_ne!(other, id);
+        assert!(super::all().contains(&(running.id(), other)));
+        running.abort();
+    }
+}
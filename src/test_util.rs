@@ -0,0 +1,158 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Helpers for testing code that creates IDs.
+//!
+//! Requires the `test-util` feature. Only enable it for tests (e.g. in `[dev-dependencies]`):
+//! it lets IDs repeat.
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{process_unique_id, scoped_unique_id, small_unique_id};
+
+// Serializes guards, so tests holding one don't see each other's IDs.
+static GUARD_LOCK: Mutex<()> = Mutex::new(());
+
+/// Resets the process unique ID generators for the duration of a test.
+///
+/// Creating a guard saves the state of the [`ProcessUniqueId`](crate::ProcessUniqueId),
+/// [`SmallUniqueId`](crate::SmallUniqueId), and [`ScopedUniqueId`](crate::ScopedUniqueId)
+/// generators (the global counters, the block size, and the current thread's state) and resets
+/// it to that of a freshly started process, so the test can assert on exact IDs. Dropping the
+/// guard puts the saved state back.
+///
+/// ```
+/// use snowflake::test_util::GeneratorGuard;
+/// use snowflake::{ProcessUniqueId, SmallUniqueId};
+///
+/// let before = ProcessUniqueId::new();
+/// {
+///     let _guard = GeneratorGuard::new();
+///     assert_eq!(ProcessUniqueId::new().to_string(), "puid-0-0");
+///     assert_eq!(ProcessUniqueId::new().to_string(), "puid-0-1");
+///     assert_eq!(SmallUniqueId::new().to_string(), "spuid-0-0");
+/// }
+/// // Picks up where it left off.
+/// assert_eq!(ProcessUniqueId::new(), ProcessUniqueId::from_u128(before.to_u128() + 1).unwrap());
+/// ```
+///
+/// Only one guard exists at a time: `new` blocks until any other guard is dropped, so tests that
+/// run in parallel and each take a guard don't interfere with each other. Tests that create IDs
+/// *without* a guard still do, and may get IDs that repeat ones created under a guard, or vice
+/// versa.
+///
+/// The guard must be dropped on the thread that created it, since that's the thread whose state
+/// it restores. Threads spawned while the guard is alive start out fresh, as they would in a new
+/// process.
+///
+/// Guards can't be used in a copy of the crate that claims prefixes from another copy's counter
+/// (see [`ProcessUniqueId::use_prefix_counter`](crate::ProcessUniqueId::use_prefix_counter)):
+/// resetting that counter would hand every copy sharing it IDs it has already handed out.
+pub struct GeneratorGuard {
+    puid: Option<process_unique_id::SavedState>,
+    small: (u32, u64),
+    scoped: Option<scoped_unique_id::SavedState>,
+    // Also keeps the guard on this thread (`MutexGuard` isn't `Send`).
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl GeneratorGuard {
+    /// Saves and resets the generator state, waiting for any other guard to be dropped first.
+    ///
+    /// **panics** if prefixes are claimed from another copy of the crate's counter:
+    ///
+    /// ```
+    /// use snowflake::test_util::GeneratorGuard;
+    /// use snowflake::{AtomicPrefixCounter, ProcessUniqueId};
+    ///
+    /// static HOST: AtomicPrefixCounter = AtomicPrefixCounter::new(0);
+    /// ProcessUniqueId::use_prefix_counter(&HOST).unwrap();
+    /// assert!(std::panic::catch_unwind(GeneratorGuard::new).is_err());
+    /// ```
+    pub fn new() -> Self {
+        let lock = GUARD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        GeneratorGuard {
+            puid: Some(process_unique_id::reset_state()),
+            small: small_unique_id::reset_state(),
+            scoped: Some(scoped_unique_id::reset_state()),
+            _lock: lock,
+        }
+    }
+
+    /// Makes the [`ProcessUniqueId`](crate::ProcessUniqueId) prefix counter run out after `n`
+    /// more prefixes, for testing what happens when there are no more IDs (`ProcessUniqueId::new`
+    /// panics, `ProcessUniqueId::try_new` fails, unless wrapping is turned on).
+    ///
+    /// Blocks already claimed by threads, including the current thread's, are left alone; use
+    /// [`set_offsets_left`](GeneratorGuard::set_offsets_left) to run the current thread's block
+    /// down too.
+    ///
+    /// **panics** if prefixes are claimed from another copy of the crate's counter, like
+    /// [`new`](GeneratorGuard::new).
+    ///
+    /// ```
+    /// use snowflake::test_util::GeneratorGuard;
+    /// use snowflake::ProcessUniqueId;
+    ///
+    /// let guard = GeneratorGuard::new();
+    /// guard.set_offsets_left(1);
+    /// guard.set_prefixes_left(0);
+    /// ProcessUniqueId::new();
+    /// assert!(ProcessUniqueId::try_new().is_err());
+    /// assert!(std::panic::catch_unwind(ProcessUniqueId::new).is_err());
+    /// ```
+    pub fn set_prefixes_left(&self, n: usize) {
+        process_unique_id::set_prefixes_left(n);
+    }
+
+    /// Gives the current thread a block of just the last `n` IDs of a new prefix, so it moves on
+    /// to a new block (and prefix) after `n` more IDs. With `n` zero, the next ID claims a new
+    /// block.
+    ///
+    /// ```
+    /// use snowflake::test_util::GeneratorGuard;
+    /// use snowflake::ProcessUniqueId;
+    ///
+    /// let guard = GeneratorGuard::new();
+    /// guard.set_offsets_left(2);
+    /// let a = ProcessUniqueId::new();
+    /// let b = ProcessUniqueId::new();
+    /// assert_eq!(b.to_u128(), a.to_u128() + 1);
+    /// assert_eq!(b.to_u128() as u64, u64::MAX);
+    /// // The prefix is used up.
+    /// assert_ne!(ProcessUniqueId::new().to_u128() >> 64, a.to_u128() >> 64);
+    /// ```
+    pub fn set_offsets_left(&self, n: u64) {
+        process_unique_id::set_offsets_left(n);
+    }
+}
+
+impl Default for GeneratorGuard {
+    #[inline]
+    fn default() -> Self {
+        GeneratorGuard::new()
+    }
+}
+
+impl Drop for GeneratorGuard {
+    fn drop(&mut self) {
+        if let Some(puid) = self.puid.take() {
+            process_unique_id::restore_state(puid);
+        }
+        small_unique_id::restore_state(self.small);
+        if let Some(scoped) = self.scoped.take() {
+            scoped_unique_id::restore_state(scoped);
+        }
+    }
+}
+
+impl fmt::Debug for GeneratorGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GeneratorGuard").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use crate::process_unique_id::{with_local, Block, EMPTY_BLOCK};
+
+/// The rest of a thread's block of process unique IDs, detached from the thread.
+///
+/// Every thread that creates IDs claims a block of offsets, and whatever is left of it goes to
+/// whichever thread next needs a block when the thread exits. Pools that recycle their worker
+/// threads can detach the block before a thread goes away and attach it to the next one, so the
+/// replacement continues where the old thread left off:
+///
+/// ```
+/// use snowflake::{ProcessUniqueId, ThreadPrefix};
+/// use std::thread;
+///
+/// let (last, prefix) = thread::spawn(|| (ProcessUniqueId::new(), ThreadPrefix::detach()))
+///     .join()
+///     .unwrap();
+///
+/// let next = thread::spawn(move || {
+///     prefix.attach();
+///     ProcessUniqueId::new()
+/// })
+/// .join()
+/// .unwrap();
+/// assert!(next > last);
+/// ```
+///
+/// A `ThreadPrefix` can't be cloned, so its IDs are handed out at most once. Dropping it loses the
+/// IDs left in it.
+#[derive(Debug)]
+pub struct ThreadPrefix {
+    block: Block,
+}
+
+impl ThreadPrefix {
+    /// Takes the current thread's block. The thread claims a new block the next time it creates
+    /// an ID.
+    pub fn detach() -> Self {
+        with_local(|local| {
+            let block = local.get();
+            local.set(EMPTY_BLOCK);
+            ThreadPrefix { block }
+        })
+    }
+
+    /// Makes this the current thread's block.
+    ///
+    /// Any IDs left in the thread's previous block are lost; detach it first to keep them.
+    pub fn attach(self) {
+        with_local(|local| local.set(self.block));
+    }
+
+    /// The number of IDs left, saturating at `u64::MAX`.
+    #[inline]
+    pub fn remaining(&self) -> u64 {
+        self.block.remaining()
+    }
+
+    /// Returns true if there are no IDs left.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ThreadPrefix;
+    use crate::ProcessUniqueId;
+    use std::thread;
+
+    #[test]
+    fn test_detach_attach() {
+        let (last, prefix) = thread::spawn(|| (ProcessUniqueId::new(), ThreadPrefix::detach()))
+            .join()
+            .unwrap();
+        assert!(!prefix.is_empty());
+        let remaining = prefix.remaining();
+
+        let (first, rest) = thread::spawn(move || {
+            // The attached block replaces the one this thread was using.
+            ProcessUniqueId::new();
+            prefix.attach();
+            let first = ProcessUniqueId::new();
+            (first, ThreadPrefix::detach())
+        })
+        .join()
+        .unwrap();
+        assert_eq!(first.prefix, last.prefix);
+        assert_eq!(first.offset, last.offset + 1);
+        assert_eq!(rest.remaining(), remaining - 1);
+
+        // Once detached, the thread has nothing left until it claims a new block.
+        ProcessUniqueId::new();
+        assert!(!ThreadPrefix::detach().is_empty());
+        assert!(ThreadPrefix::detach().is_empty());
+    }
+}
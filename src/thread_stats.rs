@@ -0,0 +1,286 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+#[cfg(not(feature = "thread-stats"))]
+use std::cell::Cell;
+use std::thread::{self, ThreadId};
+#[cfg(feature = "thread-stats")]
+use std::{
+    cmp::Reverse,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+};
+
+use crate::process_unique_id::with_local;
+
+/// What a thread has claimed so far. Only updated when the thread claims a block, so keeping
+/// track costs nothing on the `ProcessUniqueId::new()` fast path.
+#[derive(Copy, Clone)]
+struct Counts {
+    prefix: Option<usize>,
+    claimed: u128,
+    refills: u64,
+}
+
+impl Counts {
+    const NEW: Counts = Counts {
+        prefix: None,
+        claimed: 0,
+        refills: 0,
+    };
+}
+
+#[cfg(not(feature = "thread-stats"))]
+thread_local! {
+    static COUNTS: Cell<Counts> = const { Cell::new(Counts::NEW) };
+}
+
+// With the registry, each thread's counts are shared with `all_thread_stats`, which only holds
+// on to them weakly so that they go away with the thread.
+#[cfg(feature = "thread-stats")]
+type SharedCounts = Arc<Mutex<Counts>>;
+
+#[cfg(feature = "thread-stats")]
+struct Registered {
+    thread_id: ThreadId,
+    thread_name: Option<String>,
+    counts: Weak<Mutex<Counts>>,
+}
+
+#[cfg(feature = "thread-stats")]
+static REGISTRY: Mutex<Vec<Registered>> = Mutex::new(Vec::new());
+
+#[cfg(feature = "thread-stats")]
+thread_local! {
+    static COUNTS: SharedCounts = register();
+}
+
+#[cfg(feature = "thread-stats")]
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Adds the current thread to the registry, dropping threads that have exited.
+#[cfg(feature = "thread-stats")]
+fn register() -> SharedCounts {
+    let counts = Arc::new(Mutex::new(Counts::NEW));
+    let thread = thread::current();
+    let mut registry = lock(&REGISTRY);
+    registry.retain(|registered| registered.counts.strong_count() > 0);
+    registry.push(Registered {
+        thread_id: thread.id(),
+        thread_name: thread.name().map(String::from),
+        counts: Arc::downgrade(&counts),
+    });
+    counts
+}
+
+#[cfg(not(feature = "thread-stats"))]
+fn counts() -> Counts {
+    COUNTS.try_with(Cell::get).unwrap_or(Counts::NEW)
+}
+
+#[cfg(feature = "thread-stats")]
+fn counts() -> Counts {
+    COUNTS
+        .try_with(|counts| *lock(counts))
+        .unwrap_or(Counts::NEW)
+}
+
+/// Records a block of `len` offsets under `prefix` claimed by the current thread. Does nothing
+/// during thread teardown, once the thread's counts are gone.
+#[inline]
+pub(crate) fn on_block_claimed(prefix: usize, len: u128) {
+    fn update(counts: &mut Counts, prefix: usize, len: u128) {
+        counts.prefix = Some(prefix);
+        counts.claimed = counts.claimed.saturating_add(len);
+        counts.refills = counts.refills.saturating_add(1);
+    }
+
+    #[cfg(not(feature = "thread-stats"))]
+    let _ = COUNTS.try_with(|cell| {
+        let mut counts = cell.get();
+        update(&mut counts, prefix, len);
+        cell.set(counts);
+    });
+    #[cfg(feature = "thread-stats")]
+    let _ = COUNTS.try_with(|counts| update(&mut lock(counts), prefix, len));
+}
+
+/// How much of the process unique ID space a thread has used, returned by [`thread_stats`] (and,
+/// with the `thread-stats` feature, `all_thread_stats`).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ThreadStats {
+    thread_id: ThreadId,
+    thread_name: Option<String>,
+    prefix: Option<usize>,
+    offsets_consumed: u128,
+    refills: u64,
+}
+
+impl ThreadStats {
+    /// The thread's ID.
+    #[inline]
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread_id
+    }
+
+    /// The thread's name, if it has one.
+    #[inline]
+    pub fn thread_name(&self) -> Option<&str> {
+        self.thread_name.as_deref()
+    }
+
+    /// The prefix the thread is handing out IDs under, or `None` if it hasn't claimed a block
+    /// yet.
+    #[inline]
+    pub fn prefix(&self) -> Option<usize> {
+        self.prefix
+    }
+
+    /// The number of offsets the thread has used up: every offset in the blocks it claimed,
+    /// minus the ones left in its current block.
+    ///
+    /// In `all_thread_stats`, the other threads' current blocks can't be seen, so they count as used up in full: like
+    /// [`Stats::ids_claimed`](crate::Stats::ids_claimed), an upper bound that overshoots by up
+    /// to a block per thread.
+    #[inline]
+    pub fn offsets_consumed(&self) -> u128 {
+        self.offsets_consumed
+    }
+
+    /// The number of blocks the thread has claimed, including the first.
+    ///
+    /// Each refill is a trip to the shared state (see
+    /// [`set_block_size`](crate::ProcessUniqueId::set_block_size)), so a thread whose count
+    /// keeps climbing is either creating a lot of IDs or running with too small a block size.
+    #[inline]
+    pub fn refills(&self) -> u64 {
+        self.refills
+    }
+}
+
+/// Returns the current thread's process unique ID usage, to track down threads that consume more
+/// IDs than expected.
+///
+/// ```
+/// use snowflake::{thread_stats, ProcessUniqueId};
+///
+/// ProcessUniqueId::new();
+/// ProcessUniqueId::new();
+///
+/// let stats = thread_stats();
+/// assert!(stats.prefix().is_some());
+/// assert!(stats.offsets_consumed() >= 2);
+/// assert!(stats.refills() >= 1);
+/// ```
+///
+/// Blocks claimed for [`IdHandle`](crate::IdHandle)s count towards the thread that claimed
+/// them, and so do the IDs left in them. Blocks moved between threads with
+/// [`ThreadPrefix`](crate::ThreadPrefix) count towards the thread that claimed them too.
+pub fn thread_stats() -> ThreadStats {
+    let counts = counts();
+    let block = with_local(|local| local.get());
+    let thread = thread::current();
+    ThreadStats {
+        thread_id: thread.id(),
+        thread_name: thread.name().map(String::from),
+        prefix: block.prefix().or(counts.prefix),
+        offsets_consumed: counts.claimed.saturating_sub(u128::from(block.remaining())),
+        refills: counts.refills,
+    }
+}
+
+/// Returns the process unique ID usage of every live thread that has claimed a block, heaviest
+/// consumers first.
+///
+/// Requires the `thread-stats` feature, which makes each thread register itself (and allocate a
+/// little shared state) the first time it claims a block. See
+/// [`ThreadStats::offsets_consumed`] for how the threads' current blocks are counted.
+///
+/// ```
+/// use snowflake::{all_thread_stats, ProcessUniqueId};
+/// use std::thread;
+///
+/// thread::Builder::new()
+///     .name("worker".into())
+///     .spawn(|| {
+///         ProcessUniqueId::new();
+///         let stats = all_thread_stats();
+///         assert!(stats.iter().any(|s| s.thread_name() == Some("worker")));
+///     })
+///     .unwrap()
+///     .join()
+///     .unwrap();
+/// ```
+#[cfg(feature = "thread-stats")]
+pub fn all_thread_stats() -> Vec<ThreadStats> {
+    let mut stats: Vec<_> = lock(&REGISTRY)
+        .iter()
+        .filter_map(|registered| {
+            let counts = registered.counts.upgrade()?;
+            let counts = *lock(&counts);
+            Some(ThreadStats {
+                thread_id: registered.thread_id,
+                thread_name: registered.thread_name.clone(),
+                prefix: counts.prefix,
+                offsets_consumed: counts.claimed,
+                refills: counts.refills,
+            })
+        })
+        .collect();
+    stats.sort_by_key(|stats| Reverse(stats.offsets_consumed));
+    stats
+}
+
+#[cfg(test)]
+mod test {
+    use super::thread_stats;
+    use crate::{IdHandle, ProcessUniqueId};
+    use std::thread;
+
+    #[test]
+    fn test_thread_stats() {
+        thread::spawn(|| {
+            let empty = thread_stats();
+            assert_eq!(empty.prefix(), None);
+            assert_eq!((empty.offsets_consumed(), empty.refills()), (0, 0));
+
+            let id = ProcessUniqueId::new();
+            ProcessUniqueId::new();
+            let stats = thread_stats();
+            assert_eq!(stats.prefix(), Some(id.prefix));
+            assert_eq!((stats.offsets_consumed(), stats.refills()), (2, 1));
+            assert_eq!(stats.thread_id(), thread::current().id());
+
+            IdHandle::new().next();
+            assert_eq!(thread_stats().refills(), 2);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[cfg(feature = "thread-stats")]
+    #[test]
+    fn test_all_thread_stats() {
+        use super::all_thread_stats;
+
+        let id = thread::spawn(|| {
+            ProcessUniqueId::new();
+            let id = thread::current().id();
+            let stats = all_thread_stats();
+            let mine = stats.iter().find(|s| s.thread_id() == id).unwrap();
+            assert_eq!(mine.refills(), 1);
+            assert!(stats
+                .windows(2)
+                .all(|w| w[0].offsets_consumed() >= w[1].offsets_consumed()));
+            id
+        })
+        .join()
+        .unwrap();
+        assert!(all_thread_stats().iter().all(|s| s.thread_id() != id));
+    }
+}
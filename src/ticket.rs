@@ -0,0 +1,426 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A client for central ticket servers, in the style of Flickr's.
+//!
+//! Requires the `ticket` feature. Some deployments issue every ID from one central service. A
+//! [`TicketClient`] fetches blocks of IDs (tickets) from such a service through a
+//! [`TicketSource`] and hands them out locally, fetching the next block in the background before
+//! the current one runs out, so most calls don't touch the network.
+//!
+//! # Protocols
+//!
+//! Two simple protocols are included; implement [`TicketSource`] for anything else.
+//!
+//! - [`TcpTicketSource`]: line based, like the [`id_service`](crate::id_service) protocol. The
+//!   client sends `TICKETS <count>\n` and the server replies with the first ticket and the number
+//!   of tickets in the block (`1000 500\n`), or an error (`ERR <message>\n`).
+//! - [`HttpTicketSource`]: the client sends `GET <path>?count=<count>` over HTTP/1.1 and the
+//!   server replies `200 OK` with the same `<first> <count>` body.
+//!
+//! A server may return fewer tickets than asked for, but never zero.
+//!
+//! ```no_run
+//! use snowflake::ticket::{TcpTicketSource, TicketClient};
+//!
+//! let source = TcpTicketSource::new("tickets.internal:7070");
+//! let tickets = TicketClient::new(source).with_block_size(500);
+//! let id = tickets.next().unwrap();
+//! ```
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::ops::Range;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_BLOCK_SIZE: u64 = 1000;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A central service that issues blocks of tickets.
+pub trait TicketSource {
+    /// Fetches a block of at most `count` (and at least one) tickets that the service hasn't
+    /// issued to anyone else.
+    fn fetch(&self, count: u64) -> io::Result<Range<u64>>;
+}
+
+/// A [`TicketSource`] speaking the line based TCP protocol; see the
+/// [module documentation](self#protocols).
+///
+/// Each fetch opens a new connection, so the client recovers from server restarts on its own.
+#[derive(Debug, Clone)]
+pub struct TcpTicketSource {
+    addr: String,
+    timeout: Duration,
+}
+
+impl TcpTicketSource {
+    /// Fetches tickets from the server at `addr` (`host:port`).
+    ///
+    /// Doesn't connect until the first fetch.
+    pub fn new<A: Into<String>>(addr: A) -> Self {
+        TcpTicketSource {
+            addr: addr.into(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Gives up on connecting, sending, or receiving after `timeout` instead of 5 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl TicketSource for TcpTicketSource {
+    fn fetch(&self, count: u64) -> io::Result<Range<u64>> {
+        let mut stream = connect(&self.addr, self.timeout)?;
+        stream.write_all(format!("TICKETS {}\n", count).as_bytes())?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        let line = line.trim();
+        if let Some(message) = line.strip_prefix("ERR ") {
+            return Err(io::Error::other(format!("ticket server: {}", message)));
+        }
+        parse_block(line, count)
+    }
+}
+
+/// A [`TicketSource`] speaking the HTTP protocol; see the [module documentation](self#protocols).
+///
+/// This is a minimal HTTP/1.1 client: it sends one request per connection, doesn't follow
+/// redirects, and doesn't support TLS or chunked responses. Put a proxy in front of servers that
+/// need any of those.
+#[derive(Debug, Clone)]
+pub struct HttpTicketSource {
+    addr: String,
+    path: String,
+    timeout: Duration,
+}
+
+impl HttpTicketSource {
+    /// Fetches tickets from `path` on the server at `addr` (`host:port`).
+    ///
+    /// Doesn't connect until the first fetch.
+    pub fn new<A: Into<String>, P: Into<String>>(addr: A, path: P) -> Self {
+        HttpTicketSource {
+            addr: addr.into(),
+            path: path.into(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Gives up on connecting, sending, or receiving after `timeout` instead of 5 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl TicketSource for HttpTicketSource {
+    fn fetch(&self, count: u64) -> io::Result<Range<u64>> {
+        let mut stream = connect(&self.addr, self.timeout)?;
+        let separator = if self.path.contains('?') { '&' } else { '?' };
+        let request = format!(
+            "GET {}{}count={} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            self.path, separator, count, self.addr
+        );
+        stream.write_all(request.as_bytes())?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| malformed(&response))?;
+        let status = head.lines().next().unwrap_or_default();
+        if status.split(' ').nth(1) != Some("200") {
+            return Err(io::Error::other(format!("ticket server: {}", status)));
+        }
+        parse_block(body.trim(), count)
+    }
+}
+
+fn connect(addr: &str, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last_error = None;
+    for addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                return Ok(stream);
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "ticket server address resolved to nothing",
+        )
+    }))
+}
+
+/// Parses a `<first> <count>` reply to a request for `requested` tickets.
+fn parse_block(reply: &str, requested: u64) -> io::Result<Range<u64>> {
+    let mut parts = reply.split(' ');
+    let (first, count) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(first), Some(count), None) => (first, count),
+        _ => return Err(malformed(reply)),
+    };
+    let first: u64 = first.parse().map_err(|_| malformed(reply))?;
+    let count: u64 = count.parse().map_err(|_| malformed(reply))?;
+    if count == 0 || count > requested {
+        return Err(malformed(reply));
+    }
+    let end = first.checked_add(count).ok_or_else(|| malformed(reply))?;
+    Ok(first..end)
+}
+
+fn malformed(reply: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("malformed ticket server response: {:?}", reply),
+    )
+}
+
+/// Hands out tickets from a [`TicketSource`], a block at a time.
+///
+/// Once fewer than a quarter of a block's tickets are left (see
+/// [`with_prefetch_at`](Self::with_prefetch_at)), the client fetches the next block on a
+/// background thread. If the current block runs out before that fetch completes, callers wait
+/// for it, and if it failed, they fetch again themselves and get its error if that fails too.
+///
+/// Tickets are unique (as far as the server keeps its promises) but only ordered within a block,
+/// and tickets left over when the client is dropped are lost.
+pub struct TicketClient<S> {
+    inner: Arc<Inner<S>>,
+}
+
+struct Inner<S> {
+    source: S,
+    block_size: u64,
+    prefetch_at: u64,
+    state: Mutex<State>,
+    fetched: Condvar,
+}
+
+struct State {
+    current: Range<u64>,
+    next: Option<Range<u64>>,
+    fetching: bool,
+}
+
+impl<S: TicketSource + Send + Sync + 'static> TicketClient<S> {
+    /// Fetches tickets from `source`, 1000 at a time.
+    ///
+    /// Doesn't fetch anything until the first call to [`next`](Self::next).
+    pub fn new(source: S) -> Self {
+        TicketClient {
+            inner: Arc::new(Inner {
+                source,
+                block_size: DEFAULT_BLOCK_SIZE,
+                prefetch_at: DEFAULT_BLOCK_SIZE / 4,
+                state: Mutex::new(State {
+                    current: 0..0,
+                    next: None,
+                    fetching: false,
+                }),
+                fetched: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Asks for `size` tickets per block, prefetching the next block when fewer than a quarter of
+    /// them are left.
+    ///
+    /// **panics** if `size` is zero, or if the client has already been shared (cloned).
+    pub fn with_block_size(self, size: u64) -> Self {
+        assert!(size > 0, "ticket block size must be positive");
+        self.configure(|inner| {
+            inner.block_size = size;
+            inner.prefetch_at = size / 4;
+        })
+    }
+
+    /// Fetches the next block once fewer than `remaining` tickets are left in the current one.
+    ///
+    /// Zero turns prefetching off: blocks are then fetched when they're needed, by the caller
+    /// that needs them.
+    ///
+    /// **panics** if the client has already been shared (cloned).
+    pub fn with_prefetch_at(self, remaining: u64) -> Self {
+        self.configure(|inner| inner.prefetch_at = remaining)
+    }
+
+    fn configure(mut self, f: impl FnOnce(&mut Inner<S>)) -> Self {
+        f(Arc::get_mut(&mut self.inner).expect("ticket client configured after being shared"));
+        self
+    }
+
+    /// The source tickets are fetched from.
+    #[inline]
+    pub fn source(&self) -> &S {
+        &self.inner.source
+    }
+
+    /// Hands out a ticket, fetching a block from the source first if necessary.
+    pub fn next(&self) -> io::Result<u64> {
+        let inner = &self.inner;
+        let mut state = inner.lock();
+        loop {
+            if let Some(ticket) = state.current.next() {
+                let remaining = state.current.end - state.current.start;
+                if remaining < inner.prefetch_at && state.next.is_none() && !state.fetching {
+                    state.fetching = true;
+                    let inner = inner.clone();
+                    thread::spawn(move || {
+                        // Errors are left for whoever needs the block to run into.
+                        let block = inner.source.fetch(inner.block_size).ok();
+                        inner.finish_fetch(block);
+                    });
+                }
+                return Ok(ticket);
+            }
+            if let Some(next) = state.next.take() {
+                state.current = next;
+            } else if state.fetching {
+                state = inner.fetched.wait(state).unwrap_or_else(|e| e.into_inner());
+            } else {
+                state.fetching = true;
+                drop(state);
+                let result = inner.source.fetch(inner.block_size);
+                inner.finish_fetch(result.as_ref().ok().cloned());
+                result?;
+                state = inner.lock();
+            }
+        }
+    }
+}
+
+impl<S> Inner<S> {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn finish_fetch(&self, block: Option<Range<u64>>) {
+        let mut state = self.lock();
+        state.fetching = false;
+        if let Some(block) = block {
+            state.next = Some(block);
+        }
+        drop(state);
+        self.fetched.notify_all();
+    }
+}
+
+impl<S> Clone for TicketClient<S> {
+    /// Shares the client (and its blocks) rather than creating a new one.
+    #[inline]
+    fn clone(&self) -> Self {
+        TicketClient {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for TicketClient<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TicketClient")
+            .field("source", &self.inner.source)
+            .field("block_size", &self.inner.block_size)
+            .field("prefetch_at", &self.inner.prefetch_at)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HttpTicketSource, TcpTicketSource, TicketClient, TicketSource};
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::ops::Range;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::thread;
+
+    // Issues blocks of up to 3 tickets, counting up from 100.
+    struct Counter(AtomicU64);
+
+    impl TicketSource for Counter {
+        fn fetch(&self, count: u64) -> io::Result<Range<u64>> {
+            let count = count.min(3);
+            let first = self.0.fetch_add(count, Ordering::Relaxed);
+            if first >= 112 {
+                return Err(io::Error::other("out of tickets"));
+            }
+            Ok(first..first + count)
+        }
+    }
+
+    #[test]
+    fn test_ticket_client() {
+        let client = TicketClient::new(Counter(AtomicU64::new(100))).with_block_size(5);
+        let shared = client.clone();
+        let handle =
+            thread::spawn(move || (0..6).map(|_| shared.next().unwrap()).collect::<Vec<_>>());
+        let mut tickets: Vec<u64> = (0..6).map(|_| client.next().unwrap()).collect();
+        tickets.extend(handle.join().unwrap());
+        tickets.sort_unstable();
+        assert_eq!(tickets, (100..112).collect::<Vec<_>>());
+        assert!(client.next().is_err());
+    }
+
+    #[test]
+    fn test_ticket_protocols() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.starts_with("GET ") {
+                    // Read the rest of the request head.
+                    let mut header = String::new();
+                    while reader.read_line(&mut header).unwrap() > 2 {
+                        header.clear();
+                    }
+                }
+                let reply = match line.trim() {
+                    "TICKETS 10" => "7 10\n".to_owned(),
+                    "TICKETS 20" => "7 21\n".to_owned(),
+                    "TICKETS 30" => "ERR sold out\n".to_owned(),
+                    "GET /tickets?count=10 HTTP/1.1" => {
+                        "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n40 10".to_owned()
+                    }
+                    _ => "HTTP/1.1 404 Not Found\r\n\r\n".to_owned(),
+                };
+                stream.write_all(reply.as_bytes()).unwrap();
+            }
+        });
+
+        let tcp = TcpTicketSource::new(addr.clone());
+        assert_eq!(tcp.fetch(10).unwrap(), 7..17);
+        // More than was asked for.
+        assert_eq!(
+            tcp.fetch(20).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!(tcp.fetch(30).unwrap_err().to_string().contains("sold out"));
+
+        assert_eq!(
+            HttpTicketSource::new(addr.clone(), "/tickets")
+                .fetch(10)
+                .unwrap(),
+            40..50
+        );
+        let missing = HttpTicketSource::new(addr, "/missing").fetch(10);
+        assert!(missing.unwrap_err().to_string().contains("404"));
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::snowflake_id::TWITTER_EPOCH_MS;
+use crate::sonyflake::{DEFAULT_START_MS, TIME_UNIT_MS};
+use crate::{FlakeId, ObjectId, PushId, SnowflakeId, SonyflakeId, Tsid, Xid};
+
+/// An ID that records when it was created.
+///
+/// ```
+/// use snowflake::{SnowflakeGenerator, Timestamped};
+/// use std::time::{Duration, SystemTime};
+///
+/// let id = SnowflakeGenerator::new(1).next();
+/// let age = SystemTime::now().duration_since(id.created_at()).unwrap();
+/// assert!(age < Duration::from_secs(60));
+/// ```
+///
+/// Precision depends on the ID type: milliseconds for most, 10 milliseconds for Sonyflake IDs,
+/// and seconds for [`ObjectId`] and [`Xid`].
+///
+/// [`SnowflakeId`] and [`SonyflakeId`] don't record their generator's epoch, so their
+/// implementations assume the default one. For IDs from a generator with a custom epoch, add
+/// `timestamp()` (or `time()`) to the generator's `epoch()` (or `start_time()`) instead.
+pub trait Timestamped {
+    /// The time at which this ID was created.
+    fn created_at(&self) -> SystemTime;
+}
+
+fn unix_millis(ms: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(ms)
+}
+
+/// Assumes the default (Twitter) epoch.
+impl Timestamped for SnowflakeId {
+    #[inline]
+    fn created_at(&self) -> SystemTime {
+        unix_millis(TWITTER_EPOCH_MS + self.timestamp())
+    }
+}
+
+/// Assumes Sonyflake's default start time.
+impl Timestamped for SonyflakeId {
+    #[inline]
+    fn created_at(&self) -> SystemTime {
+        unix_millis(DEFAULT_START_MS + self.time() * TIME_UNIT_MS)
+    }
+}
+
+impl Timestamped for Tsid {
+    #[inline]
+    fn created_at(&self) -> SystemTime {
+        unix_millis(self.unix_millis())
+    }
+}
+
+impl Timestamped for ObjectId {
+    #[inline]
+    fn created_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.timestamp().into())
+    }
+}
+
+impl Timestamped for Xid {
+    #[inline]
+    fn created_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.time().into())
+    }
+}
+
+impl Timestamped for FlakeId {
+    #[inline]
+    fn created_at(&self) -> SystemTime {
+        unix_millis(self.timestamp())
+    }
+}
+
+impl Timestamped for PushId {
+    #[inline]
+    fn created_at(&self) -> SystemTime {
+        unix_millis(self.timestamp())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Timestamped;
+    use crate::{
+        FlakeGenerator, ObjectId, PushIdGenerator, SnowflakeGenerator, SonyflakeGenerator,
+        TsidGenerator, XidGenerator,
+    };
+    use std::time::{Duration, SystemTime};
+
+    fn assert_recent<T: Timestamped>(id: T, precision: Duration) {
+        let created = id.created_at();
+        let now = SystemTime::now();
+        assert!(created <= now);
+        assert!(created + precision + Duration::from_secs(5) > now);
+    }
+
+    #[test]
+    fn test_created_at() {
+        let ms = Duration::from_millis(1);
+        let secs = Duration::from_secs(1);
+        assert_recent(SnowflakeGenerator::new(1).next(), ms);
+        assert_recent(SonyflakeGenerator::new(1).next(), Duration::from_millis(10));
+        assert_recent(TsidGenerator::new(1).next(), ms);
+        assert_recent(ObjectId::new(), secs);
+        assert_recent(XidGenerator::new(1).next(), secs);
+        assert_recent(FlakeGenerator::new(1).next(), ms);
+        assert_recent(PushIdGenerator::new().next(), ms);
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A gRPC interceptor for correlation IDs.
+//!
+//! Requires the `tonic` feature. [`CorrelationIdInterceptor`] works on both ends of a call. On a
+//! server, it keeps the `x-correlation-id` metadata entry of incoming requests (or creates a
+//! [`ProcessUniqueId`] for requests without one) and stores it in the request's extensions as a
+//! [`CorrelationId`]. On a client, it stamps outgoing requests that don't carry an ID yet with a
+//! new one. To carry an incoming ID over to the calls made while handling a request, attach it to
+//! them:
+//!
+//! ```
+//! use snowflake::tonic::{CorrelationId, CorrelationIdInterceptor};
+//! use tonic::service::Interceptor;
+//!
+//! let mut interceptor = CorrelationIdInterceptor::new();
+//!
+//! // Server side: an incoming request without an ID gets one.
+//! let incoming = interceptor.call(tonic::Request::new(())).unwrap();
+//! let id = incoming.extensions().get::<CorrelationId>().unwrap().clone();
+//!
+//! // Client side: calls made on its behalf carry the same ID.
+//! let mut outgoing = tonic::Request::new(());
+//! id.attach(&mut outgoing);
+//! let outgoing = interceptor.call(outgoing).unwrap();
+//! assert_eq!(outgoing.metadata().get("x-correlation-id"), Some(id.metadata_value()));
+//! ```
+//!
+//! With the `tracing` feature, the interceptor also records the ID in the current span's
+//! `correlation_id` field, if the span has one.
+use std::fmt;
+
+use tonic::metadata::{AsciiMetadataKey, AsciiMetadataValue};
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+
+use crate::ProcessUniqueId;
+
+/// The metadata key correlation IDs are read from and written to unless configured otherwise.
+pub const X_CORRELATION_ID: &str = "x-correlation-id";
+
+/// A request's correlation ID, as stored in its extensions by [`CorrelationIdInterceptor`].
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct CorrelationId {
+    key: AsciiMetadataKey,
+    value: AsciiMetadataValue,
+}
+
+impl CorrelationId {
+    /// The ID as it appears in the metadata.
+    #[inline]
+    pub fn metadata_value(&self) -> &AsciiMetadataValue {
+        &self.value
+    }
+
+    /// The ID as a string, or `None` if an incoming ID isn't visible ASCII.
+    #[inline]
+    pub fn to_str(&self) -> Option<&str> {
+        self.value.to_str().ok()
+    }
+
+    /// The ID as a process unique ID, if it's one (i.e., it was created here or by another
+    /// service formatting IDs the same way).
+    pub fn process_unique_id(&self) -> Option<ProcessUniqueId> {
+        self.to_str()?.parse().ok()
+    }
+
+    /// Adds the ID to an outgoing request's metadata, under the key it came in with.
+    pub fn attach<T>(&self, request: &mut Request<T>) {
+        request
+            .metadata_mut()
+            .insert(self.key.clone(), self.value.clone());
+    }
+}
+
+/// Formats the ID lossily: bytes that aren't visible ASCII are replaced.
+impl fmt::Display for CorrelationId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        String::from_utf8_lossy(self.value.as_encoded_bytes()).fmt(f)
+    }
+}
+
+/// An [`Interceptor`] that gives every request a correlation ID; see the
+/// [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct CorrelationIdInterceptor {
+    key: AsciiMetadataKey,
+}
+
+impl CorrelationIdInterceptor {
+    /// Uses the `x-correlation-id` metadata key.
+    #[inline]
+    pub fn new() -> Self {
+        CorrelationIdInterceptor::with_key(AsciiMetadataKey::from_static(X_CORRELATION_ID))
+    }
+
+    /// Uses `key` instead of `x-correlation-id`.
+    #[inline]
+    pub fn with_key(key: AsciiMetadataKey) -> Self {
+        CorrelationIdInterceptor { key }
+    }
+}
+
+impl Default for CorrelationIdInterceptor {
+    #[inline]
+    fn default() -> Self {
+        CorrelationIdInterceptor::new()
+    }
+}
+
+impl Interceptor for CorrelationIdInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let value = match request.metadata().get(&self.key) {
+            Some(value) if !value.is_empty() => value.clone(),
+            _ => {
+                let value: AsciiMetadataValue = ProcessUniqueId::new()
+                    .to_string()
+                    .parse()
+                    .expect("process unique IDs are valid metadata values");
+                request
+                    .metadata_mut()
+                    .insert(self.key.clone(), value.clone());
+                value
+            }
+        };
+        let id = CorrelationId {
+            key: self.key.clone(),
+            value,
+        };
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("correlation_id", tracing::field::display(&id));
+        request.extensions_mut().insert(id);
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CorrelationId, CorrelationIdInterceptor};
+    use tonic::metadata::AsciiMetadataKey;
+    use tonic::service::Interceptor;
+    use tonic::Request;
+
+    fn intercept(request: Request<()>) -> CorrelationId {
+        let request = CorrelationIdInterceptor::new().call(request).unwrap();
+        let id = request.extensions().get::<CorrelationId>().unwrap().clone();
+        assert_eq!(
+            request.metadata().get("x-correlation-id"),
+            Some(id.metadata_value())
+        );
+        id
+    }
+
+    #[test]
+    fn test_correlation_id() {
+        let fresh = intercept(Request::new(()));
+        assert!(fresh.process_unique_id().is_some());
+        assert_ne!(intercept(Request::new(())), fresh);
+
+        let mut incoming = Request::new(());
+        incoming
+            .metadata_mut()
+            .insert("x-correlation-id", "abc-123".parse().unwrap());
+        let reused = intercept(incoming);
+        assert_eq!(reused.to_str(), Some("abc-123"));
+        assert_eq!(reused.process_unique_id(), None);
+
+        let mut outgoing = Request::new(());
+        reused.attach(&mut outgoing);
+        assert_eq!(intercept(outgoing), reused);
+
+        let key = AsciiMetadataKey::from_static("x-trace");
+        let request = CorrelationIdInterceptor::with_key(key)
+            .call(Request::new(()))
+            .unwrap();
+        assert!(request.metadata().get("x-trace").is_some());
+        assert!(request.metadata().get("x-correlation-id").is_none());
+    }
+}
@@ -0,0 +1,310 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Tower middleware that gives every request an ID.
+//!
+//! Requires the `tower` feature. [`SetRequestIdLayer`] reuses the ID in a request's
+//! `x-request-id` header if it has a valid one (see [`request_id::parse`]) and otherwise creates
+//! a [`ProcessUniqueId`]. Either way,
+//! the ID ends up in the request's header and extensions (as a [`RequestId`]), and in the
+//! response's header:
+//!
+//! ```
+//! use http::{Request, Response};
+//! use snowflake::tower::{RequestId, SetRequestIdLayer};
+//! use std::convert::Infallible;
+//! use tower::{service_fn, ServiceBuilder, ServiceExt};
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let service = ServiceBuilder::new()
+//!     .layer(SetRequestIdLayer::new())
+//!     .service(service_fn(|request: Request<()>| async move {
+//!         let id = request.extensions().get::<RequestId>().unwrap();
+//!         Ok::<_, Infallible>(Response::new(id.to_string()))
+//!     }));
+//! let response = service.oneshot(Request::new(())).await.unwrap();
+//! assert_eq!(response.headers()["x-request-id"], response.body().as_str());
+//! # });
+//! ```
+//!
+//! With axum, add the layer with `Router::layer` and extract the ID with `Extension<RequestId>`.
+use core::convert::TryFrom;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use http::header::{HeaderName, HeaderValue};
+use http::{Request, Response};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{request_id, ProcessUniqueId};
+
+/// The header request IDs are read from and written to unless configured otherwise.
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// A request's ID, as stored in its extensions by [`SetRequestId`].
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct RequestId {
+    value: HeaderValue,
+}
+
+impl RequestId {
+    /// The ID as it appears in the header.
+    #[inline]
+    pub fn header_value(&self) -> &HeaderValue {
+        &self.value
+    }
+
+    /// The ID as a string, or `None` if an incoming ID isn't visible ASCII.
+    #[inline]
+    pub fn to_str(&self) -> Option<&str> {
+        self.value.to_str().ok()
+    }
+
+    /// The ID as a process unique ID, if it's one (i.e., it was created here or by another
+    /// service formatting IDs the same way).
+    pub fn process_unique_id(&self) -> Option<ProcessUniqueId> {
+        self.to_str()?.parse().ok()
+    }
+}
+
+impl From<ProcessUniqueId> for RequestId {
+    fn from(id: ProcessUniqueId) -> Self {
+        RequestId {
+            value: HeaderValue::try_from(id.to_string())
+                .expect("process unique IDs are valid header values"),
+        }
+    }
+}
+
+/// Formats the ID lossily: bytes that aren't visible ASCII are replaced.
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        String::from_utf8_lossy(self.value.as_bytes()).fmt(f)
+    }
+}
+
+/// A [`Layer`] that applies [`SetRequestId`].
+#[derive(Clone, Debug)]
+pub struct SetRequestIdLayer {
+    header: HeaderName,
+}
+
+impl SetRequestIdLayer {
+    /// Uses the `x-request-id` header.
+    #[inline]
+    pub fn new() -> Self {
+        SetRequestIdLayer::with_header(X_REQUEST_ID)
+    }
+
+    /// Uses `header` instead of `x-request-id`.
+    #[inline]
+    pub fn with_header(header: HeaderName) -> Self {
+        SetRequestIdLayer { header }
+    }
+}
+
+impl Default for SetRequestIdLayer {
+    #[inline]
+    fn default() -> Self {
+        SetRequestIdLayer::new()
+    }
+}
+
+impl<S> Layer<S> for SetRequestIdLayer {
+    type Service = SetRequestId<S>;
+
+    fn layer(&self, inner: S) -> SetRequestId<S> {
+        SetRequestId {
+            inner,
+            header: self.header.clone(),
+        }
+    }
+}
+
+/// Middleware that gives every request an ID; see the [module documentation](self).
+///
+/// An incoming ID is kept as is if [`request_id::parse`] accepts it, and replaced otherwise; only
+/// the first header is used if there are several.
+#[derive(Clone, Debug)]
+pub struct SetRequestId<S> {
+    inner: S,
+    header: HeaderName,
+}
+
+impl<S> SetRequestId<S> {
+    /// Wraps `inner`, using the `x-request-id` header.
+    #[inline]
+    pub fn new(inner: S) -> Self {
+        SetRequestIdLayer::new().layer(inner)
+    }
+
+    /// The wrapped service.
+    #[inline]
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Unwraps the wrapped service.
+    #[inline]
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for SetRequestId<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+        let id = match request.headers().get(&self.header) {
+            Some(value) if request_id::is_valid(value) => RequestId {
+                value: value.clone(),
+            },
+            _ => {
+                let id = RequestId {
+                    value: request_id::generate(),
+                };
+                request
+                    .headers_mut()
+                    .insert(self.header.clone(), id.value.clone());
+                id
+            }
+        };
+        request.extensions_mut().insert(id.clone());
+        ResponseFuture {
+            inner: self.inner.call(request),
+            header: Some((self.header.clone(), id.value)),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// The response future of [`SetRequestId`], which adds the request ID header to the
+    /// response.
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        header: Option<(HeaderName, HeaderValue)>,
+    }
+}
+
+impl<F: fmt::Debug> fmt::Debug for ResponseFuture<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ResponseFuture")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F, B, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+{
+    type Output = Result<Response<B>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut response = match this.inner.poll(cx) {
+            Poll::Ready(Ok(response)) => response,
+            other => return other,
+        };
+        if let Some((name, value)) = this.header.take() {
+            // Don't clobber an ID the service chose to set itself.
+            response.headers_mut().entry(name).or_insert(value);
+        }
+        Poll::Ready(Ok(response))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RequestId, SetRequestIdLayer, X_REQUEST_ID};
+    use http::{Request, Response};
+    use std::convert::Infallible;
+    use std::future::{ready, Future, Ready};
+    use std::task::{Context, Poll};
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    // Echoes the request ID it sees back as the response body.
+    struct Echo;
+
+    impl Service<Request<()>> for Echo {
+        type Response = Response<Option<RequestId>>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Infallible>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<()>) -> Self::Future {
+            let id = request.extensions().get::<RequestId>().cloned();
+            assert_eq!(
+                id.as_ref().map(RequestId::header_value),
+                request.headers().get(X_REQUEST_ID)
+            );
+            ready(Ok(Response::new(id)))
+        }
+    }
+
+    fn send(request: Request<()>) -> Response<Option<RequestId>> {
+        let future = SetRequestIdLayer::new().layer(Echo).call(request);
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+        match std::pin::pin!(future).poll(&mut cx) {
+            Poll::Ready(Ok(response)) => response,
+            Poll::Ready(Err(e)) => match e {},
+            Poll::Pending => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_request_id() {
+        let fresh = send(Request::new(()));
+        let id = fresh.body().clone().unwrap();
+        assert!(id.process_unique_id().is_some());
+        assert_eq!(fresh.headers()[X_REQUEST_ID], id.header_value());
+        assert_ne!(send(Request::new(())).body().as_ref(), Some(&id));
+
+        let incoming = Request::builder()
+            .header(X_REQUEST_ID, "abc-123")
+            .body(())
+            .unwrap();
+        let reused = send(incoming);
+        let id = reused.body().clone().unwrap();
+        assert_eq!(id.to_str(), Some("abc-123"));
+        assert_eq!(id.process_unique_id(), None);
+        assert_eq!(reused.headers()[X_REQUEST_ID], "abc-123");
+
+        for bad in ["", "abc 123"] {
+            let invalid = Request::builder()
+                .header(X_REQUEST_ID, bad)
+                .body(())
+                .unwrap();
+            let replaced = send(invalid);
+            assert!(replaced
+                .body()
+                .as_ref()
+                .unwrap()
+                .process_unique_id()
+                .is_some());
+        }
+    }
+}
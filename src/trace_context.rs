@@ -0,0 +1,304 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! W3C Trace Context trace and span IDs.
+//!
+//! [`TraceId`]s and [`SpanId`]s are drawn entirely from the operating system's secure random
+//! number generator, which satisfies the Trace Context randomness requirements. [`TraceParent`]
+//! formats and parses the `traceparent` header that carries them between services.
+//!
+//! ```
+//! use snowflake::trace_context::TraceParent;
+//!
+//! // An incoming request continues the caller's trace in a new span.
+//! let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+//! let parent: TraceParent = header.parse().unwrap();
+//! let child = parent.child();
+//! assert_eq!(child.trace_id(), parent.trace_id());
+//! assert_ne!(child.parent_id(), parent.parent_id());
+//! assert!(child.sampled());
+//!
+//! // Requests without a header start a new trace.
+//! let root = TraceParent::new_root(true);
+//! assert_eq!(root.to_string().len(), 55);
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::process_unique_id::{expect_len, ParseIdError};
+
+const SAMPLED: u8 = 0x01;
+
+/// Fills `bytes` with random bytes, retrying in the (astronomically unlikely) all-zero case,
+/// which Trace Context reserves as invalid.
+fn random_nonzero(bytes: &mut [u8]) {
+    loop {
+        getrandom::fill(bytes).expect("failed to read from the OS random number generator");
+        if bytes.iter().any(|&b| b != 0) {
+            return;
+        }
+    }
+}
+
+/// Parses exactly `out.len() * 2` lowercase hex digits into `out`.
+fn parse_lower_hex(s: &str, out: &mut [u8]) -> Result<(), ParseIdError> {
+    fn digit(c: u8) -> Option<u8> {
+        match c {
+            b'0'..=b'9' => Some(c - b'0'),
+            b'a'..=b'f' => Some(c - b'a' + 10),
+            _ => None,
+        }
+    }
+
+    for (i, c) in s.bytes().enumerate().take(out.len() * 2) {
+        let digit = digit(c).ok_or_else(|| ParseIdError::at(s, i, "a lowercase hex digit"))?;
+        out[i / 2] = out[i / 2] << 4 | digit;
+    }
+    expect_len(s, out.len() * 2, "a lowercase hex digit")
+}
+
+macro_rules! random_id {
+    ($(#[$attr:meta])* $name:ident, $len:expr) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+        pub struct $name([u8; $len]);
+
+        impl $name {
+            /// Generates a new random ID.
+            ///
+            /// **panics** if the operating system's random number generator fails.
+            pub fn random() -> Self {
+                let mut bytes = [0; $len];
+                random_nonzero(&mut bytes);
+                $name(bytes)
+            }
+
+            /// Creates an ID from its bytes, returning `None` if they're all zero (which Trace
+            /// Context treats as invalid).
+            pub fn from_bytes(bytes: [u8; $len]) -> Option<Self> {
+                if bytes.iter().all(|&b| b == 0) {
+                    None
+                } else {
+                    Some($name(bytes))
+                }
+            }
+
+            /// The ID's bytes.
+            #[inline]
+            pub fn to_bytes(self) -> [u8; $len] {
+                self.0
+            }
+        }
+
+        /// Formats the ID as lowercase hex, as it appears in `traceparent`.
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                for b in &self.0 {
+                    write!(f, "{:02x}", b)?;
+                }
+                Ok(())
+            }
+        }
+
+        /// Parses the lowercase hex form produced by `Display`.
+        impl FromStr for $name {
+            type Err = ParseIdError;
+
+            fn from_str(s: &str) -> Result<Self, ParseIdError> {
+                let mut bytes = [0; $len];
+                parse_lower_hex(s, &mut bytes)?;
+                $name::from_bytes(bytes).ok_or_else(|| ParseIdError::at(s, 0, "a non-zero ID"))
+            }
+        }
+    };
+}
+
+random_id!(
+    /// A 16-byte trace ID, shared by every span of a trace.
+    TraceId,
+    16
+);
+
+random_id!(
+    /// An 8-byte span ID (the `parent-id` of a `traceparent` header).
+    SpanId,
+    8
+);
+
+/// A `traceparent` header: version, trace ID, parent span ID, and trace flags.
+///
+/// Displayed as `00-<trace-id>-<parent-id>-<flags>`. Parsing follows the Trace Context rules:
+/// version `00` headers must be exactly 55 characters, while headers from later versions may carry
+/// extra `-`-separated fields, which are dropped (this type always writes version `00`).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TraceParent {
+    trace_id: TraceId,
+    parent_id: SpanId,
+    flags: u8,
+}
+
+impl TraceParent {
+    /// Creates a header from its parts.
+    #[inline]
+    pub fn new(trace_id: TraceId, parent_id: SpanId, flags: u8) -> Self {
+        TraceParent {
+            trace_id,
+            parent_id,
+            flags,
+        }
+    }
+
+    /// Starts a new trace with a random trace ID and root span ID.
+    ///
+    /// **panics** if the operating system's random number generator fails.
+    pub fn new_root(sampled: bool) -> Self {
+        TraceParent::new(
+            TraceId::random(),
+            SpanId::random(),
+            if sampled { SAMPLED } else { 0 },
+        )
+    }
+
+    /// Continues this trace in a new span: same trace ID and flags, new random parent ID.
+    ///
+    /// **panics** if the operating system's random number generator fails.
+    pub fn child(&self) -> Self {
+        TraceParent::new(self.trace_id, SpanId::random(), self.flags)
+    }
+
+    /// The trace ID.
+    #[inline]
+    pub fn trace_id(&self) -> TraceId {
+        self.trace_id
+    }
+
+    /// The ID of the caller's span.
+    #[inline]
+    pub fn parent_id(&self) -> SpanId {
+        self.parent_id
+    }
+
+    /// The trace flags.
+    #[inline]
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Whether the `sampled` flag is set.
+    #[inline]
+    pub fn sampled(&self) -> bool {
+        self.flags & SAMPLED != 0
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id, self.parent_id, self.flags
+        )
+    }
+}
+
+impl FromStr for TraceParent {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        let mut version = [0];
+        parse_lower_hex(s.get(..2).unwrap_or(s), &mut version)?;
+        let known = match (version[0], s.len()) {
+            (0xff, _) => return Err(ParseIdError::at(s, 0, "a version other than `ff`")),
+            (_, len) if len < 55 => {
+                return Err(ParseIdError::at(s, len, "the rest of the `traceparent`"))
+            }
+            (_, 55) => s,
+            (0x00, _) => return Err(ParseIdError::at(s, 55, "the end of the input")),
+            // Later versions may append fields.
+            _ if s.as_bytes()[55] == b'-' => &s[..55],
+            _ => return Err(ParseIdError::at(s, 55, "`-` or the end of the input")),
+        };
+        for (i, c) in known.bytes().enumerate() {
+            if i == 2 || i == 35 || i == 52 {
+                if c != b'-' {
+                    return Err(ParseIdError::at(s, i, "`-`"));
+                }
+            } else if !matches!(c, b'0'..=b'9' | b'a'..=b'f') {
+                return Err(ParseIdError::at(s, i, "a lowercase hex digit"));
+            }
+        }
+        // All ASCII from here on.
+        let mut flags = [0];
+        parse_lower_hex(&known[53..], &mut flags)?;
+        Ok(TraceParent::new(
+            known[3..35]
+                .parse()
+                .map_err(|e: ParseIdError| e.shifted(3))?,
+            known[36..52]
+                .parse()
+                .map_err(|e: ParseIdError| e.shifted(36))?,
+            flags[0],
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SpanId, TraceId, TraceParent};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_random_ids() {
+        let traces: HashSet<_> = (0..1000).map(|_| TraceId::random()).collect();
+        let spans: HashSet<_> = (0..1000).map(|_| SpanId::random()).collect();
+        assert_eq!((traces.len(), spans.len()), (1000, 1000));
+        let id = TraceId::random();
+        assert_eq!(id.to_string().len(), 32);
+        assert_eq!(id.to_string().parse(), Ok(id));
+        assert!(SpanId::from_bytes([0; 8]).is_none());
+        assert!("0000000000000000".parse::<SpanId>().is_err());
+        assert!("00F067AA0BA902B7".parse::<SpanId>().is_err());
+    }
+
+    #[test]
+    fn test_traceparent() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let parent: TraceParent = header.parse().unwrap();
+        assert_eq!(parent.to_string(), header);
+        assert_eq!(parent.flags(), 1);
+        assert_eq!(parent.trace_id().to_bytes()[..4], [0x4b, 0xf9, 0x2f, 0x35]);
+
+        // Later versions may append fields; version 00 may not.
+        let future = "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-what";
+        assert_eq!(future.parse(), Ok(parent));
+        assert!(format!("{}-what", header).parse::<TraceParent>().is_err());
+        assert!(
+            "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01what"
+                .parse::<TraceParent>()
+                .is_err()
+        );
+        assert!("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+            .parse::<TraceParent>()
+            .is_err());
+        assert!("00-00000000000000000000000000000000-00f067aa0ba902b7-01"
+            .parse::<TraceParent>()
+            .is_err());
+        let err = "00-4bf92f3577b34da6a3ce929d0e0e4736_00f067aa0ba902b7-01"
+            .parse::<TraceParent>()
+            .unwrap_err();
+        assert_eq!((err.offset(), err.found()), (35, Some('_')));
+        let err = "00-00000000000000000000000000000000-00f067aa0ba902b7-01"
+            .parse::<TraceParent>()
+            .unwrap_err();
+        assert_eq!((err.offset(), err.expected()), (3, "a non-zero ID"));
+        assert!("".parse::<TraceParent>().is_err());
+        assert!("0\u{e9}".repeat(20).parse::<TraceParent>().is_err());
+        assert!(!TraceParent::new_root(false).sampled());
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::fmt;
+use core::mem;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, Hasher};
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "bevy")]
+use bevy_ecs::reflect::ReflectComponent;
+
+#[cfg(feature = "std")]
+use crate::atomic::{AtomicU64, Ordering};
+use crate::display::pad_id;
+#[cfg(feature = "std")]
+use crate::obfuscate::splitmix64;
+use crate::process_unique_id::{expect_len, ParseIdError};
+#[cfg(feature = "std")]
+use crate::stats;
+
+/// The number of bits below the timestamp, shared between the node ID and the counter.
+const RANDOM_BITS: u32 = 22;
+const RANDOM_MASK: u64 = (1 << RANDOM_BITS) - 1;
+
+/// Milliseconds between the UNIX epoch and the TSID epoch, 2020-01-01T00:00:00Z.
+const TSID_EPOCH_MS: u64 = 1_577_836_800_000;
+
+/// The number of node bits tsid-creator uses by default (1024 nodes).
+#[cfg(feature = "std")]
+const DEFAULT_NODE_BITS: u32 = 10;
+
+const ENCODED_LEN: usize = 13;
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// A time-sorted 64-bit ID in the layout used by the Java tsid-creator library.
+///
+/// A TSID is a 42-bit millisecond timestamp (since 2020-01-01T00:00:00Z) followed by 22 bits
+/// split between a node ID and a per-millisecond counter. Its string form is 13 characters of
+/// Crockford base32, e.g. `0DS8RXW6W0DYY`, which sorts the same way as the number.
+///
+/// # Memory layout
+///
+/// `#[repr(transparent)]` over a `u64`.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(
+        zerocopy::FromBytes,
+        zerocopy::IntoBytes,
+        zerocopy::Immutable,
+        zerocopy::KnownLayout
+    )
+)]
+#[cfg_attr(feature = "bitcode", derive(bitcode::Encode, bitcode::Decode))]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_ecs::component::Component, bevy_reflect::Reflect),
+    reflect(opaque, Component, Debug, PartialEq, Hash, Clone)
+)]
+#[repr(transparent)]
+pub struct Tsid(u64);
+
+const _: () =
+    assert!(mem::size_of::<Tsid>() == 8 && mem::align_of::<Tsid>() == mem::align_of::<u64>());
+
+impl Tsid {
+    /// Milliseconds since the TSID epoch.
+    #[inline]
+    pub fn timestamp(self) -> u64 {
+        self.0 >> RANDOM_BITS
+    }
+
+    /// Milliseconds since the UNIX epoch.
+    #[inline]
+    pub fn unix_millis(self) -> u64 {
+        self.timestamp() + TSID_EPOCH_MS
+    }
+
+    /// The 22 bits below the timestamp: the node ID followed by the counter.
+    #[inline]
+    pub fn random(self) -> u64 {
+        self.0 & RANDOM_MASK
+    }
+}
+
+/// Every `u64` is a valid TSID.
+impl From<u64> for Tsid {
+    #[inline]
+    fn from(value: u64) -> Self {
+        Tsid(value)
+    }
+}
+
+impl From<Tsid> for u64 {
+    #[inline]
+    fn from(id: Tsid) -> u64 {
+        id.0
+    }
+}
+
+/// Two's complement, as Java stores TSIDs in a `long`. TSIDs stay non-negative until 2159.
+impl From<i64> for Tsid {
+    #[inline]
+    fn from(value: i64) -> Self {
+        Tsid(value as u64)
+    }
+}
+
+impl From<Tsid> for i64 {
+    #[inline]
+    fn from(id: Tsid) -> i64 {
+        id.0 as i64
+    }
+}
+
+impl Tsid {
+    /// The Crockford base32 digits of the display form.
+    #[inline]
+    pub(crate) fn encode(self) -> [u8; ENCODED_LEN] {
+        let mut buf = [0u8; ENCODED_LEN];
+        for (i, c) in buf.iter_mut().enumerate() {
+            *c = CROCKFORD[((self.0 >> (60 - 5 * i)) & 0x1f) as usize];
+        }
+        buf
+    }
+}
+
+impl fmt::Display for Tsid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let buf = self.encode();
+        // Crockford base32 is ASCII.
+        let s = core::str::from_utf8(&buf).unwrap();
+        pad_id(f, format_args!(""), format_args!("{}", s))
+    }
+}
+
+/// Parses the 13-character Crockford base32 form. Like tsid-creator, this is case insensitive and
+/// reads `O` as `0` and `I`/`L` as `1`.
+impl FromStr for Tsid {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        fn decode(c: u8) -> Option<u64> {
+            let value = match c.to_ascii_uppercase() {
+                c @ b'0'..=b'9' => c - b'0',
+                b'O' => 0,
+                b'I' | b'L' => 1,
+                c => CROCKFORD.iter().position(|&d| d == c)? as u8,
+            };
+            Some(u64::from(value))
+        }
+
+        let mut value = 0u64;
+        for (i, c) in s.bytes().enumerate().take(ENCODED_LEN) {
+            let digit =
+                decode(c).ok_or_else(|| ParseIdError::at(s, i, "a Crockford base32 digit"))?;
+            // The first character only carries 4 bits.
+            if i == 0 && digit > 0xf {
+                return Err(ParseIdError::at(s, i, "a Crockford base32 digit up to `F`"));
+            }
+            value = (value << 5) | digit;
+        }
+        expect_len(s, ENCODED_LEN, "a Crockford base32 digit")?;
+        Ok(Tsid(value))
+    }
+}
+
+/// Generates [`Tsid`]s for a single node.
+///
+/// IDs are laid out like tsid-creator's: `node_bits` bits of node ID followed by a counter in the
+/// remaining `22 - node_bits` bits. The counter starts at a random value every millisecond and
+/// increments for each ID; if it overflows, the generator moves on to the next millisecond early.
+/// With the default 10 node bits, each node can create 4096 IDs per millisecond.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct TsidGenerator {
+    node_id: u64,
+    node_bits: u32,
+    seed: u64,
+    // The last ID issued.
+    last: AtomicU64,
+    clock: stats::ClockWatch,
+}
+
+#[cfg(feature = "std")]
+impl TsidGenerator {
+    /// Creates a generator for `node_id`, using tsid-creator's default of 10 node bits.
+    ///
+    /// **panics** if `node_id` doesn't fit in 10 bits.
+    pub fn new(node_id: u64) -> Self {
+        TsidGenerator::with_node_bits(node_id, DEFAULT_NODE_BITS)
+    }
+
+    /// Creates a generator for `node_id`, reserving `node_bits` bits for node IDs.
+    ///
+    /// **panics** if `node_bits` is greater than 20 or `node_id` doesn't fit in `node_bits` bits.
+    pub fn with_node_bits(node_id: u64, node_bits: u32) -> Self {
+        assert!(node_bits <= 20, "at most 20 node bits are supported");
+        assert!(
+            node_id >> node_bits == 0,
+            "node ID {} doesn't fit in {} bits",
+            node_id,
+            node_bits
+        );
+        TsidGenerator {
+            node_id,
+            node_bits,
+            seed: RandomState::new().build_hasher().finish(),
+            last: AtomicU64::new(0),
+            clock: stats::ClockWatch::new(),
+        }
+    }
+
+    /// The node ID embedded in generated IDs.
+    #[inline]
+    pub fn node_id(&self) -> u64 {
+        self.node_id
+    }
+
+    /// Generates a new TSID.
+    pub fn next(&self) -> Tsid {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64)
+            .saturating_sub(TSID_EPOCH_MS);
+        self.next_at(now)
+    }
+
+    fn next_at(&self, now: u64) -> Tsid {
+        self.clock.observe(now, &stats::TSID);
+        let counter_bits = RANDOM_BITS - self.node_bits;
+        let counter_mask = (1 << counter_bits) - 1;
+        let node = self.node_id << counter_bits;
+        let mut last = self.last.load(Ordering::Relaxed);
+        loop {
+            let last_ts = last >> RANDOM_BITS;
+            let (next, exhausted) = if now > last_ts {
+                let mut state = self.seed ^ now;
+                let counter = splitmix64(&mut state) & counter_mask;
+                ((now << RANDOM_BITS) | node | counter, false)
+            } else {
+                let counter = ((last & counter_mask) + 1) & counter_mask;
+                let ts = if counter == 0 { last_ts + 1 } else { last_ts };
+                ((ts << RANDOM_BITS) | node | counter, counter == 0)
+            };
+            match self
+                .last
+                .compare_exchange_weak(last, next, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => {
+                    stats::TSID.issued(1);
+                    if exhausted {
+                        stats::TSID.exhausted();
+                    }
+                    return Tsid(next);
+                }
+                Err(actual) => last = actual,
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::{Tsid, TsidGenerator};
+
+    #[test]
+    fn test_string_form() {
+        // Matches tsid-creator.
+        let id = Tsid::from(496830748901259230u64);
+        assert_eq!(id.to_string(), "0DS8RXW6W0DYY");
+        assert_eq!("0DS8RXW6W0DYY".parse(), Ok(id));
+        assert_eq!("0ds8rxw6w0dyy".parse(), Ok(id));
+        assert_eq!(
+            "0123456789ABO".parse::<Tsid>(),
+            "0123456789AB0".parse::<Tsid>()
+        );
+        assert_eq!(Tsid::from(u64::MAX).to_string(), "FZZZZZZZZZZZZ");
+        for bad in &[
+            "",
+            "0123456789AB",
+            "0123456789ABCC",
+            "0123456789ABU",
+            "G000000000000",
+        ] {
+            assert!(bad.parse::<Tsid>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_layout() {
+        let gen = TsidGenerator::with_node_bits(5, 8);
+        let a = gen.next_at(1000);
+        assert_eq!(a.timestamp(), 1000);
+        assert_eq!(a.random() >> 14, 5);
+        let b = gen.next_at(1000);
+        assert!(b > a);
+        assert_eq!(b.random() >> 14, 5);
+    }
+
+    #[test]
+    fn test_counter_overflow() {
+        let gen = TsidGenerator::with_node_bits(0, 20);
+        let ids: Vec<_> = (0..5).map(|_| gen.next_at(7)).collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert!(ids.last().unwrap().timestamp() > 7);
+    }
+
+    #[test]
+    fn test_now() {
+        let gen = TsidGenerator::new(1);
+        let id = gen.next();
+        assert!(id.unix_millis() > 1_600_000_000_000);
+        assert!(gen.next() > id);
+    }
+}
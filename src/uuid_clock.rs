@@ -0,0 +1,333 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use uuid::timestamp::{ClockSequence, UUID_TICKS_BETWEEN_EPOCHS};
+use uuid::{Builder, Uuid};
+
+use crate::node::{NodeIdError, NodeIdProvider};
+use crate::obfuscate::splitmix64;
+use crate::process_unique_id::next_global;
+
+// 100ns ticks per second, the resolution of version 1 and 6 UUID timestamps.
+const TICKS_PER_SECOND: u64 = 10_000_000;
+const MAX_SEQUENCE: u16 = (1 << 14) - 1;
+// UUID timestamps are 60 bits wide, good until the year 5236.
+const MAX_TICKS: u64 = (1 << 60) - 1;
+
+/// A node ID and clock sequence for version 1 and 6 UUIDs that never repeat a
+/// (timestamp, clock sequence, node ID) triple within the process.
+///
+/// The clock sequence counts UUIDs created within the same 100ns tick (or after the clock went
+/// backwards); when its 14 bits run out the timestamp moves ahead one tick instead of wrapping,
+/// so the timestamps handed out never go backwards. The node ID is random apart from its low
+/// three bytes, which come from a process unique prefix, so every `UuidClockSequence` in a
+/// process gets a different one. It has the multicast bit set, as RFC 9562 requires for node IDs
+/// that aren't MAC addresses.
+///
+/// Share one `UuidClockSequence` between all the threads that create UUIDs:
+///
+/// ```
+/// use snowflake::UuidClockSequence;
+/// use uuid::{Timestamp, Uuid};
+///
+/// let clock = UuidClockSequence::new();
+/// let a = Uuid::new_v6(Timestamp::now(&clock), clock.node_id());
+/// let b = Uuid::new_v6(Timestamp::now(&clock), clock.node_id());
+/// assert!(a < b);
+/// ```
+#[derive(Debug)]
+pub struct UuidClockSequence {
+    node: [u8; 6],
+    // The last (tick, sequence) handed out.
+    last: Mutex<(u64, u16)>,
+}
+
+impl UuidClockSequence {
+    /// Creates a clock sequence with a fresh node ID.
+    ///
+    /// **panics** if the process has run out of prefixes.
+    pub fn new() -> Self {
+        let mut seed = RandomState::new().build_hasher().finish();
+        let random = splitmix64(&mut seed).to_be_bytes();
+        UuidClockSequence::with_high_bytes([random[0], random[1], random[2]])
+    }
+
+    /// Creates a clock sequence whose node ID has the node ID supplied by `provider` (16 bits)
+    /// in place of random bytes, so that its UUIDs don't collide with those of other processes
+    /// given different node IDs.
+    pub fn from_provider<P: NodeIdProvider + ?Sized>(provider: &P) -> Result<Self, NodeIdError> {
+        let node_id = provider.node_id(16)?;
+        Ok(UuidClockSequence::with_high_bytes([
+            0,
+            (node_id >> 8) as u8,
+            node_id as u8,
+        ]))
+    }
+
+    fn with_high_bytes(high: [u8; 3]) -> Self {
+        let prefix = (next_global() as u64).to_be_bytes();
+        UuidClockSequence {
+            node: [
+                high[0] | 0x01,
+                high[1],
+                high[2],
+                prefix[5],
+                prefix[6],
+                prefix[7],
+            ],
+            last: Mutex::new((0, 0)),
+        }
+    }
+
+    /// The node ID to create UUIDs with.
+    #[inline]
+    pub fn node_id(&self) -> &[u8; 6] {
+        &self.node
+    }
+
+    /// Returns the tick and sequence to use for a UUID created at `tick` (since the Gregorian
+    /// epoch).
+    fn next(&self, tick: u64) -> (u64, u16) {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        *last = match *last {
+            (last_tick, _) if tick > last_tick => (tick, 0),
+            (last_tick, seq) if seq < MAX_SEQUENCE => (last_tick, seq + 1),
+            (last_tick, _) => (last_tick + 1, 0),
+        };
+        *last
+    }
+}
+
+impl Default for UuidClockSequence {
+    #[inline]
+    fn default() -> Self {
+        UuidClockSequence::new()
+    }
+}
+
+impl ClockSequence for UuidClockSequence {
+    type Output = u16;
+
+    fn generate_sequence(&self, seconds: u64, subsec_nanos: u32) -> u16 {
+        self.generate_timestamp_sequence(seconds, subsec_nanos).0
+    }
+
+    fn generate_timestamp_sequence(&self, seconds: u64, subsec_nanos: u32) -> (u16, u64, u32) {
+        let tick =
+            ticks(Duration::new(seconds, subsec_nanos)).saturating_add(UUID_TICKS_BETWEEN_EPOCHS);
+        let (tick, seq) = self.next(tick);
+        // The ticks handed out never go below the one passed in, so this can't underflow.
+        let tick = tick - UUID_TICKS_BETWEEN_EPOCHS;
+        (
+            seq,
+            tick / TICKS_PER_SECOND,
+            (tick % TICKS_PER_SECOND) as u32 * 100,
+        )
+    }
+
+    fn usable_bits(&self) -> usize {
+        14
+    }
+}
+
+/// Generates version 1 UUIDs for Cassandra and ScyllaDB `timeuuid` columns.
+///
+/// The UUIDs' 60-bit timestamps count 100ns ticks since the Gregorian epoch (1582-10-15), taken
+/// from the system clock without going through the UNIX epoch, so times before 1970 work too.
+/// Their clock sequence and node ID come from a [`UuidClockSequence`], so the generator never
+/// repeats a UUID within the process, and the UUIDs it returns come out in the order Cassandra
+/// sorts them: by timestamp, then clock sequence.
+///
+/// ```
+/// use snowflake::TimeUuidGenerator;
+/// use std::time::SystemTime;
+///
+/// let ids = TimeUuidGenerator::new();
+/// let start = TimeUuidGenerator::min_timeuuid(SystemTime::now());
+/// let (a, b) = (ids.next(), ids.next());
+/// assert_eq!(a.get_version_num(), 1);
+/// assert!(TimeUuidGenerator::timestamp(&start) <= TimeUuidGenerator::timestamp(&a));
+/// assert!(TimeUuidGenerator::timestamp(&a) <= TimeUuidGenerator::timestamp(&b));
+/// ```
+#[derive(Debug, Default)]
+pub struct TimeUuidGenerator {
+    clock: UuidClockSequence,
+}
+
+impl TimeUuidGenerator {
+    /// Creates a generator with a fresh, random node ID.
+    ///
+    /// **panics** if the process has run out of prefixes.
+    pub fn new() -> Self {
+        TimeUuidGenerator {
+            clock: UuidClockSequence::new(),
+        }
+    }
+
+    /// Creates a generator whose node ID includes the node ID supplied by `provider`; see
+    /// [`UuidClockSequence::from_provider`].
+    pub fn from_provider<P: NodeIdProvider + ?Sized>(provider: &P) -> Result<Self, NodeIdError> {
+        Ok(TimeUuidGenerator {
+            clock: UuidClockSequence::from_provider(provider)?,
+        })
+    }
+
+    /// The node ID of the UUIDs this generator creates.
+    #[inline]
+    pub fn node_id(&self) -> &[u8; 6] {
+        self.clock.node_id()
+    }
+
+    /// The clock sequence behind this generator, to create version 6 UUIDs from as well.
+    #[inline]
+    pub fn clock(&self) -> &UuidClockSequence {
+        &self.clock
+    }
+
+    /// Creates a UUID for the current time.
+    pub fn next(&self) -> Uuid {
+        self.at(SystemTime::now())
+    }
+
+    /// Creates a UUID for `time`, or for the latest time this generator has used if that's
+    /// later.
+    pub fn at(&self, time: SystemTime) -> Uuid {
+        let (tick, seq) = self.clock.next(gregorian_ticks(time));
+        Builder::from_gregorian_timestamp(tick.min(MAX_TICKS), seq, &self.clock.node).into_uuid()
+    }
+
+    /// The smallest timeuuid Cassandra sorts at `time`, the same as CQL's `minTimeuuid`. For
+    /// range queries.
+    pub fn min_timeuuid(time: SystemTime) -> Uuid {
+        bound(time, 0x80)
+    }
+
+    /// The largest timeuuid Cassandra sorts at `time`, the same as CQL's `maxTimeuuid`. For range
+    /// queries; it isn't a valid version 1 UUID.
+    pub fn max_timeuuid(time: SystemTime) -> Uuid {
+        bound(time, 0x7f)
+    }
+
+    /// The time a version 1 UUID was created, to 100ns, or `None` if it's some other version.
+    pub fn timestamp(uuid: &Uuid) -> Option<SystemTime> {
+        if uuid.get_version_num() != 1 {
+            return None;
+        }
+        let (tick, _) = uuid.get_timestamp()?.to_gregorian();
+        Some(if tick >= UUID_TICKS_BETWEEN_EPOCHS {
+            UNIX_EPOCH + from_ticks(tick - UUID_TICKS_BETWEEN_EPOCHS)
+        } else {
+            UNIX_EPOCH - from_ticks(UUID_TICKS_BETWEEN_EPOCHS - tick)
+        })
+    }
+}
+
+/// A version 1 UUID at `time` with every byte of the clock sequence and node ID set to `fill`.
+/// Cassandra compares those bytes as signed, so 0x80 is the smallest and 0x7f the largest.
+fn bound(time: SystemTime, fill: u8) -> Uuid {
+    let mut bytes = *Builder::from_gregorian_timestamp(gregorian_ticks(time), 0, &[0; 6])
+        .into_uuid()
+        .as_bytes();
+    for byte in &mut bytes[8..] {
+        *byte = fill;
+    }
+    Uuid::from_bytes(bytes)
+}
+
+/// 100ns ticks since the Gregorian epoch, clamped to the range UUIDs can hold.
+fn gregorian_ticks(time: SystemTime) -> u64 {
+    let ticks = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => UUID_TICKS_BETWEEN_EPOCHS.saturating_add(ticks(since)),
+        Err(e) => UUID_TICKS_BETWEEN_EPOCHS.saturating_sub(ticks(e.duration())),
+    };
+    ticks.min(MAX_TICKS)
+}
+
+fn ticks(duration: Duration) -> u64 {
+    duration
+        .as_secs()
+        .saturating_mul(TICKS_PER_SECOND)
+        .saturating_add(u64::from(duration.subsec_nanos() / 100))
+}
+
+fn from_ticks(ticks: u64) -> Duration {
+    Duration::new(
+        ticks / TICKS_PER_SECOND,
+        (ticks % TICKS_PER_SECOND) as u32 * 100,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TimeUuidGenerator, UuidClockSequence};
+    use std::collections::HashSet;
+    use uuid::timestamp::ClockSequence;
+    use uuid::{Timestamp, Uuid};
+
+    #[test]
+    fn test_uuid_clock_unique() {
+        let clock = UuidClockSequence::new();
+        assert_eq!(clock.node_id()[0] & 0x01, 0x01);
+        assert_ne!(
+            clock.node_id()[3..],
+            UuidClockSequence::new().node_id()[3..]
+        );
+
+        let uuids: HashSet<_> = (0..10_000)
+            .map(|_| Uuid::new_v1(Timestamp::now(&clock), clock.node_id()))
+            .collect();
+        assert_eq!(uuids.len(), 10_000);
+    }
+
+    #[test]
+    fn test_uuid_clock_overflow() {
+        let clock = UuidClockSequence::new();
+        let mut seen = HashSet::new();
+        // A stopped clock: the sequence runs out and the timestamp moves ahead.
+        for i in 0..20_000u32 {
+            let (seq, secs, nanos) = clock.generate_timestamp_sequence(100, 500);
+            assert!(seq < 1 << 14);
+            assert_eq!(nanos % 100, 0);
+            assert!(seen.insert((secs, nanos, seq)), "repeated at {}", i);
+        }
+        assert_eq!(
+            clock.generate_timestamp_sequence(100, 500),
+            (20_000 - (1 << 14), 100, 600)
+        );
+        // A clock that went backwards doesn't reset anything.
+        assert_eq!(clock.generate_sequence(99, 0), 20_001 - (1 << 14));
+    }
+
+    #[test]
+    fn test_timeuuid() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let ids = TimeUuidGenerator::new();
+        let time = UNIX_EPOCH + Duration::new(1_600_000_000, 123_456_789);
+        let a = ids.at(time);
+        assert_eq!(a.get_version_num(), 1);
+        assert_eq!(a.get_node_id().as_ref(), Some(ids.node_id()));
+        let expected = UNIX_EPOCH + Duration::new(1_600_000_000, 123_456_700);
+        assert_eq!(TimeUuidGenerator::timestamp(&a), Some(expected));
+        // Same tick: the clock sequence goes up, and so do the bytes Cassandra compares.
+        let b = ids.at(time);
+        assert_eq!(TimeUuidGenerator::timestamp(&b), Some(expected));
+        assert!(a.as_bytes()[8..] < b.as_bytes()[8..]);
+        assert!(TimeUuidGenerator::min_timeuuid(time).as_bytes()[8..] == [0x80; 8]);
+        assert!(TimeUuidGenerator::max_timeuuid(time).as_bytes()[8..] == [0x7f; 8]);
+
+        // Before the UNIX epoch.
+        let old = UNIX_EPOCH - Duration::from_secs(86_400 * 365 * 100);
+        let c = TimeUuidGenerator::new().at(old);
+        assert_eq!(TimeUuidGenerator::timestamp(&c), Some(old));
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::convert::TryFrom;
+use core::error::Error;
+use core::fmt;
+
+use crate::ProcessUniqueId;
+
+/// The most bytes a `u64` takes in LEB128: 64 bits at 7 bits per byte.
+const MAX_U64_LEN: usize = 10;
+
+/// Writes `value` as unsigned LEB128 at `pos`, returning the position after it.
+#[inline]
+fn write_u64(mut value: u64, buf: &mut [u8], mut pos: usize) -> usize {
+    while value >= 0x80 {
+        buf[pos] = value as u8 | 0x80;
+        value >>= 7;
+        pos += 1;
+    }
+    buf[pos] = value as u8;
+    pos + 1
+}
+
+/// Reads an unsigned LEB128 value at `pos`, returning it and the position after it. Only the
+/// shortest encoding of each value is accepted.
+#[inline]
+fn read_u64(bytes: &[u8], pos: usize) -> Result<(u64, usize), VarintError> {
+    let mut value = 0;
+    for i in 0..MAX_U64_LEN {
+        let byte = *bytes.get(pos + i).ok_or(VarintError::Truncated)?;
+        // The tenth byte holds the top bit and must end the value.
+        if i == MAX_U64_LEN - 1 && byte > 1 {
+            return Err(VarintError::Malformed);
+        }
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            if byte == 0 && i != 0 {
+                return Err(VarintError::Malformed);
+            }
+            return Ok((value, pos + i + 1));
+        }
+    }
+    unreachable!()
+}
+
+impl ProcessUniqueId {
+    /// The most bytes [`encode_varint`](ProcessUniqueId::encode_varint) writes.
+    pub const MAX_VARINT_LEN: usize = 2 * MAX_U64_LEN;
+
+    /// Writes the ID in a compact variable-length form into `buf`, returning the part of `buf` it
+    /// takes up: the prefix then the offset, each as an unsigned LEB128 integer (7 bits per
+    /// byte, with the high bit set on every byte but the last).
+    ///
+    /// Prefixes count threads and offsets count IDs, so both stay small in young processes: an ID
+    /// with a prefix below 128 and an offset below 2^35 takes at most 6 bytes, instead of the 16
+    /// of [`to_bytes`](ProcessUniqueId::to_bytes). The worst case is
+    /// [`MAX_VARINT_LEN`](ProcessUniqueId::MAX_VARINT_LEN) bytes. Unlike `to_bytes`, the encoded
+    /// forms don't sort the same way as the IDs.
+    ///
+    /// ```
+    /// use snowflake::ProcessUniqueId;
+    ///
+    /// let id = ProcessUniqueId::from_u128(1 << 64 | 300).unwrap();
+    /// let mut buf = [0; ProcessUniqueId::MAX_VARINT_LEN];
+    /// assert_eq!(id.encode_varint(&mut buf), [0x01, 0xac, 0x02]);
+    ///
+    /// let mut out = Vec::new();
+    /// out.extend_from_slice(id.encode_varint(&mut buf));
+    /// out.extend_from_slice(ProcessUniqueId::new().encode_varint(&mut buf));
+    /// let (first, len) = ProcessUniqueId::decode_varint(&out).unwrap();
+    /// assert_eq!(first, id);
+    /// assert!(ProcessUniqueId::decode_varint(&out[len..]).is_ok());
+    /// ```
+    #[inline]
+    pub fn encode_varint(self, buf: &mut [u8; ProcessUniqueId::MAX_VARINT_LEN]) -> &[u8] {
+        let pos = write_u64(self.prefix as u64, buf, 0);
+        let pos = write_u64(self.offset, buf, pos);
+        &buf[..pos]
+    }
+
+    /// Reads an ID written by [`encode_varint`](ProcessUniqueId::encode_varint) from the start of
+    /// `bytes`, returning it and the number of bytes it took up, so that IDs can be read back one
+    /// after another.
+    ///
+    /// Every ID has exactly one encoding: padded (overlong) integers are rejected. As with
+    /// `from_u128`, only IDs created by the current process are unique in it.
+    pub fn decode_varint(bytes: &[u8]) -> Result<(Self, usize), VarintError> {
+        let (prefix, pos) = read_u64(bytes, 0)?;
+        let (offset, pos) = read_u64(bytes, pos)?;
+        let prefix = usize::try_from(prefix).map_err(|_| VarintError::OutOfRange)?;
+        Ok((ProcessUniqueId { prefix, offset }, pos))
+    }
+}
+
+/// An error returned by [`ProcessUniqueId::decode_varint`], saying what was wrong with the input.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum VarintError {
+    /// The input ended in the middle of an ID.
+    Truncated,
+    /// A field takes more bytes than it needs, or has more than 64 bits.
+    Malformed,
+    /// The prefix doesn't fit in a `usize`.
+    OutOfRange,
+}
+
+impl fmt::Display for VarintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            VarintError::Truncated => "varint-encoded unique ID is truncated",
+            VarintError::Malformed => "malformed varint in unique ID",
+            VarintError::OutOfRange => "unique ID field out of range",
+        })
+    }
+}
+
+impl Error for VarintError {}
+
+#[cfg(test)]
+mod test {
+    use super::VarintError;
+    use crate::ProcessUniqueId;
+
+    #[test]
+    fn test_varint() {
+        let mut buf = [0; ProcessUniqueId::MAX_VARINT_LEN];
+        let cases: &[(u128, usize)] = &[
+            (0, 2),
+            (127, 2),
+            (128, 3),
+            (1 << 64 | 1 << 35, 7),
+            (1 << 64 | ((1 << 35) - 1), 6),
+            (u128::from(u64::MAX), 11),
+        ];
+        for &(value, len) in cases {
+            let id = ProcessUniqueId::from_u128(value).unwrap();
+            let encoded = id.encode_varint(&mut buf);
+            assert_eq!(encoded.len(), len);
+            assert_eq!(ProcessUniqueId::decode_varint(encoded), Ok((id, len)));
+        }
+        let id = ProcessUniqueId::new();
+        let len = id.encode_varint(&mut buf).len();
+        assert_eq!(ProcessUniqueId::decode_varint(&buf), Ok((id, len)));
+
+        #[cfg(target_pointer_width = "64")]
+        {
+            let id = ProcessUniqueId::from_u128(u128::MAX).unwrap();
+            let encoded = id.encode_varint(&mut buf);
+            assert_eq!(encoded.len(), ProcessUniqueId::MAX_VARINT_LEN);
+            assert_eq!(ProcessUniqueId::decode_varint(encoded), Ok((id, 20)));
+        }
+
+        let decode = ProcessUniqueId::decode_varint;
+        assert_eq!(decode(&[]), Err(VarintError::Truncated));
+        assert_eq!(decode(&[0x01]), Err(VarintError::Truncated));
+        assert_eq!(decode(&[0x01, 0x80]), Err(VarintError::Truncated));
+        // Overlong: 0 as two bytes.
+        assert_eq!(decode(&[0x80, 0x00, 0x00]), Err(VarintError::Malformed));
+        // 2^64.
+        let mut too_big = [0xff; 11];
+        too_big[9] = 0x02;
+        assert_eq!(decode(&too_big), Err(VarintError::Malformed));
+        too_big[9] = 0x81;
+        assert_eq!(decode(&too_big), Err(VarintError::Malformed));
+    }
+}
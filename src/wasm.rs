@@ -0,0 +1,179 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! JavaScript bindings, for using the crate from the browser or Node through `wasm-bindgen`.
+//!
+//! Requires the `wasm-bindgen` feature. The exported classes are named after the Rust types
+//! they wrap:
+//!
+//! ```js
+//! import { ProcessUniqueId, SnowflakeGenerator } from "./pkg/snowflake.js";
+//!
+//! const id = new ProcessUniqueId();
+//! console.log(id.toString());              // "puid-0-0"
+//! ProcessUniqueId.fromString("puid-0-0");
+//!
+//! const gen = new SnowflakeGenerator(1);
+//! console.log(gen.next());                 // "1541815603606036480"
+//! ```
+//!
+//! Snowflake IDs are 63 bit integers, which JavaScript numbers can't represent exactly, so they
+//! cross the boundary as decimal strings (or `BigInt`s, with `nextBigInt`).
+//!
+//! On `wasm32` targets the snowflake generator reads the time from `Date.now()`, as the standard
+//! library has no clock there.
+use wasm_bindgen::prelude::*;
+
+use crate::{ProcessUniqueId, SnowflakeGenerator, SnowflakeId};
+
+/// A process unique ID, exported to JavaScript as `ProcessUniqueId`.
+///
+/// IDs are unique within the wasm module instance that created them.
+#[wasm_bindgen(js_name = ProcessUniqueId)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct JsProcessUniqueId(ProcessUniqueId);
+
+#[wasm_bindgen(js_class = ProcessUniqueId)]
+impl JsProcessUniqueId {
+    /// Creates a new unique ID.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        JsProcessUniqueId(ProcessUniqueId::new())
+    }
+
+    /// Parses the `puid-<prefix>-<offset>` form produced by `toString`.
+    #[wasm_bindgen(js_name = fromString)]
+    pub fn from_string(s: &str) -> Result<JsProcessUniqueId, JsError> {
+        Ok(JsProcessUniqueId(s.parse()?))
+    }
+
+    /// The ID's `puid-<prefix>-<offset>` form.
+    #[wasm_bindgen(js_name = toString)]
+    #[allow(clippy::inherent_to_string)]
+    pub fn to_string(&self) -> String {
+        self.0.to_string()
+    }
+
+    /// Returns true if both objects hold the same ID.
+    pub fn equals(&self, other: &JsProcessUniqueId) -> bool {
+        self.0 == other.0
+    }
+
+    /// The ID's prefix.
+    #[wasm_bindgen(getter)]
+    pub fn prefix(&self) -> u64 {
+        self.0.prefix as u64
+    }
+
+    /// The ID's offset.
+    #[wasm_bindgen(getter)]
+    pub fn offset(&self) -> u64 {
+        self.0.offset
+    }
+}
+
+impl Default for JsProcessUniqueId {
+    #[inline]
+    fn default() -> Self {
+        JsProcessUniqueId::new()
+    }
+}
+
+impl From<ProcessUniqueId> for JsProcessUniqueId {
+    #[inline]
+    fn from(id: ProcessUniqueId) -> Self {
+        JsProcessUniqueId(id)
+    }
+}
+
+impl From<JsProcessUniqueId> for ProcessUniqueId {
+    #[inline]
+    fn from(id: JsProcessUniqueId) -> Self {
+        id.0
+    }
+}
+
+/// A snowflake ID generator, exported to JavaScript as `SnowflakeGenerator`.
+#[wasm_bindgen(js_name = SnowflakeGenerator)]
+#[derive(Debug)]
+pub struct JsSnowflakeGenerator(SnowflakeGenerator);
+
+#[wasm_bindgen(js_class = SnowflakeGenerator)]
+impl JsSnowflakeGenerator {
+    /// Creates a generator for the given node, using the default (Twitter) epoch.
+    ///
+    /// Throws if `nodeId` doesn't fit in 10 bits.
+    #[wasm_bindgen(constructor)]
+    pub fn new(node_id: u16) -> Result<JsSnowflakeGenerator, JsError> {
+        if node_id >= 1 << 10 {
+            return Err(JsError::new("snowflake node ID does not fit in 10 bits"));
+        }
+        Ok(JsSnowflakeGenerator(SnowflakeGenerator::new(node_id)))
+    }
+
+    /// Generates a new ID, as a decimal string.
+    pub fn next(&self) -> String {
+        self.0.next().to_string()
+    }
+
+    /// Generates a new ID, as a `BigInt`.
+    #[wasm_bindgen(js_name = nextBigInt)]
+    pub fn next_big_int(&self) -> u64 {
+        self.0.next().into()
+    }
+
+    /// This generator's node ID.
+    #[wasm_bindgen(getter, js_name = nodeId)]
+    pub fn node_id(&self) -> u64 {
+        self.0.node_id()
+    }
+}
+
+/// Converts a snowflake ID from a `BigInt` to its decimal string form.
+///
+/// Throws if the ID has its top (sign) bit set.
+#[wasm_bindgen(js_name = snowflakeToString)]
+pub fn snowflake_to_string(id: u64) -> Result<String, JsError> {
+    use core::convert::TryFrom;
+
+    Ok(SnowflakeId::try_from(id)?.to_string())
+}
+
+/// Converts a snowflake ID from its decimal string form to a `BigInt`.
+#[wasm_bindgen(js_name = snowflakeFromString)]
+pub fn snowflake_from_string(s: &str) -> Result<u64, JsError> {
+    Ok(s.parse::<SnowflakeId>()?.into())
+}
+
+/// Milliseconds since the UNIX epoch according to JavaScript's `Date.now()`.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn unix_ms() -> u64 {
+    js_sys::Date::now() as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        snowflake_from_string, snowflake_to_string, JsProcessUniqueId, JsSnowflakeGenerator,
+    };
+
+    #[test]
+    fn test_js_api() {
+        let id = JsProcessUniqueId::new();
+        let parsed = JsProcessUniqueId::from_string(&id.to_string()).unwrap();
+        assert!(parsed.equals(&id));
+        assert_ne!(JsProcessUniqueId::new(), id);
+
+        let gen = JsSnowflakeGenerator::new(7).unwrap();
+        assert_eq!(gen.node_id(), 7);
+        let a = gen.next();
+        let b = gen.next_big_int();
+        assert_eq!(snowflake_to_string(b).unwrap(), b.to_string());
+        assert!(snowflake_from_string(&a).unwrap() < b);
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Web framework integration: IDs in URL paths.
+//!
+//! - `rocket`: the ID types implement `FromParam`, so they can be taken straight from a route's
+//!   dynamic segments, and [`ParseIdError`] is a `Responder` that answers `400 Bad Request`.
+//! - `actix-web`: [`ParseIdError`] is a `ResponseError` (`400 Bad Request`), so handlers can
+//!   `?` the result of parsing an ID. actix-web answers `404 Not Found` when a `Path` doesn't
+//!   deserialize; register `path_config()` as app data to answer `400 Bad Request` instead.
+//! - `axum`: [`ParseIdError`] implements `IntoResponse` (`400 Bad Request`), for the same.
+//!
+//! With `actix-web` or `axum`, [`Param`] reads any ID's string form through `Path`, which only
+//! knows serde. (The IDs' own `Deserialize` implementations read their serde forms, which for
+//! most ID types isn't the string form.)
+//!
+//! ```no_run
+//! use axum::{extract::Path, routing::get, Router};
+//! use snowflake::{web::Param, Tsid};
+//!
+//! async fn get_order(Path(Param(id)): Path<Param<Tsid>>) -> String {
+//!     format!("order {}", id)
+//! }
+//!
+//! let app: Router = Router::new().route("/orders/{id}", get(get_order));
+//! ```
+//!
+//! Error messages are [`ParseIdError`]'s `Display` form, which says where the ID went wrong.
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::str::FromStr;
+
+use ::serde::de::{Deserialize, Deserializer};
+
+use crate::ParseIdError;
+#[cfg(feature = "rocket")]
+use crate::{
+    AnyId, FlakeId, LamportId, ObjectId, ProcessUniqueId, PushId, SmallUniqueId, SnowflakeId,
+    SonyflakeId, Tsid, Xid,
+};
+
+/// An ID in a URL path, read from its string form; see the [module documentation](self).
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Param<T>(pub T);
+
+impl<T> Param<T> {
+    /// Unwraps the ID.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Param<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Param<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Param<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Param<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        crate::serde::as_string::deserialize(deserializer).map(Param)
+    }
+}
+
+#[cfg(feature = "rocket")]
+macro_rules! impl_from_param {
+    ($($ty:ident),*) => {$(
+        impl<'a> rocket::request::FromParam<'a> for $ty {
+            type Error = ParseIdError;
+
+            #[inline]
+            fn from_param(param: &'a str) -> Result<Self, ParseIdError> {
+                param.parse()
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "rocket")]
+impl_from_param!(
+    ProcessUniqueId,
+    SnowflakeId,
+    FlakeId,
+    SonyflakeId,
+    Tsid,
+    SmallUniqueId,
+    ObjectId,
+    Xid,
+    PushId,
+    LamportId,
+    AnyId
+);
+
+#[cfg(feature = "rocket")]
+impl<'r, 'o: 'r> rocket::response::Responder<'r, 'o> for ParseIdError {
+    fn respond_to(self, request: &'r rocket::Request<'_>) -> rocket::response::Result<'o> {
+        rocket::response::status::BadRequest(self.to_string()).respond_to(request)
+    }
+}
+
+#[cfg(feature = "actix-web")]
+impl actix_web::ResponseError for ParseIdError {
+    #[inline]
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        actix_web::http::StatusCode::BAD_REQUEST
+    }
+}
+
+/// A `PathConfig` that answers `400 Bad Request`, with the parse error as the body, when a path
+/// doesn't deserialize (e.g., a [`Param`] doesn't parse), instead of actix-web's default
+/// `404 Not Found`.
+///
+/// ```
+/// use actix_web::{web, App};
+///
+/// let app = App::new().app_data(snowflake::web::path_config());
+/// ```
+#[cfg(feature = "actix-web")]
+pub fn path_config() -> actix_web::web::PathConfig {
+    actix_web::web::PathConfig::default()
+        .error_handler(|err, _| actix_web::error::ErrorBadRequest(err.to_string()))
+}
+
+#[cfg(feature = "axum")]
+impl axum_core::response::IntoResponse for ParseIdError {
+    fn into_response(self) -> axum_core::response::Response {
+        (http::StatusCode::BAD_REQUEST, self.to_string()).into_response()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Param;
+    use crate::{ProcessUniqueId, Tsid};
+
+    #[test]
+    fn test_param() {
+        let id = Tsid::from(42u64);
+        let param: Param<Tsid> = serde_json::from_str(&format!("\"{}\"", id)).unwrap();
+        assert_eq!(param, Param(id));
+        let err = serde_json::from_str::<Param<ProcessUniqueId>>("\"puid-1-x\"").unwrap_err();
+        assert!(err.to_string().contains("expected a hex digit"));
+    }
+
+    #[cfg(feature = "axum")]
+    #[test]
+    fn test_axum_response() {
+        use axum_core::response::IntoResponse;
+
+        let err = "puid-1".parse::<ProcessUniqueId>().unwrap_err();
+        assert_eq!(err.into_response().status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(feature = "actix-web")]
+    #[test]
+    fn test_actix_response() {
+        use actix_web::ResponseError;
+
+        let err = "puid-1".parse::<ProcessUniqueId>().unwrap_err();
+        assert_eq!(err.error_response().status().as_u16(), 400);
+    }
+
+    #[cfg(feature = "rocket")]
+    #[test]
+    fn test_rocket_param() {
+        use rocket::request::FromParam;
+
+        let id = ProcessUniqueId::new();
+        assert_eq!(ProcessUniqueId::from_param(&id.to_string()), Ok(id));
+        assert!(ProcessUniqueId::from_param("puid-1").is_err());
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::fmt;
+use core::mem;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::process;
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "bevy")]
+use bevy_ecs::reflect::ReflectComponent;
+
+use crate::display::pad_id;
+#[cfg(feature = "std")]
+use crate::node::{NodeIdError, NodeIdProvider};
+#[cfg(feature = "std")]
+use crate::object_id::next_counter;
+use crate::process_unique_id::{expect_len, ParseIdError};
+
+#[cfg(feature = "std")]
+const MACHINE_BITS: u32 = 24;
+
+const ENCODED_LEN: usize = 20;
+const BASE32_HEX: &[u8; 32] = b"0123456789abcdefghijklmnopqrstuv";
+
+/// A 12-byte ID in the layout of the Go xid package.
+///
+/// A 4-byte big-endian timestamp in seconds since the UNIX epoch, a 3-byte machine ID, a 2-byte
+/// process ID, and a 3-byte big-endian counter. The string form is 20 characters of lowercase
+/// base32hex, e.g. `9m4e2mr0ui3e8a215n4g`, which sorts the same way as the bytes.
+///
+/// # Memory layout
+///
+/// `#[repr(transparent)]` over its 12 bytes, with an alignment of 1, so it fits anywhere in a
+/// struct without padding.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[cfg_attr(
+    feature = "zerocopy",
+    derive(
+        zerocopy::FromBytes,
+        zerocopy::IntoBytes,
+        zerocopy::Immutable,
+        zerocopy::KnownLayout
+    )
+)]
+#[cfg_attr(feature = "bitcode", derive(bitcode::Encode, bitcode::Decode))]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_ecs::component::Component, bevy_reflect::Reflect),
+    reflect(opaque, Component, Debug, PartialEq, Hash, Clone)
+)]
+#[repr(transparent)]
+pub struct Xid([u8; 12]);
+
+const _: () = assert!(mem::size_of::<Xid>() == 12 && mem::align_of::<Xid>() == 1);
+
+impl Xid {
+    /// Creates an xid from its raw bytes.
+    #[inline]
+    pub const fn from_bytes(bytes: [u8; 12]) -> Self {
+        Xid(bytes)
+    }
+
+    /// The raw bytes of this ID.
+    #[inline]
+    pub const fn bytes(self) -> [u8; 12] {
+        self.0
+    }
+
+    /// Seconds since the UNIX epoch at which this ID was created.
+    #[inline]
+    pub fn time(self) -> u32 {
+        u32::from_be_bytes([self.0[0], self.0[1], self.0[2], self.0[3]])
+    }
+
+    /// The 24-bit ID of the machine that created this ID.
+    #[inline]
+    pub fn machine_id(self) -> u32 {
+        u32::from_be_bytes([0, self.0[4], self.0[5], self.0[6]])
+    }
+
+    /// The (truncated) ID of the process that created this ID.
+    #[inline]
+    pub fn pid(self) -> u16 {
+        u16::from_be_bytes([self.0[7], self.0[8]])
+    }
+
+    /// The 24-bit counter.
+    #[inline]
+    pub fn counter(self) -> u32 {
+        u32::from_be_bytes([0, self.0[9], self.0[10], self.0[11]])
+    }
+
+    #[inline]
+    fn to_u128(self) -> u128 {
+        self.0.iter().fold(0, |v, &b| (v << 8) | u128::from(b))
+    }
+
+    /// The base32hex digits of the display form.
+    #[inline]
+    pub(crate) fn encode(self) -> [u8; ENCODED_LEN] {
+        // 96 bits padded with four zero bits to 20 base32 digits.
+        let value = self.to_u128() << 4;
+        let mut buf = [0u8; ENCODED_LEN];
+        for (i, c) in buf.iter_mut().enumerate() {
+            *c = BASE32_HEX[((value >> (95 - 5 * i)) & 0x1f) as usize];
+        }
+        buf
+    }
+}
+
+impl fmt::Display for Xid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let buf = self.encode();
+        // base32hex is ASCII.
+        let s = core::str::from_utf8(&buf).unwrap();
+        pad_id(f, format_args!(""), format_args!("{}", s))
+    }
+}
+
+/// Parses the 20-character base32hex form produced by `Display`. Like the Go package, only
+/// lowercase is accepted.
+impl FromStr for Xid {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        let mut value = 0u128;
+        for (i, c) in s.bytes().enumerate().take(ENCODED_LEN) {
+            let digit = BASE32_HEX
+                .iter()
+                .position(|&d| d == c)
+                .ok_or_else(|| ParseIdError::at(s, i, "a lowercase base32hex digit"))?;
+            value = (value << 5) | digit as u128;
+        }
+        expect_len(s, ENCODED_LEN, "a lowercase base32hex digit")?;
+        // The padding bits must be zero so that every ID has exactly one string form.
+        if value & 0xf != 0 {
+            return Err(ParseIdError::at(s, ENCODED_LEN - 1, "`0` or `g`"));
+        }
+        let mut bytes = [0u8; 12];
+        bytes.copy_from_slice(&(value >> 4).to_be_bytes()[4..]);
+        Ok(Xid(bytes))
+    }
+}
+
+/// Generates [`Xid`]s, compatible with the Go xid package.
+///
+/// The machine ID is supplied by the caller (or a [`NodeIdProvider`]) rather than hashed from the
+/// host name, and the counter is the process-wide one [`ObjectId`](crate::ObjectId) uses: it
+/// starts at a random value and is shared by every generator in the process.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct XidGenerator {
+    machine_id: u32,
+    pid: u16,
+}
+
+#[cfg(feature = "std")]
+impl XidGenerator {
+    /// Creates a generator for `machine_id`.
+    ///
+    /// **panics** if `machine_id` doesn't fit in 24 bits.
+    pub fn new(machine_id: u32) -> Self {
+        assert!(
+            machine_id >> MACHINE_BITS == 0,
+            "machine ID {} doesn't fit in {} bits",
+            machine_id,
+            MACHINE_BITS
+        );
+        XidGenerator {
+            machine_id,
+            // Truncated, like the Go package.
+            pid: process::id() as u16,
+        }
+    }
+
+    /// Creates a generator using the machine ID supplied by `provider`.
+    pub fn from_provider<P: NodeIdProvider + ?Sized>(provider: &P) -> Result<Self, NodeIdError> {
+        let machine_id = provider.node_id(MACHINE_BITS)?;
+        Ok(XidGenerator::new(machine_id as u32))
+    }
+
+    /// This generator's machine ID.
+    #[inline]
+    pub fn machine_id(&self) -> u32 {
+        self.machine_id
+    }
+
+    /// Generates a new xid.
+    pub fn next(&self) -> Xid {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as u32);
+        self.at(secs, next_counter())
+    }
+
+    fn at(&self, secs: u32, counter: u32) -> Xid {
+        let mut bytes = [0u8; 12];
+        bytes[..4].copy_from_slice(&secs.to_be_bytes());
+        bytes[4..7].copy_from_slice(&self.machine_id.to_be_bytes()[1..]);
+        bytes[7..9].copy_from_slice(&self.pid.to_be_bytes());
+        bytes[9..].copy_from_slice(&counter.to_be_bytes()[1..]);
+        Xid(bytes)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::{Xid, XidGenerator};
+    use crate::node::StaticNodeId;
+
+    #[test]
+    fn test_string_form() {
+        // From the Go package's tests.
+        let id = Xid::from_bytes([
+            0x4d, 0x88, 0xe1, 0x5b, 0x60, 0xf4, 0x86, 0xe4, 0x28, 0x41, 0x2d, 0xc9,
+        ]);
+        assert_eq!(id.to_string(), "9m4e2mr0ui3e8a215n4g");
+        assert_eq!("9m4e2mr0ui3e8a215n4g".parse(), Ok(id));
+        assert_eq!(id.time(), 1_300_816_219);
+        assert_eq!(id.machine_id(), 0x60f486);
+        assert_eq!(id.pid(), 0xe428);
+        assert_eq!(id.counter(), 4_271_561);
+        assert_eq!(
+            Xid::from_bytes([0xff; 12]).to_string(),
+            "vvvvvvvvvvvvvvvvvvvg"
+        );
+        for bad in &[
+            "",
+            "9m4e2mr0ui3e8a215n4",
+            "9m4e2mr0ui3e8a215n4gg",
+            "9M4E2MR0UI3E8A215N4G",
+            "9m4e2mr0ui3e8a215n4w",
+            "9m4e2mr0ui3e8a215n4h",
+        ] {
+            assert!(bad.parse::<Xid>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_generator() {
+        let gen = XidGenerator::from_provider(&StaticNodeId(0xabcdef)).unwrap();
+        assert_eq!(gen.machine_id(), 0xabcdef);
+        let id = gen.at(1234, 0x12_3456);
+        assert_eq!(id.time(), 1234);
+        assert_eq!(id.machine_id(), 0xabcdef);
+        assert_eq!(id.pid(), std::process::id() as u16);
+        assert_eq!(id.counter(), 0x12_3456);
+
+        let a = gen.next();
+        let b = gen.next();
+        assert_ne!(a, b);
+        assert!(a.time() > 1_600_000_000);
+        assert!(XidGenerator::from_provider(&StaticNodeId(1 << 24)).is_err());
+    }
+}
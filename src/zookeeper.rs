@@ -0,0 +1,304 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Node IDs claimed with ZooKeeper sequential znodes.
+//!
+//! Requires the `zookeeper` feature. [`ZooKeeperNodeId`] is a [`NodeIdProvider`] that claims a
+//! node ID by creating an ephemeral sequential znode `<dir>/node-<sequence>`. The node ID is the
+//! sequence number modulo the size of the node ID space; if an older live znode already has the
+//! same node ID, the provider deletes its znode and tries again with the next sequence number.
+//! The znode disappears with the ZooKeeper session when the process dies, freeing the ID.
+//!
+//! ZooKeeper may expire the session at any point after the connection drops, so the node ID is
+//! surrendered as soon as that happens: [`is_valid`](ZooKeeperNodeId::is_valid) turns false and
+//! the [`on_lost`](ZooKeeperNodeId::on_lost) callbacks run, and generators using the node ID must
+//! stop.
+//!
+//! ```no_run
+//! use snowflake::zookeeper::ZooKeeperNodeId;
+//! use snowflake::SnowflakeGenerator;
+//! use std::process;
+//!
+//! let node = ZooKeeperNodeId::new("zk1:2181,zk2:2181,zk3:2181", "/snowflake/nodes").unwrap();
+//! node.on_lost(|node_id| {
+//!     eprintln!("lost node ID {}", node_id);
+//!     process::exit(1);
+//! });
+//! let ids = SnowflakeGenerator::from_provider(&node).unwrap();
+//! ```
+use std::fmt;
+use std::process;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use tokio::runtime::Runtime;
+use zookeeper_client::{Acls, Client, CreateMode, SessionState, StateWatcher};
+
+use crate::node::{background_runtime, block_on, check_width, NodeIdError, NodeIdProvider};
+
+const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The name of the sequential znodes, before the sequence number.
+const NODE_PREFIX: &str = "node-";
+
+/// The most sequence numbers to go through looking for a free node ID.
+const MAX_ATTEMPTS: u32 = 4096;
+
+type LostCallback = Arc<dyn Fn(u64) + Send + Sync>;
+
+/// A node ID claimed with a ZooKeeper sequential znode; see the [module documentation](self).
+///
+/// The node ID is claimed by the first call to [`node_id`](NodeIdProvider::node_id), in a new
+/// ZooKeeper session, and later calls return the same one. Once it's lost, the next call claims a
+/// new one in a new session. Dropping the provider closes the session, freeing the node ID right
+/// away.
+pub struct ZooKeeperNodeId {
+    // Only `None` while being dropped.
+    runtime: Option<Runtime>,
+    cluster: String,
+    dir: String,
+    session_timeout: Duration,
+    // Held while claiming, so that concurrent callers share one node ID.
+    claiming: Mutex<()>,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    held: Mutex<Option<Held>>,
+    callbacks: Mutex<Vec<LostCallback>>,
+}
+
+struct Held {
+    node_id: u64,
+    path: String,
+    // The session ends when the last client handle is dropped.
+    client: Client,
+}
+
+impl ZooKeeperNodeId {
+    /// Claims node IDs from the ZooKeeper ensemble `cluster` (`host:port,...`, optionally
+    /// followed by a chroot path), under the znode `dir`. `dir` is created if it doesn't exist.
+    ///
+    /// Doesn't connect until the first call to [`node_id`](NodeIdProvider::node_id).
+    pub fn new(cluster: &str, dir: &str) -> Result<Self, NodeIdError> {
+        Ok(ZooKeeperNodeId {
+            runtime: Some(background_runtime("snowflake-zookeeper")?),
+            cluster: cluster.to_owned(),
+            dir: dir.trim_end_matches('/').to_owned(),
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+            claiming: Mutex::new(()),
+            shared: Arc::new(Shared {
+                held: Mutex::new(None),
+                callbacks: Mutex::new(Vec::new()),
+            }),
+        })
+    }
+
+    /// Asks for a session timeout of `timeout` instead of 10 seconds. The server may negotiate
+    /// it down or up.
+    pub fn with_session_timeout(mut self, timeout: Duration) -> Self {
+        self.session_timeout = timeout;
+        self
+    }
+
+    /// The znode node IDs are claimed under.
+    #[inline]
+    pub fn dir(&self) -> &str {
+        &self.dir
+    }
+
+    /// Returns true if a node ID is claimed and hasn't been lost.
+    pub fn is_valid(&self) -> bool {
+        self.shared.held().is_some()
+    }
+
+    /// Registers a callback to be invoked (with the node ID) whenever a claimed node ID is lost.
+    ///
+    /// Callbacks run on a background thread.
+    pub fn on_lost<F>(&self, callback: F)
+    where
+        F: Fn(u64) + Send + Sync + 'static,
+    {
+        self.shared.callbacks().push(Arc::new(callback));
+    }
+
+    fn runtime(&self) -> &Runtime {
+        self.runtime
+            .as_ref()
+            .expect("zookeeper runtime already shut down")
+    }
+}
+
+impl NodeIdProvider for ZooKeeperNodeId {
+    fn node_id(&self, bits: u32) -> Result<u64, NodeIdError> {
+        let _claiming = self.claiming.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(held) = &*self.shared.held() {
+            return check_width(held.node_id, bits);
+        }
+        let connector = Client::connector().with_session_timeout(self.session_timeout);
+        let (cluster, dir) = (self.cluster.as_str(), self.dir.as_str());
+        let (held, watcher) = block_on(self.runtime(), async move {
+            let client = connector.connect(cluster).await.map_err(unavailable)?;
+            let held = claim(client, dir, bits).await?;
+            // Later changes are reported to the watcher, so this is the last one to check here.
+            let mut watcher = held.client.state_watcher();
+            if !is_connected(watcher.state()) {
+                return Err(NodeIdError::Unavailable(
+                    "zookeeper: disconnected while claiming a node ID".into(),
+                ));
+            }
+            Ok((held, watcher))
+        })?;
+        let (node_id, path) = (held.node_id, held.path.clone());
+        *self.shared.held() = Some(held);
+        self.runtime()
+            .spawn(watch(self.shared.clone(), path, watcher));
+        Ok(node_id)
+    }
+}
+
+impl Drop for ZooKeeperNodeId {
+    fn drop(&mut self) {
+        let runtime = match self.runtime.take() {
+            Some(runtime) => runtime,
+            None => return,
+        };
+        if let Some(held) = self.shared.held().take() {
+            // Don't leave the znode for the server to expire with the session.
+            let _ = block_on(&runtime, async move {
+                held.client.delete(&held.path, None).await
+            });
+        }
+        // Dropping the runtime would block, which isn't allowed in async code.
+        runtime.shutdown_background();
+    }
+}
+
+impl fmt::Debug for ZooKeeperNodeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ZooKeeperNodeId")
+            .field("cluster", &self.cluster)
+            .field("dir", &self.dir)
+            .field("session_timeout", &self.session_timeout)
+            .field("node_id", &self.shared.held().as_ref().map(|h| h.node_id))
+            .finish()
+    }
+}
+
+impl Shared {
+    fn held(&self) -> MutexGuard<'_, Option<Held>> {
+        self.held.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn callbacks(&self) -> MutexGuard<'_, Vec<LostCallback>> {
+        self.callbacks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Creates sequential znodes under `dir` until one's sequence number maps to a node ID that no
+/// older live znode maps to.
+async fn claim(client: Client, dir: &str, bits: u32) -> Result<Held, NodeIdError> {
+    let mask = if bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    };
+    client
+        .mkdir(dir, &CreateMode::Persistent.with_acls(Acls::anyone_all()))
+        .await
+        .map_err(unavailable)?;
+    let prefix = format!("{}/{}", dir, NODE_PREFIX);
+    let data = format!("pid {}", process::id());
+    let options = CreateMode::EphemeralSequential.with_acls(Acls::anyone_all());
+    for _ in 0..MAX_ATTEMPTS {
+        let (_, sequence) = client
+            .create(&prefix, data.as_bytes(), &options)
+            .await
+            .map_err(unavailable)?;
+        let path = format!("{}{}", prefix, sequence);
+        let sequence = sequence.into_i64() as u64;
+        let node_id = sequence & mask;
+        // Of two znodes with the same node ID, the younger one sees the older one here.
+        let taken = client
+            .list_children(dir)
+            .await
+            .map_err(unavailable)?
+            .iter()
+            .filter_map(|child| parse_child(child))
+            .any(|other| other < sequence && other & mask == node_id);
+        if !taken {
+            return Ok(Held {
+                node_id,
+                path,
+                client,
+            });
+        }
+        client.delete(&path, None).await.map_err(unavailable)?;
+    }
+    Err(NodeIdError::Unavailable(format!(
+        "no free node ID under {:?}",
+        dir
+    )))
+}
+
+/// Surrenders the node ID claimed as `path` once the session is no longer connected.
+async fn watch(shared: Arc<Shared>, path: String, mut watcher: StateWatcher) {
+    while is_connected(watcher.changed().await) {}
+    let held = {
+        let mut held = shared.held();
+        match &*held {
+            Some(current) if current.path == path => held.take(),
+            _ => None,
+        }
+    };
+    // Dropping the client closes the session, if it isn't over yet.
+    let node_id = match held {
+        Some(held) => held.node_id,
+        None => return,
+    };
+    let callbacks = shared.callbacks().clone();
+    // Keep callbacks that block (or claim a new node ID) off the runtime's only worker.
+    tokio::task::spawn_blocking(move || {
+        for callback in callbacks {
+            callback(node_id);
+        }
+    });
+}
+
+fn is_connected(state: SessionState) -> bool {
+    matches!(
+        state,
+        SessionState::SyncConnected | SessionState::ConnectedReadOnly
+    )
+}
+
+fn unavailable(e: zookeeper_client::Error) -> NodeIdError {
+    NodeIdError::Unavailable(format!("zookeeper: {}", e))
+}
+
+/// The sequence number of a `node-<sequence>` child.
+fn parse_child(name: &str) -> Option<u64> {
+    let sequence = name.strip_prefix(NODE_PREFIX)?;
+    if sequence.is_empty() || !sequence.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    sequence.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_child;
+
+    #[test]
+    fn test_parse_child() {
+        assert_eq!(parse_child("node-0000000042"), Some(42));
+        assert_eq!(parse_child("node-"), None);
+        assert_eq!(parse_child("node-+42"), None);
+        assert_eq!(parse_child("lock-0000000042"), None);
+    }
+}